@@ -0,0 +1,38 @@
+// Increments a counter from several workers sharing a WebAssembly memory. Each worker is a thread,
+// which on `wasm32-wasip1-threads` is a wasi-threads instance of the module on the same shared
+// memory (a web worker, in a browser host). Build and run it with
+//
+//     rustup target add wasm32-wasip1-threads
+//     cargo build --release --example wasm_counter --target wasm32-wasip1-threads
+//     wasmtime -W threads=y -S threads=y \
+//         target/wasm32-wasip1-threads/release/examples/wasm_counter.wasm
+//
+// It runs natively too, as a plain multithreaded program, which is the only way it has been run so
+// far: WebAssembly support is experimental, and this has never been built for a wasm32 target.
+
+use std::thread;
+
+use bakery::{BakeryMutex, Barrier};
+
+const WORKERS: usize = 4;
+const ITERATIONS: u64 = 10_000;
+
+fn main() {
+    let counter = BakeryMutex::<u64, WORKERS>::new(0);
+    let start = Barrier::new(WORKERS);
+    thread::scope(|scope| {
+        for _ in 0..WORKERS {
+            scope.spawn(|| {
+                let mut slot = counter.register().expect("more workers than slots");
+                start.wait();
+                for _ in 0..ITERATIONS {
+                    *slot.lock() += 1;
+                }
+            });
+        }
+    });
+
+    let total = counter.into_inner();
+    println!("{WORKERS} workers counted to {total}");
+    assert_eq!(total, WORKERS as u64 * ITERATIONS);
+}
@@ -0,0 +1,96 @@
+// Runs a few threads through the counter while the fuzzer input decides, at every event of the
+// algorithm, whether the thread carries on, yields to the scheduler or spins for a while. Crashes
+// if two threads are ever in the critical section together or the final count is off.
+//
+//   cargo +nightly fuzz run schedule
+//   cargo +nightly fuzz run schedule --features fake-fence-2
+
+#![no_main]
+
+use std::{
+    cell::UnsafeCell,
+    hint,
+    sync::atomic::{AtomicUsize, Ordering},
+    thread,
+};
+
+use bakery::{Event, Observer, RawBakeryLock};
+use libfuzzer_sys::fuzz_target;
+
+const THREADS: usize = 3;
+const ITERATIONS: usize = 16;
+
+// Interleaves one stream of decisions per thread through the input: thread `t` reads bytes
+// `t`, `t + THREADS`, `t + 2 * THREADS`, ..., wrapping around at the end.
+struct Schedule<'a> {
+    input: &'a [u8],
+    // How many decisions each thread has taken. Only touched by that thread.
+    taken: [AtomicUsize; THREADS],
+    // Threads currently between `Acquired` and `Released`.
+    inside: AtomicUsize,
+}
+
+impl Schedule<'_> {
+    fn perturb(&self, thread: usize) {
+        let taken = self.taken[thread].load(Ordering::Relaxed);
+        self.taken[thread].store(taken + 1, Ordering::Relaxed);
+
+        let byte = self.input[(taken * THREADS + thread) % self.input.len()];
+        match byte & 3 {
+            0 | 1 => {}
+            2 => thread::yield_now(),
+            _ => {
+                for _ in 0..byte >> 2 {
+                    hint::spin_loop();
+                }
+            }
+        }
+    }
+}
+
+impl Observer for Schedule<'_> {
+    fn on_event(&self, thread: usize, event: Event) {
+        match event {
+            Event::Acquired => {
+                let inside = self.inside.fetch_add(1, Ordering::Relaxed);
+                assert_eq!(inside, 0, "thread {thread} entered alongside another thread");
+            }
+            Event::Released => {
+                self.inside.fetch_sub(1, Ordering::Relaxed);
+            }
+            _ => {}
+        }
+        self.perturb(thread);
+    }
+}
+
+struct Counter(UnsafeCell<usize>);
+unsafe impl Sync for Counter {}
+
+fuzz_target!(|input: &[u8]| {
+    if input.is_empty() {
+        return;
+    }
+
+    let lock = RawBakeryLock::<THREADS, _>::with_observer(Schedule {
+        input,
+        taken: Default::default(),
+        inside: AtomicUsize::new(0),
+    });
+    let counter = Counter(UnsafeCell::new(0));
+    thread::scope(|scope| {
+        for thread in 0..THREADS {
+            let (lock, counter) = (&lock, &counter);
+            scope.spawn(move || {
+                for _ in 0..ITERATIONS {
+                    lock.lock(thread);
+                    unsafe {
+                        *counter.0.get() += 1;
+                    }
+                    lock.unlock(thread);
+                }
+            });
+        }
+    });
+    assert_eq!(counter.0.into_inner(), THREADS * ITERATIONS);
+});
@@ -0,0 +1,73 @@
+/*
+ * C API for Lamport's bakery lock, as exported by the `cdylib` feature of the `bakery` crate
+ * (see `src/ffi.rs`, which this must be kept in sync with; the `ffi_header` test in
+ * `tests/miri.rs` checks every prototype against it). Build the library with
+ *
+ *     cargo rustc --release --lib --features cdylib --crate-type cdylib
+ *
+ * Slots are indices in `0..slots` that each thread (or process) taking part uses exclusively. A
+ * slot out of range aborts the process.
+ */
+
+#ifndef BAKERY_H
+#define BAKERY_H
+
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+/* A lock for a number of threads chosen at runtime, on the heap. */
+typedef struct bakery_lock bakery_lock;
+
+bakery_lock *bakery_lock_new(size_t slots);
+/* The lock must not be in use. Does nothing for NULL. */
+void bakery_lock_free(bakery_lock *lock);
+
+void bakery_lock_acquire(const bakery_lock *lock, size_t slot);
+/* Enters the critical section if that doesn't require waiting for another thread. */
+bool bakery_lock_try_acquire(const bakery_lock *lock, size_t slot);
+void bakery_lock_release(const bakery_lock *lock, size_t slot);
+
+/*
+ * A lock with BAKERY_SHM_SLOTS slots that lives in memory shared between processes, `ShmLock` in
+ * `bakery::ffi` for a Rust process sharing it. All zeroes is an unlocked lock with every slot free,
+ * so a freshly created shared memory object needs no initialization.
+ */
+typedef struct bakery_shm_lock bakery_shm_lock;
+
+#define BAKERY_SHM_SLOTS 64
+
+/* How many bytes at the start of the region the lock takes up. */
+size_t bakery_shm_lock_size(void);
+/* The lock at `region`, or NULL if it isn't aligned to 4 bytes. */
+const bakery_shm_lock *bakery_shm_lock_at(void *region);
+
+/*
+ * Claims a free slot for `owner`, which must be neither 0 nor UINT32_MAX, returning it, or -1 if
+ * every slot is taken.
+ */
+intptr_t bakery_shm_lock_claim(const bakery_shm_lock *lock, uint32_t owner);
+/* Claims a free slot with the calling process's id as the owner. Unix only. */
+intptr_t bakery_shm_lock_claim_for_process(const bakery_shm_lock *lock);
+/* Gives back a claimed slot, which must not be holding the lock. */
+void bakery_shm_lock_release_slot(const bakery_shm_lock *lock, size_t slot);
+
+void bakery_shm_lock_acquire(const bakery_shm_lock *lock, size_t slot);
+bool bakery_shm_lock_try_acquire(const bakery_shm_lock *lock, size_t slot);
+void bakery_shm_lock_release(const bakery_shm_lock *lock, size_t slot);
+
+/*
+ * Frees the slots of owners whose process no longer exists, even if they died holding the lock,
+ * and returns how many there were. Unix only.
+ */
+size_t bakery_shm_lock_recover_dead(const bakery_shm_lock *lock);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif /* BAKERY_H */
@@ -0,0 +1,318 @@
+use core::sync::atomic::{self, Ordering};
+
+use crate::{backoff::Backoff, layout, sc_fence_1, sc_fence_2, spin, Event};
+
+// Why a waiter can't get past another slot yet.
+#[derive(Clone, Copy)]
+pub enum Blocked {
+    // The slot is still choosing its ticket.
+    Choosing,
+    // The slot holds this ticket, which goes before ours.
+    Ticket(u64),
+}
+
+impl Blocked {
+    // The event for waiting on `other` for this reason.
+    fn event(self, other: usize) -> Event {
+        match self {
+            Blocked::Choosing => Event::WaitChoosing { other },
+            Blocked::Ticket(ticket) => Event::WaitTicket { other, ticket },
+        }
+    }
+}
+
+// What a waiter does after being told it's blocked.
+pub enum Next {
+    // Looks at the slot again.
+    Wait,
+    // Enters the critical section without waiting for anyone else, because the lock was handed to
+    // it.
+    Enter,
+    // Leaves without entering. Its ticket is still published, for the lock to withdraw.
+    GiveUp,
+    // Stops waiting for this slot, which doesn't keep it out after all, and moves on to the next,
+    // as for a reader passing a reader ahead of it. Only `BakeryRwLock`, which needs `std`, does.
+    #[cfg_attr(not(feature = "std"), allow(dead_code))]
+    Pass,
+}
+
+// The `choosing` flags and tickets of one of the bakery locks, and the doorway and wait loop that
+// every lock taking slot indices runs on them. The locks differ only in how they keep their slots,
+// how they wait for one to change, and what they do around the algorithm (parking, masking
+// interrupts, handing the lock off), all of which stays in the lock.
+pub trait Bakery {
+    // The largest ticket a slot can hold, which must be one less than a power of two.
+    const MAX_TICKET: u64;
+
+    // Whether `state` reads a slot's flag and ticket with a single load, as for
+    // `SlotLayout::FUSED`.
+    const FUSED: bool = false;
+
+    fn is_choosing(&self, slot: usize, order: Ordering) -> bool;
+    fn set_choosing(&self, slot: usize, choosing: bool, order: Ordering);
+
+    fn ticket(&self, slot: usize, order: Ordering) -> u64;
+    fn set_ticket(&self, slot: usize, ticket: u64, order: Ordering);
+
+    fn state(&self, slot: usize, order: Ordering) -> (bool, u64) {
+        (self.is_choosing(slot, order), self.ticket(slot, order))
+    }
+
+    // Every slot that might currently be in use, in increasing order, as for
+    // `SlotLayout::active_slots`.
+    fn active_slots(&self) -> impl Iterator<Item = usize> + '_;
+
+    // Told about every step `slot` takes in the doorway and the wait loop.
+    fn on_event(&self, _slot: usize, _event: Event) {}
+
+    // Passes through the doorway, returning the ticket taken. If the tickets in use are too far
+    // apart to take another one yet, backs out and tries again until they aren't. `RawBakeryLock`
+    // retries by itself, to keep an eye out for handoffs, so without the other locks nothing calls
+    // this.
+    #[cfg_attr(
+        not(any(feature = "alloc", feature = "shm", feature = "irq")),
+        allow(dead_code)
+    )]
+    fn doorway(&self, slot: usize) -> u64 {
+        loop {
+            if let Some(ticket) = self.try_doorway(slot) {
+                return ticket;
+            }
+            spin::relax();
+        }
+    }
+
+    // Passes through the doorway, returning the ticket taken, or `None` if that ticket would have
+    // been a quarter of the range or more past the oldest one still in use. The slot then backs
+    // out of the doorway without a ticket, to try again once the oldest has left.
+    //
+    // Tickets are compared by their distance around the circle of ticket values, which only works
+    // while every ticket in use is within half the range of every other. Threads that always wait
+    // for their turn never come close, since each waits behind the oldest ticket before taking
+    // another, but ones that give up can leapfrog: two of them taking turns to withdraw and
+    // come back each take the ticket after the other's, and get further past an unmoving holder
+    // every time, until the holder seems to be the newer one. Refusing tickets a quarter of the
+    // range out stops that, with room to spare for the at most `N` doorways racing this one,
+    // each of which can only add one more.
+    fn try_doorway(&self, slot: usize) -> Option<u64> {
+        self.set_choosing(slot, true, Ordering::Relaxed);
+        self.on_event(slot, Event::Doorway);
+
+        // This fence helps enforce the core invariant of the bakery lock: (intuitively) at any
+        // given moment, out of all threads that have currently chosen a ticket, _exactly_ the
+        // one with minimal `(ticket[i], i)` is in its critical section. It coordinates with the
+        // second SC fence in this function to prevent the following store buffering scenario:
+        //
+        //  Thread 0:                                          Thread 1:
+        //
+        //  choosing[0] = true                              |  choosing[1] = true
+        //                                                  |  ticket[1] = max(ticket[0], ticket[1]) + 1 // 1
+        //  // Store from thread 1 not visible:             |
+        //  ticket[0] = max(ticket[0], ticket[1]) + 1 // 1  |
+        //  choosing[0] = false                             |
+        //  choosing[1] == true                             |
+        //                                                  |  choosing[1] = false
+        //                                                  |  // Stores from thread 0 not visible:
+        //                                                  |  choosing[0] == false
+        //                                                  |  ticket[0] == 0
+        //  choosing[1] == false                            |  // Critical section...
+        //  ticket[0] == 1 // (1, 0) < (1, 1)               |  // Critical section...
+        //  // Critical section..                           |  // Critical section...
+        //
+        // The problem here is that thread 1 doesn't see thread 0's write to `choosing[0]` and
+        // incorrectly assumes that it now has the lowest-numbered ticket, while thread 0 has
+        // already chosen a ticket of 1 as well and can (correctly) enter its critical section
+        // because it has priority over thread 1.
+        //
+        // More formally, abbreviating `choosing` as `c` and `ticket` as `t`, the problematic
+        // scenario is a
+        //
+        // W(c[0], 1) -po-> R(t[1], 0) -rb-> W(t[1], 1) -po-> R(c[0], 0) -rb-> W(c[0], 1)
+        //
+        // cycle, so SC fences are necessary somewhere along both `po` edges to forbid it. This
+        // fence covers the `W c -> R t` edge, while the one below covers the `W t -> R c` edge.
+        sc_fence_1();
+
+        // The earliest and latest tickets in use, which are the minimum and maximum as long as
+        // the tickets haven't wrapped around.
+        let (oldest, newest) = self
+            .active_slots()
+            .map(|other| self.ticket(other, Ordering::Relaxed))
+            .filter(|&ticket| ticket != 0)
+            .fold(None, |range, ticket| {
+                let Some((oldest, newest)) = range else {
+                    return Some((ticket, ticket));
+                };
+                let precedes = |a, b| layout::precedes(a, b, Self::MAX_TICKET);
+                Some((
+                    if precedes(ticket, oldest) {
+                        ticket
+                    } else {
+                        oldest
+                    },
+                    if precedes(newest, ticket) {
+                        ticket
+                    } else {
+                        newest
+                    },
+                ))
+            })
+            .unwrap_or((0, 0));
+
+        let ticket = layout::next_ticket(newest, Self::MAX_TICKET);
+        if oldest != 0 && ticket.wrapping_sub(oldest) & Self::MAX_TICKET >= Self::MAX_TICKET / 4 {
+            // Nothing was published, so there's nothing to order.
+            self.set_choosing(slot, false, Ordering::Relaxed);
+            return None;
+        }
+
+        if newest == Self::MAX_TICKET {
+            self.on_event(slot, Event::TicketWrapped);
+        }
+        self.set_ticket(slot, ticket, Ordering::Relaxed);
+
+        // This fence serves two distinct purposes:
+        // 1. It covers the `W t -> R c` edge of the store buffering scenario discussed above.
+        // 2. It synchronizes-with the acquire fence in `chosen_ticket` to make sure that any
+        //    threads observing the write to `choosing` below also observe our new ticket.
+        sc_fence_2();
+
+        self.set_choosing(slot, false, Ordering::Relaxed);
+        self.on_event(slot, Event::Ticket(ticket));
+        Some(ticket)
+    }
+
+    // Whether `(ticket, slot)` goes before `(other_ticket, other)`, both holding nonzero tickets:
+    // the earlier ticket first, with ties broken by slot.
+    fn ahead((ticket, slot): (u64, usize), (other_ticket, other): (u64, usize)) -> bool {
+        layout::precedes(ticket, other_ticket, Self::MAX_TICKET)
+            || (ticket == other_ticket && slot < other)
+    }
+
+    // Whether `slot`, holding `ticket`, doesn't have to wait for `other`, which holds
+    // `other_ticket` or 0. Both `wait_turn` and `try_turn` decide with this, so that they agree
+    // on every pair of tickets, including two exactly half the range apart, neither of which
+    // precedes the other.
+    fn goes_before((ticket, slot): (u64, usize), (other_ticket, other): (u64, usize)) -> bool {
+        other_ticket == 0 || Self::ahead((ticket, slot), (other_ticket, other))
+    }
+
+    // `other`'s ticket, or `None` if it's still choosing one. The ticket is at least as new as
+    // the one `other` published before it last cleared `choosing`.
+    fn chosen_ticket(&self, other: usize) -> Option<u64> {
+        if Self::FUSED {
+            // The ticket comes from the same load that saw `choosing[other]` clear.
+            let (choosing, ticket) = self.state(other, Ordering::Relaxed);
+            return (!choosing).then_some(ticket);
+        }
+        if self.is_choosing(other, Ordering::Relaxed) {
+            return None;
+        }
+        // Synchronizes-with the SC fence just before the store to `choosing[other]` to make sure
+        // we observe the correct value of `ticket[other]` below.
+        atomic::fence(Ordering::Acquire);
+        Some(self.ticket(other, Ordering::Relaxed))
+    }
+
+    // Waits until every slot ahead of `slot`, which holds `ticket`, has left the bakery, returning
+    // whether it entered the critical section. Every time another slot keeps us out, `blocked` is
+    // told which and why, along with a backoff that starts afresh for every phase of every slot we
+    // wait for, and decides what to do next.
+    fn wait_turn<B: Backoff>(
+        &self,
+        slot: usize,
+        ticket: u64,
+        mut blocked: impl FnMut(usize, Blocked, &mut B) -> Next,
+    ) -> bool {
+        'wait: for other in self.active_slots() {
+            if other == slot {
+                continue;
+            }
+
+            let mut backoff = B::new();
+            let mut other_ticket = loop {
+                if let Some(other_ticket) = self.chosen_ticket(other) {
+                    break other_ticket;
+                }
+                self.on_event(slot, Blocked::Choosing.event(other));
+                match blocked(other, Blocked::Choosing, &mut backoff) {
+                    Next::Wait => {}
+                    Next::Enter => break 'wait,
+                    Next::GiveUp => return false,
+                    Next::Pass => continue 'wait,
+                }
+            };
+
+            let mut backoff = B::new();
+            loop {
+                if Self::goes_before((ticket, slot), (other_ticket, other)) {
+                    self.on_event(
+                        slot,
+                        Event::Passed {
+                            other,
+                            ticket: other_ticket,
+                        },
+                    );
+                    break;
+                }
+                let why = Blocked::Ticket(other_ticket);
+                self.on_event(slot, why.event(other));
+                match blocked(other, why, &mut backoff) {
+                    Next::Wait => {}
+                    Next::Enter => break 'wait,
+                    Next::GiveUp => return false,
+                    Next::Pass => continue 'wait,
+                }
+                other_ticket = self.ticket(other, Ordering::Relaxed);
+            }
+        }
+
+        // Synchronizes-with the release stores retiring the tickets of the threads that went
+        // before us (as observed by our reads of them).
+        atomic::fence(Ordering::Acquire);
+        true
+    }
+
+    // Enters the critical section like `wait_turn` if `slot`, which holds `ticket`, is already
+    // first in the bakery, returning whether it did. If another slot is ahead or still choosing,
+    // `blocked` is told which and why, and decides whether to give up (`Wait` gives up too, since
+    // there's no waiting here), enter anyway or pass that slot.
+    fn try_turn(
+        &self,
+        slot: usize,
+        ticket: u64,
+        mut blocked: impl FnMut(usize, Blocked) -> Next,
+    ) -> bool {
+        for other in self.active_slots() {
+            if other == slot {
+                continue;
+            }
+
+            let why = match self.chosen_ticket(other) {
+                None => Blocked::Choosing,
+                Some(other_ticket) => {
+                    if Self::goes_before((ticket, slot), (other_ticket, other)) {
+                        self.on_event(
+                            slot,
+                            Event::Passed {
+                                other,
+                                ticket: other_ticket,
+                            },
+                        );
+                        continue;
+                    }
+                    Blocked::Ticket(other_ticket)
+                }
+            };
+
+            match blocked(other, why) {
+                Next::Wait | Next::GiveUp => return false,
+                Next::Enter => break,
+                Next::Pass => {}
+            }
+        }
+
+        atomic::fence(Ordering::Acquire);
+        true
+    }
+}
@@ -0,0 +1,169 @@
+#[cfg(feature = "burns-lynch")]
+use bakery::burns_lynch::BurnsLynchLock;
+#[cfg(feature = "dekker")]
+use bakery::dekker::DekkerLock;
+#[cfg(feature = "filter")]
+use bakery::filter::FilterLock;
+#[cfg(feature = "lamport-fast")]
+use bakery::lamport_fast::LamportFastLock;
+#[cfg(feature = "peterson")]
+use bakery::peterson::PetersonLock;
+#[cfg(feature = "szymanski")]
+use bakery::szymanski::SzymanskiLock;
+#[cfg(feature = "ticket")]
+use bakery::ticket::TicketLock;
+#[cfg(feature = "ttas")]
+use bakery::ttas::TtasLock;
+#[cfg(feature = "black-white")]
+use bakery::BWBakeryLock;
+#[cfg(feature = "futex")]
+use bakery::FutexBakeryLock;
+#[cfg(feature = "hierarchical")]
+use bakery::HierarchicalBakeryLock;
+use bakery::{DynBakeryLock, SlotLock};
+
+// The lock implementations compiled into this binary. Everything apart from the bakery lock
+// itself sits behind its own cargo feature, so that a minimal build only carries the lock.
+const ALGORITHMS: &[(&str, &str)] = &[
+    ("bakery", "Lamport's bakery lock, always built"),
+    #[cfg(feature = "black-white")]
+    (
+        "black-white",
+        "Taubenfeld's black-white bakery lock, with tickets bounded by the number of slots",
+    ),
+    #[cfg(feature = "burns-lynch")]
+    (
+        "burns-lynch",
+        "Burns and Lynch's lock, with one bit per slot and no fairness",
+    ),
+    #[cfg(feature = "dekker")]
+    ("dekker", "Dekker's lock for two threads"),
+    #[cfg(feature = "filter")]
+    (
+        "filter",
+        "the filter lock, Peterson's lock generalized to N threads",
+    ),
+    #[cfg(feature = "flawed-bakery")]
+    (
+        "flawed-bakery",
+        "copies of the bakery lock with planted bugs, used by `exercises` and the weak-fence \
+         experiments",
+    ),
+    #[cfg(feature = "futex")]
+    (
+        "futex",
+        "the bakery lock with waiters parking on the slot they wait for",
+    ),
+    #[cfg(feature = "hierarchical")]
+    (
+        "hierarchical",
+        "a bakery lock per group of slots under a bakery lock between the groups",
+    ),
+    #[cfg(feature = "irq")]
+    (
+        "irq",
+        "the bakery lock with interrupts masked while in the bakery, for bare metal",
+    ),
+    #[cfg(feature = "lamport-fast")]
+    (
+        "lamport-fast",
+        "Lamport's fast lock, with constant-time acquisitions while uncontended",
+    ),
+    #[cfg(feature = "peterson")]
+    ("peterson", "Peterson's lock for two threads"),
+    #[cfg(feature = "shm")]
+    (
+        "shm",
+        "the bakery lock laid out for shared memory, with slots claimed by process id",
+    ),
+    #[cfg(feature = "szymanski")]
+    (
+        "szymanski",
+        "Szymanski's lock, which lets threads in by batches with five-state flags",
+    ),
+    #[cfg(feature = "test-and-set")]
+    (
+        "test-and-set",
+        "a test-and-set spinlock, the baseline for `starvation`",
+    ),
+    #[cfg(feature = "ticket")]
+    (
+        "ticket",
+        "a ticket spinlock taking tickets with `fetch_add`, the read-modify-write baseline",
+    ),
+    #[cfg(feature = "ttas")]
+    (
+        "ttas",
+        "a test-and-test-and-set spinlock with exponential backoff, the unfair baseline",
+    ),
+];
+
+pub fn list() {
+    for (name, description) in ALGORITHMS {
+        println!("{name:<16} {description}");
+    }
+}
+
+// The smallest of a few sizes of the lock `$lock`, generic over its number of slots, with room for
+// `$threads` threads, or the largest if none has. A lock sized well beyond the threads using it
+// would spend most of its time scanning empty slots. Without any of the locks that are generic
+// over their number of slots, nothing uses it.
+#[cfg_attr(
+    not(any(
+        feature = "black-white",
+        feature = "burns-lynch",
+        feature = "filter",
+        feature = "futex",
+        feature = "lamport-fast",
+        feature = "szymanski"
+    )),
+    allow(unused_macros)
+)]
+macro_rules! sized {
+    ($lock:ident, $threads:expr) => {
+        match $threads {
+            0..=2 => Box::new($lock::<2>::default()) as Box<dyn SlotLock + Sync>,
+            3..=4 => Box::new($lock::<4>::default()),
+            5..=8 => Box::new($lock::<8>::default()),
+            9..=16 => Box::new($lock::<16>::default()),
+            17..=32 => Box::new($lock::<32>::default()),
+            _ => Box::new($lock::<64>::default()),
+        }
+    };
+}
+
+// The lock named `name` for `threads` threads to run the counter on, if it's built and takes
+// explicit slots. Locks with a fixed number of slots may have fewer than `threads`, which is up
+// to the caller to check.
+pub fn slot_lock(name: &str, threads: usize) -> Option<Box<dyn SlotLock + Sync>> {
+    Some(match name {
+        "bakery" => Box::new(DynBakeryLock::new(threads)),
+        #[cfg(feature = "black-white")]
+        "black-white" => sized!(BWBakeryLock, threads),
+        #[cfg(feature = "burns-lynch")]
+        "burns-lynch" => sized!(BurnsLynchLock, threads),
+        #[cfg(feature = "dekker")]
+        "dekker" => Box::new(DekkerLock::default()),
+        #[cfg(feature = "filter")]
+        "filter" => sized!(FilterLock, threads),
+        #[cfg(feature = "futex")]
+        "futex" => sized!(FutexBakeryLock, threads),
+        #[cfg(feature = "hierarchical")]
+        "hierarchical" => match threads {
+            0..=4 => Box::new(HierarchicalBakeryLock::<2, 2>::default()),
+            5..=16 => Box::new(HierarchicalBakeryLock::<4, 4>::default()),
+            _ => Box::new(HierarchicalBakeryLock::<8, 8>::default()),
+        },
+        #[cfg(feature = "lamport-fast")]
+        "lamport-fast" => sized!(LamportFastLock, threads),
+        #[cfg(feature = "peterson")]
+        "peterson" => Box::new(PetersonLock::default()),
+        #[cfg(feature = "szymanski")]
+        "szymanski" => sized!(SzymanskiLock, threads),
+        #[cfg(feature = "ticket")]
+        "ticket" => Box::new(TicketLock::default()),
+        #[cfg(feature = "ttas")]
+        "ttas" => Box::new(TtasLock::default()),
+        _ => return None,
+    })
+}
@@ -0,0 +1,156 @@
+use std::{env, hint, process, process::Command};
+
+use bakery::RawBakeryLock;
+
+const NUM_SLOTS: usize = 10;
+
+// Out-of-line copies of the lock operations on the default lock, so that their code ends up in a
+// symbol of its own once everything else has been inlined into it.
+#[inline(never)]
+fn lock_sample(lock: &RawBakeryLock<NUM_SLOTS>, thread: usize) {
+    lock.lock(thread);
+}
+
+#[inline(never)]
+fn unlock_sample(lock: &RawBakeryLock<NUM_SLOTS>, thread: usize) {
+    lock.unlock(thread);
+}
+
+// Explains what an instruction contributes to the ordering of memory accesses, if anything.
+fn annotate(instruction: &str) -> Option<&'static str> {
+    let mnemonic = instruction.split_whitespace().next()?;
+    let annotation = match mnemonic {
+        // x86
+        "mfence" => "full fence",
+        "lfence" | "sfence" => "partial fence",
+        // What `fence(SeqCst)` compiles to on modern x86.
+        "lock" if instruction.contains("$0x0,") && instruction.contains("(%rsp)") => {
+            "SeqCst fence (locked no-op)"
+        }
+        "lock" => "locked RMW (full fence)",
+        "xchg" if instruction.contains('(') => "implicitly locked RMW (full fence)",
+        "pause" => "spin hint",
+        // AArch64
+        "dmb" | "dsb" => "barrier",
+        "isb" => "instruction barrier",
+        "ldar" | "ldarb" | "ldarh" | "ldapr" => "acquire load",
+        "stlr" | "stlrb" | "stlrh" => "release store",
+        "ldaxr" | "ldaxrb" | "ldaxrh" | "stlxr" | "stlxrb" | "stlxrh" => "exclusive access",
+        "yield" => "spin hint",
+        _ if mnemonic.starts_with("cas")
+            || mnemonic.starts_with("ldadd")
+            || mnemonic.starts_with("ldset")
+            || mnemonic.starts_with("ldclr")
+            || mnemonic.starts_with("swp") =>
+        {
+            "atomic RMW"
+        }
+        _ => return None,
+    };
+    Some(annotation)
+}
+
+// Prints the first function whose header matches `is_wanted`, returning the addresses of the lock
+// methods it calls (which are only out of line in debug builds or when the inliner gives up), or
+// `None` if there is no such function.
+fn print_function(disassembly: &str, is_wanted: impl Fn(&str) -> bool) -> Option<Vec<String>> {
+    // objdump starts every function with a line like `0000000000012340 <symbol>:` and ends it with
+    // a blank line.
+    let mut lines = disassembly
+        .lines()
+        .skip_while(|line| !(line.ends_with(">:") && is_wanted(line)));
+
+    let header = lines.next()?;
+    println!("{header}");
+
+    let mut callees = Vec::new();
+    for line in lines.take_while(|line| !line.trim().is_empty()) {
+        let instruction = line.split_once(":\t").map_or("", |(_, rest)| rest.trim());
+        // Skip the padding between functions.
+        if instruction == "int3" || instruction.starts_with("nop") {
+            continue;
+        }
+
+        match annotate(instruction) {
+            Some(annotation) => println!("{line:<60} <-- {annotation}"),
+            None => println!("{line}"),
+        }
+
+        let mut words = instruction.split_whitespace();
+        if let (Some("call" | "jmp" | "bl" | "b"), Some(target), Some(symbol)) =
+            (words.next(), words.next(), words.next())
+        {
+            if symbol.contains("RawBakeryLock") && !symbol.contains('+') {
+                callees.push(format!("{target:0>16}"));
+            }
+        }
+    }
+    println!();
+
+    Some(callees)
+}
+
+// Disassembles this executable and prints the code of `lock` and `unlock` as they were compiled
+// for the current target and fence configuration, pointing out barriers and atomic instructions.
+pub fn run(args: &[String]) {
+    if !args.is_empty() {
+        eprintln!("usage: bakery asm-dump");
+        process::exit(2);
+    }
+
+    // Exercise the samples once, both as a sanity check and so they aren't dropped from the binary.
+    let lock = RawBakeryLock::<NUM_SLOTS>::new();
+    let thread = hint::black_box(0);
+    lock_sample(&lock, thread);
+    unlock_sample(&lock, thread);
+
+    let exe = env::current_exe().unwrap_or_else(|err| {
+        eprintln!("can't locate the executable: {err}");
+        process::exit(1);
+    });
+    let objdump = env::var("OBJDUMP").unwrap_or_else(|_| "objdump".to_owned());
+    let output = Command::new(&objdump)
+        .args(["-d", "-C", "--no-show-raw-insn"])
+        .arg(&exe)
+        .output()
+        .unwrap_or_else(|err| {
+            eprintln!("failed to run {objdump} (set OBJDUMP to use another disassembler): {err}");
+            process::exit(1);
+        });
+    if !output.status.success() {
+        eprintln!(
+            "{objdump} failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+        process::exit(1);
+    }
+    let disassembly = String::from_utf8_lossy(&output.stdout);
+
+    println!("{} on {}", exe.display(), env::consts::ARCH);
+    print!("{}", lock.ordering());
+    if cfg!(debug_assertions) {
+        println!(
+            "note: this is a debug build, so the lock's methods are called rather than inlined"
+        );
+    }
+    println!();
+
+    let mut printed = Vec::new();
+    for name in ["asm_dump::lock_sample", "asm_dump::unlock_sample"] {
+        let Some(mut callees) = print_function(&disassembly, |header| header.contains(name)) else {
+            eprintln!("couldn't find {name} in the disassembly");
+            process::exit(1);
+        };
+
+        while let Some(address) = callees.pop() {
+            if printed.contains(&address) {
+                continue;
+            }
+            if let Some(more) = print_function(&disassembly, |header| header.starts_with(&address))
+            {
+                callees.extend(more);
+            }
+            printed.push(address);
+        }
+    }
+}
@@ -0,0 +1,341 @@
+use std::{
+    cell::{Cell, UnsafeCell},
+    fmt,
+    future::Future,
+    marker::PhantomData,
+    ops::{Deref, DerefMut},
+    pin::Pin,
+    sync::{
+        atomic::{self, AtomicBool, AtomicU32, Ordering},
+        Mutex, PoisonError,
+    },
+    task::{Context, Poll, Waker},
+};
+
+use crate::{
+    algorithm::{Bakery, Next},
+    registry::SlotRegistry,
+};
+
+/// A value protected by a bakery lock for up to `N` tasks at a time, whose [`lock`](Self::lock)
+/// returns a future instead of spinning.
+///
+/// A task claims a free slot and takes a ticket when the future is first polled, and from then on
+/// waits for the slots ahead of it the same way a thread in [`RawBakeryLock`](crate::RawBakeryLock)
+/// would, except that instead of rereading a slot it registers its waker there and returns
+/// `Pending`. Leaving the doorway and unlocking wake everyone registered on the slot, so tasks
+/// still get in in ticket order, with the same first come, first served fairness as the other
+/// locks. Slots aren't tied to threads, since a task may be polled from any of them: every lock
+/// future claims one for as long as it and its guard live, and waits for one to be given back if
+/// all `N` are taken.
+///
+/// Dropping the future before it completes withdraws its ticket, as though it had entered and
+/// left an empty critical section, so the lock can be raced against a timeout; see
+/// [`AsyncBakeryLock`].
+pub struct AsyncBakeryMutex<T, const N: usize> {
+    choosing: [AtomicBool; N],
+    tickets: [AtomicU32; N],
+    // The tasks waiting for each slot's `choosing` flag or ticket to change.
+    wakers: [Mutex<Vec<Waker>>; N],
+    registry: SlotRegistry<N>,
+    // The tasks waiting for a slot to be given back.
+    slot_wakers: Mutex<Vec<Waker>>,
+    data: UnsafeCell<T>,
+}
+
+// SAFETY: the lock hands out access to `data` to one task at a time.
+unsafe impl<T: Send, const N: usize> Sync for AsyncBakeryMutex<T, N> {}
+
+impl<T, const N: usize> AsyncBakeryMutex<T, N> {
+    /// Puts `data` behind an unlocked lock with every slot free.
+    pub fn new(data: T) -> Self {
+        assert!(
+            (N as u64) < Self::MAX_TICKET / 4,
+            "too many slots to tell tickets apart across a wraparound"
+        );
+
+        Self {
+            choosing: std::array::from_fn(|_| AtomicBool::new(false)),
+            tickets: std::array::from_fn(|_| AtomicU32::new(0)),
+            wakers: std::array::from_fn(|_| Mutex::new(Vec::new())),
+            registry: SlotRegistry::new(),
+            slot_wakers: Mutex::new(Vec::new()),
+            data: UnsafeCell::new(data),
+        }
+    }
+
+    /// Waits for the lock and gives access to the value until the returned guard is dropped.
+    pub fn lock(&self) -> AsyncBakeryLock<'_, T, N> {
+        AsyncBakeryLock {
+            mutex: self,
+            state: State::Unclaimed,
+        }
+    }
+
+    /// Gives access to the value like [`lock`](Self::lock) if that doesn't require waiting for
+    /// another task (or for a slot to be given back), or returns `None`.
+    pub fn try_lock(&self) -> Option<AsyncBakeryMutexGuard<'_, T, N>> {
+        let slot = self.registry.claim()?;
+        let entered = match self.enter(slot) {
+            Some(ticket) => self.try_turn(slot, ticket, |_, _| Next::GiveUp),
+            None => false,
+        };
+        if !entered {
+            self.leave(slot);
+            return None;
+        }
+        Some(AsyncBakeryMutexGuard::new(self, slot))
+    }
+
+    /// The value, without locking: the exclusive borrow already rules out any other access.
+    pub fn get_mut(&mut self) -> &mut T {
+        self.data.get_mut()
+    }
+
+    pub fn into_inner(self) -> T {
+        self.data.into_inner()
+    }
+
+    // Passes through the doorway from `slot`, waking everyone who was waiting for it to leave,
+    // and returns the ticket taken, or `None` if the tickets in use were too far apart to take
+    // another one yet.
+    fn enter(&self, slot: usize) -> Option<u64> {
+        let ticket = self.try_doorway(slot);
+        self.wake(slot);
+        ticket
+    }
+
+    // Retires `slot`'s ticket and gives the slot back, whether or not it got into the critical
+    // section.
+    fn leave(&self, slot: usize) {
+        self.tickets[slot].store(0, Ordering::Release);
+        self.wake(slot);
+
+        self.registry.release(slot);
+        for waker in lock(&self.slot_wakers).drain(..) {
+            waker.wake();
+        }
+    }
+
+    // Whether `slot` has to wait for `other` to change, registering `waker` with it if so.
+    fn blocked_by(&self, (ticket, slot): (u64, usize), other: usize, waker: &Waker) -> bool {
+        let blocked = || match self.chosen_ticket(other) {
+            None => true,
+            Some(other_ticket) => !Self::goes_before((ticket, slot), (other_ticket, other)),
+        };
+
+        if !blocked() {
+            return false;
+        }
+        register(&self.wakers[other], waker);
+        // Anything `other` changed before taking its wakers is visible now that we've had the
+        // mutex after it, and anything after will wake us.
+        blocked()
+    }
+
+    // Wakes everyone waiting for `slot` to change, after it has.
+    fn wake(&self, slot: usize) {
+        atomic::fence(Ordering::SeqCst);
+        for waker in lock(&self.wakers[slot]).drain(..) {
+            waker.wake();
+        }
+    }
+}
+
+impl<T, const N: usize> Bakery for AsyncBakeryMutex<T, N> {
+    const MAX_TICKET: u64 = u32::MAX as u64;
+
+    fn is_choosing(&self, slot: usize, order: Ordering) -> bool {
+        self.choosing[slot].load(order)
+    }
+
+    fn set_choosing(&self, slot: usize, choosing: bool, order: Ordering) {
+        self.choosing[slot].store(choosing, order);
+    }
+
+    fn ticket(&self, slot: usize, order: Ordering) -> u64 {
+        self.tickets[slot].load(order).into()
+    }
+
+    fn set_ticket(&self, slot: usize, ticket: u64, order: Ordering) {
+        self.tickets[slot].store(ticket as u32, order);
+    }
+
+    fn active_slots(&self) -> impl Iterator<Item = usize> + '_ {
+        0..N
+    }
+}
+
+impl<T: Default, const N: usize> Default for AsyncBakeryMutex<T, N> {
+    fn default() -> Self {
+        Self::new(T::default())
+    }
+}
+
+// The waker lists only ever hold wakers, which a panic can't leave half-updated.
+fn lock<T>(mutex: &Mutex<T>) -> std::sync::MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+// Adds `waker` to `wakers` unless it's already there from an earlier poll.
+fn register(wakers: &Mutex<Vec<Waker>>, waker: &Waker) {
+    let mut wakers = lock(wakers);
+    if !wakers.iter().any(|registered| registered.will_wake(waker)) {
+        wakers.push(waker.clone());
+    }
+}
+
+enum State {
+    // Waiting for a free slot.
+    Unclaimed,
+    // Through the doorway with `ticket` in `slot`, and past every slot before `next`.
+    Waiting {
+        slot: usize,
+        ticket: u64,
+        next: usize,
+    },
+    Done,
+}
+
+/// The future returned by [`AsyncBakeryMutex::lock`].
+///
+/// It's safe to cancel, as when it loses a `select!` or a timeout runs out: dropping it before it
+/// completes withdraws its ticket and gives its slot back, waking everyone who was waiting for
+/// either, so the tasks behind it carry on as though it had never been there. Wakeups are never
+/// handed to one task in particular, so a future that was woken and then dropped without being
+/// polled again doesn't take anyone else's turn with it.
+#[must_use = "futures do nothing unless polled"]
+pub struct AsyncBakeryLock<'a, T, const N: usize> {
+    mutex: &'a AsyncBakeryMutex<T, N>,
+    state: State,
+}
+
+impl<'a, T, const N: usize> Future for AsyncBakeryLock<'a, T, N> {
+    type Output = AsyncBakeryMutexGuard<'a, T, N>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let mutex = self.mutex;
+
+        if let State::Unclaimed = self.state {
+            let slot = match mutex.registry.claim() {
+                Some(slot) => slot,
+                None => {
+                    register(&mutex.slot_wakers, cx.waker());
+                    // A slot given back before we registered would have woken nobody.
+                    match mutex.registry.claim() {
+                        Some(slot) => slot,
+                        None => return Poll::Pending,
+                    }
+                }
+            };
+            let Some(ticket) = mutex.enter(slot) else {
+                // Rare enough, and over as soon as the oldest ticket leaves, that coming back
+                // right away is good enough.
+                mutex.leave(slot);
+                cx.waker().wake_by_ref();
+                return Poll::Pending;
+            };
+            self.state = State::Waiting {
+                slot,
+                ticket,
+                next: 0,
+            };
+        }
+
+        let State::Waiting { slot, ticket, next } = &mut self.state else {
+            panic!("`AsyncBakeryLock` polled after completion");
+        };
+        let (slot, ticket) = (*slot, *ticket);
+        while *next < N {
+            if *next != slot && mutex.blocked_by((ticket, slot), *next, cx.waker()) {
+                return Poll::Pending;
+            }
+            *next += 1;
+        }
+
+        // Synchronizes-with the release stores in `leave` of the tasks that went before us.
+        atomic::fence(Ordering::Acquire);
+        self.state = State::Done;
+        Poll::Ready(AsyncBakeryMutexGuard::new(mutex, slot))
+    }
+}
+
+impl<T, const N: usize> Drop for AsyncBakeryLock<'_, T, N> {
+    fn drop(&mut self) {
+        if let State::Waiting { slot, .. } = self.state {
+            // Withdrawing our ticket looks like an empty critical section to everyone else, and
+            // wakes whoever is waiting for it just as unlocking would.
+            self.mutex.leave(slot);
+        }
+    }
+}
+
+/// Access to the value in an [`AsyncBakeryMutex`], which is given up when the guard is dropped.
+///
+/// The guard can be sent to another thread along with its task, but only shared between threads
+/// if the value can:
+///
+/// ```compile_fail
+/// use std::{cell::Cell, thread};
+///
+/// let mutex = bakery::AsyncBakeryMutex::<Cell<u64>, 4>::new(Cell::new(0));
+/// let guard = mutex.try_lock().unwrap();
+/// thread::scope(|scope| {
+///     scope.spawn(|| guard.set(1));
+///     guard.set(2);
+/// });
+/// ```
+#[must_use = "dropping the guard immediately unlocks the mutex"]
+pub struct AsyncBakeryMutexGuard<'a, T, const N: usize> {
+    mutex: &'a AsyncBakeryMutex<T, N>,
+    slot: usize,
+    // Opts out of the automatic `Sync`, which is implemented below. Unlike `BakeryMutexGuard`, the
+    // guard stays `Send`, since slots aren't tied to threads.
+    _not_sync: PhantomData<Cell<()>>,
+}
+
+// SAFETY: sharing the guard only gives out `&T`, which is fine to share if `T` is `Sync`.
+unsafe impl<T: Sync, const N: usize> Sync for AsyncBakeryMutexGuard<'_, T, N> {}
+
+impl<'a, T, const N: usize> AsyncBakeryMutexGuard<'a, T, N> {
+    fn new(mutex: &'a AsyncBakeryMutex<T, N>, slot: usize) -> Self {
+        Self {
+            mutex,
+            slot,
+            _not_sync: PhantomData,
+        }
+    }
+
+    /// The slot the mutex was locked from.
+    pub fn slot(&self) -> usize {
+        self.slot
+    }
+}
+
+impl<T, const N: usize> Deref for AsyncBakeryMutexGuard<'_, T, N> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        // SAFETY: we hold the lock.
+        unsafe { &*self.mutex.data.get() }
+    }
+}
+
+impl<T, const N: usize> DerefMut for AsyncBakeryMutexGuard<'_, T, N> {
+    fn deref_mut(&mut self) -> &mut T {
+        // SAFETY: we hold the lock, and the guard is borrowed mutably.
+        unsafe { &mut *self.mutex.data.get() }
+    }
+}
+
+impl<T, const N: usize> Drop for AsyncBakeryMutexGuard<'_, T, N> {
+    fn drop(&mut self) {
+        self.mutex.leave(self.slot);
+    }
+}
+
+impl<T: fmt::Debug, const N: usize> fmt::Debug for AsyncBakeryMutexGuard<'_, T, N> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(&**self, f)
+    }
+}
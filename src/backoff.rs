@@ -0,0 +1,120 @@
+use crate::spin;
+
+/// How long a waiter backs off between two looks at the slot it's waiting on. A fresh one is made
+/// for every slot a thread waits on in [`lock`](crate::RawBakeryLock::lock), so the backoff
+/// starts short again whenever the thread moves on to the next slot.
+///
+/// Under contention every waiter scanning the same slot keeps its cache line moving between cores
+/// for every reread. Spinning longer between rereads gives the line a chance to stay put, at the
+/// cost of noticing a little later that the slot has let us past.
+pub trait Backoff {
+    /// A backoff that hasn't waited yet, and so starts out short.
+    fn new() -> Self;
+
+    /// Waits a little before the slot is read again.
+    fn snooze(&mut self);
+}
+
+/// Rereads the slot after every spin hint, as the lock always used to.
+pub struct NoBackoff;
+
+impl Backoff for NoBackoff {
+    fn new() -> Self {
+        NoBackoff
+    }
+
+    #[inline]
+    fn snooze(&mut self) {
+        spin::relax();
+    }
+}
+
+/// Doubles the number of spin hints between rereads every time, from `1 << MIN_EXPONENT` up to
+/// `1 << MAX_EXPONENT`. The default limit of 64 is a few microseconds with `pause`; `bench --tune`
+/// tries others.
+pub struct Exponential<const MIN_EXPONENT: u32 = 0, const MAX_EXPONENT: u32 = 6> {
+    exponent: u32,
+}
+
+impl<const MIN_EXPONENT: u32, const MAX_EXPONENT: u32> Backoff
+    for Exponential<MIN_EXPONENT, MAX_EXPONENT>
+{
+    fn new() -> Self {
+        Exponential {
+            exponent: MIN_EXPONENT,
+        }
+    }
+
+    fn snooze(&mut self) {
+        for _ in 0..1u32 << self.exponent {
+            spin::relax();
+        }
+        self.exponent = (self.exponent + 1).min(MAX_EXPONENT);
+    }
+}
+
+/// Like [`Exponential`], but waits for a random number of spin hints up to the current limit, so
+/// that waiters that started together don't all come back to the slot at the same time. The limit
+/// goes from `1 << MIN_EXPONENT` to `1 << MAX_EXPONENT` spin hints, as for `Exponential`.
+pub struct RandomizedExponential<const MIN_EXPONENT: u32 = 0, const MAX_EXPONENT: u32 = 6> {
+    exponent: u32,
+    // xorshift state, seeded on first use.
+    state: u64,
+}
+
+impl<const MIN_EXPONENT: u32, const MAX_EXPONENT: u32> Backoff
+    for RandomizedExponential<MIN_EXPONENT, MAX_EXPONENT>
+{
+    fn new() -> Self {
+        RandomizedExponential {
+            exponent: MIN_EXPONENT,
+            state: 0,
+        }
+    }
+
+    fn snooze(&mut self) {
+        if self.state == 0 {
+            // Each waiter's backoff lives on its own stack, so its address tells waiters apart
+            // without any shared state to contend on. Scrambled (splitmix64's finalizer) since
+            // nearby stacks differ only in a few bits, and forced odd so that it's never 0.
+            let mut seed = self as *const Self as usize as u64;
+            seed = (seed ^ (seed >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+            seed = (seed ^ (seed >> 27)).wrapping_mul(0x94d049bb133111eb);
+            self.state = (seed ^ (seed >> 31)) | 1;
+        }
+        self.state ^= self.state << 13;
+        self.state ^= self.state >> 7;
+        self.state ^= self.state << 17;
+
+        let limit = 1u64 << self.exponent;
+        for _ in 0..=self.state % limit {
+            spin::relax();
+        }
+        self.exponent = (self.exponent + 1).min(MAX_EXPONENT);
+    }
+}
+
+/// Spins for a while like [`NoBackoff`], and then gives up the rest of the time slice on every
+/// reread. With more runnable threads than cores, the thread we're waiting for may well be
+/// descheduled, and every quantum spent spinning is one it doesn't get to run in. It spins
+/// `SPIN_BUDGET` times before it starts yielding.
+#[cfg(feature = "std")]
+pub struct SpinThenYield<const SPIN_BUDGET: u32 = 100> {
+    spins: u32,
+}
+
+#[cfg(feature = "std")]
+impl<const SPIN_BUDGET: u32> Backoff for SpinThenYield<SPIN_BUDGET> {
+    fn new() -> Self {
+        SpinThenYield { spins: 0 }
+    }
+
+    fn snooze(&mut self) {
+        if self.spins < SPIN_BUDGET {
+            self.spins += 1;
+            spin::relax();
+        } else {
+            std::thread::yield_now();
+        }
+    }
+}
@@ -0,0 +1,107 @@
+use core::sync::atomic::{AtomicUsize, Ordering};
+#[cfg(feature = "std")]
+use std::time::{Duration, Instant};
+
+use crate::spin;
+
+// The phase lives in the high half of the state word and the arrival count in the low half, so
+// that a thread taking back its arrival can't mistake the next phase's count for its own.
+const PHASE_SHIFT: u32 = usize::BITS / 2;
+const ARRIVED: usize = (1 << PHASE_SHIFT) - 1;
+
+/// A sense-reversing barrier that lets `n` threads wait for each other, spinning rather than
+/// sleeping, so it works without `std` and releases everyone within a few cycles of the last
+/// arrival.
+///
+/// Like [`std::sync::Barrier`], it can be reused: once all `n` threads have called
+/// [`wait`](Self::wait), they're all let go and the next `n` calls make up the next phase. The
+/// sense is the parity of a phase counter, which the last thread to arrive bumps in the same
+/// store that resets the arrival count, so threads can't mix up two consecutive phases.
+pub struct Barrier {
+    n: usize,
+    state: AtomicUsize,
+}
+
+/// What [`Barrier::wait`] returns, telling whether this thread was the one that let the others go.
+#[derive(Clone, Copy, Debug)]
+pub struct BarrierWaitResult {
+    leader: bool,
+}
+
+impl BarrierWaitResult {
+    /// Whether this thread arrived last in its phase, which is true in exactly one of them.
+    pub fn is_leader(&self) -> bool {
+        self.leader
+    }
+}
+
+impl Barrier {
+    /// A barrier for `n` threads. With `n` of 0 or 1, nobody ever waits.
+    ///
+    /// # Panics
+    ///
+    /// If `n` doesn't fit in half a `usize`, which is where the arrival count is kept.
+    pub const fn new(n: usize) -> Self {
+        assert!(n <= ARRIVED, "too many threads for a barrier");
+        Self {
+            n,
+            state: AtomicUsize::new(0),
+        }
+    }
+
+    /// Waits until `n` threads have called `wait` in this phase. Exactly one of them, the last to
+    /// arrive, gets a result that [`is_leader`](BarrierWaitResult::is_leader).
+    pub fn wait(&self) -> BarrierWaitResult {
+        match self.wait_until(|| false) {
+            Some(result) => result,
+            None => unreachable!("waited without a deadline"),
+        }
+    }
+
+    /// Like [`wait`](Self::wait), but gives up after `timeout`, returning `None`. The arrival is
+    /// taken back first, so the phase still needs `n` threads other than this one to call `wait`
+    /// again before anyone is let go; a thread that times out can try again or leave for good
+    /// without the others being released early.
+    #[cfg(feature = "std")]
+    pub fn wait_timeout(&self, timeout: Duration) -> Option<BarrierWaitResult> {
+        let deadline = Instant::now().checked_add(timeout);
+        self.wait_until(|| deadline.is_some_and(|deadline| Instant::now() >= deadline))
+    }
+
+    fn wait_until(&self, mut expired: impl FnMut() -> bool) -> Option<BarrierWaitResult> {
+        // Acquire-release so that whoever arrives last sees everything done before the barrier by
+        // everyone else, and publishes it along with its own in the store below.
+        let state = self.state.fetch_add(1, Ordering::AcqRel);
+        // Can't have moved on yet, since moving on needs us to arrive first.
+        let phase = state >> PHASE_SHIFT;
+        if (state & ARRIVED) + 1 >= self.n {
+            // Reset the count and let everyone go at once, so nobody's next arrival is counted
+            // twice. The phase wraps within its half, since the shift drops the top bit.
+            self.state
+                .store(phase.wrapping_add(1) << PHASE_SHIFT, Ordering::Release);
+            return Some(BarrierWaitResult { leader: true });
+        }
+
+        loop {
+            let current = self.state.load(Ordering::Acquire);
+            if current >> PHASE_SHIFT != phase {
+                return Some(BarrierWaitResult { leader: false });
+            }
+            // A full count means the last thread has arrived and is about to let us go, having
+            // counted us, so it's too late to back out.
+            if current & ARRIVED < self.n && expired() {
+                // Fails if anyone else arrived or backed out meanwhile, or the phase moved on, in
+                // which case look again.
+                if self
+                    .state
+                    .compare_exchange(current, current - 1, Ordering::Relaxed, Ordering::Relaxed)
+                    .is_ok()
+                {
+                    return None;
+                }
+                continue;
+            }
+            spin::relax();
+        }
+    }
+}
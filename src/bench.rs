@@ -0,0 +1,1104 @@
+use std::{
+    cell::{Cell, UnsafeCell},
+    fs, hint, process,
+    sync::{
+        atomic::{self, AtomicUsize, Ordering},
+        Mutex,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+use bakery::{
+    backoff::{Backoff, Exponential, NoBackoff, RandomizedExponential, SpinThenYield},
+    layout::{Compact, Fused, Packed, Padded, SlotLayout},
+    spin::{self, SpinHint},
+    BakeryMutex, BakeryRwLock, NoObserver, RawBakeryLock, SlotLock,
+};
+
+#[cfg(feature = "burns-lynch")]
+use bakery::burns_lynch::BurnsLynchLock;
+#[cfg(feature = "dekker")]
+use bakery::dekker::DekkerLock;
+#[cfg(feature = "filter")]
+use bakery::filter::FilterLock;
+#[cfg(feature = "lamport-fast")]
+use bakery::lamport_fast::LamportFastLock;
+#[cfg(feature = "peterson")]
+use bakery::peterson::PetersonLock;
+#[cfg(feature = "szymanski")]
+use bakery::szymanski::SzymanskiLock;
+#[cfg(feature = "ticket")]
+use bakery::ticket::TicketLock;
+#[cfg(feature = "ttas")]
+use bakery::ttas::TtasLock;
+#[cfg(feature = "black-white")]
+use bakery::BWBakeryLock;
+#[cfg(feature = "futex")]
+use bakery::FutexBakeryLock;
+#[cfg(feature = "hierarchical")]
+use bakery::HierarchicalBakeryLock;
+
+#[cfg(feature = "test-and-set")]
+use crate::starvation::SpinLock;
+
+use crate::{
+    energy::Rapl,
+    output::{Format, Table},
+    results, stats, topology, workers, UnsafeSyncCell,
+};
+
+const NUM_SLOTS: usize = 10;
+
+// Returns the average time in nanoseconds of `iterations` runs of `op`, which gets the iteration
+// number so that it can't be hoisted out of the loop.
+fn time(iterations: usize, mut op: impl FnMut(usize)) -> f64 {
+    let start = Instant::now();
+    for i in 0..iterations {
+        op(hint::black_box(i));
+    }
+    start.elapsed().as_secs_f64() * 1e9 / iterations as f64
+}
+
+// Has `threads` workers count to `iterations` each on `lock`, panicking on any lost update.
+fn count<S: SlotLayout<NUM_SLOTS> + Sync>(
+    lock: &RawBakeryLock<NUM_SLOTS, NoObserver, S>,
+    threads: usize,
+    iterations: usize,
+) -> Duration {
+    let mut num = UnsafeSyncCell(UnsafeCell::new(0));
+
+    let start = Instant::now();
+    thread::scope(|scope| {
+        for thread_id in 0..threads {
+            let num = &num;
+            workers::spawn(scope, "bench", thread_id, move |worker| {
+                for _ in 0..iterations {
+                    lock.lock(worker.id);
+                    unsafe {
+                        *num.0.get() += 1;
+                    }
+                    lock.unlock(worker.id);
+                }
+            });
+        }
+    });
+    let elapsed = start.elapsed();
+
+    assert_eq!(
+        *num.0.get_mut(),
+        threads * iterations,
+        "lost updates while measuring"
+    );
+    elapsed
+}
+
+// Measures the building blocks of an acquisition on their own, on a single thread so that nothing
+// else competes for the cache lines involved.
+fn fences(iterations: usize, format: Format) {
+    let atomic = AtomicUsize::new(0);
+    let lock = RawBakeryLock::<NUM_SLOTS>::new();
+
+    let baseline = time(iterations, |i| atomic.store(i, Ordering::Relaxed));
+    let rows = [
+        ("relaxed store", baseline),
+        (
+            "SeqCst fence",
+            time(iterations, |i| {
+                atomic.store(i, Ordering::Relaxed);
+                atomic::fence(Ordering::SeqCst);
+            }),
+        ),
+        (
+            "Acquire fence",
+            time(iterations, |i| {
+                atomic.store(i, Ordering::Relaxed);
+                atomic::fence(Ordering::Acquire);
+            }),
+        ),
+        (
+            "SeqCst store",
+            time(iterations, |i| atomic.store(i, Ordering::SeqCst)),
+        ),
+        (
+            "locked RMW",
+            time(iterations, |i| {
+                atomic.fetch_add(i, Ordering::Relaxed);
+            }),
+        ),
+        (
+            "spin hint",
+            time(iterations, |i| {
+                atomic.store(i, Ordering::Relaxed);
+                spin::relax();
+            }),
+        ),
+        (
+            "uncontended lock",
+            time(iterations, |_| {
+                lock.lock(0);
+                lock.unlock(0);
+            }),
+        ),
+    ];
+
+    if format.is_text() {
+        println!(
+            "{iterations} iterations, fences {}, spin hint {}, costs beyond a relaxed store:",
+            results::fence_config(),
+            spin::hint().name()
+        );
+    }
+    let table = Table::new(
+        format,
+        &[
+            "operation",
+            "iterations",
+            "fences",
+            "spin",
+            "ns",
+            "extra_ns",
+        ],
+    );
+    for (name, cost) in rows {
+        if format.is_text() {
+            println!("{name:<20} {cost:>8.2}ns {:>+8.2}ns", cost - baseline);
+        }
+        table.row([
+            name.into(),
+            iterations.into(),
+            results::fence_config().into(),
+            spin::hint().name().into(),
+            cost.into(),
+            (cost - baseline).into(),
+        ]);
+    }
+}
+
+// Runs the contended counter once with every spin hint available on this CPU and reports how much
+// energy the packages used per million acquisitions.
+fn energy(iterations: usize, format: Format) {
+    let Some(rapl) = Rapl::open() else {
+        eprintln!("no readable RAPL energy counters (they usually require root)");
+        process::exit(1);
+    };
+
+    let topology = topology::detect();
+    let threads = topology.physical_cores.clamp(1, NUM_SLOTS);
+    let acquisitions = threads * iterations;
+
+    let ((), idle) = rapl.measure(|| thread::sleep(Duration::from_secs(1)));
+    if format.is_text() {
+        println!(
+            "{threads} threads, {iterations} iterations each ({topology}), idle power {idle:.2}W"
+        );
+        println!(
+            "{:<10} {:>12} {:>12} {:>10}",
+            "hint", "elapsed", "J/M acq", "power"
+        );
+    }
+    let table = Table::new(
+        format,
+        &[
+            "hint",
+            "threads",
+            "iterations",
+            "elapsed_ms",
+            "joules_per_million",
+            "watts",
+            "idle_watts",
+        ],
+    );
+
+    // Without `spin-hint`, the lock only ever runs the standard library's.
+    let hints = if cfg!(feature = "spin-hint") {
+        &SpinHint::ALL[..]
+    } else {
+        &[SpinHint::Std]
+    };
+    for &hint in hints.iter().filter(|hint| hint.is_available()) {
+        #[cfg(feature = "spin-hint")]
+        spin::set_hint(hint);
+
+        let lock = RawBakeryLock::<NUM_SLOTS>::new();
+        let (elapsed, joules) = rapl.measure(|| count(&lock, threads, iterations));
+
+        let per_million = joules * 1e6 / acquisitions as f64;
+        let power = joules / elapsed.as_secs_f64();
+        if format.is_text() {
+            println!(
+                "{:<10} {elapsed:>12.2?} {per_million:>12.3} {power:>9.2}W",
+                hint.name()
+            );
+        }
+        table.row([
+            hint.name().into(),
+            threads.into(),
+            iterations.into(),
+            (elapsed.as_secs_f64() * 1e3).into(),
+            per_million.into(),
+            power.into(),
+            idle.into(),
+        ]);
+    }
+}
+
+// The lock variants `compare` pits against each other.
+const CONTENDERS: [&str; 4] = ["packed", "padded", "compact", "fused"];
+
+// Returns the time per acquisition in nanoseconds of a single trial with contender `index`.
+fn trial(index: usize, threads: usize, iterations: usize) -> f64 {
+    let elapsed = match index {
+        0 => count(
+            &RawBakeryLock::from_parts(NoObserver, Packed::<NUM_SLOTS>::new()),
+            threads,
+            iterations,
+        ),
+        1 => count(
+            &RawBakeryLock::from_parts(NoObserver, Padded::<NUM_SLOTS>::new()),
+            threads,
+            iterations,
+        ),
+        2 => count(
+            &RawBakeryLock::from_parts(NoObserver, Compact::new()),
+            threads,
+            iterations,
+        ),
+        _ => count(
+            &RawBakeryLock::from_parts(NoObserver, Fused::new()),
+            threads,
+            iterations,
+        ),
+    };
+    elapsed.as_secs_f64() * 1e9 / (threads * iterations) as f64
+}
+
+// Runs interleaved trials of every contender and tests whether the differences between each pair
+// are more than noise.
+fn compare(trials: usize, iterations: usize, format: Format) {
+    let topology = topology::detect();
+    let threads = topology.physical_cores.clamp(1, NUM_SLOTS);
+    if format.is_text() {
+        println!("{threads} threads, {iterations} iterations each ({topology}), {trials} trials");
+    }
+
+    // Rotate the order within each round so that slow drifts in the machine's state (frequency
+    // scaling, other load) affect every contender alike.
+    let mut samples = vec![Vec::new(); CONTENDERS.len()];
+    for round in 0..trials {
+        for offset in 0..CONTENDERS.len() {
+            let index = (round + offset) % CONTENDERS.len();
+            samples[index].push(trial(index, threads, iterations));
+        }
+    }
+
+    if format.is_text() {
+        for (name, samples) in CONTENDERS.iter().zip(&samples) {
+            println!("{name:<10} median {:>8.2}ns/acq", stats::median(samples));
+        }
+        println!();
+        println!("{:<20} {:>10} {:>10} {:>8}", "pair", "ratio", "p", "effect");
+    }
+
+    // One row per pair, so that every row has the same columns.
+    let table = Table::new(
+        format,
+        &[
+            "a",
+            "b",
+            "threads",
+            "iterations",
+            "trials",
+            "a_median_ns",
+            "b_median_ns",
+            "ratio",
+            "p_value",
+            "effect_size",
+        ],
+    );
+    for a in 0..CONTENDERS.len() {
+        for b in a + 1..CONTENDERS.len() {
+            let comparison = stats::mann_whitney(&samples[a], &samples[b]);
+            let (median_a, median_b) = (stats::median(&samples[a]), stats::median(&samples[b]));
+            let ratio = median_a / median_b;
+            if format.is_text() {
+                println!(
+                    "{:<20} {ratio:>10.3} {:>10.4} {:>+8.2}{}",
+                    format!("{} vs {}", CONTENDERS[a], CONTENDERS[b]),
+                    comparison.p_value,
+                    comparison.effect_size,
+                    if comparison.p_value < 0.05 { "  *" } else { "" }
+                );
+            }
+            table.row([
+                CONTENDERS[a].into(),
+                CONTENDERS[b].into(),
+                threads.into(),
+                iterations.into(),
+                trials.into(),
+                median_a.into(),
+                median_b.into(),
+                ratio.into(),
+                comparison.p_value.into(),
+                comparison.effect_size.into(),
+            ]);
+        }
+    }
+}
+
+// Has `threads` workers call `increment` `iterations` times each, returning the time per call in
+// nanoseconds across all of them along with the median and 99th percentile of single calls.
+fn contend(threads: usize, iterations: usize, increment: impl Fn() + Sync) -> [f64; 3] {
+    let mut latencies = Vec::with_capacity(threads * iterations);
+
+    let start = Instant::now();
+    thread::scope(|scope| {
+        let workers: Vec<_> = (0..threads)
+            .map(|thread_id| {
+                let increment = &increment;
+                workers::spawn(scope, "bench", thread_id, move |_| {
+                    (0..iterations)
+                        .map(|_| {
+                            let start = Instant::now();
+                            increment();
+                            start.elapsed().as_secs_f64() * 1e9
+                        })
+                        .collect::<Vec<_>>()
+                })
+            })
+            .collect();
+        for worker in workers {
+            latencies.extend(worker.join().expect("bench worker panicked"));
+        }
+    });
+    let elapsed = start.elapsed();
+
+    [
+        elapsed.as_secs_f64() * 1e9 / latencies.len() as f64,
+        stats::median(&latencies),
+        stats::percentile(&latencies, 0.99),
+    ]
+}
+
+// Measures throughput and latency of the bakery lock next to the standard library's mutex (and the
+// test-and-set, ticket and TTAS spinlocks, if built) as the number of contending threads grows.
+fn mutex(iterations: usize, format: Format) {
+    let topology = topology::detect();
+    if format.is_text() {
+        println!("{iterations} iterations per thread ({topology}), times per acquisition:");
+        println!(
+            "{:<8} {:<14} {:>12} {:>12} {:>12}",
+            "threads", "lock", "throughput", "median", "p99"
+        );
+    }
+    let table = Table::new(
+        format,
+        &[
+            "threads",
+            "lock",
+            "iterations",
+            "throughput_ns",
+            "median_ns",
+            "p99_ns",
+        ],
+    );
+
+    for threads in [1, 2, 4, 8]
+        .into_iter()
+        .filter(|&threads| threads <= NUM_SLOTS)
+    {
+        let expected = threads * iterations;
+        let mut rows = Vec::new();
+
+        let bakery = BakeryMutex::<usize, NUM_SLOTS>::new(0);
+        rows.push((
+            "bakery",
+            contend(threads, iterations, || *bakery.lock() += 1),
+        ));
+        assert_eq!(
+            bakery.into_inner(),
+            expected,
+            "lost updates while measuring"
+        );
+
+        let std = Mutex::new(0);
+        rows.push((
+            "std::sync",
+            contend(threads, iterations, || *std.lock().unwrap() += 1),
+        ));
+        assert_eq!(std.into_inner().unwrap(), expected);
+
+        #[cfg(feature = "test-and-set")]
+        {
+            let spin_lock = SpinLock::default();
+            let mut num = UnsafeSyncCell(UnsafeCell::new(0));
+            let (spin_lock, cell) = (&spin_lock, &num);
+            rows.push((
+                "test-and-set",
+                contend(threads, iterations, move || {
+                    spin_lock.lock();
+                    unsafe {
+                        *cell.0.get() += 1;
+                    }
+                    spin_lock.unlock();
+                }),
+            ));
+            assert_eq!(*num.0.get_mut(), expected, "lost updates while measuring");
+        }
+
+        #[cfg(feature = "ticket")]
+        {
+            let ticket_lock = TicketLock::new();
+            let mut num = UnsafeSyncCell(UnsafeCell::new(0));
+            let (ticket_lock, cell) = (&ticket_lock, &num);
+            rows.push((
+                "ticket",
+                contend(threads, iterations, move || {
+                    ticket_lock.lock();
+                    unsafe {
+                        *cell.0.get() += 1;
+                    }
+                    ticket_lock.unlock();
+                }),
+            ));
+            assert_eq!(*num.0.get_mut(), expected, "lost updates while measuring");
+        }
+
+        #[cfg(feature = "ttas")]
+        {
+            let ttas_lock = TtasLock::new();
+            let mut num = UnsafeSyncCell(UnsafeCell::new(0));
+            let (ttas_lock, cell) = (&ttas_lock, &num);
+            rows.push((
+                "ttas",
+                contend(threads, iterations, move || {
+                    ttas_lock.lock();
+                    unsafe {
+                        *cell.0.get() += 1;
+                    }
+                    ttas_lock.unlock();
+                }),
+            ));
+            assert_eq!(*num.0.get_mut(), expected, "lost updates while measuring");
+        }
+
+        for (name, [throughput, median, p99]) in rows {
+            if format.is_text() {
+                println!(
+                    "{threads:<8} {name:<14} {throughput:>10.1}ns {median:>10.1}ns {p99:>10.1}ns"
+                );
+            }
+            table.row([
+                threads.into(),
+                name.into(),
+                iterations.into(),
+                throughput.into(),
+                median.into(),
+                p99.into(),
+            ]);
+        }
+    }
+}
+
+// How many of every `RW_OPS` operations in `rwlock` are writes, and how long every operation
+// spends in the critical section, in iterations of `spin_loop`.
+const RW_OPS: usize = 10;
+const RW_WRITES: usize = 1;
+const RW_WORK: usize = 200;
+
+// Measures a read-mostly workload on the bakery mutex and on the reader-writer bakery lock, where
+// consecutive readers share the critical section, as the number of contending threads grows.
+fn rwlock(iterations: usize, format: Format) {
+    thread_local! {
+        // How many operations the current thread has done, to pick the writes.
+        static OPS: Cell<usize> = const { Cell::new(0) };
+    }
+    fn is_write() -> bool {
+        OPS.with(|ops| {
+            ops.set(ops.get() + 1);
+            ops.get() % RW_OPS < RW_WRITES
+        })
+    }
+    fn work() {
+        for _ in 0..RW_WORK {
+            hint::spin_loop();
+        }
+    }
+
+    let topology = topology::detect();
+    if format.is_text() {
+        println!(
+            "{iterations} iterations per thread, {RW_WRITES} in {RW_OPS} of them writes \
+             ({topology}), times per operation:"
+        );
+        println!(
+            "{:<8} {:<14} {:>12} {:>12} {:>12}",
+            "threads", "lock", "throughput", "median", "p99"
+        );
+    }
+    let table = Table::new(
+        format,
+        &[
+            "threads",
+            "lock",
+            "iterations",
+            "throughput_ns",
+            "median_ns",
+            "p99_ns",
+        ],
+    );
+
+    for threads in [1, 2, 4, 8]
+        .into_iter()
+        .filter(|&threads| threads <= NUM_SLOTS)
+    {
+        let mutex = BakeryMutex::<usize, NUM_SLOTS>::new(0);
+        let exclusive = contend(threads, iterations, || {
+            let mut value = mutex.lock();
+            if is_write() {
+                *value += 1;
+            } else {
+                hint::black_box(*value);
+            }
+            work();
+        });
+
+        let rwlock = BakeryRwLock::<usize, NUM_SLOTS>::new(0);
+        let shared = contend(threads, iterations, || {
+            if is_write() {
+                let mut value = rwlock.write();
+                *value += 1;
+                work();
+            } else {
+                let value = rwlock.read();
+                hint::black_box(*value);
+                work();
+            }
+        });
+        assert_eq!(
+            mutex.into_inner(),
+            rwlock.into_inner(),
+            "lost updates while measuring"
+        );
+
+        for (name, [throughput, median, p99]) in [("bakery", exclusive), ("bakery-rw", shared)] {
+            if format.is_text() {
+                println!(
+                    "{threads:<8} {name:<14} {throughput:>10.1}ns {median:>10.1}ns {p99:>10.1}ns"
+                );
+            }
+            table.row([
+                threads.into(),
+                name.into(),
+                iterations.into(),
+                throughput.into(),
+                median.into(),
+                p99.into(),
+            ]);
+        }
+    }
+}
+
+// The slots of the locks `hierarchical` compares, and how they're split into groups.
+#[cfg(feature = "hierarchical")]
+const MANY_SLOTS: usize = 256;
+#[cfg(feature = "hierarchical")]
+const GROUPS: usize = 16;
+#[cfg(feature = "hierarchical")]
+const PER_GROUP: usize = MANY_SLOTS / GROUPS;
+
+// Has `threads` workers count to `iterations` each through `lock` and `unlock`, with worker `i`
+// using slot `slot(i)`, and returns the time per acquisition in nanoseconds across all of them.
+fn acquisitions(
+    threads: usize,
+    iterations: usize,
+    slot: impl Fn(usize) -> usize + Sync,
+    lock: impl Fn(usize) + Sync,
+    unlock: impl Fn(usize) + Sync,
+) -> f64 {
+    let mut num = UnsafeSyncCell(UnsafeCell::new(0));
+
+    let start = Instant::now();
+    thread::scope(|scope| {
+        for thread_id in 0..threads {
+            let (num, slot, lock, unlock) = (&num, &slot, &lock, &unlock);
+            workers::spawn(scope, "bench", thread_id, move |worker| {
+                let slot = slot(worker.id);
+                for _ in 0..iterations {
+                    lock(slot);
+                    unsafe {
+                        *num.0.get() += 1;
+                    }
+                    unlock(slot);
+                }
+            });
+        }
+    });
+    let elapsed = start.elapsed();
+
+    assert_eq!(
+        *num.0.get_mut(),
+        threads * iterations,
+        "lost updates while measuring"
+    );
+    elapsed.as_secs_f64() * 1e9 / (threads * iterations) as f64
+}
+
+// Measures the time per acquisition of a flat bakery lock with `MANY_SLOTS` slots next to a
+// hierarchical one with the same number of slots, as the number of contending threads grows. The
+// hierarchical lock runs twice: with every thread in a group of its own, so that all of them meet
+// in the top-level bakery, and with the threads packed into as few groups as possible.
+#[cfg(feature = "hierarchical")]
+fn hierarchical(iterations: usize, format: Format) {
+    let topology = topology::detect();
+    if format.is_text() {
+        println!(
+            "{MANY_SLOTS} slots ({GROUPS} groups of {PER_GROUP}), {iterations} iterations per \
+             thread ({topology}), time per acquisition:"
+        );
+        println!(
+            "{:<8} {:>12} {:>12} {:>12}",
+            "threads", "flat", "spread", "grouped"
+        );
+    }
+    let table = Table::new(
+        format,
+        &[
+            "threads",
+            "iterations",
+            "flat_ns",
+            "spread_ns",
+            "grouped_ns",
+        ],
+    );
+
+    for threads in [1, 2, 4, 8, 16] {
+        let flat = RawBakeryLock::<MANY_SLOTS>::new();
+        let flat = acquisitions(
+            threads,
+            iterations,
+            |thread| thread,
+            |slot| flat.lock(slot),
+            |slot| flat.unlock(slot),
+        );
+
+        let lock = HierarchicalBakeryLock::<GROUPS, PER_GROUP>::new();
+        let spread = acquisitions(
+            threads,
+            iterations,
+            |thread| thread % GROUPS * PER_GROUP + thread / GROUPS,
+            |slot| lock.lock(slot),
+            |slot| lock.unlock(slot),
+        );
+        let lock = HierarchicalBakeryLock::<GROUPS, PER_GROUP>::new();
+        let grouped = acquisitions(
+            threads,
+            iterations,
+            |thread| thread,
+            |slot| lock.lock(slot),
+            |slot| lock.unlock(slot),
+        );
+
+        if format.is_text() {
+            println!("{threads:<8} {flat:>10.1}ns {spread:>10.1}ns {grouped:>10.1}ns");
+        }
+        table.row([
+            threads.into(),
+            iterations.into(),
+            flat.into(),
+            spread.into(),
+            grouped.into(),
+        ]);
+    }
+}
+
+// Enough slots for the thread counts at which backing off starts to matter.
+const BACKOFF_SLOTS: usize = 32;
+
+// Has `threads` workers count on a lock with `BACKOFF_SLOTS` slots waiting with backoff `B`, and
+// returns the time per acquisition in nanoseconds.
+fn with_backoff<B: Backoff>(threads: usize, iterations: usize) -> f64 {
+    let lock = RawBakeryLock::<BACKOFF_SLOTS>::new().with_backoff::<B>();
+    acquisitions(
+        threads,
+        iterations,
+        |thread| thread,
+        |slot| lock.lock(slot),
+        |slot| lock.unlock(slot),
+    )
+}
+
+// Measures the time per acquisition with each backoff strategy as the number of contending threads
+// grows. Backing off trades a little latency in noticing the slot has changed for less traffic on
+// its cache line, which should only pay off once enough threads scan the same slots. Yielding
+// should win by far once there are more threads than hardware threads to run them.
+fn backoff(iterations: usize, format: Format) {
+    let topology = topology::detect();
+    if format.is_text() {
+        println!(
+            "{BACKOFF_SLOTS} slots, {iterations} iterations per thread ({topology}), spin hint {}, \
+             time per acquisition:",
+            spin::hint().name()
+        );
+        println!(
+            "{:<8} {:>12} {:>12} {:>12} {:>12}",
+            "threads", "none", "exponential", "randomized", "yield"
+        );
+    }
+    let table = Table::new(
+        format,
+        &[
+            "threads",
+            "iterations",
+            "none_ns",
+            "exponential_ns",
+            "randomized_ns",
+            "yield_ns",
+        ],
+    );
+
+    for threads in [1, 2, 4, 8, 16, 32] {
+        let none = with_backoff::<NoBackoff>(threads, iterations);
+        let exponential = with_backoff::<Exponential>(threads, iterations);
+        let randomized = with_backoff::<RandomizedExponential>(threads, iterations);
+        let yielding = with_backoff::<SpinThenYield>(threads, iterations);
+
+        if format.is_text() {
+            println!(
+                "{threads:<8} {none:>10.1}ns {exponential:>10.1}ns {randomized:>10.1}ns \
+                 {yielding:>10.1}ns"
+            );
+        }
+        table.row([
+            threads.into(),
+            iterations.into(),
+            none.into(),
+            exponential.into(),
+            randomized.into(),
+            yielding.into(),
+        ]);
+    }
+}
+
+// One backoff's run of `tune`, as `with_backoff` is for each backoff type.
+type Tuning = fn(usize, usize) -> f64;
+
+// Every backoff `tune` tries, named as the type it is, with the default parameters of each
+// strategy among them.
+const TUNINGS: &[(&str, Tuning)] = &[
+    ("NoBackoff", with_backoff::<NoBackoff>),
+    ("Exponential<0, 2>", with_backoff::<Exponential<0, 2>>),
+    ("Exponential<0, 4>", with_backoff::<Exponential<0, 4>>),
+    ("Exponential<0, 6>", with_backoff::<Exponential<0, 6>>),
+    ("Exponential<0, 8>", with_backoff::<Exponential<0, 8>>),
+    ("Exponential<2, 6>", with_backoff::<Exponential<2, 6>>),
+    ("Exponential<2, 8>", with_backoff::<Exponential<2, 8>>),
+    ("Exponential<4, 10>", with_backoff::<Exponential<4, 10>>),
+    (
+        "RandomizedExponential<0, 4>",
+        with_backoff::<RandomizedExponential<0, 4>>,
+    ),
+    (
+        "RandomizedExponential<0, 6>",
+        with_backoff::<RandomizedExponential<0, 6>>,
+    ),
+    (
+        "RandomizedExponential<0, 8>",
+        with_backoff::<RandomizedExponential<0, 8>>,
+    ),
+    (
+        "RandomizedExponential<2, 8>",
+        with_backoff::<RandomizedExponential<2, 8>>,
+    ),
+    (
+        "RandomizedExponential<4, 10>",
+        with_backoff::<RandomizedExponential<4, 10>>,
+    ),
+    ("SpinThenYield<10>", with_backoff::<SpinThenYield<10>>),
+    ("SpinThenYield<100>", with_backoff::<SpinThenYield<100>>),
+    ("SpinThenYield<1000>", with_backoff::<SpinThenYield<1000>>),
+    ("SpinThenYield<10000>", with_backoff::<SpinThenYield<10000>>),
+];
+
+// Times every backoff in `TUNINGS` at each number of contending threads, taking the median of
+// `trials` runs, and reports the fastest one for each along with how it compares to not backing
+// off at all. With `emit`, also writes the winners to that path as type aliases, `Tuned<threads>`,
+// which a build can `include!` and pass to `RawBakeryLock::with_backoff`.
+fn tune(trials: usize, iterations: usize, emit: Option<&str>, format: Format) {
+    let topology = topology::detect();
+    if format.is_text() {
+        println!(
+            "{BACKOFF_SLOTS} slots, {iterations} iterations per thread ({topology}), spin hint {}, \
+             median of {trials} trials:",
+            spin::hint().name()
+        );
+        println!(
+            "{:<8} {:<30} {:>12} {:>12}",
+            "threads", "best", "time", "vs none"
+        );
+    }
+    let table = Table::new(
+        format,
+        &[
+            "threads",
+            "iterations",
+            "trials",
+            "best",
+            "best_ns",
+            "none_ns",
+        ],
+    );
+
+    let mut best = Vec::new();
+    for threads in [1, 2, 4, 8, 16, 32] {
+        let times: Vec<_> = TUNINGS
+            .iter()
+            .map(|&(_, measure)| {
+                let samples: Vec<_> = (0..trials).map(|_| measure(threads, iterations)).collect();
+                stats::median(&samples)
+            })
+            .collect();
+        let (fastest, &time) = times
+            .iter()
+            .enumerate()
+            .min_by(|(_, a), (_, b)| a.total_cmp(b))
+            .expect("no backoffs to tune");
+        let (name, none) = (TUNINGS[fastest].0, times[0]);
+
+        if format.is_text() {
+            println!(
+                "{threads:<8} {name:<30} {time:>10.1}ns {:>11.2}x",
+                none / time
+            );
+        }
+        table.row([
+            threads.into(),
+            iterations.into(),
+            trials.into(),
+            name.into(),
+            time.into(),
+            none.into(),
+        ]);
+        best.push((threads, name, time));
+    }
+
+    let Some(path) = emit else {
+        return;
+    };
+    let mut config = format!(
+        "// The fastest backoff for each number of threads contending for a lock with \
+         {BACKOFF_SLOTS} slots,\n// as found by `bakery bench --tune` on {} ({topology}) with \
+         spin hint {}.\n",
+        results::hostname(),
+        spin::hint().name()
+    );
+    for (threads, name, time) in best {
+        config += &format!(
+            "\n// {time:.1}ns per acquisition.\npub type Tuned{threads} = bakery::backoff::{name};\n"
+        );
+    }
+    if let Err(err) = fs::write(path, config) {
+        eprintln!("failed to write tuned backoffs to {path}: {err}");
+        process::exit(1);
+    }
+}
+
+// Has `threads` workers count on a fresh `L`, and returns the time per acquisition in nanoseconds,
+// or `None` if it doesn't have enough slots.
+fn slot_lock<L: SlotLock + Default + Sync>(threads: usize, iterations: usize) -> Option<f64> {
+    let lock = L::default();
+    if threads > lock.slots() {
+        return None;
+    }
+    Some(acquisitions(
+        threads,
+        iterations,
+        |thread| thread,
+        |slot| lock.lock(slot),
+        |slot| lock.unlock(slot),
+    ))
+}
+
+// One lock's run of `locks`, as `slot_lock` is for each lock type.
+type Measure = fn(usize, usize) -> Option<f64>;
+
+// Every built lock that takes explicit slots, with `NUM_SLOTS` slots where it's up to us.
+const SLOT_LOCKS: &[(&str, Measure)] = &[
+    ("bakery", slot_lock::<RawBakeryLock<NUM_SLOTS>>),
+    #[cfg(feature = "black-white")]
+    ("black-white", slot_lock::<BWBakeryLock<NUM_SLOTS>>),
+    #[cfg(feature = "burns-lynch")]
+    ("burns-lynch", slot_lock::<BurnsLynchLock<NUM_SLOTS>>),
+    #[cfg(feature = "dekker")]
+    ("dekker", slot_lock::<DekkerLock>),
+    #[cfg(feature = "filter")]
+    ("filter", slot_lock::<FilterLock<NUM_SLOTS>>),
+    #[cfg(feature = "futex")]
+    ("futex", slot_lock::<FutexBakeryLock<NUM_SLOTS>>),
+    #[cfg(feature = "hierarchical")]
+    (
+        "hierarchical",
+        slot_lock::<HierarchicalBakeryLock<{ NUM_SLOTS / 2 }, 2>>,
+    ),
+    #[cfg(feature = "lamport-fast")]
+    ("lamport-fast", slot_lock::<LamportFastLock<NUM_SLOTS>>),
+    #[cfg(feature = "peterson")]
+    ("peterson", slot_lock::<PetersonLock>),
+    #[cfg(feature = "szymanski")]
+    ("szymanski", slot_lock::<SzymanskiLock<NUM_SLOTS>>),
+    #[cfg(feature = "ticket")]
+    ("ticket", slot_lock::<TicketLock>),
+    #[cfg(feature = "ttas")]
+    ("ttas", slot_lock::<TtasLock>),
+];
+
+// Measures the time per acquisition of every lock in `SLOT_LOCKS` as the number of contending
+// threads grows, up to as many as each lock has slots for.
+fn locks(iterations: usize, format: Format) {
+    let topology = topology::detect();
+    if format.is_text() {
+        println!("{iterations} iterations per thread ({topology}), time per acquisition:");
+        println!("{:<8} {:<14} {:>12}", "threads", "lock", "time");
+    }
+    let table = Table::new(format, &["threads", "lock", "iterations", "time_ns"]);
+
+    for threads in [1, 2, 4, 8]
+        .into_iter()
+        .filter(|&threads| threads <= NUM_SLOTS)
+    {
+        for &(name, measure) in SLOT_LOCKS {
+            let Some(time) = measure(threads, iterations) else {
+                continue;
+            };
+            if format.is_text() {
+                println!("{threads:<8} {name:<14} {time:>10.1}ns");
+            }
+            table.row([threads.into(), name.into(), iterations.into(), time.into()]);
+        }
+    }
+}
+
+// Returns the time in nanoseconds of an uncontended lock and unlock of a fresh `L`.
+#[cfg(feature = "lamport-fast")]
+fn uncontended_lock<L: SlotLock + Default>(iterations: usize) -> f64 {
+    let lock = L::default();
+    time(iterations, |_| {
+        lock.lock(0);
+        lock.unlock(0);
+    })
+}
+
+// `uncontended_lock` for some lock type.
+#[cfg(feature = "lamport-fast")]
+type Uncontended = fn(usize) -> f64;
+
+// The slot counts `uncontended` compares at, with the bakery lock and the fast lock of that size.
+#[cfg(feature = "lamport-fast")]
+const UNCONTENDED: &[(usize, Uncontended, Uncontended)] = &[
+    (
+        4,
+        uncontended_lock::<RawBakeryLock<4>>,
+        uncontended_lock::<LamportFastLock<4>>,
+    ),
+    (
+        16,
+        uncontended_lock::<RawBakeryLock<16>>,
+        uncontended_lock::<LamportFastLock<16>>,
+    ),
+    (
+        64,
+        uncontended_lock::<RawBakeryLock<64>>,
+        uncontended_lock::<LamportFastLock<64>>,
+    ),
+    (
+        256,
+        uncontended_lock::<RawBakeryLock<256>>,
+        uncontended_lock::<LamportFastLock<256>>,
+    ),
+];
+
+// Measures an uncontended lock and unlock on a single thread as the number of slots grows. The
+// bakery lock's doorway and wait loop scan every slot whether or not anyone else is there, so its
+// time grows with the slots, while Lamport's fast lock only touches `x`, `y` and its own flag when
+// nobody else is trying to enter.
+#[cfg(feature = "lamport-fast")]
+fn uncontended(iterations: usize, format: Format) {
+    if format.is_text() {
+        println!("{iterations} iterations, time per uncontended lock and unlock:");
+        println!("{:<8} {:>12} {:>12}", "slots", "bakery", "fast");
+    }
+    let table = Table::new(format, &["slots", "iterations", "bakery_ns", "fast_ns"]);
+
+    for &(slots, bakery, fast) in UNCONTENDED {
+        let (bakery, fast) = (bakery(iterations), fast(iterations));
+        if format.is_text() {
+            println!("{slots:<8} {bakery:>10.1}ns {fast:>10.1}ns");
+        }
+        table.row([slots.into(), iterations.into(), bakery.into(), fast.into()]);
+    }
+}
+
+fn usage() -> ! {
+    eprintln!(
+        "usage: bakery bench <fences|energy|compare|mutex|rwlock|hierarchical|backoff|locks|uncontended> \
+         [--iterations <n>] [--trials <n>] [--output <text|json|csv>]\n       \
+         bakery bench --tune [--iterations <n>] [--trials <n>] [--emit <path>] \
+         [--output <text|json|csv>]"
+    );
+    process::exit(2);
+}
+
+pub fn run(args: &[String]) {
+    let Some((bench, mut flags)) = args.split_first() else {
+        usage()
+    };
+
+    let mut iterations = None;
+    let mut trials = None;
+    let mut emit = None;
+    let mut format = Format::Text;
+    while let [flag, value, rest @ ..] = flags {
+        if flag == "--output" {
+            format = Format::from_name(value).unwrap_or_else(|| usage());
+            flags = rest;
+            continue;
+        }
+        if flag == "--emit" && bench == "--tune" {
+            emit = Some(value.as_str());
+            flags = rest;
+            continue;
+        }
+        let value = value.parse().unwrap_or_else(|_| usage());
+        match flag.as_str() {
+            "--iterations" => iterations = Some(value),
+            "--trials" if bench == "compare" && value >= 2 => trials = Some(value),
+            "--trials" if bench == "--tune" && value >= 1 => trials = Some(value),
+            _ => usage(),
+        }
+        flags = rest;
+    }
+    if !flags.is_empty() {
+        usage();
+    }
+
+    match bench.as_str() {
+        "fences" => fences(iterations.unwrap_or(10000000), format),
+        "energy" => energy(iterations.unwrap_or(100000), format),
+        "compare" => compare(trials.unwrap_or(10), iterations.unwrap_or(20000), format),
+        "mutex" => mutex(iterations.unwrap_or(20000), format),
+        "rwlock" => rwlock(iterations.unwrap_or(10000), format),
+        #[cfg(feature = "hierarchical")]
+        "hierarchical" => hierarchical(iterations.unwrap_or(5000), format),
+        "backoff" => backoff(iterations.unwrap_or(2000), format),
+        "--tune" => tune(
+            trials.unwrap_or(3),
+            iterations.unwrap_or(1000),
+            emit,
+            format,
+        ),
+        "locks" => locks(iterations.unwrap_or(5000), format),
+        #[cfg(feature = "lamport-fast")]
+        "uncontended" => uncontended(iterations.unwrap_or(1000000), format),
+        _ => usage(),
+    }
+}
@@ -0,0 +1,127 @@
+use core::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+
+use crate::{spin, SlotLock};
+
+// A slot's ticket packs the color it was taken under into the lowest bit, with the number above
+// it. Zero while the slot isn't competing for the lock.
+fn pack(color: bool, number: u32) -> u32 {
+    number << 1 | color as u32
+}
+
+fn unpack(ticket: u32) -> (bool, u32) {
+    (ticket & 1 != 0, ticket >> 1)
+}
+
+/// Taubenfeld's black-white bakery lock: Lamport's bakery with tickets bounded by `N`, so they
+/// never wrap around at all.
+///
+/// Every ticket is taken under the current color, and only tickets of the same color are compared
+/// by number. A thread leaving the critical section sets the color to the opposite of its own
+/// ticket's, which makes every thread arriving afterwards wait behind all those already holding a
+/// ticket of the old color. Numbers therefore only grow within one batch of competing threads,
+/// which never has more than `N` members.
+///
+/// Slots work the same way as in [`RawBakeryLock`](crate::RawBakeryLock): every thread taking part
+/// passes its own slot in `0..N` to every call. Every shared access is sequentially consistent,
+/// which is what the algorithm's proof assumes.
+pub struct BWBakeryLock<const N: usize> {
+    // The color new tickets are taken under.
+    color: AtomicBool,
+    choosing: [AtomicBool; N],
+    tickets: [AtomicU32; N],
+}
+
+impl<const N: usize> BWBakeryLock<N> {
+    /// An unlocked lock with every slot free.
+    pub fn new() -> Self {
+        assert!(N < 1 << 31, "too many slots to number within a ticket");
+
+        Self {
+            color: AtomicBool::new(false),
+            choosing: core::array::from_fn(|_| AtomicBool::new(false)),
+            tickets: core::array::from_fn(|_| AtomicU32::new(0)),
+        }
+    }
+
+    /// Waits until every thread ahead of `thread` has left and enters the critical section.
+    /// `thread` must be a slot in `0..N` that no other thread is currently using.
+    pub fn lock(&self, thread: usize) {
+        self.choosing[thread].store(true, Ordering::SeqCst);
+        let color = self.color.load(Ordering::SeqCst);
+        let number = 1 + self
+            .tickets
+            .iter()
+            .map(|ticket| unpack(ticket.load(Ordering::SeqCst)))
+            .filter(|&(other_color, _)| other_color == color)
+            .map(|(_, number)| number)
+            .max()
+            .unwrap_or(0);
+        debug_assert!(number as usize <= N);
+        self.tickets[thread].store(pack(color, number), Ordering::SeqCst);
+        self.choosing[thread].store(false, Ordering::SeqCst);
+
+        for other in (0..N).filter(|&other| other != thread) {
+            while self.choosing[other].load(Ordering::SeqCst) {
+                spin::relax();
+            }
+
+            let (other_color, _) = unpack(self.tickets[other].load(Ordering::SeqCst));
+            if other_color == color {
+                // Same batch: the lower `(number, slot)` goes first, as in the plain bakery.
+                loop {
+                    let (other_color, other_number) =
+                        unpack(self.tickets[other].load(Ordering::SeqCst));
+                    if other_number == 0
+                        || other_color != color
+                        || (number, thread) < (other_number, other)
+                    {
+                        break;
+                    }
+                    spin::relax();
+                }
+            } else {
+                // While the color is still ours, a ticket of the other color belongs to the batch
+                // before ours, which goes first. Once a thread of our batch has left and changed
+                // the color, it belongs to the batch after ours instead.
+                loop {
+                    let (other_color, other_number) =
+                        unpack(self.tickets[other].load(Ordering::SeqCst));
+                    if other_number == 0
+                        || other_color == color
+                        || self.color.load(Ordering::SeqCst) != color
+                    {
+                        break;
+                    }
+                    spin::relax();
+                }
+            }
+        }
+    }
+
+    /// Leaves the critical section entered with `lock(thread)`, letting the next thread in.
+    pub fn unlock(&self, thread: usize) {
+        let (color, _) = unpack(self.tickets[thread].load(Ordering::Relaxed));
+        self.color.store(!color, Ordering::SeqCst);
+        self.tickets[thread].store(0, Ordering::SeqCst);
+    }
+}
+
+impl<const N: usize> Default for BWBakeryLock<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<const N: usize> SlotLock for BWBakeryLock<N> {
+    fn slots(&self) -> usize {
+        N
+    }
+
+    fn lock(&self, slot: usize) {
+        BWBakeryLock::lock(self, slot);
+    }
+
+    fn unlock(&self, slot: usize) {
+        BWBakeryLock::unlock(self, slot);
+    }
+}
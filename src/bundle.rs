@@ -0,0 +1,137 @@
+use std::{
+    env, fs, io,
+    path::PathBuf,
+    sync::atomic::{AtomicU64, AtomicUsize, Ordering},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use bakery::{ordering::OrderingConfig, spin, Event, Observer};
+
+use crate::{placement, results, teach, topology, workers};
+
+// How many of the most recent events are kept.
+const RING_CAPACITY: usize = 4096;
+
+// Keeps the last `RING_CAPACITY` events of a lock (skipping repeats while a thread spins) so that
+// there's something to look at when a run fails.
+pub struct EventRing<const N: usize> {
+    // Packed events, or 0 for slots that haven't been written yet.
+    entries: Box<[AtomicU64]>,
+    next: AtomicUsize,
+    // The last event each thread recorded. Only accessed by that thread.
+    last: [AtomicU64; N],
+}
+
+// Packs `event` from `thread` into a non-zero word: 12 bits each for the thread and the other
+// slot involved, 8 for the kind of event and 32 for the ticket, saturating wider ones.
+pub fn pack(thread: usize, event: Event) -> u64 {
+    let (kind, other, ticket) = match event {
+        Event::Doorway => (1, 0, 0),
+        Event::TicketWrapped => (2, 0, 0),
+        Event::Ticket(ticket) => (3, 0, ticket),
+        Event::WaitChoosing { other } => (4, other, 0),
+        Event::WaitTicket { other, ticket } => (5, other, ticket),
+        Event::Passed { other, ticket } => (6, other, ticket),
+        Event::Acquired => (7, 0, 0),
+        Event::Released => (8, 0, 0),
+        Event::GaveUp => (9, 0, 0),
+    };
+    (thread as u64) << 52 | (other as u64) << 40 | kind << 32 | ticket.min(u32::MAX.into())
+}
+
+pub fn unpack(word: u64) -> (usize, Event) {
+    let thread = (word >> 52) as usize;
+    let other = (word >> 40) as usize & 0xfff;
+    let ticket = word & u64::from(u32::MAX);
+    let event = match (word >> 32) & 0xff {
+        1 => Event::Doorway,
+        2 => Event::TicketWrapped,
+        3 => Event::Ticket(ticket),
+        4 => Event::WaitChoosing { other },
+        5 => Event::WaitTicket { other, ticket },
+        6 => Event::Passed { other, ticket },
+        7 => Event::Acquired,
+        8 => Event::Released,
+        _ => Event::GaveUp,
+    };
+    (thread, event)
+}
+
+impl<const N: usize> EventRing<N> {
+    pub fn new() -> Self {
+        assert!(N <= 1 << 12, "too many slots to pack into ring entries");
+
+        Self {
+            entries: (0..RING_CAPACITY).map(|_| AtomicU64::new(0)).collect(),
+            next: AtomicUsize::new(0),
+            last: std::array::from_fn(|_| AtomicU64::new(0)),
+        }
+    }
+
+    // Describes the recorded events, oldest first. Entries being written concurrently may be
+    // missing or stale.
+    pub fn dump(&self) -> String {
+        let next = self.next.load(Ordering::Relaxed);
+        (next.saturating_sub(RING_CAPACITY)..next)
+            .map(|index| self.entries[index % RING_CAPACITY].load(Ordering::Relaxed))
+            .filter(|&word| word != 0)
+            .map(|word| {
+                let (thread, event) = unpack(word);
+                teach::describe(thread, event) + "\n"
+            })
+            .collect()
+    }
+}
+
+impl<const N: usize> Observer for EventRing<N> {
+    fn on_event(&self, thread: usize, event: Event) {
+        let word = pack(thread, event);
+        if self.last[thread].swap(word, Ordering::Relaxed) == word {
+            return;
+        }
+
+        let index = self.next.fetch_add(1, Ordering::Relaxed);
+        self.entries[index % RING_CAPACITY].store(word, Ordering::Relaxed);
+    }
+}
+
+// Writes everything needed to reproduce and report a failure to a new directory under `root`:
+// the configuration (including the seed and the lock's ordering), the environment, a description of the failure and the
+// events leading up to it. Returns the directory.
+pub fn write(root: &str, failure: &str, events: &str) -> io::Result<PathBuf> {
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_secs();
+    let dir = PathBuf::from(root).join(format!("{timestamp}-{}", workers::seed()));
+    fs::create_dir_all(&dir)?;
+
+    let config = format!(
+        "command={}\nseed={}\nfences={}\nspin={}\nplacement={}\n",
+        env::args().collect::<Vec<_>>().join(" "),
+        workers::seed(),
+        results::fence_config(),
+        spin::hint().name(),
+        placement::current().map_or("none", |placement| placement.name()),
+    );
+    let environment = format!(
+        "host={}\nos={}\narch={}\ntopology={}\ncpu_model={}\ntarget_features={}\n",
+        results::hostname(),
+        env::consts::OS,
+        env::consts::ARCH,
+        topology::detect(),
+        results::cpu_model(),
+        results::target_features(),
+    );
+
+    fs::write(dir.join("config.txt"), config)?;
+    fs::write(
+        dir.join("ordering.txt"),
+        OrderingConfig::build().to_string(),
+    )?;
+    fs::write(dir.join("environment.txt"), environment)?;
+    fs::write(dir.join("failure.txt"), format!("{failure}\n"))?;
+    fs::write(dir.join("events.txt"), events)?;
+
+    Ok(dir)
+}
@@ -0,0 +1,115 @@
+use core::sync::atomic::{self, AtomicBool, Ordering};
+
+use crate::{spin, SlotLock};
+
+/// Burns and Lynch's one-bit lock for up to `N` threads, which needs no more shared memory than a
+/// flag per thread.
+///
+/// A thread raises its flag once every flag of a lower slot is down, and starts over if it then
+/// finds one of them up after all. Once it has kept its flag up past all of those, it waits for
+/// the flags of the higher slots to come down, and enters. Burns and Lynch showed that any lock for
+/// `N` threads that only reads and writes memory needs `N` shared variables, so a bit each is as
+/// small as it gets. The price is fairness: the lock never deadlocks, but lower slots always win,
+/// so a thread in a high slot can be kept out for as long as lower ones keep coming back.
+pub struct BurnsLynchLock<const N: usize> {
+    flags: [AtomicBool; N],
+}
+
+impl<const N: usize> BurnsLynchLock<N> {
+    /// An unlocked lock.
+    pub const fn new() -> Self {
+        Self {
+            flags: [const { AtomicBool::new(false) }; N],
+        }
+    }
+
+    /// Waits until `thread` has its flag up with every other flag down, and enters the critical
+    /// section.
+    pub fn lock(&self, thread: usize) {
+        assert!(thread < N, "slot out of range");
+        loop {
+            self.wait_for_lower(thread);
+            if self.raise(thread) {
+                break;
+            }
+            self.lower(thread);
+        }
+        for other in thread + 1..N {
+            while self.flags[other].load(Ordering::Relaxed) {
+                spin::relax();
+            }
+        }
+
+        // Synchronizes-with the release store that lowered each of the other flags we saw down
+        // since raising our own, one of which is the unlock of whoever held the lock before us, or
+        // a store it made after it.
+        atomic::fence(Ordering::Acquire);
+    }
+
+    /// Enters the critical section like [`lock`](Self::lock) if every other flag is down,
+    /// returning whether it did.
+    pub fn try_lock(&self, thread: usize) -> bool {
+        assert!(thread < N, "slot out of range");
+        if !self.raise(thread)
+            || (thread + 1..N).any(|other| self.flags[other].load(Ordering::Relaxed))
+        {
+            self.lower(thread);
+            return false;
+        }
+        atomic::fence(Ordering::Acquire);
+        true
+    }
+
+    /// Leaves the critical section entered with `lock(thread)`.
+    pub fn unlock(&self, thread: usize) {
+        self.lower(thread);
+    }
+
+    // Waits with our flag down until every lower slot's flag is down. Raising it any earlier would
+    // only make us start over, after holding up the lower slots waiting for the higher ones.
+    fn wait_for_lower(&self, thread: usize) {
+        for other in 0..thread {
+            while self.flags[other].load(Ordering::Relaxed) {
+                spin::relax();
+            }
+        }
+    }
+
+    // Raises our flag and returns whether every lower slot's flag is still down.
+    fn raise(&self, thread: usize) -> bool {
+        self.flags[thread].store(true, Ordering::Relaxed);
+        // Our flag must be visible before we read the others, or two threads raising theirs at
+        // once can each see the other's down, and both enter. That's the store-buffering pattern,
+        // as in `PetersonLock::lock`. With the fence, whichever thread's fence comes later in their
+        // total order sees the other's flag up, so either the lower slot waits for the higher one
+        // to lower its flag, or the higher one starts over.
+        atomic::fence(Ordering::SeqCst);
+        (0..thread).all(|other| !self.flags[other].load(Ordering::Relaxed))
+    }
+
+    fn lower(&self, thread: usize) {
+        // Release, so that whoever sees our flag down after we've held the lock also sees our
+        // critical section, whether it's the store in `unlock` or one made while starting over.
+        self.flags[thread].store(false, Ordering::Release);
+    }
+}
+
+impl<const N: usize> Default for BurnsLynchLock<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<const N: usize> SlotLock for BurnsLynchLock<N> {
+    fn slots(&self) -> usize {
+        N
+    }
+
+    fn lock(&self, slot: usize) {
+        BurnsLynchLock::lock(self, slot);
+    }
+
+    fn unlock(&self, slot: usize) {
+        BurnsLynchLock::unlock(self, slot);
+    }
+}
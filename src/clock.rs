@@ -0,0 +1,170 @@
+use std::{
+    fmt, thread,
+    time::{Duration, Instant},
+};
+
+// Where timestamps come from. Reading `Instant` can cost tens of nanoseconds (or much more in a
+// VM), which is enough to dominate a short critical section, so cheaper sources are available
+// when measuring individual acquisitions.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum ClockSource {
+    // `std::time::Instant`.
+    Instant,
+    // The CPU's cycle/virtual counter (`rdtsc` on x86_64, `cntvct_el0` on aarch64), calibrated
+    // against `Instant` at startup.
+    Counter,
+    // The kernel's coarse monotonic clock: very cheap, but only as precise as the timer tick.
+    Coarse,
+}
+
+impl ClockSource {
+    pub const ALL: [ClockSource; 3] = [
+        ClockSource::Instant,
+        ClockSource::Counter,
+        ClockSource::Coarse,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            ClockSource::Instant => "instant",
+            ClockSource::Counter => "counter",
+            ClockSource::Coarse => "coarse",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|source| source.name() == name)
+    }
+
+    pub fn is_available(self) -> bool {
+        match self {
+            ClockSource::Instant => true,
+            ClockSource::Counter => counter::read().is_some(),
+            ClockSource::Coarse => coarse::read().is_some(),
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+pub struct Clock {
+    source: ClockSource,
+    epoch: Instant,
+    // Raw reading of the counter or coarse clock at `epoch`.
+    raw_epoch: u64,
+    // Nanoseconds per counter tick.
+    scale: f64,
+}
+
+impl Clock {
+    // Panics if `source` isn't available on this platform.
+    pub fn new(source: ClockSource) -> Self {
+        assert!(
+            source.is_available(),
+            "clock source `{}` is not available on this platform",
+            source.name()
+        );
+
+        let read_raw = || match source {
+            ClockSource::Instant => 0,
+            ClockSource::Counter => counter::read().unwrap(),
+            ClockSource::Coarse => coarse::read().unwrap(),
+        };
+
+        let scale = if source == ClockSource::Counter {
+            // Counters tick at a fixed rate on every CPU we care about, so measuring it over a
+            // short interval is enough.
+            let start = Instant::now();
+            let raw_start = read_raw();
+            thread::sleep(Duration::from_millis(20));
+            let raw_end = read_raw();
+            start.elapsed().as_nanos() as f64 / raw_end.wrapping_sub(raw_start).max(1) as f64
+        } else {
+            1.0
+        };
+
+        Self {
+            source,
+            epoch: Instant::now(),
+            raw_epoch: read_raw(),
+            scale,
+        }
+    }
+
+    // Nanoseconds elapsed since the clock was created.
+    pub fn now(&self) -> u64 {
+        match self.source {
+            ClockSource::Instant => self.epoch.elapsed().as_nanos() as u64,
+            ClockSource::Counter => {
+                let ticks = counter::read().unwrap().wrapping_sub(self.raw_epoch);
+                (ticks as f64 * self.scale) as u64
+            }
+            ClockSource::Coarse => coarse::read().unwrap().saturating_sub(self.raw_epoch),
+        }
+    }
+}
+
+impl fmt::Display for Clock {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.source {
+            ClockSource::Counter => write!(f, "counter ({:.3} ns/tick)", self.scale),
+            source => f.write_str(source.name()),
+        }
+    }
+}
+
+mod counter {
+    #[cfg(target_arch = "x86_64")]
+    pub fn read() -> Option<u64> {
+        // SAFETY: `rdtsc` is available on every x86_64 CPU.
+        Some(unsafe { std::arch::x86_64::_rdtsc() })
+    }
+
+    #[cfg(target_arch = "aarch64")]
+    pub fn read() -> Option<u64> {
+        let ticks: u64;
+        // SAFETY: `cntvct_el0` is readable from EL0 on every aarch64 OS we run on.
+        unsafe {
+            std::arch::asm!("mrs {}, cntvct_el0", out(reg) ticks, options(nomem, nostack));
+        }
+        Some(ticks)
+    }
+
+    #[cfg(not(any(target_arch = "x86_64", target_arch = "aarch64")))]
+    pub fn read() -> Option<u64> {
+        None
+    }
+}
+
+mod coarse {
+    #[cfg(target_os = "linux")]
+    pub fn read() -> Option<u64> {
+        use std::ffi::{c_int, c_long};
+
+        #[repr(C)]
+        struct Timespec {
+            tv_sec: c_long,
+            tv_nsec: c_long,
+        }
+
+        const CLOCK_MONOTONIC_COARSE: c_int = 6;
+
+        extern "C" {
+            fn clock_gettime(clock: c_int, tp: *mut Timespec) -> c_int;
+        }
+
+        let mut ts = Timespec {
+            tv_sec: 0,
+            tv_nsec: 0,
+        };
+        // SAFETY: `ts` is a valid, writable `timespec`.
+        if unsafe { clock_gettime(CLOCK_MONOTONIC_COARSE, &mut ts) } != 0 {
+            return None;
+        }
+        Some(ts.tv_sec as u64 * 1_000_000_000 + ts.tv_nsec as u64)
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    pub fn read() -> Option<u64> {
+        None
+    }
+}
@@ -0,0 +1,113 @@
+use std::{
+    sync::atomic::{AtomicUsize, Ordering},
+    thread,
+    time::{Duration, Instant},
+};
+
+use crate::{layout::SlotLayout, BakeryMutexGuard, Observer};
+
+/// A condition variable for threads holding a [`BakeryMutex`](crate::BakeryMutex).
+///
+/// Waiting leaves the critical section, giving up the ticket, and comes back through the doorway
+/// in the same slot once notified, so a woken thread queues up behind everyone who took a ticket
+/// in the meantime. Waiters spin (yielding to the scheduler) rather than sleep, and can wake up
+/// spuriously, so the condition has to be checked again after every wait.
+#[derive(Default)]
+pub struct BakeryCondvar {
+    // Bumped by `notify_all`, waking everyone who started waiting before.
+    generation: AtomicUsize,
+    // Threads currently waiting, and wakeups handed out by `notify_one` that no waiter has taken
+    // yet. The latter never exceeds the former when handed out.
+    waiters: AtomicUsize,
+    tokens: AtomicUsize,
+}
+
+impl BakeryCondvar {
+    /// A condition variable nobody is waiting on.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Leaves the critical section of `guard` until the condition variable is notified, and then
+    /// enters it again in the same slot.
+    pub fn wait<'a, T, const N: usize, O: Observer, S: SlotLayout<N>>(
+        &self,
+        guard: BakeryMutexGuard<'a, T, N, O, S>,
+    ) -> BakeryMutexGuard<'a, T, N, O, S> {
+        self.park(guard, None).0
+    }
+
+    /// Waits until `condition` returns false, checking it whenever the condition variable is
+    /// notified.
+    pub fn wait_while<'a, T, const N: usize, O: Observer, S: SlotLayout<N>>(
+        &self,
+        mut guard: BakeryMutexGuard<'a, T, N, O, S>,
+        mut condition: impl FnMut(&mut T) -> bool,
+    ) -> BakeryMutexGuard<'a, T, N, O, S> {
+        while condition(&mut guard) {
+            guard = self.wait(guard);
+        }
+        guard
+    }
+
+    /// Like [`wait`](Self::wait), but stops waiting after `timeout`, returning whether it did so
+    /// without being notified. The critical section is entered again either way.
+    pub fn wait_timeout<'a, T, const N: usize, O: Observer, S: SlotLayout<N>>(
+        &self,
+        guard: BakeryMutexGuard<'a, T, N, O, S>,
+        timeout: Duration,
+    ) -> (BakeryMutexGuard<'a, T, N, O, S>, bool) {
+        self.park(guard, Instant::now().checked_add(timeout))
+    }
+
+    /// Wakes up one of the threads currently waiting, if there are any.
+    pub fn notify_one(&self) {
+        let _ = self
+            .tokens
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |tokens| {
+                (tokens < self.waiters.load(Ordering::SeqCst)).then_some(tokens + 1)
+            });
+    }
+
+    /// Wakes up every thread currently waiting.
+    pub fn notify_all(&self) {
+        // Everyone a leftover wakeup was meant for is woken by the new generation anyway.
+        self.tokens.store(0, Ordering::SeqCst);
+        self.generation.fetch_add(1, Ordering::SeqCst);
+    }
+
+    fn park<'a, T, const N: usize, O: Observer, S: SlotLayout<N>>(
+        &self,
+        guard: BakeryMutexGuard<'a, T, N, O, S>,
+        deadline: Option<Instant>,
+    ) -> (BakeryMutexGuard<'a, T, N, O, S>, bool) {
+        // Registered while still in the critical section, so that a thread that changes the
+        // condition under the lock and then notifies can't miss us.
+        let generation = self.generation.load(Ordering::SeqCst);
+        self.waiters.fetch_add(1, Ordering::SeqCst);
+        let (mutex, slot) = (guard.mutex, guard.slot);
+        drop(guard);
+
+        let timed_out = loop {
+            if self.generation.load(Ordering::SeqCst) != generation || self.take_token() {
+                break false;
+            }
+            if deadline.is_some_and(|deadline| Instant::now() >= deadline) {
+                break true;
+            }
+            thread::yield_now();
+        };
+        self.waiters.fetch_sub(1, Ordering::SeqCst);
+
+        mutex.raw().lock(slot);
+        (BakeryMutexGuard::new(mutex, slot), timed_out)
+    }
+
+    fn take_token(&self) -> bool {
+        self.tokens
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |tokens| {
+                tokens.checked_sub(1)
+            })
+            .is_ok()
+    }
+}
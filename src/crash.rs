@@ -0,0 +1,225 @@
+use std::{
+    cell::UnsafeCell,
+    panic::{self, AssertUnwindSafe},
+    process,
+    sync::atomic::{AtomicUsize, Ordering},
+    thread,
+    time::{Duration, Instant},
+};
+
+use bakery::{layout::SlotLayout, Event, Observer, RawBakeryLock};
+
+use crate::{
+    workers::{self, Rng},
+    UnsafeSyncCell,
+};
+
+const NUM_THREADS: usize = 4;
+
+// How long the surviving threads may go without completing a single acquisition before the lock
+// is considered wedged.
+const STALL: Duration = Duration::from_secs(1);
+
+// Where the victim dies.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Site {
+    // With `choosing` set, before picking a ticket.
+    Doorway,
+    // With its ticket published, before it has been let in.
+    Waiting,
+    // While holding the lock.
+    CriticalSection,
+}
+
+const SITES: [Site; 3] = [Site::Doorway, Site::Waiting, Site::CriticalSection];
+
+impl Site {
+    fn name(self) -> &'static str {
+        match self {
+            Site::Doorway => "doorway",
+            Site::Waiting => "waiting",
+            Site::CriticalSection => "critical-section",
+        }
+    }
+
+    fn from_name(name: &str) -> Option<Self> {
+        SITES.into_iter().find(|site| site.name() == name)
+    }
+}
+
+// Panics in the victim's `on_event` callback, which unwinds straight out of `lock` and leaves the
+// victim's slot exactly as it was at that point.
+struct Saboteur {
+    victim: usize,
+    site: Site,
+    // How many times the victim has to get to `site` before it dies.
+    at: usize,
+    reached: AtomicUsize,
+}
+
+impl Observer for Saboteur {
+    fn on_event(&self, thread: usize, event: Event) {
+        let reached_site = match event {
+            Event::Doorway => self.site == Site::Doorway,
+            Event::Ticket(_) => self.site == Site::Waiting,
+            Event::Acquired => self.site == Site::CriticalSection,
+            _ => false,
+        };
+        if thread != self.victim || !reached_site {
+            return;
+        }
+
+        // Only the victim touches `reached`.
+        let reached = self.reached.load(Ordering::Relaxed) + 1;
+        self.reached.store(reached, Ordering::Relaxed);
+        if reached == self.at {
+            panic!("thread {thread} crashed at `{}`", self.site.name());
+        }
+    }
+}
+
+// Describes the state a slot was left in, as seen by the other threads.
+fn describe_slot<const N: usize, O: Observer, S: SlotLayout<N>>(
+    lock: &RawBakeryLock<N, O, S>,
+    slot: usize,
+) -> String {
+    let choosing = lock.slots().is_choosing(slot, Ordering::Relaxed);
+    let ticket = lock.slots().ticket(slot, Ordering::Relaxed);
+    match (choosing, ticket) {
+        (false, 0) => "clear".to_owned(),
+        (true, _) => format!("`choosing` still set (ticket {ticket})"),
+        (false, _) => format!("ticket {ticket} still published"),
+    }
+}
+
+fn usage() -> ! {
+    eprintln!("usage: bakery crash [--site <doorway|waiting|critical-section>] [--iterations <n>]");
+    process::exit(2);
+}
+
+// Has one thread die at a random point inside `lock` or its critical section while the others
+// keep counting. The harness catches the unwind, reports what the dead thread's slot was left
+// looking like and recovers it, and the run fails unless everyone then gets through the lock and
+// leaves it clean.
+pub fn run(args: &[String]) {
+    let mut site = None;
+    let mut iterations = 2000;
+    let mut args = args;
+    while let [flag, value, rest @ ..] = args {
+        match flag.as_str() {
+            "--site" => site = Some(Site::from_name(value).unwrap_or_else(|| usage())),
+            "--iterations" => iterations = value.parse().unwrap_or_else(|_| usage()),
+            _ => usage(),
+        }
+        args = rest;
+    }
+    if !args.is_empty() || iterations == 0 {
+        usage();
+    }
+
+    let seed = workers::seed();
+    let mut rng = Rng::new(seed);
+    let site = site.unwrap_or_else(|| SITES[rng.below(SITES.len() as u64) as usize]);
+    let saboteur = Saboteur {
+        victim: rng.below(NUM_THREADS as u64) as usize,
+        site,
+        at: rng.below(iterations as u64) as usize + 1,
+        reached: AtomicUsize::new(0),
+    };
+    let victim = saboteur.victim;
+    println!(
+        "seed {seed}: thread {victim} of {NUM_THREADS} will crash at `{}` on its acquisition #{}",
+        site.name(),
+        saboteur.at
+    );
+
+    let lock = RawBakeryLock::<NUM_THREADS, _>::with_observer(saboteur);
+    let mut num = UnsafeSyncCell(UnsafeCell::new(0usize));
+    let progress = AtomicUsize::new(0);
+    let finished = AtomicUsize::new(0);
+
+    // The harness reports the crash itself.
+    panic::set_hook(Box::new(|_| {}));
+
+    thread::scope(|scope| {
+        for thread_id in 0..NUM_THREADS {
+            let (lock, num, progress, finished) = (&lock, &num, &progress, &finished);
+            workers::spawn(scope, "crash", thread_id, move |worker| {
+                let mut done = 0;
+                let count = |done: &mut usize| {
+                    while *done < iterations {
+                        lock.lock(worker.id);
+                        unsafe {
+                            *num.0.get() += 1;
+                        }
+                        lock.unlock(worker.id);
+                        progress.fetch_add(1, Ordering::Relaxed);
+                        *done += 1;
+                    }
+                };
+
+                if let Err(payload) = panic::catch_unwind(AssertUnwindSafe(|| count(&mut done))) {
+                    let message = payload
+                        .downcast_ref::<String>()
+                        .map_or("unknown panic", String::as_str);
+                    println!(
+                        "harness: caught \"{message}\", slot {} is {}",
+                        worker.id,
+                        describe_slot(lock, worker.id)
+                    );
+
+                    // Stand in for a supervisor noticing the dead thread. This thread doesn't
+                    // touch the lock again until it has recovered the slot.
+                    unsafe {
+                        if site == Site::CriticalSection {
+                            lock.force_unlock(worker.id);
+                        } else {
+                            lock.reclaim_slot(worker.id);
+                        }
+                    }
+                    println!(
+                        "harness: recovered slot {}, now {}, resuming its remaining {} acquisitions",
+                        worker.id,
+                        describe_slot(lock, worker.id),
+                        iterations - done
+                    );
+                    count(&mut done);
+                }
+                finished.fetch_add(1, Ordering::Relaxed);
+            });
+        }
+
+        // The workers can't be interrupted, so a wedged lock ends the whole process from here.
+        let mut last = (0, Instant::now());
+        while finished.load(Ordering::Relaxed) < NUM_THREADS {
+            thread::sleep(STALL / 10);
+
+            let done = progress.load(Ordering::Relaxed);
+            if done != last.0 {
+                last = (done, Instant::now());
+            } else if last.1.elapsed() >= STALL {
+                println!(
+                    "no acquisitions for {STALL:.2?} after {done} in total, the lock is wedged:"
+                );
+                for slot in 0..NUM_THREADS {
+                    println!("  slot {slot}: {}", describe_slot(&lock, slot));
+                }
+                process::exit(1);
+            }
+        }
+    });
+
+    let count = *num.0.get_mut();
+    let expected = NUM_THREADS * iterations;
+    println!("every thread finished, counted to {count} (expected {expected})");
+
+    let dirty: Vec<_> = (0..NUM_THREADS)
+        .filter(|&slot| describe_slot(&lock, slot) != "clear")
+        .collect();
+    for &slot in &dirty {
+        println!("slot {slot} is still {}", describe_slot(&lock, slot));
+    }
+    if count != expected || !dirty.is_empty() {
+        process::exit(1);
+    }
+}
@@ -0,0 +1,109 @@
+use core::sync::atomic::{self, AtomicBool, AtomicUsize, Ordering};
+
+use crate::{spin, SlotLock};
+
+/// Dekker's lock for two threads, in slots 0 and 1, the first correct mutual exclusion algorithm.
+///
+/// A thread raises its flag and enters once it sees the other thread's flag down. If both flags
+/// are up, the thread whose turn it isn't lowers its own flag and waits for its turn before raising
+/// it again, and leaving the critical section hands the turn to the other thread. Unlike Peterson's
+/// lock, a thread can raise its flag more than once per acquisition, and every time it does, the
+/// flag has to be visible before it looks at the other thread's.
+pub struct DekkerLock {
+    wants: [AtomicBool; 2],
+    turn: AtomicUsize,
+}
+
+impl DekkerLock {
+    /// An unlocked lock, with the first turn going to slot 0.
+    pub const fn new() -> Self {
+        Self {
+            wants: [AtomicBool::new(false), AtomicBool::new(false)],
+            turn: AtomicUsize::new(0),
+        }
+    }
+
+    /// Waits until the other thread has lowered its flag, and enters the critical section.
+    /// `thread` must be 0 or 1, and the other slot must be used by another thread.
+    pub fn lock(&self, thread: usize) {
+        assert!(thread < 2, "Dekker's lock only has slots 0 and 1");
+        let other = 1 - thread;
+
+        self.raise(thread);
+        while self.wants[other].load(Ordering::Relaxed) {
+            if self.turn.load(Ordering::Relaxed) != thread {
+                // Release, so that the other thread entering because it saw our flag down also
+                // sees the critical section we last left, as it would have from `unlock`.
+                self.wants[thread].store(false, Ordering::Release);
+                while self.turn.load(Ordering::Relaxed) != thread {
+                    spin::relax();
+                }
+                self.raise(thread);
+            } else {
+                spin::relax();
+            }
+        }
+
+        // Synchronizes-with the release store that lowered the other thread's flag, in `unlock` or
+        // while it stepped back for us.
+        atomic::fence(Ordering::Acquire);
+    }
+
+    /// Enters the critical section like [`lock`](Self::lock) if the other thread's flag is down,
+    /// returning whether it did.
+    pub fn try_lock(&self, thread: usize) -> bool {
+        assert!(thread < 2, "Dekker's lock only has slots 0 and 1");
+        let other = 1 - thread;
+
+        self.raise(thread);
+        if self.wants[other].load(Ordering::Relaxed) {
+            // See `lock`.
+            self.wants[thread].store(false, Ordering::Release);
+            return false;
+        }
+        atomic::fence(Ordering::Acquire);
+        true
+    }
+
+    /// Leaves the critical section entered with `lock(thread)`, handing the turn to the other
+    /// thread.
+    pub fn unlock(&self, thread: usize) {
+        self.turn.store(1 - thread, Ordering::Relaxed);
+        self.wants[thread].store(false, Ordering::Release);
+    }
+
+    fn raise(&self, thread: usize) {
+        self.wants[thread].store(true, Ordering::Relaxed);
+        // Our flag must be visible before we read the other thread's. Without the fence, this is
+        // the store-buffering pattern: both threads raise their flags, both read the other's as
+        // still down, and both enter. With it, whichever thread's fence comes later in the total
+        // order of SC fences sees the other's flag up. The `fake-fence-dekker` feature weakens it
+        // to a compiler fence, the way the `fake-fence-*` features do the bakery's, so that the
+        // difference shows up on real hardware.
+        if cfg!(feature = "fake-fence-dekker") {
+            atomic::compiler_fence(Ordering::SeqCst);
+        } else {
+            atomic::fence(Ordering::SeqCst);
+        }
+    }
+}
+
+impl Default for DekkerLock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SlotLock for DekkerLock {
+    fn slots(&self) -> usize {
+        2
+    }
+
+    fn lock(&self, slot: usize) {
+        DekkerLock::lock(self, slot);
+    }
+
+    fn unlock(&self, slot: usize) {
+        DekkerLock::unlock(self, slot);
+    }
+}
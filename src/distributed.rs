@@ -0,0 +1,306 @@
+// A simple line-based protocol for running the fence-weakening experiments on several machines at
+// once:
+//
+//  worker -> coordinator: hello <host> <arch> <topology> <fences>
+//  coordinator -> worker: run <experiment> <iterations>
+//  worker -> coordinator: result <count> <expected> <elapsed_ms>
+//  ...
+//  coordinator -> worker: done
+
+use std::{
+    cell::UnsafeCell,
+    collections::BTreeMap,
+    env,
+    io::{self, BufRead, BufReader, Write},
+    net::{TcpListener, TcpStream},
+    process,
+    sync::Mutex,
+    thread,
+    time::Instant,
+};
+
+#[cfg(feature = "flawed-bakery")]
+use crate::exercises::{Flaw, FlawedBakeryLock};
+use bakery::RawBakeryLock;
+
+use crate::{results, topology, workers, UnsafeSyncCell};
+
+const NUM_THREADS: usize = 4;
+
+// The experiments handed out to every worker. The fences of `RawBakeryLock` are fixed at compile
+// time, so the weakened variants run on the flawed copy of the lock used by the exercises.
+const EXPERIMENTS: [&str; 3] = ["default", "weak-fence-1", "weak-fence-2"];
+
+fn usage() -> ! {
+    eprintln!("usage: bakery coordinator [--listen <addr>] [--workers <n>] [--rounds <n>] [--iterations <n>]");
+    eprintln!("       bakery worker --connect <addr>");
+    process::exit(2);
+}
+
+fn parse_flags(args: &[String], flags: &mut [(&str, &mut String)]) {
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        let Some((_, value)) = flags.iter_mut().find(|(flag, _)| flag == arg) else {
+            usage();
+        };
+        **value = args.next().cloned().unwrap_or_else(|| usage());
+    }
+}
+
+fn parse<T: std::str::FromStr>(value: &str) -> T {
+    value.parse().unwrap_or_else(|_| usage())
+}
+
+// Counts to `NUM_THREADS * iterations` under the given lock operations. These are deliberately
+// the raw `lock` and `unlock` calls, since the weak-fence experiments run on the flawed copies of
+// the lock, which have no guards, and are meant to lose updates.
+fn count(iterations: usize, lock: impl Fn(usize) + Sync, unlock: impl Fn(usize) + Sync) -> usize {
+    let mut num = UnsafeSyncCell(UnsafeCell::new(0));
+
+    thread::scope(|scope| {
+        for thread_id in 0..NUM_THREADS {
+            let lock = &lock;
+            let unlock = &unlock;
+            let num = &num;
+            workers::spawn(scope, "worker", thread_id, move |worker| {
+                for _ in 0..iterations {
+                    lock(worker.id);
+                    unsafe {
+                        *num.0.get() += 1;
+                    }
+                    unlock(worker.id);
+                }
+            });
+        }
+    });
+
+    *num.0.get_mut()
+}
+
+fn run_experiment(experiment: &str, iterations: usize) -> Option<usize> {
+    // Workers built without the flawed locks report the weak-fence experiments as unknown.
+    #[cfg(feature = "flawed-bakery")]
+    let flawed = |flaw| {
+        let lock = FlawedBakeryLock::<NUM_THREADS>::new(flaw);
+        Some(count(iterations, |t| lock.lock(t), |t| lock.unlock(t)))
+    };
+
+    match experiment {
+        "default" => {
+            let lock = RawBakeryLock::<NUM_THREADS>::new();
+            Some(count(iterations, |t| lock.lock(t), |t| lock.unlock(t)))
+        }
+        #[cfg(feature = "flawed-bakery")]
+        "weak-fence-1" => flawed(Flaw::MissingFirstFence),
+        #[cfg(feature = "flawed-bakery")]
+        "weak-fence-2" => flawed(Flaw::MissingSecondFence),
+        _ => None,
+    }
+}
+
+fn invalid_data(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+pub fn worker(args: &[String]) {
+    let mut connect = String::new();
+    parse_flags(args, &mut [("--connect", &mut connect)]);
+    if connect.is_empty() {
+        usage();
+    }
+
+    if let Err(err) = serve(&connect) {
+        eprintln!("worker failed: {err}");
+        process::exit(1);
+    }
+}
+
+fn serve(addr: &str) -> io::Result<()> {
+    let stream = TcpStream::connect(addr)?;
+    let mut reader = BufReader::new(stream.try_clone()?);
+    let mut writer = stream;
+
+    writeln!(
+        writer,
+        "hello {} {} {} {}",
+        results::hostname().replace(' ', "_"),
+        env::consts::ARCH,
+        topology::detect(),
+        results::fence_config()
+    )?;
+
+    let mut line = String::new();
+    loop {
+        line.clear();
+        if reader.read_line(&mut line)? == 0 {
+            return Err(io::ErrorKind::UnexpectedEof.into());
+        }
+
+        let words: Vec<_> = line.split_whitespace().collect();
+        match words[..] {
+            ["run", experiment, iterations] => {
+                let iterations = iterations
+                    .parse()
+                    .map_err(|_| invalid_data(format!("bad iteration count `{iterations}`")))?;
+
+                println!("running {experiment} ({iterations} iterations)");
+                let start = Instant::now();
+                let count = run_experiment(experiment, iterations)
+                    .ok_or_else(|| invalid_data(format!("unknown experiment `{experiment}`")))?;
+                let elapsed = start.elapsed();
+
+                writeln!(
+                    writer,
+                    "result {count} {} {:.3}",
+                    NUM_THREADS * iterations,
+                    elapsed.as_secs_f64() * 1000.0
+                )?;
+            }
+            ["done"] => return Ok(()),
+            _ => {
+                return Err(invalid_data(format!(
+                    "unexpected message `{}`",
+                    line.trim()
+                )))
+            }
+        }
+    }
+}
+
+#[derive(Default)]
+struct Stats {
+    runs: usize,
+    violations: usize,
+    lost_updates: usize,
+    total_elapsed_ms: f64,
+}
+
+// Keyed by worker description and experiment.
+type StatsTable = Mutex<BTreeMap<(String, String), Stats>>;
+
+pub fn coordinator(args: &[String]) {
+    let mut listen = "0.0.0.0:7878".to_owned();
+    let mut workers = "1".to_owned();
+    let mut rounds = "3".to_owned();
+    let mut iterations = "100000".to_owned();
+    parse_flags(
+        args,
+        &mut [
+            ("--listen", &mut listen),
+            ("--workers", &mut workers),
+            ("--rounds", &mut rounds),
+            ("--iterations", &mut iterations),
+        ],
+    );
+    let workers: usize = parse(&workers);
+    let rounds: usize = parse(&rounds);
+    let iterations: usize = parse(&iterations);
+
+    let listener = TcpListener::bind(&listen).unwrap_or_else(|err| {
+        eprintln!("failed to listen on {listen}: {err}");
+        process::exit(1);
+    });
+    println!("waiting for {workers} workers on {listen}");
+
+    let stats = StatsTable::default();
+    thread::scope(|scope| {
+        for stream in listener.incoming().take(workers) {
+            let stream = match stream {
+                Ok(stream) => stream,
+                Err(err) => {
+                    eprintln!("failed to accept worker: {err}");
+                    continue;
+                }
+            };
+
+            let stats = &stats;
+            scope.spawn(move || {
+                let peer = stream
+                    .peer_addr()
+                    .map_or_else(|_| "unknown peer".to_owned(), |addr| addr.to_string());
+                if let Err(err) = schedule(stream, rounds, iterations, stats) {
+                    eprintln!("worker {peer} failed: {err}");
+                }
+            });
+        }
+    });
+
+    println!(
+        "{:<40} {:<17} {:>6} {:>10} {:>12} {:>12}",
+        "worker", "experiment", "runs", "violating", "lost", "mean ms"
+    );
+    for ((worker, experiment), stats) in stats.into_inner().unwrap() {
+        println!(
+            "{:<40} {:<17} {:>6} {:>10} {:>12} {:>12.1}",
+            worker,
+            experiment,
+            stats.runs,
+            stats.violations,
+            stats.lost_updates,
+            stats.total_elapsed_ms / stats.runs as f64
+        );
+    }
+}
+
+fn schedule(
+    stream: TcpStream,
+    rounds: usize,
+    iterations: usize,
+    stats: &StatsTable,
+) -> io::Result<()> {
+    let mut reader = BufReader::new(stream.try_clone()?);
+    let mut writer = stream;
+
+    let mut line = String::new();
+    reader.read_line(&mut line)?;
+    let (worker, fences) = match line.split_whitespace().collect::<Vec<_>>()[..] {
+        ["hello", host, arch, topology, fences] => {
+            (format!("{host} ({arch}, {topology})"), fences.to_owned())
+        }
+        _ => {
+            return Err(invalid_data(format!(
+                "unexpected greeting `{}`",
+                line.trim()
+            )))
+        }
+    };
+    println!("{worker} connected");
+
+    for _ in 0..rounds {
+        for experiment in EXPERIMENTS {
+            writeln!(writer, "run {experiment} {iterations}")?;
+
+            line.clear();
+            reader.read_line(&mut line)?;
+            let (count, expected, elapsed_ms) = match line.split_whitespace().collect::<Vec<_>>()[..]
+            {
+                ["result", count, expected, elapsed_ms] => (
+                    count.parse::<usize>(),
+                    expected.parse::<usize>(),
+                    elapsed_ms.parse::<f64>(),
+                ),
+                _ => return Err(invalid_data(format!("unexpected reply `{}`", line.trim()))),
+            };
+            let (Ok(count), Ok(expected), Ok(elapsed_ms)) = (count, expected, elapsed_ms) else {
+                return Err(invalid_data(format!("malformed result `{}`", line.trim())));
+            };
+
+            let experiment = if experiment == "default" {
+                format!("default ({fences})")
+            } else {
+                experiment.to_owned()
+            };
+
+            let mut stats = stats.lock().unwrap();
+            let entry = stats.entry((worker.clone(), experiment)).or_default();
+            entry.runs += 1;
+            if count != expected {
+                entry.violations += 1;
+            }
+            entry.lost_updates += expected.saturating_sub(count);
+            entry.total_elapsed_ms += elapsed_ms;
+        }
+    }
+
+    writeln!(writer, "done")
+}
@@ -0,0 +1,109 @@
+use alloc::boxed::Box;
+use core::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+
+use crate::{
+    algorithm::{Bakery, Next},
+    backoff::{Backoff, NoBackoff},
+    SlotLock,
+};
+
+/// Lamport's bakery lock for a number of threads only known at runtime, with its slots on the heap.
+///
+/// Every thread taking part passes its own slot in `0..len()` to every call, as with
+/// [`RawBakeryLock`](crate::RawBakeryLock). There is no observer and no handoff.
+pub struct DynBakeryLock {
+    choosing: Box<[AtomicBool]>,
+    tickets: Box<[AtomicU32]>,
+}
+
+impl DynBakeryLock {
+    /// An unlocked lock for `n` threads, with every slot free.
+    pub fn new(n: usize) -> Self {
+        assert!(
+            (n as u64) < Self::MAX_TICKET / 4,
+            "too many slots to tell tickets apart across a wraparound"
+        );
+
+        Self {
+            choosing: (0..n).map(|_| AtomicBool::new(false)).collect(),
+            tickets: (0..n).map(|_| AtomicU32::new(0)).collect(),
+        }
+    }
+
+    /// The number of slots.
+    pub fn len(&self) -> usize {
+        self.tickets.len()
+    }
+
+    /// Whether the lock has no slots at all, and can't be locked.
+    pub fn is_empty(&self) -> bool {
+        self.tickets.is_empty()
+    }
+
+    /// Waits until every thread ahead of `thread` in the bakery has left and enters the critical
+    /// section. `thread` must be a slot in `0..len()` that no other thread is currently using.
+    pub fn lock(&self, thread: usize) {
+        let ticket = self.doorway(thread);
+        self.wait_turn(thread, ticket, |_, _, backoff: &mut NoBackoff| {
+            backoff.snooze();
+            Next::Wait
+        });
+    }
+
+    /// Enters the critical section like [`lock`](Self::lock) if that doesn't require waiting for
+    /// anyone, returning whether it did.
+    pub fn try_lock(&self, thread: usize) -> bool {
+        let Some(ticket) = self.try_doorway(thread) else {
+            return false;
+        };
+        let entered = self.try_turn(thread, ticket, |_, _| Next::GiveUp);
+        if !entered {
+            // Withdrawing our ticket looks like an empty critical section to everyone else.
+            self.unlock(thread);
+        }
+        entered
+    }
+
+    /// Leaves the critical section entered with `lock(thread)`, letting the next thread in.
+    pub fn unlock(&self, thread: usize) {
+        self.tickets[thread].store(0, Ordering::Release);
+    }
+}
+
+impl Bakery for DynBakeryLock {
+    const MAX_TICKET: u64 = u32::MAX as u64;
+
+    fn is_choosing(&self, slot: usize, order: Ordering) -> bool {
+        self.choosing[slot].load(order)
+    }
+
+    fn set_choosing(&self, slot: usize, choosing: bool, order: Ordering) {
+        self.choosing[slot].store(choosing, order);
+    }
+
+    fn ticket(&self, slot: usize, order: Ordering) -> u64 {
+        self.tickets[slot].load(order).into()
+    }
+
+    fn set_ticket(&self, slot: usize, ticket: u64, order: Ordering) {
+        self.tickets[slot].store(ticket as u32, order);
+    }
+
+    fn active_slots(&self) -> impl Iterator<Item = usize> + '_ {
+        0..self.len()
+    }
+}
+
+impl SlotLock for DynBakeryLock {
+    fn slots(&self) -> usize {
+        self.len()
+    }
+
+    fn lock(&self, slot: usize) {
+        DynBakeryLock::lock(self, slot);
+    }
+
+    fn unlock(&self, slot: usize) {
+        DynBakeryLock::unlock(self, slot);
+    }
+}
@@ -0,0 +1,73 @@
+use std::{fs, path::PathBuf};
+
+// The package energy counters Linux exposes through the powercap framework (Intel RAPL, which
+// recent AMD CPUs implement too).
+pub struct Rapl {
+    packages: Vec<Package>,
+}
+
+struct Package {
+    counter: PathBuf,
+    // The counter wraps around to 0 after this many microjoules.
+    range: u64,
+}
+
+impl Rapl {
+    // Finds the counter of every package, or returns `None` if there are none or they can't be
+    // read (which usually requires root).
+    pub fn open() -> Option<Self> {
+        let mut packages = Vec::new();
+
+        for entry in fs::read_dir("/sys/class/powercap").ok()? {
+            let path = entry.ok()?.path();
+            // Packages are `intel-rapl:<n>`, their subdomains (cores, DRAM) `intel-rapl:<n>:<m>`.
+            let name = path.file_name()?.to_str()?;
+            if !name.starts_with("intel-rapl:") || name.matches(':').count() != 1 {
+                continue;
+            }
+
+            let range = read_number(&path.join("max_energy_range_uj"))?;
+            packages.push(Package {
+                counter: path.join("energy_uj"),
+                range,
+            });
+        }
+
+        let rapl = Self { packages };
+        (!rapl.packages.is_empty() && rapl.read().is_some()).then_some(rapl)
+    }
+
+    fn read(&self) -> Option<Vec<u64>> {
+        self.packages
+            .iter()
+            .map(|package| read_number(&package.counter))
+            .collect()
+    }
+
+    // Runs `f`, returning its result and the energy used by all packages meanwhile in joules.
+    // Panics if the counters stop being readable.
+    pub fn measure<T>(&self, f: impl FnOnce() -> T) -> (T, f64) {
+        let start = self.read().expect("failed to read energy counters");
+        let result = f();
+        let end = self.read().expect("failed to read energy counters");
+
+        let microjoules: u64 = self
+            .packages
+            .iter()
+            .zip(start.iter().zip(end))
+            .map(|(package, (&start, end))| {
+                if end >= start {
+                    end - start
+                } else {
+                    end + package.range - start
+                }
+            })
+            .sum();
+
+        (result, microjoules as f64 / 1e6)
+    }
+}
+
+fn read_number(path: &PathBuf) -> Option<u64> {
+    fs::read_to_string(path).ok()?.trim().parse().ok()
+}
@@ -0,0 +1,68 @@
+use std::{
+    sync::atomic::{AtomicU64, Ordering},
+    time::{Duration, Instant},
+};
+
+use bakery::{layout::SlotLayout, BakeryMutex, Event, Observer};
+
+// Weight of the newest sample in the moving average of hold times, as a power of two.
+const SMOOTHING_SHIFT: u32 = 3;
+
+// Keeps a moving average of how long the lock is held for.
+pub struct HoldTimer<const N: usize> {
+    epoch: Instant,
+    // When each thread last entered its critical section, in nanoseconds since `epoch`.
+    acquired: [AtomicU64; N],
+    // Exponential moving average of recent hold times in nanoseconds. Only updated by the owner.
+    mean_hold: AtomicU64,
+}
+
+impl<const N: usize> HoldTimer<N> {
+    pub fn new() -> Self {
+        Self {
+            epoch: Instant::now(),
+            acquired: std::array::from_fn(|_| AtomicU64::new(0)),
+            mean_hold: AtomicU64::new(0),
+        }
+    }
+
+    fn now(&self) -> u64 {
+        self.epoch.elapsed().as_nanos() as u64
+    }
+
+    pub fn mean_hold(&self) -> Duration {
+        Duration::from_nanos(self.mean_hold.load(Ordering::Relaxed))
+    }
+}
+
+impl<const N: usize> Observer for HoldTimer<N> {
+    fn on_event(&self, thread: usize, event: Event) {
+        match event {
+            Event::Acquired => self.acquired[thread].store(self.now(), Ordering::Relaxed),
+            Event::Released => {
+                let hold = self
+                    .now()
+                    .saturating_sub(self.acquired[thread].load(Ordering::Relaxed));
+                // We still own the lock, so nobody else is updating the average.
+                let mean = self.mean_hold.load(Ordering::Relaxed);
+                let mean = if hold >= mean {
+                    mean + ((hold - mean) >> SMOOTHING_SHIFT)
+                } else {
+                    mean - ((mean - hold) >> SMOOTHING_SHIFT)
+                };
+                self.mean_hold.store(mean, Ordering::Relaxed);
+            }
+            _ => {}
+        }
+    }
+}
+
+// Roughly how long `slot` can expect to wait before entering its critical section: one recent
+// average hold time for every thread ahead of it. `None` if `slot` isn't waiting for the lock.
+pub fn estimated_wait<T, const N: usize, S: SlotLayout<N>>(
+    lock: &BakeryMutex<T, N, HoldTimer<N>, S>,
+    slot: usize,
+) -> Option<Duration> {
+    let ahead = lock.queue_position(slot)?;
+    Some(lock.observer().mean_hold() * ahead as u32)
+}
@@ -0,0 +1,208 @@
+use std::{
+    cell::UnsafeCell,
+    process,
+    sync::atomic::{self, AtomicBool, AtomicU32, Ordering},
+    thread,
+    time::Instant,
+};
+
+use bakery::{ordering::OrderingConfig, spin};
+
+use crate::{
+    workers::{self, Rng},
+    UnsafeSyncCell,
+};
+
+const NUM_THREADS: usize = 4;
+const ITERATIONS: usize = 50000;
+
+// The bugs planted in `FlawedBakeryLock`. Each one is a small, plausible-looking deviation from
+// the real implementation in `lib.rs`.
+#[derive(Clone, Copy)]
+pub enum Flaw {
+    MissingFirstFence,
+    MissingSecondFence,
+    WrongTieBreak,
+    EarlyChoosingReset,
+}
+
+const FLAWS: [Flaw; 4] = [
+    Flaw::MissingFirstFence,
+    Flaw::MissingSecondFence,
+    Flaw::WrongTieBreak,
+    Flaw::EarlyChoosingReset,
+];
+
+impl Flaw {
+    fn answer(self) -> &'static str {
+        match self {
+            Flaw::MissingFirstFence => {
+                "the SC fence between setting `choosing` and reading the other tickets is only a \
+                 compiler fence, so the `W c -> R t` edge of the store buffering cycle is allowed"
+            }
+            Flaw::MissingSecondFence => {
+                "the SC fence between publishing the ticket and reading the other `choosing` \
+                 flags is only a compiler fence, so the `W t -> R c` edge of the store buffering \
+                 cycle is allowed"
+            }
+            Flaw::WrongTieBreak => {
+                "equal tickets aren't ordered by slot index, so two threads that pick the same \
+                 ticket both believe they have priority"
+            }
+            Flaw::EarlyChoosingReset => {
+                "`choosing` is cleared before the ticket is published, so other threads can read \
+                 a stale ticket of 0 and walk straight past a thread that is about to win"
+            }
+        }
+    }
+}
+
+// A copy of the bakery lock with one `Flaw` planted in it.
+pub struct FlawedBakeryLock<const N: usize> {
+    flaw: Flaw,
+    choosing: [AtomicBool; N],
+    ticket: [AtomicU32; N],
+}
+
+impl<const N: usize> FlawedBakeryLock<N> {
+    pub fn new(flaw: Flaw) -> Self {
+        Self {
+            flaw,
+            choosing: std::array::from_fn(|_| AtomicBool::new(false)),
+            ticket: std::array::from_fn(|_| AtomicU32::new(0)),
+        }
+    }
+
+    // The ordering of the synchronizing accesses, which only reflects flaws involving fences.
+    pub fn ordering(&self) -> OrderingConfig {
+        OrderingConfig::bakery(
+            matches!(self.flaw, Flaw::MissingFirstFence),
+            matches!(self.flaw, Flaw::MissingSecondFence),
+        )
+    }
+
+    fn fence(&self, weakened: bool) {
+        if weakened {
+            atomic::compiler_fence(Ordering::SeqCst);
+        } else {
+            atomic::fence(Ordering::SeqCst);
+        }
+    }
+
+    pub fn lock(&self, thread: usize) {
+        let ticket = loop {
+            self.choosing[thread].store(true, Ordering::Relaxed);
+            self.fence(matches!(self.flaw, Flaw::MissingFirstFence));
+
+            let max_existing = self
+                .ticket
+                .iter()
+                .map(|ticket| ticket.load(Ordering::Relaxed))
+                .max()
+                .unwrap();
+
+            if let Some(ticket) = max_existing.checked_add(1) {
+                break ticket;
+            }
+
+            self.choosing[thread].store(false, Ordering::Relaxed);
+            spin::relax();
+        };
+
+        if matches!(self.flaw, Flaw::EarlyChoosingReset) {
+            self.choosing[thread].store(false, Ordering::Relaxed);
+            self.fence(false);
+            self.ticket[thread].store(ticket, Ordering::Relaxed);
+        } else {
+            self.ticket[thread].store(ticket, Ordering::Relaxed);
+            self.fence(matches!(self.flaw, Flaw::MissingSecondFence));
+            self.choosing[thread].store(false, Ordering::Relaxed);
+        }
+
+        for other in 0..N {
+            if other == thread {
+                continue;
+            }
+
+            while self.choosing[other].load(Ordering::Relaxed) {
+                spin::relax();
+            }
+
+            atomic::fence(Ordering::Acquire);
+
+            loop {
+                let other_ticket = self.ticket[other].load(Ordering::Relaxed);
+                let has_priority = if matches!(self.flaw, Flaw::WrongTieBreak) {
+                    ticket <= other_ticket
+                } else {
+                    (ticket, thread) < (other_ticket, other)
+                };
+                if other_ticket == 0 || has_priority {
+                    break;
+                }
+                spin::relax();
+            }
+        }
+
+        atomic::fence(Ordering::Acquire);
+    }
+
+    pub fn unlock(&self, thread: usize) {
+        self.ticket[thread].store(0, Ordering::Release);
+    }
+}
+
+fn usage() -> ! {
+    eprintln!("usage: bakery exercises [--seed <seed>] [--answer]");
+    process::exit(2);
+}
+
+// Runs the counter demo on a lock with a randomly chosen planted bug and leaves it to the user
+// to work out which one it is.
+pub fn run(args: &[String]) {
+    let mut show_answer = false;
+    for arg in args {
+        match arg.as_str() {
+            "--answer" => show_answer = true,
+            _ => usage(),
+        }
+    }
+
+    let seed = workers::seed();
+    let flaw = FLAWS[Rng::new(seed).below(FLAWS.len() as u64) as usize];
+
+    println!("exercise seed {seed}: one of the bakery lock's invariants has been broken.");
+    println!(
+        "{NUM_THREADS} threads will now count to {} under it.",
+        NUM_THREADS * ITERATIONS
+    );
+
+    let lock = FlawedBakeryLock::<NUM_THREADS>::new(flaw);
+    let mut num = UnsafeSyncCell(UnsafeCell::new(0));
+
+    let start = Instant::now();
+    thread::scope(|scope| {
+        for thread_id in 0..NUM_THREADS {
+            let lock = &lock;
+            let num = &num;
+            workers::spawn(scope, "exercise", thread_id, move |worker| {
+                for _ in 0..ITERATIONS {
+                    lock.lock(worker.id);
+                    unsafe {
+                        *num.0.get() += 1;
+                    }
+                    lock.unlock(worker.id);
+                }
+            });
+        }
+    });
+
+    println!("counted to {} in {:.2?}", num.0.get_mut(), start.elapsed());
+
+    if show_answer {
+        println!("answer: {}", flaw.answer());
+        print!("{}", lock.ordering());
+    } else {
+        println!("what went wrong? rerun with `--seed {seed} --answer` to check.");
+    }
+}
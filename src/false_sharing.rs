@@ -0,0 +1,112 @@
+use std::{
+    cell::UnsafeCell,
+    process, thread,
+    time::{Duration, Instant},
+};
+
+use bakery::{
+    layout::{CachePadded, Compact, Packed, Padded, SlotLayout},
+    NoObserver, RawBakeryLock,
+};
+
+use crate::{topology, workers};
+
+const NUM_SLOTS: usize = 8;
+
+// The counter sharing a cache line with the lock's own state, so that every critical section
+// also invalidates the line all waiters are scanning.
+#[repr(C, align(128))]
+struct Colliding<S> {
+    lock: RawBakeryLock<NUM_SLOTS, NoObserver, S>,
+    counter: UnsafeCell<usize>,
+}
+
+unsafe impl<S: Sync> Sync for Colliding<S> {}
+
+// Counts to `threads * iterations` on `lock`, with the counter at `counter`.
+fn measure<S: SlotLayout<NUM_SLOTS> + Sync>(
+    lock: &RawBakeryLock<NUM_SLOTS, NoObserver, S>,
+    counter: &UnsafeCell<usize>,
+    threads: usize,
+    iterations: usize,
+) -> Duration {
+    struct SharedCounter<'a>(&'a UnsafeCell<usize>);
+    unsafe impl Sync for SharedCounter<'_> {}
+
+    let counter = SharedCounter(counter);
+    let start = Instant::now();
+    thread::scope(|scope| {
+        for thread_id in 0..threads {
+            let counter = &counter;
+            workers::spawn(scope, "false-sharing", thread_id, move |worker| {
+                for _ in 0..iterations {
+                    lock.lock(worker.id);
+                    unsafe {
+                        *counter.0.get() += 1;
+                    }
+                    lock.unlock(worker.id);
+                }
+            });
+        }
+    });
+    let elapsed = start.elapsed();
+
+    assert_eq!(
+        unsafe { *counter.0.get() },
+        threads * iterations,
+        "lost updates while measuring"
+    );
+    elapsed
+}
+
+fn usage() -> ! {
+    eprintln!("usage: bakery false-sharing [--iterations <n>]");
+    process::exit(2);
+}
+
+// Runs the same counter benchmark with every slot on its own cache line, with the default packed
+// and the compact layouts, and with the packed layout sharing a line with the counter, and reports
+// how much the cache line traffic costs.
+pub fn run(args: &[String]) {
+    let iterations = match args {
+        [] => 100000,
+        [flag, value] if flag == "--iterations" => value.parse().unwrap_or_else(|_| usage()),
+        _ => usage(),
+    };
+
+    let topology = topology::detect();
+    let threads = topology.physical_cores.clamp(1, NUM_SLOTS);
+    println!(
+        "{threads} threads, {iterations} iterations each ({topology}, {}-byte padding)",
+        std::mem::align_of::<CachePadded<()>>()
+    );
+
+    let separate_counter = CachePadded(UnsafeCell::new(0));
+    let padded = RawBakeryLock::from_parts(NoObserver, Padded::<NUM_SLOTS>::new());
+    let baseline = measure(&padded, &separate_counter.0, threads, iterations);
+
+    let separate_counter = CachePadded(UnsafeCell::new(0));
+    let packed = RawBakeryLock::from_parts(NoObserver, Packed::<NUM_SLOTS>::new());
+    let packed_time = measure(&packed, &separate_counter.0, threads, iterations);
+
+    let separate_counter = CachePadded(UnsafeCell::new(0));
+    let compact = RawBakeryLock::from_parts(NoObserver, Compact::new());
+    let compact_time = measure(&compact, &separate_counter.0, threads, iterations);
+
+    let colliding = Colliding {
+        lock: RawBakeryLock::from_parts(NoObserver, Packed::<NUM_SLOTS>::new()),
+        counter: UnsafeCell::new(0),
+    };
+    let colliding_time = measure(&colliding.lock, &colliding.counter, threads, iterations);
+
+    let report = |name: &str, elapsed: Duration| {
+        let per_acquisition = elapsed / (threads * iterations) as u32;
+        let delta = (elapsed.as_secs_f64() / baseline.as_secs_f64() - 1.0) * 100.0;
+        println!("{name:<20} {elapsed:>12.2?} {per_acquisition:>10.2?}/acq {delta:>+8.1}%");
+    };
+
+    report("padded", baseline);
+    report("packed", packed_time);
+    report("compact", compact_time);
+    report("packed + counter", colliding_time);
+}
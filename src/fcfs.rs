@@ -0,0 +1,170 @@
+use std::{
+    process,
+    sync::{
+        atomic::{AtomicU64, AtomicUsize, Ordering},
+        Mutex,
+    },
+    thread,
+};
+
+use bakery::{Event, Observer, RawBakeryLock};
+
+use crate::{trace::TraceRecorder, workers};
+
+const MAX_THREADS: usize = 8;
+
+// A thread that finished its doorway before another began its own, and was overtaken anyway.
+struct Violation {
+    overtaken: usize,
+    overtaker: usize,
+    // Positions in the global order of doorway events.
+    finished_doorway: u64,
+    began_doorway: u64,
+}
+
+// Stamps every doorway with its position in a single global order and checks, whenever a thread
+// enters the critical section, that no thread which finished its doorway before the entering
+// thread began its own is still waiting.
+//
+// The stamps are taken with SeqCst RMWs after the ticket is published and before the tickets are
+// read, so a doorway that was stamped as finished before another was stamped as begun really was
+// visible to it. Under the real fences that makes any violation a genuine FCFS failure rather than
+// an artifact of the measurement.
+#[derive(Default)]
+struct OrderChecker {
+    next: AtomicU64,
+    // Where each thread's latest doorway began.
+    began: [AtomicU64; MAX_THREADS],
+    // Where each thread's doorway finished, plus one, while it waits for the lock; zero otherwise.
+    waiting: [AtomicU64; MAX_THREADS],
+    // Acquisitions that had at least one thread ahead of them in doorway order, i.e. the ones the
+    // check actually constrained.
+    constrained: AtomicUsize,
+    violations: AtomicUsize,
+    first_violation: Mutex<Option<Violation>>,
+}
+
+impl OrderChecker {
+    fn stamp(&self) -> u64 {
+        self.next.fetch_add(1, Ordering::SeqCst)
+    }
+}
+
+impl Observer for OrderChecker {
+    fn on_event(&self, thread: usize, event: Event) {
+        match event {
+            Event::Doorway => self.began[thread].store(self.stamp(), Ordering::SeqCst),
+            Event::Ticket(_) => self.waiting[thread].store(self.stamp() + 1, Ordering::SeqCst),
+            Event::GaveUp => self.waiting[thread].store(0, Ordering::SeqCst),
+            Event::Acquired => {
+                self.waiting[thread].store(0, Ordering::SeqCst);
+                let began = self.began[thread].load(Ordering::SeqCst);
+                let mut constrained = false;
+                for other in (0..MAX_THREADS).filter(|&other| other != thread) {
+                    match self.waiting[other].load(Ordering::SeqCst) {
+                        0 => {}
+                        finished if finished - 1 < began => {
+                            self.violations.fetch_add(1, Ordering::Relaxed);
+                            let mut first = self.first_violation.lock().unwrap();
+                            first.get_or_insert(Violation {
+                                overtaken: other,
+                                overtaker: thread,
+                                finished_doorway: finished - 1,
+                                began_doorway: began,
+                            });
+                        }
+                        _ => constrained = true,
+                    }
+                }
+                if constrained {
+                    // Only touched inside the critical section.
+                    let count = self.constrained.load(Ordering::Relaxed);
+                    self.constrained.store(count + 1, Ordering::Relaxed);
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+fn usage() -> ! {
+    eprintln!("usage: bakery fcfs [--threads <n>] [--iterations <n>] [--trace <path>]");
+    process::exit(2);
+}
+
+// Verifies the bakery's first-come, first-served guarantee: once a thread has finished its
+// doorway, no thread that starts its own doorway later may enter the critical section before it.
+// Fails the run if that ever happens.
+pub fn run(args: &[String]) {
+    let mut threads = 4;
+    let mut iterations = 20000;
+    let mut trace = None;
+    let mut flags = args;
+    while let [flag, value, rest @ ..] = flags {
+        if flag == "--trace" {
+            trace = Some(value);
+            flags = rest;
+            continue;
+        }
+        let value = value.parse().unwrap_or_else(|_| usage());
+        match flag.as_str() {
+            "--threads" if (2..=MAX_THREADS).contains(&value) => threads = value,
+            "--iterations" => iterations = value,
+            _ => usage(),
+        }
+        flags = rest;
+    }
+    if !flags.is_empty() {
+        usage();
+    }
+
+    // Some work on both sides of the critical section, varied a lot, so that threads arrive at
+    // the doorway in an ever-changing order.
+    let profile = workers::Profile {
+        critical_section: 20,
+        outside: 200,
+        jitter: 100,
+    };
+    let lock = RawBakeryLock::<MAX_THREADS, _>::with_observer((
+        OrderChecker::default(),
+        trace.map(|_| TraceRecorder::<MAX_THREADS>::new()),
+    ));
+    thread::scope(|scope| {
+        for thread_id in 0..threads {
+            let lock = &lock;
+            workers::spawn(scope, "fcfs", thread_id, move |mut worker| {
+                for _ in 0..iterations {
+                    lock.lock(worker.id);
+                    profile.critical_section(&mut worker.rng);
+                    lock.unlock(worker.id);
+                    profile.outside(&mut worker.rng);
+                }
+            });
+        }
+    });
+
+    let (checker, recorder) = lock.observer();
+    if let (Some(trace), Some(recorder)) = (trace, recorder) {
+        if let Err(err) = recorder.write(trace) {
+            eprintln!("failed to write trace to {trace}: {err}");
+        }
+    }
+    println!(
+        "{threads} threads, {} acquisitions, {} of them with another thread ahead in doorway order",
+        threads * iterations,
+        checker.constrained.load(Ordering::Relaxed)
+    );
+    let violations = checker.violations.load(Ordering::Relaxed);
+    if let Some(violation) = &*checker.first_violation.lock().unwrap() {
+        println!(
+            "FCFS VIOLATED {violations} times: thread {} finished its doorway (at {}) before \
+             thread {} began its own (at {}), but was overtaken by it",
+            violation.overtaken,
+            violation.finished_doorway,
+            violation.overtaker,
+            violation.began_doorway
+        );
+        process::exit(1);
+    }
+    println!("no thread was overtaken by one that started its doorway later");
+}
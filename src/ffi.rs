@@ -0,0 +1,170 @@
+// Every function takes the lock by pointer, which must be one these functions returned and, for
+// `bakery_lock`, not yet freed. A slot out of range panics, which aborts the process rather than
+// unwinding into C.
+
+use core::ffi::c_void;
+
+use crate::{DynBakeryLock, ShmBakeryLock};
+
+/// The number of slots in the locks the C API places in shared memory, so that a Rust process
+/// sharing one with a C component can use [`ShmLock`] for the same layout.
+pub const SHM_SLOTS: usize = 64;
+
+/// The shared-memory lock the C API uses, `bakery_shm_lock` in C.
+pub type ShmLock = ShmBakeryLock<SHM_SLOTS>;
+
+/// A lock for `slots` threads, to be freed with [`bakery_lock_free`].
+#[no_mangle]
+pub extern "C" fn bakery_lock_new(slots: usize) -> *mut DynBakeryLock {
+    Box::into_raw(Box::new(DynBakeryLock::new(slots)))
+}
+
+/// Frees a lock returned by [`bakery_lock_new`], which must not be in use.
+///
+/// # Safety
+///
+/// `lock` must have come from `bakery_lock_new` and not have been freed already, or be null.
+#[no_mangle]
+pub unsafe extern "C" fn bakery_lock_free(lock: *mut DynBakeryLock) {
+    if !lock.is_null() {
+        // SAFETY: the caller guarantees that we own the lock.
+        drop(unsafe { Box::from_raw(lock) });
+    }
+}
+
+/// [`DynBakeryLock::lock`].
+///
+/// # Safety
+///
+/// `lock` must have come from `bakery_lock_new` and not have been freed.
+#[no_mangle]
+pub unsafe extern "C" fn bakery_lock_acquire(lock: *const DynBakeryLock, slot: usize) {
+    // SAFETY: the caller guarantees that the lock is live.
+    unsafe { &*lock }.lock(slot);
+}
+
+/// [`DynBakeryLock::try_lock`].
+///
+/// # Safety
+///
+/// As for [`bakery_lock_acquire`].
+#[no_mangle]
+pub unsafe extern "C" fn bakery_lock_try_acquire(lock: *const DynBakeryLock, slot: usize) -> bool {
+    // SAFETY: the caller guarantees that the lock is live.
+    unsafe { &*lock }.try_lock(slot)
+}
+
+/// [`DynBakeryLock::unlock`].
+///
+/// # Safety
+///
+/// As for [`bakery_lock_acquire`].
+#[no_mangle]
+pub unsafe extern "C" fn bakery_lock_release(lock: *const DynBakeryLock, slot: usize) {
+    // SAFETY: the caller guarantees that the lock is live.
+    unsafe { &*lock }.unlock(slot);
+}
+
+/// The size of a shared-memory lock in bytes, which is how much of the region
+/// [`bakery_shm_lock_at`] uses.
+#[no_mangle]
+pub extern "C" fn bakery_shm_lock_size() -> usize {
+    core::mem::size_of::<ShmLock>()
+}
+
+/// The shared-memory lock at `region`, or null if `region` isn't aligned for it.
+///
+/// # Safety
+///
+/// As for [`ShmBakeryLock::from_ptr`], with the region staying mapped for as long as the lock is
+/// used.
+#[no_mangle]
+pub unsafe extern "C" fn bakery_shm_lock_at(region: *mut c_void) -> *const ShmLock {
+    if !region.cast::<u32>().is_aligned() {
+        return core::ptr::null();
+    }
+    // SAFETY: the caller guarantees the rest.
+    unsafe { ShmLock::from_ptr(region.cast()) }
+}
+
+/// [`ShmBakeryLock::claim`], returning the slot or -1 if all are taken.
+///
+/// # Safety
+///
+/// `lock` must have come from `bakery_shm_lock_at`, with its region still mapped.
+#[no_mangle]
+pub unsafe extern "C" fn bakery_shm_lock_claim(lock: *const ShmLock, owner: u32) -> isize {
+    // SAFETY: the caller guarantees that the lock is mapped.
+    match unsafe { &*lock }.claim(owner) {
+        Some(slot) => slot as isize,
+        None => -1,
+    }
+}
+
+/// [`ShmBakeryLock::claim_for_process`], returning the slot or -1 if all are taken.
+///
+/// # Safety
+///
+/// As for [`bakery_shm_lock_claim`].
+#[cfg(unix)]
+#[no_mangle]
+pub unsafe extern "C" fn bakery_shm_lock_claim_for_process(lock: *const ShmLock) -> isize {
+    // SAFETY: as above.
+    unsafe { bakery_shm_lock_claim(lock, std::process::id()) }
+}
+
+/// [`ShmBakeryLock::release`].
+///
+/// # Safety
+///
+/// As for [`bakery_shm_lock_claim`].
+#[no_mangle]
+pub unsafe extern "C" fn bakery_shm_lock_release_slot(lock: *const ShmLock, slot: usize) {
+    // SAFETY: the caller guarantees that the lock is mapped.
+    unsafe { &*lock }.release(slot);
+}
+
+/// [`ShmBakeryLock::lock`].
+///
+/// # Safety
+///
+/// As for [`bakery_shm_lock_claim`].
+#[no_mangle]
+pub unsafe extern "C" fn bakery_shm_lock_acquire(lock: *const ShmLock, slot: usize) {
+    // SAFETY: the caller guarantees that the lock is mapped.
+    unsafe { &*lock }.lock(slot);
+}
+
+/// [`ShmBakeryLock::try_lock`].
+///
+/// # Safety
+///
+/// As for [`bakery_shm_lock_claim`].
+#[no_mangle]
+pub unsafe extern "C" fn bakery_shm_lock_try_acquire(lock: *const ShmLock, slot: usize) -> bool {
+    // SAFETY: the caller guarantees that the lock is mapped.
+    unsafe { &*lock }.try_lock(slot)
+}
+
+/// [`ShmBakeryLock::unlock`].
+///
+/// # Safety
+///
+/// As for [`bakery_shm_lock_claim`].
+#[no_mangle]
+pub unsafe extern "C" fn bakery_shm_lock_release(lock: *const ShmLock, slot: usize) {
+    // SAFETY: the caller guarantees that the lock is mapped.
+    unsafe { &*lock }.unlock(slot);
+}
+
+/// [`ShmBakeryLock::recover_dead`].
+///
+/// # Safety
+///
+/// As for [`bakery_shm_lock_claim`].
+#[cfg(unix)]
+#[no_mangle]
+pub unsafe extern "C" fn bakery_shm_lock_recover_dead(lock: *const ShmLock) -> usize {
+    // SAFETY: the caller guarantees that the lock is mapped.
+    unsafe { &*lock }.recover_dead()
+}
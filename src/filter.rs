@@ -0,0 +1,113 @@
+use core::sync::atomic::{self, AtomicUsize, Ordering};
+
+use crate::{spin, SlotLock};
+
+/// The filter lock for up to `N` threads, Peterson's lock generalized to `N` threads.
+///
+/// There are `N - 1` levels between a thread calling `lock` and entering the critical section, and
+/// each one is Peterson's lock between the thread that arrived there last (the level's victim) and
+/// everyone else. A thread moves up a level once no other thread is at that level or above, or
+/// once another thread has arrived there after it, so at least one thread is held back at every
+/// level and only one makes it through all of them. Like the bakery lock it only needs loads and
+/// stores, and nobody starves, but it's not first come, first served: a thread waiting at a level
+/// can be overtaken by any number of threads that arrive after it, for as long as each of them
+/// takes the victim's place from someone else on the way.
+pub struct FilterLock<const N: usize> {
+    // The level each slot has reached, or 0 if it isn't trying to enter.
+    levels: [AtomicUsize; N],
+    // The slot that arrived at each level last. Level 0 has no victim.
+    victims: [AtomicUsize; N],
+}
+
+impl<const N: usize> FilterLock<N> {
+    /// An unlocked lock.
+    pub const fn new() -> Self {
+        Self {
+            levels: [const { AtomicUsize::new(0) }; N],
+            victims: [const { AtomicUsize::new(0) }; N],
+        }
+    }
+
+    /// Waits until `thread` has made it through every level, and enters the critical section.
+    pub fn lock(&self, thread: usize) {
+        assert!(thread < N, "slot out of range");
+        for level in 1..N {
+            self.arrive(thread, level);
+            while self.blocked(thread, level) {
+                spin::relax();
+            }
+        }
+
+        // Synchronizes-with the release stores that let us through: the unlock, or a later store to
+        // its level, of every thread we saw below a level, and the victim store of every thread
+        // that took our place. A thread that took our place had to get past the threads we didn't
+        // see leave the same way, so whoever held the lock before us has left in both cases.
+        atomic::fence(Ordering::Acquire);
+    }
+
+    /// Enters the critical section like [`lock`](Self::lock) if that doesn't require waiting at
+    /// any level, returning whether it did.
+    pub fn try_lock(&self, thread: usize) -> bool {
+        assert!(thread < N, "slot out of range");
+        for level in 1..N {
+            self.arrive(thread, level);
+            if self.blocked(thread, level) {
+                // Dropping back to level 0 can only let other threads through sooner, and leaving
+                // ourselves as the victim only holds back whoever arrives at the level next until
+                // they see us gone.
+                self.levels[thread].store(0, Ordering::Release);
+                return false;
+            }
+        }
+        atomic::fence(Ordering::Acquire);
+        true
+    }
+
+    /// Leaves the critical section entered with `lock(thread)`.
+    pub fn unlock(&self, thread: usize) {
+        self.levels[thread].store(0, Ordering::Release);
+    }
+
+    fn arrive(&self, thread: usize, level: usize) {
+        // Release, like both stores in `PetersonLock::lock`, and for the same reason: a thread that
+        // moves up because of either one must see everything we did before, including the critical
+        // section we last left.
+        self.levels[thread].store(level, Ordering::Release);
+        self.victims[level].store(thread, Ordering::Release);
+
+        // Both stores must become visible before we look for other threads at this level, or two
+        // threads arriving together can each see the other below it and both move up, which is
+        // the store-buffering pattern `PetersonLock::lock` describes. With the fence, whichever of
+        // the two threads' fences comes later in the total order of SC fences sees the other
+        // thread at the level and itself as the victim.
+        atomic::fence(Ordering::SeqCst);
+    }
+
+    // Whether there's a thread at `level` or above other than `thread`, which is still the level's
+    // victim. `any` only comes back false once it has read every other slot's level, so a thread
+    // that moves up because nobody else is left has seen each of them leave.
+    fn blocked(&self, thread: usize, level: usize) -> bool {
+        (0..N).any(|other| other != thread && self.levels[other].load(Ordering::Relaxed) >= level)
+            && self.victims[level].load(Ordering::Relaxed) == thread
+    }
+}
+
+impl<const N: usize> Default for FilterLock<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<const N: usize> SlotLock for FilterLock<N> {
+    fn slots(&self) -> usize {
+        N
+    }
+
+    fn lock(&self, slot: usize) {
+        FilterLock::lock(self, slot);
+    }
+
+    fn unlock(&self, slot: usize) {
+        FilterLock::unlock(self, slot);
+    }
+}
@@ -0,0 +1,316 @@
+use std::sync::atomic::{self, AtomicU32, Ordering};
+
+use crate::{
+    algorithm::{Bakery, Blocked, Next},
+    backoff::NoBackoff,
+    spin, SlotLock,
+};
+
+// How many times a waiter rereads a slot before parking on it.
+const SPIN_BUDGET: u32 = 100;
+
+/// Lamport's bakery lock for up to `N` threads whose waiters sleep in the kernel instead of
+/// spinning, for critical sections long enough that spinning through them would waste a core.
+///
+/// Every thread taking part passes its own slot in `0..N` to every call, as with
+/// [`RawBakeryLock`](crate::RawBakeryLock). A waiter rereads the slot it's waiting on for a while,
+/// and then parks on that slot's `choosing` flag or ticket (whichever it's waiting to change), with
+/// a futex on Linux, with `WaitOnAddress` on Windows, with `memory.atomic.wait32` on WebAssembly
+/// with shared memory, and with a table of condition variables keyed by the word's address anywhere
+/// else. Leaving the doorway and unlocking wake whoever is parked on the slot, which costs them an
+/// extra SC fence each, and a system call when someone is actually parked. There is no observer
+/// and no handoff.
+pub struct FutexBakeryLock<const N: usize> {
+    // 0 or 1, as 32-bit words so that waiters can park on them.
+    choosing: [AtomicU32; N],
+    tickets: [AtomicU32; N],
+    // How many threads are parked, or about to park, on each slot's words.
+    parked: [AtomicU32; N],
+}
+
+impl<const N: usize> FutexBakeryLock<N> {
+    /// An unlocked lock with every slot free.
+    pub fn new() -> Self {
+        assert!(
+            (N as u64) < Self::MAX_TICKET / 4,
+            "too many slots to tell tickets apart across a wraparound"
+        );
+
+        Self {
+            choosing: core::array::from_fn(|_| AtomicU32::new(0)),
+            tickets: core::array::from_fn(|_| AtomicU32::new(0)),
+            parked: core::array::from_fn(|_| AtomicU32::new(0)),
+        }
+    }
+
+    /// Waits until every thread ahead of `thread` in the bakery has left and enters the critical
+    /// section. `thread` must be a slot in `0..N` that no other thread is currently using.
+    pub fn lock(&self, thread: usize) {
+        let ticket = self.doorway(thread);
+        self.wake(thread, &self.choosing[thread]);
+
+        self.wait_turn(thread, ticket, |other, blocked, _: &mut NoBackoff| {
+            match blocked {
+                Blocked::Choosing => self.wait(other, &self.choosing[other], 1),
+                Blocked::Ticket(ticket) => self.wait(other, &self.tickets[other], ticket as u32),
+            }
+            Next::Wait
+        });
+    }
+
+    /// Leaves the critical section entered with `lock(thread)`, letting the next thread in.
+    pub fn unlock(&self, thread: usize) {
+        self.tickets[thread].store(0, Ordering::Release);
+        self.wake(thread, &self.tickets[thread]);
+    }
+
+    // Waits for `word`, one of `slot`'s, to change from `value` (or returns early, spuriously).
+    fn wait(&self, slot: usize, word: &AtomicU32, value: u32) {
+        for _ in 0..SPIN_BUDGET {
+            if word.load(Ordering::Relaxed) != value {
+                return;
+            }
+            spin::relax();
+        }
+
+        // Either `wake` sees us counted here, or we see the store it follows in the check below
+        // (and the kernel's own check of the word), thanks to the SC accesses on both sides.
+        self.parked[slot].fetch_add(1, Ordering::SeqCst);
+        if word.load(Ordering::SeqCst) == value {
+            sys::wait(word, value);
+        }
+        self.parked[slot].fetch_sub(1, Ordering::Relaxed);
+    }
+
+    // Wakes everyone parked on `word`, one of `slot`'s, after it has been changed.
+    fn wake(&self, slot: usize, word: &AtomicU32) {
+        atomic::fence(Ordering::SeqCst);
+        if self.parked[slot].load(Ordering::Relaxed) != 0 {
+            sys::wake_all(word);
+        }
+    }
+}
+
+impl<const N: usize> Bakery for FutexBakeryLock<N> {
+    const MAX_TICKET: u64 = u32::MAX as u64;
+
+    fn is_choosing(&self, slot: usize, order: Ordering) -> bool {
+        self.choosing[slot].load(order) != 0
+    }
+
+    fn set_choosing(&self, slot: usize, choosing: bool, order: Ordering) {
+        self.choosing[slot].store(choosing.into(), order);
+    }
+
+    fn ticket(&self, slot: usize, order: Ordering) -> u64 {
+        self.tickets[slot].load(order).into()
+    }
+
+    fn set_ticket(&self, slot: usize, ticket: u64, order: Ordering) {
+        self.tickets[slot].store(ticket as u32, order);
+    }
+
+    fn active_slots(&self) -> impl Iterator<Item = usize> + '_ {
+        0..N
+    }
+}
+
+impl<const N: usize> Default for FutexBakeryLock<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<const N: usize> SlotLock for FutexBakeryLock<N> {
+    fn slots(&self) -> usize {
+        N
+    }
+
+    fn lock(&self, slot: usize) {
+        FutexBakeryLock::lock(self, slot);
+    }
+
+    fn unlock(&self, slot: usize) {
+        FutexBakeryLock::unlock(self, slot);
+    }
+}
+
+// Parks the calling thread until an atomic changes, the one thing the lock needs from the OS.
+#[cfg(all(
+    target_os = "linux",
+    any(
+        target_arch = "x86",
+        target_arch = "x86_64",
+        target_arch = "arm",
+        target_arch = "aarch64",
+        target_arch = "riscv64"
+    )
+))]
+mod sys {
+    use std::{
+        ffi::{c_int, c_long},
+        ptr,
+        sync::atomic::AtomicU32,
+    };
+
+    #[cfg(any(target_arch = "x86", target_arch = "arm"))]
+    const SYS_FUTEX: c_long = 240;
+    #[cfg(target_arch = "x86_64")]
+    const SYS_FUTEX: c_long = 202;
+    #[cfg(any(target_arch = "aarch64", target_arch = "riscv64"))]
+    const SYS_FUTEX: c_long = 98;
+
+    // Private to the process, which lets the kernel skip looking up shared mappings.
+    const FUTEX_WAIT_PRIVATE: c_int = 128;
+    const FUTEX_WAKE_PRIVATE: c_int = 129;
+
+    extern "C" {
+        fn syscall(number: c_long, ...) -> c_long;
+    }
+
+    // Sleeps until woken if `word` still holds `value`. Interruptions and wakeups meant for someone
+    // else just make the caller look again.
+    pub fn wait(word: &AtomicU32, value: u32) {
+        // SAFETY: the word is a valid, aligned `u32` for the whole call, and there's no timeout.
+        unsafe {
+            syscall(
+                SYS_FUTEX,
+                word.as_ptr(),
+                FUTEX_WAIT_PRIVATE,
+                value,
+                ptr::null::<()>(),
+            );
+        }
+    }
+
+    pub fn wake_all(word: &AtomicU32) {
+        // SAFETY: as above. Nobody can be woken into anything but rereading the word.
+        unsafe {
+            syscall(SYS_FUTEX, word.as_ptr(), FUTEX_WAKE_PRIVATE, c_int::MAX);
+        }
+    }
+}
+
+// WebAssembly's shared-memory threads (web workers, or wasi-threads) come with their own futex
+// instructions. The main thread of a browser isn't allowed to wait, so the lock can only be taken
+// there if nobody ever has to park. Experimental: this has never been compiled for wasm32.
+#[cfg(all(target_arch = "wasm32", target_feature = "atomics"))]
+mod sys {
+    use core::arch::wasm32;
+    use std::sync::atomic::AtomicU32;
+
+    // Sleeps until woken if `word` still holds `value`. Wakeups meant for someone else just make
+    // the caller look again.
+    pub fn wait(word: &AtomicU32, value: u32) {
+        // SAFETY: the word is a valid, aligned 32-bit word in shared memory for the whole call,
+        // and a negative timeout means none.
+        unsafe {
+            wasm32::memory_atomic_wait32(word.as_ptr().cast(), value as i32, -1);
+        }
+    }
+
+    pub fn wake_all(word: &AtomicU32) {
+        // SAFETY: as above.
+        unsafe {
+            wasm32::memory_atomic_notify(word.as_ptr().cast(), u32::MAX);
+        }
+    }
+}
+
+#[cfg(windows)]
+mod sys {
+    use std::{ffi::c_void, mem, sync::atomic::AtomicU32};
+
+    const INFINITE: u32 = u32::MAX;
+
+    #[link(name = "synchronization")]
+    extern "system" {
+        fn WaitOnAddress(
+            address: *const c_void,
+            compare_address: *const c_void,
+            address_size: usize,
+            milliseconds: u32,
+        ) -> i32;
+        fn WakeByAddressAll(address: *const c_void);
+    }
+
+    // Sleeps until woken if `word` still holds `value`. Wakeups meant for someone else just make
+    // the caller look again.
+    pub fn wait(word: &AtomicU32, value: u32) {
+        // SAFETY: both addresses point to valid, aligned `u32`s for the whole call.
+        unsafe {
+            WaitOnAddress(
+                word.as_ptr().cast(),
+                (&value as *const u32).cast(),
+                mem::size_of::<u32>(),
+                INFINITE,
+            );
+        }
+    }
+
+    pub fn wake_all(word: &AtomicU32) {
+        // SAFETY: as above.
+        unsafe { WakeByAddressAll(word.as_ptr().cast()) };
+    }
+}
+
+// Everywhere else, including Linux on architectures whose futex system call number we don't
+// know, a small table of condition variables stands in for the kernel's, with every word hashed to
+// one of them. Waiters check the word and wakers notify under the bucket's mutex, so a wakeup
+// can't slip in between the check and the wait.
+#[cfg(not(any(
+    all(
+        target_os = "linux",
+        any(
+            target_arch = "x86",
+            target_arch = "x86_64",
+            target_arch = "arm",
+            target_arch = "aarch64",
+            target_arch = "riscv64"
+        )
+    ),
+    all(target_arch = "wasm32", target_feature = "atomics"),
+    windows
+)))]
+mod sys {
+    use std::sync::{
+        atomic::{AtomicU32, Ordering},
+        Condvar, Mutex, PoisonError,
+    };
+
+    const BUCKETS: usize = 64;
+
+    struct Bucket {
+        mutex: Mutex<()>,
+        condvar: Condvar,
+    }
+
+    static TABLE: [Bucket; BUCKETS] = [const {
+        Bucket {
+            mutex: Mutex::new(()),
+            condvar: Condvar::new(),
+        }
+    }; BUCKETS];
+
+    fn bucket(word: &AtomicU32) -> &'static Bucket {
+        // Fibonacci hashing, so that the slots of one lock, a word apart, spread over the table.
+        let hash = (word.as_ptr() as usize as u64).wrapping_mul(0x9e3779b97f4a7c15);
+        &TABLE[(hash >> 58) as usize]
+    }
+
+    // Sleeps until woken if `word` still holds `value`. Wakeups meant for another word in the
+    // same bucket just make the caller look again.
+    pub fn wait(word: &AtomicU32, value: u32) {
+        let bucket = bucket(word);
+        let guard = bucket.mutex.lock().unwrap_or_else(PoisonError::into_inner);
+        if word.load(Ordering::Relaxed) == value {
+            drop(bucket.condvar.wait(guard));
+        }
+    }
+
+    pub fn wake_all(word: &AtomicU32) {
+        let bucket = bucket(word);
+        let _guard = bucket.mutex.lock().unwrap_or_else(PoisonError::into_inner);
+        bucket.condvar.notify_all();
+    }
+}
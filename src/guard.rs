@@ -0,0 +1,187 @@
+use std::{
+    fmt,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use crate::{
+    registry::{self, SlotRegistry},
+    RawBakeryLock,
+};
+
+/// The lock with the default layout and no diagnostics, as most users want it.
+///
+/// Threads can simply call [`lock`](Self::lock), which assigns them one of the lock's `N` slots
+/// the first time and gives it back when they exit, or [`register`](Self::register) explicitly
+/// and lock through the returned handle. Unlike [`RawBakeryLock`], acquiring it returns a [`BakeryGuard`]
+/// that leaves the critical section when it goes out of scope, including when the thread panics
+/// inside it.
+pub struct BakeryLock<const N: usize> {
+    raw: RawBakeryLock<N>,
+    registry: Arc<SlotRegistry<N>>,
+}
+
+impl<const N: usize> BakeryLock<N> {
+    /// An unlocked lock with every slot free.
+    pub fn new() -> Self {
+        Self {
+            raw: RawBakeryLock::new(),
+            registry: Arc::new(SlotRegistry::new()),
+        }
+    }
+
+    /// Waits for the lock and enters the critical section until the returned guard is dropped,
+    /// using the slot assigned to the calling thread. Locking it again from the same thread before
+    /// then deadlocks.
+    ///
+    /// A thread locking it for the first time while all `N` slots are taken waits for another
+    /// thread to give its slot back by exiting or dropping its handle.
+    pub fn lock(&self) -> BakeryGuard<'_, N> {
+        let slot = registry::assigned(&self.registry);
+        self.raw.lock(slot);
+        BakeryGuard { lock: self, slot }
+    }
+
+    /// Enters the critical section like [`lock`](Self::lock) if that doesn't require waiting for
+    /// another thread (or for a slot to be given back), or returns `None`.
+    pub fn try_lock(&self) -> Option<BakeryGuard<'_, N>> {
+        let slot = registry::try_assigned(&self.registry)?;
+        self.raw
+            .try_lock(slot)
+            .then(|| BakeryGuard { lock: self, slot })
+    }
+
+    /// Like [`lock`](Self::lock), but gives up and returns `None` after waiting for `timeout`.
+    pub fn try_lock_for(&self, timeout: Duration) -> Option<BakeryGuard<'_, N>> {
+        match Instant::now().checked_add(timeout) {
+            Some(deadline) => self.try_lock_until(deadline),
+            None => Some(self.lock()),
+        }
+    }
+
+    /// Like [`lock`](Self::lock), but gives up and returns `None` once `deadline` has passed,
+    /// whether the thread was still waiting for a slot or for the lock itself.
+    pub fn try_lock_until(&self, deadline: Instant) -> Option<BakeryGuard<'_, N>> {
+        let slot = registry::assigned_until(&self.registry, deadline)?;
+        self.raw
+            .try_lock_until(slot, deadline)
+            .then(|| BakeryGuard { lock: self, slot })
+    }
+
+    /// Claims a free slot for the calling thread until the returned handle is dropped, or returns
+    /// `None` if all `N` are taken.
+    pub fn register(&self) -> Option<SlotHandle<'_, N>> {
+        let slot = self.registry.claim()?;
+        Some(SlotHandle { lock: self, slot })
+    }
+
+    /// The underlying lock, for the operations the guard doesn't cover. Locking it directly
+    /// bypasses the registry, so the caller has to make sure no registered handle uses the same
+    /// slot.
+    pub fn raw(&self) -> &RawBakeryLock<N> {
+        &self.raw
+    }
+}
+
+impl<const N: usize> Default for BakeryLock<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// One of the slots of a [`BakeryLock`], which is returned to the lock when the handle is
+/// dropped.
+pub struct SlotHandle<'a, const N: usize> {
+    lock: &'a BakeryLock<N>,
+    slot: usize,
+}
+
+impl<const N: usize> SlotHandle<'_, N> {
+    /// Waits for the lock and enters the critical section until the returned guard is dropped.
+    pub fn lock(&mut self) -> BakeryGuard<'_, N> {
+        self.lock.raw.lock(self.slot);
+        BakeryGuard {
+            lock: self.lock,
+            slot: self.slot,
+        }
+    }
+
+    /// Enters the critical section like [`lock`](Self::lock) if that doesn't require waiting for
+    /// another thread, or returns `None`.
+    pub fn try_lock(&mut self) -> Option<BakeryGuard<'_, N>> {
+        self.lock.raw.try_lock(self.slot).then(|| BakeryGuard {
+            lock: self.lock,
+            slot: self.slot,
+        })
+    }
+
+    /// Like [`lock`](Self::lock), but gives up and returns `None` after waiting for `timeout`.
+    pub fn try_lock_for(&mut self, timeout: Duration) -> Option<BakeryGuard<'_, N>> {
+        self.lock
+            .raw
+            .try_lock_for(self.slot, timeout)
+            .then(|| BakeryGuard {
+                lock: self.lock,
+                slot: self.slot,
+            })
+    }
+
+    /// Like [`lock`](Self::lock), but gives up and returns `None` once `deadline` has passed.
+    pub fn try_lock_until(&mut self, deadline: Instant) -> Option<BakeryGuard<'_, N>> {
+        self.lock
+            .raw
+            .try_lock_until(self.slot, deadline)
+            .then(|| BakeryGuard {
+                lock: self.lock,
+                slot: self.slot,
+            })
+    }
+
+    /// The index of the slot in `0..N`.
+    pub fn slot(&self) -> usize {
+        self.slot
+    }
+}
+
+impl<const N: usize> Drop for SlotHandle<'_, N> {
+    fn drop(&mut self) {
+        self.lock.registry.release(self.slot);
+    }
+}
+
+impl<const N: usize> fmt::Debug for SlotHandle<'_, N> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SlotHandle")
+            .field("slot", &self.slot)
+            .finish()
+    }
+}
+
+/// Proof that a thread is in the critical section of a [`BakeryLock`], which it leaves when the
+/// guard is dropped.
+#[must_use = "dropping the guard immediately leaves the critical section"]
+pub struct BakeryGuard<'a, const N: usize> {
+    lock: &'a BakeryLock<N>,
+    slot: usize,
+}
+
+impl<const N: usize> BakeryGuard<'_, N> {
+    /// The slot the lock was acquired in.
+    pub fn slot(&self) -> usize {
+        self.slot
+    }
+}
+
+impl<const N: usize> Drop for BakeryGuard<'_, N> {
+    fn drop(&mut self) {
+        self.lock.raw.unlock(self.slot);
+    }
+}
+
+impl<const N: usize> fmt::Debug for BakeryGuard<'_, N> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("BakeryGuard")
+            .field("slot", &self.slot)
+            .finish()
+    }
+}
@@ -0,0 +1,69 @@
+use crate::{RawBakeryLock, SlotLock};
+
+/// A two-level bakery lock for large numbers of threads: `GROUPS` groups of `PER_GROUP` slots
+/// each, with a bakery lock per group and another one between the groups.
+///
+/// A thread first gets through its group's bakery, and then competes for the top-level bakery in
+/// its group's slot, which nobody else can be using since only one thread of each group gets that
+/// far at a time. A flat lock scans all of its slots in every doorway and wait loop, while here each
+/// acquisition scans `PER_GROUP + GROUPS` slots, 32 rather than 256 for 16 groups of 16.
+///
+/// Slots are numbered `0..GROUPS * PER_GROUP`, with slot `s` in group `s / PER_GROUP`, and work the
+/// same way as in [`RawBakeryLock`]: every thread taking part passes its own slot to every call.
+/// Each level is first come, first served on its own, but the lock as a whole is not: a thread can
+/// be overtaken at the top level by threads of other groups that arrived after it.
+pub struct HierarchicalBakeryLock<const GROUPS: usize, const PER_GROUP: usize> {
+    groups: [RawBakeryLock<PER_GROUP>; GROUPS],
+    top: RawBakeryLock<GROUPS>,
+}
+
+impl<const GROUPS: usize, const PER_GROUP: usize> HierarchicalBakeryLock<GROUPS, PER_GROUP> {
+    /// An unlocked lock with every slot free.
+    pub fn new() -> Self {
+        Self {
+            groups: core::array::from_fn(|_| RawBakeryLock::new()),
+            top: RawBakeryLock::new(),
+        }
+    }
+
+    /// Waits until the thread using `thread` can enter the critical section and enters it.
+    /// `thread` must be a slot in `0..GROUPS * PER_GROUP` that no other thread is currently using.
+    pub fn lock(&self, thread: usize) {
+        let (group, slot) = (thread / PER_GROUP, thread % PER_GROUP);
+        self.groups[group].lock(slot);
+        self.top.lock(group);
+    }
+
+    /// Leaves the critical section entered with `lock(thread)`, letting the next thread in.
+    pub fn unlock(&self, thread: usize) {
+        let (group, slot) = (thread / PER_GROUP, thread % PER_GROUP);
+        // The other way around would let the next thread of our group into the top-level bakery
+        // in our slot while we're still in it.
+        self.top.unlock(group);
+        self.groups[group].unlock(slot);
+    }
+}
+
+impl<const GROUPS: usize, const PER_GROUP: usize> Default
+    for HierarchicalBakeryLock<GROUPS, PER_GROUP>
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<const GROUPS: usize, const PER_GROUP: usize> SlotLock
+    for HierarchicalBakeryLock<GROUPS, PER_GROUP>
+{
+    fn slots(&self) -> usize {
+        GROUPS * PER_GROUP
+    }
+
+    fn lock(&self, slot: usize) {
+        HierarchicalBakeryLock::lock(self, slot);
+    }
+
+    fn unlock(&self, slot: usize) {
+        HierarchicalBakeryLock::unlock(self, slot);
+    }
+}
@@ -0,0 +1,282 @@
+use core::{
+    marker::PhantomData,
+    sync::atomic::{AtomicBool, AtomicU32, Ordering},
+};
+
+use crate::{
+    algorithm::{Bakery, Next},
+    backoff::{Backoff, NoBackoff},
+};
+
+/// How [`IrqBakeryLock`] masks interrupts on the core it runs on, for as long as a slot is in the
+/// bakery.
+///
+/// This is the same pair of operations the `critical-section` crate's implementations provide,
+/// so with that crate an implementation is one line each:
+///
+/// ```ignore
+/// struct CriticalSection;
+///
+/// impl InterruptMask for CriticalSection {
+///     type State = critical_section::RestoreState;
+///
+///     fn disable() -> Self::State {
+///         unsafe { critical_section::acquire() }
+///     }
+///
+///     fn restore(state: Self::State) {
+///         unsafe { critical_section::release(state) }
+///     }
+/// }
+/// ```
+pub trait InterruptMask {
+    /// Whatever [`restore`](Self::restore) needs to put the interrupts back as they were.
+    type State;
+
+    /// Masks interrupts on the calling core, returning their previous state.
+    fn disable() -> Self::State;
+
+    /// Restores the state returned by the matching [`disable`](Self::disable), which will be the
+    /// most recent one that hasn't been restored yet.
+    fn restore(state: Self::State);
+}
+
+/// Doesn't mask anything, for locks that are never taken from an interrupt handler, and for
+/// testing on a hosted target.
+pub struct NoMask;
+
+impl InterruptMask for NoMask {
+    type State = ();
+
+    fn disable() {}
+
+    fn restore(_state: ()) {}
+}
+
+/// Masks interrupts with `PRIMASK` on Cortex-M, like `cortex_m::interrupt::free`.
+#[cfg(all(target_arch = "arm", target_os = "none"))]
+pub struct CortexM;
+
+#[cfg(all(target_arch = "arm", target_os = "none"))]
+impl InterruptMask for CortexM {
+    // Whether interrupts were enabled.
+    type State = bool;
+
+    fn disable() -> bool {
+        let primask: u32;
+        // SAFETY: reading `PRIMASK` and setting it have no effect other than masking interrupts.
+        unsafe {
+            core::arch::asm!(
+                "mrs {}, PRIMASK",
+                out(reg) primask,
+                options(nomem, nostack, preserves_flags),
+            );
+            core::arch::asm!("cpsid i", options(nomem, nostack, preserves_flags));
+        }
+        // Keeps the compiler from moving the lock's accesses to before interrupts are masked.
+        core::sync::atomic::compiler_fence(Ordering::SeqCst);
+        primask & 1 == 0
+    }
+
+    fn restore(enabled: bool) {
+        core::sync::atomic::compiler_fence(Ordering::SeqCst);
+        if enabled {
+            // SAFETY: interrupts were enabled when the matching `disable` masked them.
+            unsafe { core::arch::asm!("cpsie i", options(nomem, nostack, preserves_flags)) };
+        }
+    }
+}
+
+/// Masks machine-mode interrupts with `mstatus.MIE` on RISC-V, like the `riscv` crate's
+/// `critical-section` implementation.
+#[cfg(all(
+    any(target_arch = "riscv32", target_arch = "riscv64"),
+    target_os = "none"
+))]
+pub struct RiscvMachine;
+
+#[cfg(all(
+    any(target_arch = "riscv32", target_arch = "riscv64"),
+    target_os = "none"
+))]
+impl InterruptMask for RiscvMachine {
+    // Whether interrupts were enabled.
+    type State = bool;
+
+    fn disable() -> bool {
+        let mstatus: usize;
+        // SAFETY: clearing `MIE` has no effect other than masking machine-mode interrupts.
+        unsafe {
+            core::arch::asm!("csrrci {}, mstatus, 8", out(reg) mstatus, options(nomem, nostack));
+        }
+        core::sync::atomic::compiler_fence(Ordering::SeqCst);
+        mstatus & 8 != 0
+    }
+
+    fn restore(enabled: bool) {
+        core::sync::atomic::compiler_fence(Ordering::SeqCst);
+        if enabled {
+            // SAFETY: interrupts were enabled when the matching `disable` masked them.
+            unsafe { core::arch::asm!("csrsi mstatus, 8", options(nomem, nostack)) };
+        }
+    }
+}
+
+/// Lamport's bakery lock for up to `N` execution contexts, some of which may be interrupt
+/// handlers, on bare metal.
+///
+/// The plain lock can't be taken from an interrupt handler: if the handler interrupts its own
+/// core's thread context while that is in the doorway or holding a ticket, the handler waits for
+/// something that can't happen until it returns. This lock masks interrupts with `M` from before
+/// the doorway until the guard is dropped, so that on any one core nothing can get in between.
+/// Every context taking part (the thread context of each core and each handler) passes its own
+/// slot in `0..N`, and contexts on other cores wait for each other as usual.
+///
+/// A slot that is used again while it's already in the bakery, by a higher-priority handler
+/// sharing its slot with the code it interrupted or by something `M` can't mask like an NMI, would
+/// corrupt its ticket. `lock` detects that from the slot's own `choosing` flag and ticket and
+/// panics, since whoever re-entered could only ever wait for itself, and `try_lock` returns `None`.
+/// Like the rest of the lock, this only takes loads and stores, so it works on cores without
+/// compare-and-swap such as Cortex-M0.
+pub struct IrqBakeryLock<const N: usize, M = NoMask> {
+    choosing: [AtomicBool; N],
+    tickets: [AtomicU32; N],
+    mask: PhantomData<M>,
+}
+
+impl<const N: usize, M: InterruptMask> IrqBakeryLock<N, M> {
+    /// An unlocked lock with every slot free.
+    pub const fn new() -> Self {
+        assert!(
+            (N as u64) < Self::MAX_TICKET / 4,
+            "too many slots to tell tickets apart across a wraparound"
+        );
+
+        Self {
+            choosing: [const { AtomicBool::new(false) }; N],
+            tickets: [const { AtomicU32::new(0) }; N],
+            mask: PhantomData,
+        }
+    }
+
+    /// Masks interrupts, waits until every context ahead of `slot` in the bakery has left, and
+    /// enters the critical section until the guard is dropped. Panics if `slot` is already in the
+    /// bakery.
+    pub fn lock(&self, slot: usize) -> IrqBakeryGuard<'_, N, M> {
+        let Some(state) = self.enter(slot) else {
+            panic!("slot {slot} re-entered the bakery while already using it");
+        };
+        let ticket = self.doorway(slot);
+        self.wait_turn(slot, ticket, |_, _, backoff: &mut NoBackoff| {
+            backoff.snooze();
+            Next::Wait
+        });
+        IrqBakeryGuard {
+            lock: self,
+            slot,
+            state: Some(state),
+            _not_send: PhantomData,
+        }
+    }
+
+    /// Enters the critical section like [`lock`](Self::lock) if that doesn't require waiting for
+    /// another context, or returns `None`, as it does if `slot` is already in the bakery.
+    pub fn try_lock(&self, slot: usize) -> Option<IrqBakeryGuard<'_, N, M>> {
+        let state = self.enter(slot)?;
+        let entered = match self.try_doorway(slot) {
+            Some(ticket) => self.try_turn(slot, ticket, |_, _| Next::GiveUp),
+            None => false,
+        };
+        if !entered {
+            self.leave(slot, state);
+            return None;
+        }
+        Some(IrqBakeryGuard {
+            lock: self,
+            slot,
+            state: Some(state),
+            _not_send: PhantomData,
+        })
+    }
+
+    /// Runs `f` in the critical section entered from `slot`.
+    pub fn with<R>(&self, slot: usize, f: impl FnOnce() -> R) -> R {
+        let _guard = self.lock(slot);
+        f()
+    }
+
+    // Masks interrupts and marks `slot` as in use, or restores them and returns `None` if it
+    // already was.
+    fn enter(&self, slot: usize) -> Option<M::State> {
+        let state = M::disable();
+        // A slot is in the bakery from setting its `choosing` flag until its ticket is cleared, and
+        // the ticket is taken before the flag is cleared. With interrupts masked, nothing else on
+        // this core can set either before our doorway does, and no other core uses the slot.
+        if self.is_choosing(slot, Ordering::Relaxed) || self.ticket(slot, Ordering::Relaxed) != 0 {
+            M::restore(state);
+            return None;
+        }
+        Some(state)
+    }
+
+    fn leave(&self, slot: usize, state: M::State) {
+        self.tickets[slot].store(0, Ordering::Release);
+        M::restore(state);
+    }
+}
+
+impl<const N: usize, M: InterruptMask> Bakery for IrqBakeryLock<N, M> {
+    const MAX_TICKET: u64 = u32::MAX as u64;
+
+    fn is_choosing(&self, slot: usize, order: Ordering) -> bool {
+        self.choosing[slot].load(order)
+    }
+
+    fn set_choosing(&self, slot: usize, choosing: bool, order: Ordering) {
+        self.choosing[slot].store(choosing, order);
+    }
+
+    fn ticket(&self, slot: usize, order: Ordering) -> u64 {
+        self.tickets[slot].load(order).into()
+    }
+
+    fn set_ticket(&self, slot: usize, ticket: u64, order: Ordering) {
+        self.tickets[slot].store(ticket as u32, order);
+    }
+
+    fn active_slots(&self) -> impl Iterator<Item = usize> + '_ {
+        0..N
+    }
+}
+
+impl<const N: usize, M: InterruptMask> Default for IrqBakeryLock<N, M> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// The critical section of an [`IrqBakeryLock`], with interrupts masked until it's dropped.
+#[must_use = "dropping the guard immediately unlocks the lock"]
+pub struct IrqBakeryGuard<'a, const N: usize, M: InterruptMask> {
+    lock: &'a IrqBakeryLock<N, M>,
+    slot: usize,
+    // Always `Some` until the guard is dropped.
+    state: Option<M::State>,
+    // Interrupts have to be restored on the core that masked them.
+    _not_send: PhantomData<*const ()>,
+}
+
+impl<const N: usize, M: InterruptMask> IrqBakeryGuard<'_, N, M> {
+    /// The slot the lock was taken from.
+    pub fn slot(&self) -> usize {
+        self.slot
+    }
+}
+
+impl<const N: usize, M: InterruptMask> Drop for IrqBakeryGuard<'_, N, M> {
+    fn drop(&mut self) {
+        if let Some(state) = self.state.take() {
+            self.lock.leave(self.slot, state);
+        }
+    }
+}
@@ -0,0 +1,117 @@
+use core::sync::atomic::{self, AtomicBool, AtomicUsize, Ordering};
+
+use crate::{spin, SlotLock};
+
+// What `y` holds while nobody has claimed the lock. Slots are stored one up from their index.
+const NOBODY: usize = 0;
+
+/// Lamport's fast mutual exclusion lock for up to `N` threads, which takes a constant number of
+/// steps when nobody else is trying to enter.
+///
+/// A thread writes its slot to `x` and, unless someone has already claimed `y`, claims `y` and
+/// checks that `x` is still its own. If it is, no other thread got in between, and it enters
+/// without looking at any other slot. Otherwise it falls back to waiting for every announced slot
+/// to go quiet and enters only if its claim on `y` survived, starting over if not. The bakery
+/// lock scans all `N` slots in every doorway and wait loop, contended or not; this lock only does
+/// when two threads actually collide. It's deadlock-free, but a thread can be kept out for as long
+/// as others keep colliding with it.
+pub struct LamportFastLock<const N: usize> {
+    x: AtomicUsize,
+    y: AtomicUsize,
+    // Whether each slot is trying to enter, for the slow path.
+    trying: [AtomicBool; N],
+}
+
+impl<const N: usize> LamportFastLock<N> {
+    /// An unlocked lock.
+    pub const fn new() -> Self {
+        Self {
+            x: AtomicUsize::new(NOBODY),
+            y: AtomicUsize::new(NOBODY),
+            trying: [const { AtomicBool::new(false) }; N],
+        }
+    }
+
+    /// Enters the critical section, taking the fast path if no other thread is in the way.
+    pub fn lock(&self, thread: usize) {
+        assert!(thread < N, "slot out of range");
+        let me = thread + 1;
+
+        loop {
+            self.trying[thread].store(true, Ordering::Relaxed);
+            // Release, so that the slow path of whoever sees our write to `x` also sees us trying.
+            self.x.store(me, Ordering::Release);
+            // `x` must be visible before we read `y`, and below, `y` before we read `x`: without
+            // either fence, a thread writing `x` and one writing `y` could each miss the other's
+            // write and both take the fast path. That's the store-buffering pattern, and the fences
+            // rule it out as they do in `PetersonLock::lock`: whichever of the two threads' fences
+            // comes later sees the other's write, and either finds `y` claimed or `x` overwritten.
+            atomic::fence(Ordering::SeqCst);
+            if self.y.load(Ordering::Relaxed) != NOBODY {
+                // Give way to whoever has claimed `y` until it leaves. Release, like the store in
+                // `unlock`, since a thread in the slow path may see this instead of our last
+                // unlock.
+                self.trying[thread].store(false, Ordering::Release);
+                self.wait_for_y();
+                continue;
+            }
+
+            self.y.store(me, Ordering::Relaxed);
+            atomic::fence(Ordering::SeqCst);
+            if self.x.load(Ordering::Relaxed) == me {
+                break;
+            }
+
+            // Someone overwrote `x` after we wrote it, so we collided: wait for every thread that
+            // could have got through to give up or leave, and see whose claim on `y` is left.
+            self.trying[thread].store(false, Ordering::Release);
+            // Synchronizes-with the release store to `x`, so that we see its writer trying.
+            atomic::fence(Ordering::Acquire);
+            for other in 0..N {
+                while self.trying[other].load(Ordering::Relaxed) {
+                    spin::relax();
+                }
+            }
+            if self.y.load(Ordering::Relaxed) == me {
+                break;
+            }
+            self.wait_for_y();
+        }
+
+        // Synchronizes-with the release store to `y` in the `unlock` of whoever held the lock
+        // before us, or the one to its `trying` flag if we took the slow path.
+        atomic::fence(Ordering::Acquire);
+    }
+
+    /// Leaves the critical section entered with `lock(thread)`.
+    pub fn unlock(&self, thread: usize) {
+        self.y.store(NOBODY, Ordering::Release);
+        self.trying[thread].store(false, Ordering::Release);
+    }
+
+    fn wait_for_y(&self) {
+        while self.y.load(Ordering::Relaxed) != NOBODY {
+            spin::relax();
+        }
+    }
+}
+
+impl<const N: usize> Default for LamportFastLock<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<const N: usize> SlotLock for LamportFastLock<N> {
+    fn slots(&self) -> usize {
+        N
+    }
+
+    fn lock(&self, slot: usize) {
+        LamportFastLock::lock(self, slot);
+    }
+
+    fn unlock(&self, slot: usize) {
+        LamportFastLock::unlock(self, slot);
+    }
+}
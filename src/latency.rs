@@ -0,0 +1,135 @@
+use std::{
+    array,
+    sync::atomic::{AtomicU64, Ordering},
+    time::{Duration, Instant},
+};
+
+use bakery::{Event, Observer};
+
+// Every power of two is split into `1 << SUB_BITS` equal buckets, so a recorded value is off by
+// at most 1/32 of itself (about 3%), however large it is.
+const SUB_BITS: u32 = 5;
+const SUB_BUCKETS: usize = 1 << SUB_BITS;
+// Values below `2 * SUB_BUCKETS` get a bucket each. Above that, each of the remaining powers of two
+// gets `SUB_BUCKETS`.
+const BUCKETS: usize = (65 - SUB_BITS as usize) * SUB_BUCKETS;
+
+// A histogram of nanosecond latencies in the style of HdrHistogram: logarithmic buckets with a
+// fixed relative precision, cheap enough to record into on every acquisition and small enough to
+// cover anything from a few nanoseconds to hours.
+pub struct Histogram {
+    buckets: [AtomicU64; BUCKETS],
+    count: AtomicU64,
+    max: AtomicU64,
+}
+
+impl Histogram {
+    pub fn new() -> Self {
+        Self {
+            buckets: array::from_fn(|_| AtomicU64::new(0)),
+            count: AtomicU64::new(0),
+            max: AtomicU64::new(0),
+        }
+    }
+
+    fn index(value: u64) -> usize {
+        if value < 2 * SUB_BUCKETS as u64 {
+            return value as usize;
+        }
+        let magnitude = 63 - value.leading_zeros() - SUB_BITS;
+        magnitude as usize * SUB_BUCKETS + (value >> magnitude) as usize
+    }
+
+    // The largest value that lands in bucket `index`.
+    fn highest_in(index: usize) -> u64 {
+        if index < 2 * SUB_BUCKETS {
+            return index as u64;
+        }
+        let magnitude = index / SUB_BUCKETS - 1;
+        let sub = (index % SUB_BUCKETS + SUB_BUCKETS) as u64;
+        (sub << magnitude) + ((1 << magnitude) - 1)
+    }
+
+    pub fn record(&self, nanos: u64) {
+        self.buckets[Self::index(nanos)].fetch_add(1, Ordering::Relaxed);
+        self.count.fetch_add(1, Ordering::Relaxed);
+        self.max.fetch_max(nanos, Ordering::Relaxed);
+    }
+
+    pub fn count(&self) -> u64 {
+        self.count.load(Ordering::Relaxed)
+    }
+
+    pub fn max(&self) -> Duration {
+        Duration::from_nanos(self.max.load(Ordering::Relaxed))
+    }
+
+    // The latency a fraction `p` of the recorded values are at most, to within the precision of
+    // the buckets. Zero if nothing has been recorded.
+    pub fn percentile(&self, p: f64) -> Duration {
+        let rank = ((p * self.count() as f64).ceil() as u64).max(1);
+        let mut seen = 0;
+        for (index, bucket) in self.buckets.iter().enumerate() {
+            seen += bucket.load(Ordering::Relaxed);
+            if seen >= rank {
+                // Never report more than was actually seen.
+                return Duration::from_nanos(Self::highest_in(index)).min(self.max());
+            }
+        }
+        self.max()
+    }
+}
+
+// Records how long every acquisition of the lock takes, from the doorway to the critical section.
+pub struct AcquireTimer<const N: usize> {
+    epoch: Instant,
+    // When each thread entered the doorway of its current acquisition, in nanoseconds since
+    // `epoch`, plus one. Zero while the thread isn't acquiring the lock.
+    started: [AtomicU64; N],
+    histogram: Histogram,
+}
+
+impl<const N: usize> AcquireTimer<N> {
+    pub fn new() -> Self {
+        Self {
+            epoch: Instant::now(),
+            started: array::from_fn(|_| AtomicU64::new(0)),
+            histogram: Histogram::new(),
+        }
+    }
+
+    fn now(&self) -> u64 {
+        self.epoch.elapsed().as_nanos() as u64 + 1
+    }
+
+    pub fn report(&self) {
+        let histogram = &self.histogram;
+        println!(
+            "acquisition latency over {} acquisitions: p50 {:.2?}, p95 {:.2?}, p99 {:.2?}, max {:.2?}",
+            histogram.count(),
+            histogram.percentile(0.5),
+            histogram.percentile(0.95),
+            histogram.percentile(0.99),
+            histogram.max()
+        );
+    }
+}
+
+impl<const N: usize> Observer for AcquireTimer<N> {
+    fn on_event(&self, thread: usize, event: Event) {
+        match event {
+            // Only the thread itself touches its entry.
+            Event::Doorway => self.started[thread].store(self.now(), Ordering::Relaxed),
+            Event::Acquired => {
+                // A lock handed over with `unlock_to` skips the doorway, and there's nothing to
+                // time then.
+                let started = self.started[thread].swap(0, Ordering::Relaxed);
+                if started != 0 {
+                    self.histogram.record(self.now() - started);
+                }
+            }
+            Event::GaveUp => self.started[thread].store(0, Ordering::Relaxed),
+            _ => {}
+        }
+    }
+}
@@ -0,0 +1,387 @@
+#[cfg(feature = "alloc")]
+use alloc::boxed::Box;
+#[cfg(feature = "alloc")]
+use core::mem;
+use core::sync::atomic::{AtomicBool, AtomicU16, AtomicU32, AtomicU64, Ordering};
+
+/// How the per-slot `choosing` flags and tickets of a lock are laid out in memory. Every waiter
+/// scans all slots while every slot is written by its owner, so the layout decides how much cache
+/// line traffic each acquisition causes, as well as how large the lock is.
+pub trait SlotLayout<const N: usize> {
+    /// The largest ticket the layout can hold, which must be one less than a power of two. Tickets
+    /// wrap around from here back to 1.
+    const MAX_TICKET: u64 = u32::MAX as u64;
+
+    /// Whether each slot's flag and ticket share a single atomic, so that [`state`](Self::state)
+    /// reads both with one load. The wait loop in [`lock`](crate::RawBakeryLock::lock) then needs
+    /// no fence between seeing `choosing` clear and reading the ticket, since both come from the
+    /// same load.
+    const FUSED: bool = false;
+
+    /// Every slot out of the doorway and without a ticket.
+    fn new() -> Self;
+
+    /// Whether `slot` is in the doorway, picking its ticket.
+    fn is_choosing(&self, slot: usize, order: Ordering) -> bool;
+    fn set_choosing(&self, slot: usize, choosing: bool, order: Ordering);
+
+    /// `slot`'s ticket, or 0 if it has none.
+    fn ticket(&self, slot: usize, order: Ordering) -> u64;
+    fn set_ticket(&self, slot: usize, ticket: u64, order: Ordering);
+
+    /// `slot`'s flag and ticket. Only called by `lock` for `FUSED` layouts, which read both at
+    /// once.
+    fn state(&self, slot: usize, order: Ordering) -> (bool, u64) {
+        (self.is_choosing(slot, order), self.ticket(slot, order))
+    }
+
+    /// Marks `slot` as (not) in use, for layouts that keep track of that. A slot is in use from
+    /// just before it enters the doorway until just after its ticket is retired.
+    fn set_active(&self, _slot: usize, _active: bool, _order: Ordering) {}
+
+    /// Every slot that might currently be in use, in increasing order. Slots that aren't returned
+    /// must appear to have `choosing == false` and `ticket == 0`.
+    fn active_slots(&self) -> impl Iterator<Item = usize> + '_ {
+        0..N
+    }
+
+    /// Memory owned by the layout outside of the lock itself.
+    fn heap_size(&self) -> usize {
+        0
+    }
+
+    /// The ticket to take after `ticket`, skipping 0 (which means no ticket at all) when wrapping
+    /// around.
+    fn next_ticket(ticket: u64) -> u64 {
+        next_ticket(ticket, Self::MAX_TICKET)
+    }
+
+    /// Whether the nonzero `ticket` was taken before `other`. Tickets are compared by their
+    /// distance around the circle of ticket values, which stays correct across a wraparound as long
+    /// as all tickets in use at once are less than half the range apart. Threads giving up on the
+    /// lock and coming back could otherwise push their tickets ever further past an unmoving
+    /// holder's, so the doorway never hands out a ticket a quarter of the range or more past the
+    /// oldest one in use, and has the thread back out and try again instead.
+    fn precedes(ticket: u64, other: u64) -> bool {
+        precedes(ticket, other, Self::MAX_TICKET)
+    }
+}
+
+// `SlotLayout::next_ticket` and `SlotLayout::precedes` for tickets up to `max`, for locks that
+// don't keep their state in a `SlotLayout`.
+pub(crate) fn next_ticket(ticket: u64, max: u64) -> u64 {
+    if ticket == max {
+        1
+    } else {
+        ticket + 1
+    }
+}
+
+pub(crate) fn precedes(ticket: u64, other: u64, max: u64) -> bool {
+    let distance = other.wrapping_sub(ticket) & max;
+    distance != 0 && distance <= max / 2
+}
+
+/// The atomic a layout keeps each ticket in, for the layouts that let the ticket width be picked:
+/// `AtomicU16` to save memory on small systems, `AtomicU32` by default, or `AtomicU64` for tickets
+/// that never wrap around in practice.
+pub trait TicketCell: Sync {
+    /// The largest ticket the cell can hold.
+    const MAX: u64;
+
+    /// A cell holding no ticket, that is 0.
+    fn empty() -> Self;
+    fn load(&self, order: Ordering) -> u64;
+    fn store(&self, ticket: u64, order: Ordering);
+}
+
+macro_rules! ticket_cell {
+    ($atomic:ty, $int:ty) => {
+        impl TicketCell for $atomic {
+            const MAX: u64 = <$int>::MAX as u64;
+
+            fn empty() -> Self {
+                <$atomic>::new(0)
+            }
+
+            fn load(&self, order: Ordering) -> u64 {
+                <$atomic>::load(self, order).into()
+            }
+
+            fn store(&self, ticket: u64, order: Ordering) {
+                debug_assert!(ticket <= Self::MAX);
+                <$atomic>::store(self, ticket as $int, order);
+            }
+        }
+    };
+}
+
+ticket_cell!(AtomicU16, u16);
+ticket_cell!(AtomicU32, u32);
+ticket_cell!(AtomicU64, u64);
+
+/// All flags next to each other, followed by all tickets: scanning touches as few cache lines as
+/// possible, but every write to a slot invalidates the line for every other thread.
+pub struct Packed<const N: usize, T = AtomicU32> {
+    choosing: [AtomicBool; N],
+    ticket: [T; N],
+}
+
+impl<const N: usize, T: TicketCell> SlotLayout<N> for Packed<N, T> {
+    const MAX_TICKET: u64 = T::MAX;
+
+    fn new() -> Self {
+        Self {
+            choosing: core::array::from_fn(|_| AtomicBool::new(false)),
+            ticket: core::array::from_fn(|_| T::empty()),
+        }
+    }
+
+    fn is_choosing(&self, slot: usize, order: Ordering) -> bool {
+        self.choosing[slot].load(order)
+    }
+
+    fn set_choosing(&self, slot: usize, choosing: bool, order: Ordering) {
+        self.choosing[slot].store(choosing, order);
+    }
+
+    fn ticket(&self, slot: usize, order: Ordering) -> u64 {
+        self.ticket[slot].load(order)
+    }
+
+    fn set_ticket(&self, slot: usize, ticket: u64, order: Ordering) {
+        self.ticket[slot].store(ticket, order);
+    }
+}
+
+/// Aligns (and so pads) its contents to 128 bytes: a cache line on most CPUs, two on those where
+/// the adjacent-line prefetcher effectively works in pairs (x86_64 in particular).
+#[repr(align(128))]
+pub struct CachePadded<T>(pub T);
+
+struct Slot<T> {
+    choosing: AtomicBool,
+    ticket: T,
+}
+
+/// Every slot on its own cache line: writes to one slot don't disturb the others, at the cost of
+/// scanning `N` lines per pass.
+pub struct Padded<const N: usize, T = AtomicU32> {
+    slots: [CachePadded<Slot<T>>; N],
+}
+
+impl<const N: usize, T: TicketCell> SlotLayout<N> for Padded<N, T> {
+    const MAX_TICKET: u64 = T::MAX;
+
+    fn new() -> Self {
+        Self {
+            slots: core::array::from_fn(|_| {
+                CachePadded(Slot {
+                    choosing: AtomicBool::new(false),
+                    ticket: T::empty(),
+                })
+            }),
+        }
+    }
+
+    fn is_choosing(&self, slot: usize, order: Ordering) -> bool {
+        self.slots[slot].0.choosing.load(order)
+    }
+
+    fn set_choosing(&self, slot: usize, choosing: bool, order: Ordering) {
+        self.slots[slot].0.choosing.store(choosing, order);
+    }
+
+    fn ticket(&self, slot: usize, order: Ordering) -> u64 {
+        self.slots[slot].0.ticket.load(order)
+    }
+
+    fn set_ticket(&self, slot: usize, ticket: u64, order: Ordering) {
+        self.slots[slot].0.ticket.store(ticket, order);
+    }
+}
+
+const FUSED_CHOOSING: u32 = 1 << 31;
+
+/// Each slot's flag and ticket in a single word, with the flag in the top bit: waiters read both
+/// with one load, and the fence that otherwise orders reading a ticket after seeing its flag clear
+/// is replaced by plain coherence. The cost is a ticket space of 31 bits.
+///
+/// A slot is only ever written by its owner, or by the thread retiring its ticket at the end of a
+/// handoff chain while the owner waits for exactly that, so updating one half of the word can be
+/// a load followed by a store rather than an RMW.
+pub struct Fused<const N: usize> {
+    slots: [AtomicU32; N],
+}
+
+impl<const N: usize> SlotLayout<N> for Fused<N> {
+    const MAX_TICKET: u64 = (FUSED_CHOOSING - 1) as u64;
+    const FUSED: bool = true;
+
+    fn new() -> Self {
+        Self {
+            slots: core::array::from_fn(|_| AtomicU32::new(0)),
+        }
+    }
+
+    fn is_choosing(&self, slot: usize, order: Ordering) -> bool {
+        self.slots[slot].load(order) & FUSED_CHOOSING != 0
+    }
+
+    fn set_choosing(&self, slot: usize, choosing: bool, order: Ordering) {
+        let ticket = self.slots[slot].load(Ordering::Relaxed) & !FUSED_CHOOSING;
+        let flag = if choosing { FUSED_CHOOSING } else { 0 };
+        self.slots[slot].store(flag | ticket, order);
+    }
+
+    fn ticket(&self, slot: usize, order: Ordering) -> u64 {
+        (self.slots[slot].load(order) & !FUSED_CHOOSING).into()
+    }
+
+    fn set_ticket(&self, slot: usize, ticket: u64, order: Ordering) {
+        debug_assert!(ticket <= Self::MAX_TICKET);
+        let flag = self.slots[slot].load(Ordering::Relaxed) & FUSED_CHOOSING;
+        self.slots[slot].store(flag | ticket as u32, order);
+    }
+
+    fn state(&self, slot: usize, order: Ordering) -> (bool, u64) {
+        let word = self.slots[slot].load(order);
+        (word & FUSED_CHOOSING != 0, (word & !FUSED_CHOOSING).into())
+    }
+}
+
+/// The smallest layout, for locks with thousands of slots: one bit per `choosing` flag and 16-bit
+/// tickets.
+///
+/// Since neighbouring flags share a word, they have to be updated with RMW operations rather than
+/// plain stores. Narrow tickets also wrap around every 65535 acquisitions or so, which limits the
+/// lock to `MAX_TICKET / 4` slots like any other layout.
+#[cfg(feature = "alloc")]
+pub struct Compact<const N: usize> {
+    // `N` bits, rounded up to a whole number of words. This can't be an array until const
+    // generic expressions stabilize.
+    choosing: Box<[AtomicU64]>,
+    ticket: [AtomicU16; N],
+}
+
+#[cfg(feature = "alloc")]
+impl<const N: usize> Compact<N> {
+    fn flag(&self, slot: usize) -> (&AtomicU64, u64) {
+        (&self.choosing[slot / 64], 1 << (slot % 64))
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl<const N: usize> SlotLayout<N> for Compact<N> {
+    const MAX_TICKET: u64 = u16::MAX as u64;
+
+    fn new() -> Self {
+        Self {
+            choosing: (0..N.div_ceil(64)).map(|_| AtomicU64::new(0)).collect(),
+            ticket: core::array::from_fn(|_| AtomicU16::new(0)),
+        }
+    }
+
+    fn is_choosing(&self, slot: usize, order: Ordering) -> bool {
+        let (word, bit) = self.flag(slot);
+        word.load(order) & bit != 0
+    }
+
+    fn set_choosing(&self, slot: usize, choosing: bool, order: Ordering) {
+        let (word, bit) = self.flag(slot);
+        if choosing {
+            word.fetch_or(bit, order);
+        } else {
+            word.fetch_and(!bit, order);
+        }
+    }
+
+    fn ticket(&self, slot: usize, order: Ordering) -> u64 {
+        self.ticket[slot].load(order).into()
+    }
+
+    fn set_ticket(&self, slot: usize, ticket: u64, order: Ordering) {
+        debug_assert!(ticket <= Self::MAX_TICKET);
+        self.ticket[slot].store(ticket as u16, order);
+    }
+
+    fn heap_size(&self) -> usize {
+        mem::size_of_val(&*self.choosing)
+    }
+}
+
+/// Wraps another layout with a bitmap of the slots that are currently in use, which lets `lock`
+/// skip over idle slots a whole word at a time. This is what makes locks with hundreds of slots
+/// practical when only a few of them are contending at any given moment.
+///
+/// A slot's bit is set before it enters the doorway and cleared after it retires its ticket, so
+/// observing a clear bit is equivalent to observing `choosing == false` and `ticket == 0`: the SC
+/// fences in `lock` order the bitmap accesses in exactly the same way as those to the underlying
+/// flags and tickets.
+#[cfg(feature = "alloc")]
+pub struct Tracked<const N: usize, L> {
+    inner: L,
+    // `N` bits, rounded up to a whole number of words.
+    active: Box<[AtomicU64]>,
+}
+
+#[cfg(feature = "alloc")]
+impl<const N: usize, L: SlotLayout<N>> SlotLayout<N> for Tracked<N, L> {
+    const MAX_TICKET: u64 = L::MAX_TICKET;
+    const FUSED: bool = L::FUSED;
+
+    fn new() -> Self {
+        Self {
+            inner: L::new(),
+            active: (0..N.div_ceil(64)).map(|_| AtomicU64::new(0)).collect(),
+        }
+    }
+
+    fn is_choosing(&self, slot: usize, order: Ordering) -> bool {
+        self.inner.is_choosing(slot, order)
+    }
+
+    fn set_choosing(&self, slot: usize, choosing: bool, order: Ordering) {
+        self.inner.set_choosing(slot, choosing, order);
+    }
+
+    fn ticket(&self, slot: usize, order: Ordering) -> u64 {
+        self.inner.ticket(slot, order)
+    }
+
+    fn set_ticket(&self, slot: usize, ticket: u64, order: Ordering) {
+        self.inner.set_ticket(slot, ticket, order);
+    }
+
+    fn state(&self, slot: usize, order: Ordering) -> (bool, u64) {
+        self.inner.state(slot, order)
+    }
+
+    fn set_active(&self, slot: usize, active: bool, order: Ordering) {
+        let word = &self.active[slot / 64];
+        let bit = 1 << (slot % 64);
+        if active {
+            word.fetch_or(bit, order);
+        } else {
+            word.fetch_and(!bit, order);
+        }
+    }
+
+    fn active_slots(&self) -> impl Iterator<Item = usize> + '_ {
+        self.active.iter().enumerate().flat_map(|(index, word)| {
+            let mut bits = word.load(Ordering::Relaxed);
+            core::iter::from_fn(move || {
+                if bits == 0 {
+                    return None;
+                }
+                let bit = bits.trailing_zeros() as usize;
+                bits &= bits - 1;
+                Some(index * 64 + bit)
+            })
+        })
+    }
+
+    fn heap_size(&self) -> usize {
+        self.inner.heap_size() + mem::size_of_val(&*self.active)
+    }
+}
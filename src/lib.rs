@@ -0,0 +1,670 @@
+//! Lamport's bakery lock, implemented on top of the C11 memory model.
+//!
+//! ```
+//! use std::thread;
+//!
+//! use bakery::BakeryLock;
+//!
+//! let lock = BakeryLock::<4>::new();
+//! thread::scope(|scope| {
+//!     for _ in 0..4 {
+//!         scope.spawn(|| {
+//!             let _guard = lock.lock();
+//!             // Critical section, until `_guard` is dropped...
+//!         });
+//!     }
+//! });
+//! ```
+//!
+//! Without the default `std` feature the crate is `no_std`, leaving [`RawBakeryLock`] with
+//! explicit slot indices, the layouts that don't allocate, and [`Barrier`], [`Once`] and
+//! [`Lazy`]. The `alloc` feature brings back the allocating layouts; automatic slot assignment,
+//! the guards and timed locking need `std`.
+
+#![cfg_attr(not(feature = "std"), no_std)]
+
+#[cfg(feature = "alloc")]
+extern crate alloc;
+
+use core::{
+    fmt,
+    marker::PhantomData,
+    mem,
+    sync::atomic::{self, AtomicUsize, Ordering},
+};
+#[cfg(feature = "std")]
+use std::time::{Duration, Instant};
+
+use algorithm::{Bakery, Next};
+#[cfg(feature = "std")]
+pub use async_mutex::{AsyncBakeryLock, AsyncBakeryMutex, AsyncBakeryMutexGuard};
+use backoff::{Backoff, NoBackoff};
+pub use barrier::{Barrier, BarrierWaitResult};
+#[cfg(feature = "black-white")]
+pub use black_white::BWBakeryLock;
+#[cfg(feature = "std")]
+pub use condvar::BakeryCondvar;
+#[cfg(feature = "alloc")]
+pub use dynamic::DynBakeryLock;
+#[cfg(feature = "futex")]
+pub use futex::FutexBakeryLock;
+#[cfg(feature = "std")]
+pub use guard::{BakeryGuard, BakeryLock, SlotHandle};
+#[cfg(feature = "hierarchical")]
+pub use hierarchical::HierarchicalBakeryLock;
+use layout::{Packed, SlotLayout};
+#[cfg(feature = "std")]
+pub use mutex::{ArcBakeryMutexGuard, BakeryMutex, BakeryMutexGuard, MutexSlotHandle};
+pub use once::{Lazy, Once};
+#[cfg(feature = "std")]
+pub use poison::{PoisonBakeryMutex, PoisonBakeryMutexGuard};
+#[cfg(feature = "std")]
+pub use reentrant::{ReentrantBakeryGuard, ReentrantBakeryLock};
+#[cfg(feature = "std")]
+pub use rwlock::{BakeryReadGuard, BakeryRwLock, BakeryUpgradeableGuard, BakeryWriteGuard};
+#[cfg(feature = "std")]
+pub use semaphore::BakerySemaphore;
+#[cfg(feature = "shm")]
+pub use shm::ShmBakeryLock;
+pub use snapshot::LockSnapshot;
+
+mod algorithm;
+#[cfg(feature = "std")]
+mod async_mutex;
+/// How long the lock's waiters spin between two looks at a slot.
+pub mod backoff;
+mod barrier;
+#[cfg(feature = "black-white")]
+mod black_white;
+/// Burns and Lynch's one-bit lock, with a single flag per thread.
+#[cfg(feature = "burns-lynch")]
+pub mod burns_lynch;
+#[cfg(feature = "std")]
+mod condvar;
+/// Dekker's lock for two threads, the first correct mutual exclusion algorithm.
+#[cfg(feature = "dekker")]
+pub mod dekker;
+#[cfg(feature = "alloc")]
+mod dynamic;
+/// The C API declared in `include/bakery.h`.
+#[cfg(feature = "cdylib")]
+pub mod ffi;
+/// The filter lock, Peterson's lock generalized to any number of threads.
+#[cfg(feature = "filter")]
+pub mod filter;
+#[cfg(feature = "futex")]
+mod futex;
+#[cfg(feature = "std")]
+mod guard;
+#[cfg(feature = "hierarchical")]
+mod hierarchical;
+/// A bakery lock that can be shared with interrupt handlers on bare metal.
+#[cfg(feature = "irq")]
+pub mod irq;
+/// Lamport's fast lock, which skips scanning the other slots when there's no contention.
+#[cfg(feature = "lamport-fast")]
+pub mod lamport_fast;
+/// How the lock's per-slot state is laid out in memory.
+pub mod layout;
+#[cfg(feature = "std")]
+mod mutex;
+mod once;
+/// Introspection of the orderings the lock's synchronizing accesses use.
+pub mod ordering;
+/// Peterson's lock for two threads, the bakery's two-thread ancestor.
+#[cfg(feature = "peterson")]
+pub mod peterson;
+#[cfg(feature = "std")]
+mod poison;
+#[cfg(kani)]
+mod proofs;
+#[cfg(feature = "std")]
+mod reentrant;
+#[cfg(feature = "std")]
+mod registry;
+#[cfg(feature = "std")]
+mod rwlock;
+#[cfg(feature = "std")]
+mod semaphore;
+#[cfg(feature = "shm")]
+mod shm;
+mod snapshot;
+/// The instruction the lock's wait loops spin on.
+pub mod spin;
+/// Szymanski's lock, with bounded flags and linear wait.
+#[cfg(feature = "szymanski")]
+pub mod szymanski;
+/// A `fetch_add` ticket spinlock, the read-modify-write baseline the bakery avoids.
+#[cfg(feature = "ticket")]
+pub mod ticket;
+/// A test-and-test-and-set spinlock with backoff, the unfair baseline.
+#[cfg(feature = "ttas")]
+pub mod ttas;
+
+fn sc_fence_1() {
+    if cfg!(feature = "fake-fence-1") {
+        // Make sure the compiler doesn't do anything tricky to prove this is really the CPU's
+        // fault.
+        atomic::compiler_fence(Ordering::SeqCst);
+    } else {
+        atomic::fence(Ordering::SeqCst);
+    }
+}
+
+fn sc_fence_2() {
+    if cfg!(feature = "fake-fence-2") {
+        // Make sure the compiler doesn't do anything tricky to prove this is really the CPU's
+        // fault.
+        atomic::compiler_fence(Ordering::SeqCst);
+    } else {
+        atomic::fence(Ordering::SeqCst);
+    }
+}
+
+/// A single step of the algorithm, as observed by the thread taking it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Event {
+    /// The thread has set its `choosing` flag and is about to pick a ticket. If the tickets in use
+    /// are too far apart for it to take another one yet, it backs out and starts over, and this
+    /// comes again.
+    Doorway,
+    /// The largest ticket the layout can hold was the latest one taken, so the thread wrapped
+    /// around to ticket 1.
+    TicketWrapped,
+    /// The thread has published its ticket and left the doorway.
+    Ticket(u64),
+    /// `other` is still choosing a ticket, so the thread has to wait before inspecting it.
+    WaitChoosing { other: usize },
+    /// `other` holds `ticket`, which takes priority over ours.
+    WaitTicket { other: usize, ticket: u64 },
+    /// `other` holds `ticket` (possibly 0), which doesn't take priority over ours.
+    Passed { other: usize, ticket: u64 },
+    /// `try_lock` found another thread ahead of it, or a timed lock ran out of time, and the
+    /// thread withdrew from the bakery.
+    GaveUp,
+    /// The thread has entered its critical section.
+    Acquired,
+    /// The thread is about to leave its critical section.
+    Released,
+}
+
+/// Receives every `Event` generated by a lock. The default methods do nothing, so the default
+/// `NoObserver` compiles away entirely.
+///
+/// This is the lock's one instrumentation hook, which every metric, trace and check in the demo is
+/// layered on without touching the algorithm: a thread starting through the doorway is
+/// [`Event::Doorway`], finding itself behind someone is [`Event::WaitChoosing`] or
+/// [`Event::WaitTicket`], and entering and leaving the critical section are [`Event::Acquired`]
+/// and [`Event::Released`]. Observers combine as pairs, and as `Option`s to switch them at runtime.
+pub trait Observer {
+    fn on_event(&self, _thread: usize, _event: Event) {}
+}
+
+/// An observer that ignores every event.
+pub struct NoObserver;
+impl Observer for NoObserver {}
+
+// Lets diagnostics be switched on and off at runtime.
+impl<O: Observer> Observer for Option<O> {
+    fn on_event(&self, thread: usize, event: Event) {
+        if let Some(observer) = self {
+            observer.on_event(thread, event);
+        }
+    }
+}
+
+impl<A: Observer, B: Observer> Observer for (A, B) {
+    fn on_event(&self, thread: usize, event: Event) {
+        self.0.on_event(thread, event);
+        self.1.on_event(thread, event);
+    }
+}
+
+/// A mutual exclusion lock whose callers identify themselves by a slot in `0..slots()`, the
+/// interface every lock in the crate that takes explicit slots shares, so that the same test or
+/// benchmark can run on any of them.
+///
+/// As with [`RawBakeryLock`], each slot must be used by one thread at a time, and `unlock` only
+/// called from the slot holding the lock.
+pub trait SlotLock {
+    /// How many threads can take part.
+    fn slots(&self) -> usize;
+
+    /// Waits for the lock and enters the critical section.
+    fn lock(&self, slot: usize);
+
+    /// Leaves the critical section entered with `lock(slot)`.
+    fn unlock(&self, slot: usize);
+}
+
+const NO_SLOT: usize = usize::MAX;
+
+/// Lamport's bakery lock for up to `N` threads, which needs nothing more than loads and stores.
+///
+/// Each thread taking part identifies itself by a slot in `0..N`, which it passes to every call.
+/// Slots are not exclusive with respect to the type system: two threads sharing a slot, or a
+/// thread calling `unlock` without holding the lock, break mutual exclusion (though never memory
+/// safety on their own). The observer `O` is notified of every step of the algorithm, the
+/// layout `S` decides how the per-slot state is arranged in memory, and the backoff `B` how long
+/// waiters spin between two looks at a slot.
+pub struct RawBakeryLock<const N: usize, O = NoObserver, S = Packed<N>, B = NoBackoff> {
+    slots: S,
+    // The slot the current owner has handed the critical section to with `unlock_to`, if that
+    // slot hasn't picked it up yet.
+    handoff: AtomicUsize,
+    // The slot whose (still published) ticket is keeping everyone else out of the bakery on
+    // behalf of the current owner, if the lock was acquired through a handoff. Only ever accessed
+    // by the owner.
+    blocker: AtomicUsize,
+    observer: O,
+    backoff: PhantomData<fn() -> B>,
+}
+
+impl<const N: usize> RawBakeryLock<N> {
+    /// An unlocked lock with every slot free.
+    pub fn new() -> Self {
+        Self::with_observer(NoObserver)
+    }
+}
+
+impl<const N: usize> Default for RawBakeryLock<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<const N: usize, O: Observer> RawBakeryLock<N, O> {
+    /// An unlocked lock reporting to `observer`.
+    pub fn with_observer(observer: O) -> Self {
+        Self::from_parts(observer, Packed::new())
+    }
+}
+
+impl<const N: usize, O: Observer, S: SlotLayout<N>> RawBakeryLock<N, O, S> {
+    /// An unlocked lock reporting to `observer` and keeping its state in `slots`, which must be
+    /// freshly created.
+    pub fn from_parts(observer: O, slots: S) -> Self {
+        assert!(
+            (N as u64) < S::MAX_TICKET / 4,
+            "too many slots to tell tickets apart across a wraparound"
+        );
+
+        Self {
+            slots,
+            handoff: AtomicUsize::new(NO_SLOT),
+            blocker: AtomicUsize::new(NO_SLOT),
+            observer,
+            backoff: PhantomData,
+        }
+    }
+}
+
+impl<const N: usize, O: Observer, S: SlotLayout<N>, B: Backoff> RawBakeryLock<N, O, S, B> {
+    /// The same lock with waiters backing off according to `B2`. Like the layout, this has to be
+    /// picked before anyone uses the lock.
+    pub fn with_backoff<B2: Backoff>(self) -> RawBakeryLock<N, O, S, B2> {
+        RawBakeryLock {
+            slots: self.slots,
+            handoff: self.handoff,
+            blocker: self.blocker,
+            observer: self.observer,
+            backoff: PhantomData,
+        }
+    }
+
+    /// Takes the lock apart again, usually to inspect what the observer collected.
+    pub fn into_parts(self) -> (O, S) {
+        (self.observer, self.slots)
+    }
+
+    /// The observer the lock reports to.
+    pub fn observer(&self) -> &O {
+        &self.observer
+    }
+
+    /// The raw per-slot state, for diagnostics. Anything read from here may be stale by the time
+    /// it is looked at.
+    pub fn slots(&self) -> &S {
+        &self.slots
+    }
+
+    /// Waits until every thread ahead of `thread` in the bakery has left and enters the critical
+    /// section. `thread` must be a slot in `0..N` that no other thread is currently using.
+    pub fn lock(&self, thread: usize) {
+        let acquired = self.acquire(thread, || false);
+        debug_assert!(acquired);
+    }
+
+    /// Like [`lock`](Self::lock), but gives up after waiting for `timeout`, returning whether it
+    /// entered the critical section.
+    #[cfg(feature = "std")]
+    pub fn try_lock_for(&self, thread: usize, timeout: Duration) -> bool {
+        match Instant::now().checked_add(timeout) {
+            Some(deadline) => self.try_lock_until(thread, deadline),
+            None => {
+                self.lock(thread);
+                true
+            }
+        }
+    }
+
+    /// Like [`lock`](Self::lock), but gives up once `deadline` has passed, returning whether it
+    /// entered the critical section. A thread that gives up withdraws its ticket, which to everyone
+    /// behind it looks like an empty critical section, and takes a new one next time.
+    #[cfg(feature = "std")]
+    pub fn try_lock_until(&self, thread: usize, deadline: Instant) -> bool {
+        self.acquire(thread, || Instant::now() >= deadline)
+    }
+
+    /// Enters the critical section like [`lock`](Self::lock) if that doesn't require waiting for
+    /// anyone, returning whether it did. Gives up as soon as another thread has priority or is
+    /// still choosing its ticket.
+    pub fn try_lock(&self, thread: usize) -> bool {
+        if self.slots.ticket(thread, Ordering::Acquire) != 0 {
+            // Our ticket is still keeping the bakery closed for a handoff chain, see `lock`.
+            let handed_off = self.take_handoff(thread);
+            if handed_off {
+                self.observer.on_event(thread, Event::Acquired);
+            }
+            return handed_off;
+        }
+
+        self.slots.set_active(thread, true, Ordering::Relaxed);
+
+        let entered = match self.try_doorway(thread) {
+            Some(ticket) => self.try_turn(thread, ticket, |_, _| {
+                if self.take_handoff(thread) {
+                    Next::Enter
+                } else {
+                    Next::GiveUp
+                }
+            }),
+            // The oldest ticket, too far behind for us to take one, may be kept published for a
+            // handoff to us.
+            None => self.take_handoff(thread),
+        };
+        if !entered {
+            self.withdraw(thread);
+            return false;
+        }
+
+        self.observer.on_event(thread, Event::Acquired);
+        true
+    }
+
+    /// Leaves the critical section entered with `lock(thread)`, letting the next thread in.
+    pub fn unlock(&self, thread: usize) {
+        self.observer.on_event(thread, Event::Released);
+
+        let blocker = self.blocker.load(Ordering::Relaxed);
+        if blocker != NO_SLOT {
+            // We got here through a handoff, so the original owner's ticket is still published and
+            // needs to be retired along with ours.
+            //
+            // The original owner may be about to reuse its slot as soon as it sees its ticket
+            // retired, so its active bit has to be cleared first.
+            self.blocker.store(NO_SLOT, Ordering::Relaxed);
+            self.slots.set_active(blocker, false, Ordering::Release);
+            self.slots.set_ticket(blocker, 0, Ordering::Release);
+        }
+
+        // Synchronizes-with the acquire fence at the end of `lock` to establish a proper
+        // happens-before relationship with future owners.
+        self.slots.set_ticket(thread, 0, Ordering::Release);
+        self.slots.set_active(thread, false, Ordering::Release);
+    }
+
+    /// Leaves the critical section by handing it directly to `successor`, regardless of ticket
+    /// order. If `successor` isn't currently waiting in `lock`, the lock stays reserved for it until
+    /// it next calls `lock`.
+    ///
+    /// This works because the owner's ticket is minimal among all threads in the bakery: as long
+    /// as it stays published, every other waiter (including ones that arrive later) keeps deferring
+    /// to it. Instead of retiring it, we leave it in place and let `successor` (and only
+    /// `successor`) skip its wait loop, retiring our ticket when it unlocks in turn.
+    pub fn unlock_to(&self, thread: usize, successor: usize) {
+        assert!(successor < N, "successor slot out of range");
+        assert_ne!(thread, successor, "cannot hand the lock off to ourselves");
+
+        let blocker = self.blocker.load(Ordering::Relaxed);
+        if blocker == NO_SLOT {
+            self.blocker.store(thread, Ordering::Relaxed);
+        } else {
+            // We already hold the lock on behalf of an earlier owner whose ticket is keeping the
+            // bakery closed, so our own ticket isn't needed any more.
+            self.slots.set_ticket(thread, 0, Ordering::Release);
+            self.slots.set_active(thread, false, Ordering::Release);
+
+            if blocker == successor {
+                // That earlier owner is getting the lock back, and will hold it with its own
+                // ticket from now on.
+                self.blocker.store(NO_SLOT, Ordering::Relaxed);
+            }
+        }
+
+        self.observer.on_event(thread, Event::Released);
+
+        // Synchronizes-with the acquire in `take_handoff` so that `successor` observes both our
+        // critical section and the updated `blocker`.
+        self.handoff.store(successor, Ordering::Release);
+    }
+
+    /// Releases the lock on behalf of the thread using `slot`, which died in its critical section.
+    ///
+    /// # Safety
+    ///
+    /// The thread using `slot` must currently hold the lock and never touch it again
+    /// without calling `lock` first. Anything it left half-updated in its critical section is now
+    /// visible to the next owner.
+    pub unsafe fn force_unlock(&self, slot: usize) {
+        assert!(slot < N, "slot out of range");
+        self.unlock(slot);
+    }
+
+    /// Clears the doorway and waiting state left behind by the thread using `slot`, which died
+    /// inside `lock`, so that the other threads stop deferring to it and the slot can be used again.
+    ///
+    /// # Safety
+    ///
+    /// The thread using `slot` must not hold the lock (use `force_unlock` for that) and must
+    /// never touch it again without calling `lock` first.
+    pub unsafe fn reclaim_slot(&self, slot: usize) {
+        assert!(slot < N, "slot out of range");
+
+        self.slots.set_choosing(slot, false, Ordering::Relaxed);
+
+        if self.take_handoff(slot) {
+            // The lock was handed to the dead thread, which now holds it without knowing.
+            self.observer.on_event(slot, Event::Acquired);
+            self.unlock(slot);
+            return;
+        }
+
+        // A ticket still keeping the bakery closed for a handoff chain is retired by whoever ends
+        // the chain.
+        if self.blocker.load(Ordering::Relaxed) != slot {
+            self.slots.set_ticket(slot, 0, Ordering::Release);
+            self.slots.set_active(slot, false, Ordering::Release);
+        }
+    }
+
+    /// Which ordering every synchronizing access in `lock` and `unlock` uses.
+    pub fn ordering(&self) -> ordering::OrderingConfig {
+        let mut config = ordering::OrderingConfig::build();
+        if S::FUSED {
+            config.sites[2] = ordering::SiteOrdering::SingleLoad;
+        }
+        config
+    }
+
+    /// The total memory used by the lock, including anything allocated by its layout.
+    pub fn memory_usage(&self) -> usize {
+        mem::size_of_val(self) + self.slots.heap_size()
+    }
+
+    /// How many threads are ahead of `thread` in the bakery, including the one in its critical
+    /// section, or `None` if `thread` doesn't hold a ticket. Threads still in the doorway aren't
+    /// counted and a pending handoff isn't taken into account, so this is only an estimate.
+    pub fn queue_position(&self, thread: usize) -> Option<usize> {
+        let ticket = self.slots.ticket(thread, Ordering::Relaxed);
+        if ticket == 0 {
+            return None;
+        }
+
+        let ahead = self
+            .slots
+            .active_slots()
+            .filter(|&other| {
+                let other_ticket = self.slots.ticket(other, Ordering::Relaxed);
+                other != thread && !Self::goes_before((ticket, thread), (other_ticket, other))
+            })
+            .count();
+        Some(ahead)
+    }
+
+    /// Samples every slot's flag and ticket, for printing when something goes wrong. See
+    /// [`LockSnapshot`] for how consistent that is.
+    pub fn snapshot(&self) -> LockSnapshot<N> {
+        let handoff = self.handoff.load(Ordering::Relaxed);
+        LockSnapshot::sample(&self.slots, (handoff != NO_SLOT).then_some(handoff))
+    }
+
+    // The body of `lock`, giving up (and returning false) as soon as `expired` returns true.
+    fn acquire(&self, thread: usize, expired: impl Fn() -> bool) -> bool {
+        // If we last left the critical section with `unlock_to`, our ticket stays published until
+        // the last owner in the handoff chain retires it, and overwriting it before then would let
+        // other waiters in alongside that owner.
+        //
+        // Acquire so that the retiring owner's update to our active bit is ordered before ours
+        // below.
+        while self.slots.ticket(thread, Ordering::Acquire) != 0 {
+            if self.take_handoff(thread) {
+                // The chain has come back around to us, and our old ticket is still keeping
+                // everyone else out.
+                self.observer.on_event(thread, Event::Acquired);
+                return true;
+            }
+            if expired() {
+                // The ticket isn't ours to withdraw.
+                self.observer.on_event(thread, Event::GaveUp);
+                return false;
+            }
+            spin::relax();
+        }
+
+        self.slots.set_active(thread, true, Ordering::Relaxed);
+
+        let mut backoff = B::new();
+        let ticket = loop {
+            if let Some(ticket) = self.try_doorway(thread) {
+                break ticket;
+            }
+            // The oldest ticket may be one kept published for a handoff to us, which won't leave
+            // until we've had our turn.
+            if self.take_handoff(thread) {
+                self.observer.on_event(thread, Event::Acquired);
+                return true;
+            }
+            if expired() {
+                self.withdraw(thread);
+                return false;
+            }
+            backoff.snooze();
+        };
+
+        let entered = self.wait_turn(thread, ticket, |_, _, backoff: &mut B| {
+            if self.take_handoff(thread) {
+                Next::Enter
+            } else if expired() {
+                Next::GiveUp
+            } else {
+                backoff.snooze();
+                Next::Wait
+            }
+        });
+        if !entered {
+            self.withdraw(thread);
+            return false;
+        }
+
+        self.observer.on_event(thread, Event::Acquired);
+        true
+    }
+
+    // Leaves the bakery from the wait loop without having entered the critical section. Withdrawing
+    // our ticket looks to everyone else like an empty critical section: threads behind us stop
+    // waiting for it, and nobody ahead of us was waiting for it in the first place. Who goes next
+    // is still decided by the remaining tickets, so the order among them is unaffected.
+    fn withdraw(&self, thread: usize) {
+        self.slots.set_ticket(thread, 0, Ordering::Release);
+        self.slots.set_active(thread, false, Ordering::Release);
+        self.observer.on_event(thread, Event::GaveUp);
+    }
+
+    fn take_handoff(&self, thread: usize) -> bool {
+        self.handoff
+            .compare_exchange(thread, NO_SLOT, Ordering::Acquire, Ordering::Relaxed)
+            .is_ok()
+    }
+}
+
+impl<const N: usize, O: Observer, S: SlotLayout<N>, B: Backoff> Bakery
+    for RawBakeryLock<N, O, S, B>
+{
+    const MAX_TICKET: u64 = S::MAX_TICKET;
+    const FUSED: bool = S::FUSED;
+
+    fn is_choosing(&self, slot: usize, order: Ordering) -> bool {
+        self.slots.is_choosing(slot, order)
+    }
+
+    fn set_choosing(&self, slot: usize, choosing: bool, order: Ordering) {
+        self.slots.set_choosing(slot, choosing, order);
+    }
+
+    fn ticket(&self, slot: usize, order: Ordering) -> u64 {
+        self.slots.ticket(slot, order)
+    }
+
+    fn set_ticket(&self, slot: usize, ticket: u64, order: Ordering) {
+        self.slots.set_ticket(slot, ticket, order);
+    }
+
+    fn state(&self, slot: usize, order: Ordering) -> (bool, u64) {
+        self.slots.state(slot, order)
+    }
+
+    fn active_slots(&self) -> impl Iterator<Item = usize> + '_ {
+        self.slots.active_slots()
+    }
+
+    fn on_event(&self, slot: usize, event: Event) {
+        self.observer.on_event(slot, event);
+    }
+}
+
+impl<const N: usize, O: Observer, S: SlotLayout<N>, B: Backoff> fmt::Debug
+    for RawBakeryLock<N, O, S, B>
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RawBakeryLock")
+            .field("slots", &format_args!("{}", self.snapshot()))
+            .finish_non_exhaustive()
+    }
+}
+
+impl<const N: usize, O: Observer, S: SlotLayout<N>, B: Backoff> SlotLock
+    for RawBakeryLock<N, O, S, B>
+{
+    fn slots(&self) -> usize {
+        N
+    }
+
+    fn lock(&self, slot: usize) {
+        RawBakeryLock::lock(self, slot);
+    }
+
+    fn unlock(&self, slot: usize) {
+        RawBakeryLock::unlock(self, slot);
+    }
+}
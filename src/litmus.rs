@@ -0,0 +1,231 @@
+use std::{
+    collections::BTreeMap,
+    process,
+    sync::atomic::{self, AtomicU32, Ordering},
+    thread,
+};
+
+use bakery::Barrier;
+
+use crate::workers;
+
+// How many instances of a test each thread runs through between two barriers. The threads race
+// through their halves of a batch unsynchronized, which is when the interesting outcomes happen.
+const BATCH: usize = 1000;
+
+// The two-thread programs `litmus` can run. Each reads two values, `(r0, r1)`, one per thread.
+#[derive(Clone, Copy)]
+enum Test {
+    // Store buffering: `x = 1; r0 = y` against `y = 1; r1 = x`. The cycle the bakery's two SC
+    // fences exist to forbid.
+    StoreBuffering { fenced: bool },
+    // Message passing: `data = 1; flag = 1` against `r0 = flag; r1 = data`.
+    MessagePassing { release_acquire: bool },
+    // The entry of Peterson's lock, once, without waiting: `flag0 = 1; turn = 1` and then `r0` is
+    // whether thread 0 may enter (`flag1 == 0 || turn == 0`), and the other way around for thread
+    // 1. Store buffering with a third location, and the reason `PetersonLock` needs its fence.
+    Peterson { fenced: bool },
+}
+
+const TESTS: [(&str, Test); 6] = [
+    ("sb", Test::StoreBuffering { fenced: false }),
+    ("sb+fences", Test::StoreBuffering { fenced: true }),
+    (
+        "mp",
+        Test::MessagePassing {
+            release_acquire: false,
+        },
+    ),
+    (
+        "mp+rel-acq",
+        Test::MessagePassing {
+            release_acquire: true,
+        },
+    ),
+    ("peterson", Test::Peterson { fenced: false }),
+    ("peterson+fence", Test::Peterson { fenced: true }),
+];
+
+impl Test {
+    // The outcome a sequentially consistent execution (or a correctly synchronized one) can never
+    // produce, if adding orderings rules it out.
+    fn forbidden(self) -> Option<(u32, u32)> {
+        match self {
+            Test::StoreBuffering { fenced: true } => Some((0, 0)),
+            // Both threads entering at once.
+            Test::Peterson { fenced: true } => Some((1, 1)),
+            Test::MessagePassing {
+                release_acquire: true,
+            } => Some((1, 0)),
+            _ => None,
+        }
+    }
+
+    // The outcome weak hardware may produce without the orderings.
+    fn relaxed(self) -> (u32, u32) {
+        match self {
+            Test::StoreBuffering { .. } => (0, 0),
+            Test::MessagePassing { .. } => (1, 0),
+            Test::Peterson { .. } => (1, 1),
+        }
+    }
+
+    // Runs `thread`'s half of one instance on the locations `a`, `b` and `c`, of which only
+    // `Peterson` uses the last, returning what it read.
+    fn run(self, thread: usize, a: &AtomicU32, b: &AtomicU32, c: &AtomicU32) -> u32 {
+        match (self, thread) {
+            (Test::StoreBuffering { fenced }, _) => {
+                // Thread 0 writes `a` and reads `b`, thread 1 the other way around.
+                let (mine, theirs) = if thread == 0 { (a, b) } else { (b, a) };
+                mine.store(1, Ordering::Relaxed);
+                if fenced {
+                    atomic::fence(Ordering::SeqCst);
+                } else {
+                    // Keep the compiler from reordering, so that only the CPU can.
+                    atomic::compiler_fence(Ordering::SeqCst);
+                }
+                theirs.load(Ordering::Relaxed)
+            }
+            (Test::MessagePassing { release_acquire }, 0) => {
+                let (data, flag) = (a, b);
+                data.store(1, Ordering::Relaxed);
+                atomic::compiler_fence(Ordering::SeqCst);
+                flag.store(
+                    1,
+                    if release_acquire {
+                        Ordering::Release
+                    } else {
+                        Ordering::Relaxed
+                    },
+                );
+                // Thread 0 doesn't read anything.
+                0
+            }
+            (Test::MessagePassing { release_acquire }, _) => {
+                let (data, flag) = (a, b);
+                let seen = flag.load(if release_acquire {
+                    Ordering::Acquire
+                } else {
+                    Ordering::Relaxed
+                });
+                atomic::compiler_fence(Ordering::SeqCst);
+                // Report `(flag, data)` as `(r0, r1)` by packing both into thread 1's result.
+                seen << 1 | data.load(Ordering::Relaxed)
+            }
+            (Test::Peterson { fenced }, _) => {
+                let (mine, theirs, turn) = if thread == 0 { (a, b, c) } else { (b, a, c) };
+                let other = 1 - thread as u32;
+                mine.store(1, Ordering::Relaxed);
+                turn.store(other, Ordering::Relaxed);
+                if fenced {
+                    atomic::fence(Ordering::SeqCst);
+                } else {
+                    atomic::compiler_fence(Ordering::SeqCst);
+                }
+                let blocked =
+                    theirs.load(Ordering::Relaxed) == 1 && turn.load(Ordering::Relaxed) == other;
+                u32::from(!blocked)
+            }
+        }
+    }
+}
+
+// Runs `iterations` instances of `test`, returning how often each outcome came up.
+fn tally(test: Test, iterations: usize) -> BTreeMap<(u32, u32), usize> {
+    let a: Vec<_> = (0..BATCH).map(|_| AtomicU32::new(0)).collect();
+    let b: Vec<_> = (0..BATCH).map(|_| AtomicU32::new(0)).collect();
+    let c: Vec<_> = (0..BATCH).map(|_| AtomicU32::new(0)).collect();
+    // Reset between batches by thread 0, while thread 1 waits at the barrier. The barrier spins, so
+    // both threads start their halves of a batch within a few cycles of each other.
+    let barrier = Barrier::new(2);
+    let mut outcomes = BTreeMap::new();
+
+    let reads: Vec<Vec<u32>> = thread::scope(|scope| {
+        let workers: Vec<_> = (0..2)
+            .map(|thread_id| {
+                let (a, b, c, barrier) = (&a, &b, &c, &barrier);
+                workers::spawn(scope, "litmus", thread_id, move |worker| {
+                    let mut reads = Vec::with_capacity(iterations);
+                    for batch in 0..iterations.div_ceil(BATCH) {
+                        let len = BATCH.min(iterations - batch * BATCH);
+                        if worker.id == 0 {
+                            for i in 0..len {
+                                a[i].store(0, Ordering::Relaxed);
+                                b[i].store(0, Ordering::Relaxed);
+                                c[i].store(0, Ordering::Relaxed);
+                            }
+                        }
+                        barrier.wait();
+                        reads.extend((0..len).map(|i| test.run(worker.id, &a[i], &b[i], &c[i])));
+                        barrier.wait();
+                    }
+                    reads
+                })
+            })
+            .collect();
+        workers
+            .into_iter()
+            .map(|worker| worker.join().expect("litmus worker panicked"))
+            .collect()
+    });
+
+    for (&r0, &r1) in reads[0].iter().zip(&reads[1]) {
+        let outcome = match test {
+            Test::StoreBuffering { .. } | Test::Peterson { .. } => (r0, r1),
+            Test::MessagePassing { .. } => (r1 >> 1, r1 & 1),
+        };
+        *outcomes.entry(outcome).or_insert(0) += 1;
+    }
+    outcomes
+}
+
+fn usage() -> ! {
+    eprintln!("usage: bakery litmus [--iterations <n>] [sb|sb+fences|mp|mp+rel-acq|peterson|peterson+fence]...");
+    process::exit(2);
+}
+
+// Runs classic memory-model litmus tests on the host CPU and tallies the outcomes it produced,
+// flagging any that the orderings in the test are supposed to rule out.
+pub fn run(args: &[String]) {
+    let mut iterations = 1000000;
+    let mut tests = Vec::new();
+    let mut args = args;
+    while let [arg, rest @ ..] = args {
+        match (arg.as_str(), rest) {
+            ("--iterations", [value, rest @ ..]) => {
+                iterations = value.parse().unwrap_or_else(|_| usage());
+                args = rest;
+                continue;
+            }
+            (name, _) => match TESTS.iter().find(|(test, _)| *test == name) {
+                Some(&test) => tests.push(test),
+                None => usage(),
+            },
+        }
+        args = rest;
+    }
+    if tests.is_empty() {
+        tests = TESTS.to_vec();
+    }
+
+    let mut violated = false;
+    for (name, test) in tests {
+        let outcomes = tally(test, iterations);
+        println!("{name}: {iterations} runs");
+        for (&(r0, r1), &count) in &outcomes {
+            let note = if Some((r0, r1)) == test.forbidden() {
+                violated = true;
+                "  forbidden!"
+            } else if (r0, r1) == test.relaxed() {
+                "  reordered"
+            } else {
+                ""
+            };
+            println!("  r0={r0} r1={r1} {count:>10}{note}");
+        }
+    }
+
+    if violated {
+        process::exit(1);
+    }
+}
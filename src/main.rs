@@ -1,175 +1,504 @@
 use std::{
     cell::UnsafeCell,
-    hint,
-    sync::atomic::{self, AtomicBool, AtomicU32, Ordering},
+    env,
+    sync::atomic::{AtomicUsize, Ordering},
     thread,
+    time::{Duration, Instant},
 };
 
-fn sc_fence_1() {
-    if cfg!(feature = "fake-fence-1") {
-        // Make sure the compiler doesn't do anything tricky to prove this is really the CPU's
-        // fault.
-        atomic::compiler_fence(Ordering::SeqCst);
-    } else {
-        atomic::fence(Ordering::SeqCst);
-    }
-}
+#[cfg(feature = "spin-hint")]
+use bakery::spin;
+use bakery::{BakeryMutex, Barrier, Observer, RawBakeryLock};
 
-fn sc_fence_2() {
-    if cfg!(feature = "fake-fence-2") {
-        // Make sure the compiler doesn't do anything tricky to prove this is really the CPU's
-        // fault.
-        atomic::compiler_fence(Ordering::SeqCst);
-    } else {
-        atomic::fence(Ordering::SeqCst);
-    }
-}
+mod algorithms;
+mod asm_dump;
+mod bench;
+mod bundle;
+mod clock;
+mod crash;
+mod distributed;
+mod energy;
+mod estimate;
+#[cfg(feature = "flawed-bakery")]
+mod exercises;
+mod false_sharing;
+mod fcfs;
+mod latency;
+mod litmus;
+mod memory;
+mod output;
+mod placement;
+mod results;
+mod scale;
+mod scenario;
+mod soak;
+mod starvation;
+mod stats;
+mod stress;
+mod teach;
+mod timeline;
+mod topology;
+mod trace;
+mod tui;
+mod watchdog;
+mod workers;
 
-struct RawBakeryLock<const N: usize> {
-    choosing: [AtomicBool; N],
-    ticket: [AtomicU32; N],
-}
+struct UnsafeSyncCell<T>(UnsafeCell<T>);
+unsafe impl<T> Sync for UnsafeSyncCell<T> {}
 
-impl<const N: usize> RawBakeryLock<N> {
-    fn new() -> Self {
-        #![allow(clippy::declare_interior_mutable_const)]
+fn main() {
+    // How many threads an observed run can have, since the observers are sized at build time.
+    const OBSERVED_SLOTS: usize = 10;
+    const ITERATIONS: usize = 100000;
 
-        const NOT_CHOOSING: AtomicBool = AtomicBool::new(false);
-        const NO_TICKET: AtomicU32 = AtomicU32::new(0);
+    let mut args: Vec<String> = env::args().skip(1).collect();
 
-        Self {
-            choosing: [NOT_CHOOSING; N],
-            ticket: [NO_TICKET; N],
-        }
+    // `--seed` applies to every mode, so strip it before dispatching.
+    if let Some(pos) = args.iter().position(|arg| arg == "--seed") {
+        let seed = args
+            .get(pos + 1)
+            .and_then(|seed| seed.parse().ok())
+            .expect("`--seed` requires a number");
+        workers::set_seed(seed);
+        args.drain(pos..pos + 2);
     }
 
-    fn lock(&self, thread: usize) {
-        let ticket = loop {
-            self.choosing[thread].store(true, Ordering::Relaxed);
-
-            // This fence helps enforce the core invariant of the bakery lock: (intuitively) at any
-            // given moment, out of all threads that have currently chosen a ticket, _exactly_ the
-            // one with minimal `(ticket[i], i)` is in its critical section. It coordinates with the
-            // second SC fence in this function to prevent the following store buffering scenario:
-            //
-            //  Thread 0:                                          Thread 1:
-            //
-            //  choosing[0] = true                              |  choosing[1] = true
-            //                                                  |  ticket[1] = max(ticket[0], ticket[1]) + 1 // 1
-            //  // Store from thread 1 not visible:             |
-            //  ticket[0] = max(ticket[0], ticket[1]) + 1 // 1  |
-            //  choosing[0] = false                             |
-            //  choosing[1] == true                             |
-            //                                                  |  choosing[1] = false
-            //                                                  |  // Stores from thread 0 not visible:
-            //                                                  |  choosing[0] == false
-            //                                                  |  ticket[0] == 0
-            //  choosing[1] == false                            |  // Critical section...
-            //  ticket[0] == 1 // (1, 0) < (1, 1)               |  // Critical section...
-            //  // Critical section..                           |  // Critical section...
-            //
-            // The problem here is that thread 1 doesn't see thread 0's write to `choosing[0]` and
-            // incorrectly assumes that it now has the lowest-numbered ticket, while thread 0 has
-            // already chosen a ticket of 1 as well and can (correctly) enter its critical section
-            // because it has priority over thread 1.
-            //
-            // More formally, abbreviating `choosing` as `c` and `ticket` as `t`, the problematic
-            // scenario is a
-            //
-            // W(c[0], 1) -po-> R(t[1], 0) -rb-> W(t[1], 1) -po-> R(c[0], 0) -rb-> W(c[0], 1)
-            //
-            // cycle, so SC fences are necessary somewhere along both `po` edges to forbid it. This
-            // fence covers the `W c -> R t` edge, while the one below covers the `W t -> R c` edge.
-            sc_fence_1();
-
-            let max_existing = self
-                .ticket
-                .iter()
-                .map(|ticket| ticket.load(Ordering::Relaxed))
-                .max()
-                .unwrap();
-
-            if let Some(ticket) = max_existing.checked_add(1) {
-                // Common case: we have a new ticket larger than all tickets observed.
-                break ticket;
-            }
+    // So does `--spin`, which picks the instruction the lock's wait loops run.
+    #[cfg(not(feature = "spin-hint"))]
+    assert!(
+        !args.iter().any(|arg| arg == "--spin"),
+        "`--spin` requires building with `--features spin-hint`"
+    );
+    #[cfg(feature = "spin-hint")]
+    if let Some(pos) = args.iter().position(|arg| arg == "--spin") {
+        let hint = args
+            .get(pos + 1)
+            .and_then(|name| spin::SpinHint::from_name(name))
+            .filter(|hint| hint.is_available())
+            .expect("`--spin` requires one of `std`, `pause`, `tpause`, `isb`, `yield` or `none` (if available)");
+        spin::set_hint(hint);
+        args.drain(pos..pos + 2);
+    }
 
-            // We've failed to get a ticket now because of overflow - stop choosing now to let
-            // currently waiting threads into the bakery and try again.
-            self.choosing[thread].store(false, Ordering::Relaxed);
+    // And `--placement`, which pins every worker thread.
+    if let Some(pos) = args.iter().position(|arg| arg == "--placement") {
+        let placement = args
+            .get(pos + 1)
+            .and_then(|name| placement::Placement::from_name(name))
+            .expect("`--placement` requires one of `compact`, `scatter` or `numa-interleave`");
+        if !placement::set(placement) {
+            eprintln!("warning: thread placement isn't supported on this platform, ignoring it");
+        }
+        args.drain(pos..pos + 2);
+    }
 
-            hint::spin_loop();
-        };
+    // `demo` names the default mode, the shared counter, explicitly.
+    if args.first().is_some_and(|arg| arg == "demo") {
+        args.remove(0);
+    }
 
-        self.ticket[thread].store(ticket, Ordering::Relaxed);
+    match args.first().map(String::as_str) {
+        Some("asm-dump") => {
+            asm_dump::run(&args[1..]);
+            return;
+        }
+        Some("bench") => {
+            bench::run(&args[1..]);
+            return;
+        }
+        #[cfg(feature = "flawed-bakery")]
+        Some("exercises") => {
+            exercises::run(&args[1..]);
+            return;
+        }
+        #[cfg(not(feature = "flawed-bakery"))]
+        Some("exercises") => {
+            eprintln!("this binary was built without the `flawed-bakery` feature");
+            std::process::exit(2);
+        }
+        Some("report") => {
+            results::report(&args[1..]);
+            return;
+        }
+        Some("crash") => {
+            crash::run(&args[1..]);
+            return;
+        }
+        Some("coordinator") => {
+            distributed::coordinator(&args[1..]);
+            return;
+        }
+        Some("worker") => {
+            distributed::worker(&args[1..]);
+            return;
+        }
+        Some("litmus") => {
+            litmus::run(&args[1..]);
+            return;
+        }
+        Some("fcfs") => {
+            fcfs::run(&args[1..]);
+            return;
+        }
+        Some("false-sharing") => {
+            false_sharing::run(&args[1..]);
+            return;
+        }
+        Some("memory") => {
+            memory::report();
+            return;
+        }
+        Some("scale") => {
+            scale::run(&args[1..]);
+            return;
+        }
+        Some("scenario") => {
+            scenario::run(&args[1..]);
+            return;
+        }
+        Some("starvation") => {
+            starvation::run(&args[1..]);
+            return;
+        }
+        Some("stress") => {
+            stress::run(&args[1..]);
+            return;
+        }
+        Some("soak") => {
+            soak::run(&args[1..]);
+            return;
+        }
+        _ => {}
+    }
 
-        // This fence serves two distinct purposes:
-        // 1. It covers the `W t -> R c` edge of the store buffering scenario discussed above.
-        // 2. It synchronizes-with the acquire fence in the loop below to make sure that any
-        //    threads observing the write to `choosing` below also observe our new ticket.
-        sc_fence_2();
+    if args.iter().any(|arg| arg == "--list-algos") {
+        algorithms::list();
+        return;
+    }
 
-        self.choosing[thread].store(false, Ordering::Relaxed);
+    if args.iter().any(|arg| arg == "--teach") {
+        teach::run();
+        return;
+    }
 
-        for other in 0..N {
-            if other == thread {
-                continue;
-            }
+    if args.iter().any(|arg| arg == "--timeline") {
+        let clock = args
+            .iter()
+            .position(|arg| arg == "--clock")
+            .map_or(Some(clock::ClockSource::Instant), |pos| {
+                args.get(pos + 1)
+                    .and_then(|name| clock::ClockSource::from_name(name))
+            })
+            .filter(|source| source.is_available())
+            .expect("`--clock` requires one of `instant`, `counter` or `coarse` (if available)");
+        let flame = args
+            .iter()
+            .position(|arg| arg == "--flame")
+            .map(|pos| args.get(pos + 1).expect("`--flame` requires a path"));
+        timeline::run(clock, flame.map(String::as_str));
+        return;
+    }
 
-            while self.choosing[other].load(Ordering::Relaxed) {
-                hint::spin_loop();
-            }
+    let tui = args.iter().any(|arg| arg == "--tui");
+    let db = args
+        .iter()
+        .position(|arg| arg == "--db")
+        .map(|pos| args.get(pos + 1).expect("`--db` requires a path"));
+    let bundle = args
+        .iter()
+        .position(|arg| arg == "--bundle")
+        .map(|pos| args.get(pos + 1).expect("`--bundle` requires a directory"));
+    let stall_threshold = args
+        .iter()
+        .position(|arg| arg == "--watch-starvation")
+        .map(|pos| {
+            args.get(pos + 1)
+                .and_then(|ms| ms.parse().ok())
+                .map(Duration::from_millis)
+                .expect("`--watch-starvation` requires a number of milliseconds")
+        });
+    let latency = args.iter().any(|arg| arg == "--latency");
+    let check_overlap = args.iter().any(|arg| arg == "--check-overlap");
+    let trace = args
+        .iter()
+        .position(|arg| arg == "--trace")
+        .map(|pos| args.get(pos + 1).expect("`--trace` requires a path"));
+    let instrumented = bundle.is_some()
+        || stall_threshold.is_some()
+        || latency
+        || check_overlap
+        || trace.is_some();
+    assert!(
+        !(tui && instrumented),
+        "`--tui` can't be combined with `--bundle`, `--watch-starvation`, `--latency`, \
+         `--check-overlap` or `--trace`"
+    );
+    let check_every = args
+        .iter()
+        .position(|arg| arg == "--check-every")
+        .map(|pos| {
+            args.get(pos + 1)
+                .and_then(|interval| interval.parse().ok())
+                .expect("`--check-every` requires a number")
+        });
 
-            // Synchronizes-with the SC fence just before the store to `choosing[other]` to make
-            // sure we observe the correct value of `ticket[other]` below.
-            atomic::fence(Ordering::Acquire);
+    // Sweeps run in containers are easier to drive through the environment than through
+    // arguments. The fences are fixed at build time, so that variable only makes sure the sweep is
+    // running the build it thinks it is.
+    let env_number = |name| {
+        env::var(name).ok().map(|value| {
+            value
+                .parse()
+                .unwrap_or_else(|_| panic!("`{name}` must be a number"))
+        })
+    };
+    let iterations = env_number("BAKERY_ITERS").unwrap_or(ITERATIONS);
+    let profile = workers::Profile {
+        critical_section: env_number("BAKERY_CS_WORK").unwrap_or(0) as u64,
+        outside: env_number("BAKERY_OUTSIDE").unwrap_or(0) as u64,
+        jitter: env_number("BAKERY_JITTER").unwrap_or(0) as u64,
+    };
+    let algo = env::var("BAKERY_ALGO").unwrap_or_else(|_| "bakery".to_owned());
+    // The live view and the instrumentation are observers of the bakery mutex, which has its
+    // number of slots fixed at build time.
+    let observed = tui || instrumented;
+    assert!(
+        !observed || algo == "bakery",
+        "`--tui`, `--bundle`, `--watch-starvation`, `--latency`, `--check-overlap` and `--trace` \
+         only work with the bakery lock, not `BAKERY_ALGO={algo}`"
+    );
+    if let Ok(fences) = env::var("BAKERY_FENCES") {
+        assert_eq!(
+            fences,
+            results::fence_config(),
+            "`BAKERY_FENCES`: this binary was built with fences {}, rebuild it with the \
+             `fake-fence-*` features to change them",
+            results::fence_config()
+        );
+    }
 
-            loop {
-                let other_ticket = self.ticket[other].load(Ordering::Relaxed);
-                if other_ticket == 0 || (ticket, thread) < (other_ticket, other) {
-                    break;
-                }
-                hint::spin_loop();
+    // Running more spinning threads than there are cores mostly measures the scheduler, so use at
+    // most one thread per physical core unless asked otherwise.
+    let topology = topology::detect();
+    let max_threads = if observed { OBSERVED_SLOTS } else { usize::MAX };
+    let num_threads = match env_number("BAKERY_THREADS") {
+        Some(threads) if observed => {
+            assert!(
+                (1..=max_threads).contains(&threads),
+                "`BAKERY_THREADS` must be between 1 and {OBSERVED_SLOTS} with an observer"
+            );
+            threads
+        }
+        Some(threads) => {
+            assert!(threads >= 1, "`BAKERY_THREADS` must be at least 1");
+            threads
+        }
+        None => {
+            let threads = topology.physical_cores.clamp(1, max_threads);
+            if observed && threads < OBSERVED_SLOTS {
+                eprintln!(
+                    "warning: only {} physical cores detected ({topology}), running {threads} \
+                     threads on a lock with {OBSERVED_SLOTS} slots",
+                    topology.physical_cores
+                );
             }
+            threads
         }
+    };
 
-        // Synchronizes-with the release stores to `ticket` by other threads that have already
-        // unlocked (as observed by our reads from `ticket`).
-        atomic::fence(Ordering::Acquire);
+    // Has `num_threads` workers count to `iterations` each, bumping `finished` as they finish. Each
+    // worker gets its slot from `start` before it waits for the others, and then calls what that
+    // returns once per iteration to increment the counter inside the critical section.
+    fn count<I: FnMut(&mut workers::Rng)>(
+        num_threads: usize,
+        iterations: usize,
+        profile: workers::Profile,
+        finished: &AtomicUsize,
+        quiet: bool,
+        start: impl Fn(usize) -> I + Sync,
+    ) {
+        // Everyone starts counting at once, rather than the first threads spawned getting through
+        // a good part of their iterations alone.
+        let start_line = Barrier::new(num_threads);
+        thread::scope(|scope| {
+            for thread_id in 0..num_threads {
+                let (start_line, start) = (&start_line, &start);
+                workers::spawn(scope, "counter", thread_id, move |mut worker| {
+                    if !quiet {
+                        println!("thread {} startup", worker.id);
+                    }
+                    let mut increment = start(thread_id);
+                    start_line.wait();
+                    for _ in 0..iterations {
+                        increment(&mut worker.rng);
+                        profile.outside(&mut worker.rng);
+                    }
+                    finished.fetch_add(1, Ordering::Relaxed);
+                });
+            }
+        });
     }
 
-    fn unlock(&self, thread: usize) {
-        // Synchronizes-with the acquire fence at the end of `lock` to establish a proper
-        // happens-before relationship with future owners.
-        self.ticket[thread].store(0, Ordering::Release);
+    // `count` on the mutex of an observed run.
+    fn count_observed<O: Observer + Sync>(
+        counter: &BakeryMutex<usize, OBSERVED_SLOTS, O>,
+        num_threads: usize,
+        iterations: usize,
+        profile: workers::Profile,
+        watchdog: Option<&watchdog::CounterWatchdog>,
+        finished: &AtomicUsize,
+        quiet: bool,
+    ) {
+        count(num_threads, iterations, profile, finished, quiet, |_| {
+            let mut slot = counter.register().expect("more workers than slots");
+            move |rng| {
+                let mut num = slot.lock();
+                *num += 1;
+                if let Some(watchdog) = watchdog {
+                    watchdog.record(num.slot(), *num);
+                }
+                profile.critical_section(rng);
+            }
+        });
     }
-}
 
-struct UnsafeSyncCell<T>(UnsafeCell<T>);
-unsafe impl<T> Sync for UnsafeSyncCell<T> {}
+    let watchdog =
+        check_every.map(|interval| watchdog::CounterWatchdog::new(interval, num_threads));
+    let finished = AtomicUsize::new(0);
 
-fn main() {
-    const NUM_THREADS: usize = 10;
-
-    let lock = RawBakeryLock::<NUM_THREADS>::new();
-    let mut num = UnsafeSyncCell(UnsafeCell::new(0));
-
-    thread::scope(|scope| {
-        for thread_id in 0..NUM_THREADS {
-            let lock = &lock;
-            let num = &num;
-            scope.spawn(move || {
-                println!("thread {thread_id} startup");
-                for _ in 0..100000 {
-                    lock.lock(thread_id);
-                    unsafe {
-                        *num.0.get() += 1;
-                    }
-                    lock.unlock(thread_id);
+    let start = Instant::now();
+    let num = if tui {
+        // Timing every critical section isn't free, so only do it when someone's watching.
+        let counter = BakeryMutex::from_raw(
+            RawBakeryLock::<OBSERVED_SLOTS, _>::with_observer(estimate::HoldTimer::new()),
+            0,
+        );
+        thread::scope(|scope| {
+            thread::Builder::new()
+                .name("tui".to_owned())
+                .spawn_scoped(scope, || tui::run(&counter, &finished, num_threads))
+                .expect("failed to spawn tui thread");
+            count_observed(
+                &counter,
+                num_threads,
+                iterations,
+                profile,
+                watchdog.as_ref(),
+                &finished,
+                true,
+            );
+        });
+        counter.into_inner()
+    } else if instrumented {
+        let mut counter = BakeryMutex::from_raw(
+            RawBakeryLock::<OBSERVED_SLOTS, _>::with_observer((
+                (
+                    bundle.map(|_| bundle::EventRing::<OBSERVED_SLOTS>::new()),
+                    stall_threshold.map(watchdog::StallWatchdog::<OBSERVED_SLOTS>::new),
+                ),
+                (
+                    latency.then(latency::AcquireTimer::<OBSERVED_SLOTS>::new),
+                    (
+                        check_overlap.then(watchdog::OverlapChecker::<OBSERVED_SLOTS>::new),
+                        trace.map(|_| trace::TraceRecorder::<OBSERVED_SLOTS>::new()),
+                    ),
+                ),
+            )),
+            0,
+        );
+        thread::scope(|scope| {
+            if let Some(stall) = &counter.observer().0 .1 {
+                let (counter, finished) = (&counter, &finished);
+                thread::Builder::new()
+                    .name("starvation-watchdog".to_owned())
+                    .spawn_scoped(scope, move || {
+                        stall.watch(
+                            || counter.snapshot(),
+                            || finished.load(Ordering::Relaxed) == num_threads,
+                        )
+                    })
+                    .expect("failed to spawn starvation watchdog thread");
+            }
+            count_observed(
+                &counter,
+                num_threads,
+                iterations,
+                profile,
+                watchdog.as_ref(),
+                &finished,
+                false,
+            );
+        });
+
+        let num = *counter.get_mut();
+        let expected = num_threads * iterations;
+        let ((ring, _), (timer, (overlap, recorder))) = counter.observer();
+        if let (Some(bundle), Some(ring)) = (bundle, ring) {
+            if num != expected {
+                let failure = format!("counted to {num} instead of {expected}");
+                match bundle::write(bundle, &failure, &ring.dump()) {
+                    Ok(dir) => eprintln!("failure bundle written to {}", dir.display()),
+                    Err(err) => eprintln!("failed to write failure bundle to {bundle}: {err}"),
                 }
-            });
+            }
+        }
+        if let Some(timer) = timer {
+            timer.report();
+        }
+        if let Some(overlap) = overlap.as_ref().filter(|overlap| overlap.overlaps() > 0) {
+            eprintln!(
+                "overlap checker: {} entries into an occupied critical section",
+                overlap.overlaps()
+            );
         }
-    });
+        if let (Some(trace), Some(recorder)) = (trace, recorder) {
+            if let Err(err) = recorder.write(trace) {
+                eprintln!("failed to write trace to {trace}: {err}");
+            }
+        }
+        num
+    } else {
+        let lock = algorithms::slot_lock(&algo, num_threads).unwrap_or_else(|| {
+            panic!(
+                "`BAKERY_ALGO`: `{algo}` isn't built into this binary or doesn't take slots, see \
+                 `--list-algos`"
+            )
+        });
+        assert!(
+            num_threads <= lock.slots(),
+            "`BAKERY_ALGO`: `{algo}` only has room for {} threads",
+            lock.slots()
+        );
+        // `SlotLock` only has the raw protocol, so the counter sits in a bare cell and each
+        // worker calls `lock` and `unlock` with its own slot around the increment. That's also
+        // what lets a lock missing a fence lose updates here instead of being hidden by a guard.
+        let num = UnsafeSyncCell(UnsafeCell::new(0));
+        let (lock, cell, watchdog) = (&*lock, &num, watchdog.as_ref());
+        count(num_threads, iterations, profile, &finished, false, |slot| {
+            move |rng| {
+                lock.lock(slot);
+                // SAFETY: Every access to the counter is between `lock(slot)` and `unlock(slot)`
+                // with the worker's own slot, and the counter is only read after every worker
+                // has finished.
+                let num = unsafe { &mut *cell.0.get() };
+                *num += 1;
+                if let Some(watchdog) = watchdog {
+                    watchdog.record(slot, *num);
+                }
+                profile.critical_section(rng);
+                lock.unlock(slot);
+            }
+        });
+        num.0.into_inner()
+    };
+    let elapsed = start.elapsed();
+
+    println!("{num}");
 
-    println!("{}", num.0.get_mut());
+    if let Some(db) = db {
+        let record = results::Record::new(topology, &algo, num_threads, iterations, num, elapsed);
+        if let Err(err) = results::append(db, &record) {
+            eprintln!("failed to record results in {db}: {err}");
+        }
+    }
 }
@@ -0,0 +1,95 @@
+use std::sync::atomic::{AtomicU16, AtomicU64};
+
+use bakery::{
+    layout::{Compact, Fused, Packed, Padded, SlotLayout, Tracked},
+    NoObserver, RawBakeryLock,
+};
+
+const SLOT_COUNTS: [usize; 4] = [2, 10, 256, 4096];
+
+fn usage<const N: usize, S: SlotLayout<N>>() -> usize {
+    RawBakeryLock::<N, NoObserver, S>::from_parts(NoObserver, S::new()).memory_usage()
+}
+
+// Prints the footprint in bytes of the lock with each slot layout, for a range of slot counts.
+pub fn report() {
+    let rows = [
+        (
+            "packed",
+            [
+                usage::<2, Packed<2>>(),
+                usage::<10, Packed<10>>(),
+                usage::<256, Packed<256>>(),
+                usage::<4096, Packed<4096>>(),
+            ],
+        ),
+        (
+            "packed-u16",
+            [
+                usage::<2, Packed<2, AtomicU16>>(),
+                usage::<10, Packed<10, AtomicU16>>(),
+                usage::<256, Packed<256, AtomicU16>>(),
+                usage::<4096, Packed<4096, AtomicU16>>(),
+            ],
+        ),
+        (
+            "packed-u64",
+            [
+                usage::<2, Packed<2, AtomicU64>>(),
+                usage::<10, Packed<10, AtomicU64>>(),
+                usage::<256, Packed<256, AtomicU64>>(),
+                usage::<4096, Packed<4096, AtomicU64>>(),
+            ],
+        ),
+        (
+            "padded",
+            [
+                usage::<2, Padded<2>>(),
+                usage::<10, Padded<10>>(),
+                usage::<256, Padded<256>>(),
+                usage::<4096, Padded<4096>>(),
+            ],
+        ),
+        (
+            "compact",
+            [
+                usage::<2, Compact<2>>(),
+                usage::<10, Compact<10>>(),
+                usage::<256, Compact<256>>(),
+                usage::<4096, Compact<4096>>(),
+            ],
+        ),
+        (
+            "fused",
+            [
+                usage::<2, Fused<2>>(),
+                usage::<10, Fused<10>>(),
+                usage::<256, Fused<256>>(),
+                usage::<4096, Fused<4096>>(),
+            ],
+        ),
+        (
+            "tracked",
+            [
+                usage::<2, Tracked<2, Packed<2>>>(),
+                usage::<10, Tracked<10, Packed<10>>>(),
+                usage::<256, Tracked<256, Packed<256>>>(),
+                usage::<4096, Tracked<4096, Packed<4096>>>(),
+            ],
+        ),
+    ];
+
+    print!("{:<12}", "layout");
+    for slots in SLOT_COUNTS {
+        print!(" {:>10}", format!("N={slots}"));
+    }
+    println!();
+
+    for (name, sizes) in rows {
+        print!("{name:<12}");
+        for size in sizes {
+            print!(" {size:>10}");
+        }
+        println!();
+    }
+}
@@ -0,0 +1,361 @@
+use std::{
+    cell::UnsafeCell,
+    fmt,
+    marker::PhantomData,
+    ops::{Deref, DerefMut},
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use crate::{
+    layout::SlotLayout,
+    registry::{self, SlotRegistry},
+    LockSnapshot, NoObserver, Observer, Packed, RawBakeryLock,
+};
+
+/// A value protected by a bakery lock for up to `N` threads, only reachable through the guard
+/// returned by [`lock`](Self::lock) or by locking a [`MutexSlotHandle`].
+pub struct BakeryMutex<T, const N: usize, O = NoObserver, S = Packed<N>> {
+    raw: RawBakeryLock<N, O, S>,
+    registry: Arc<SlotRegistry<N>>,
+    data: UnsafeCell<T>,
+}
+
+// SAFETY: the lock hands out access to `data` to one thread at a time.
+unsafe impl<T: Send, const N: usize, O: Sync, S: Sync> Sync for BakeryMutex<T, N, O, S> {}
+
+impl<T, const N: usize> BakeryMutex<T, N> {
+    /// Puts `data` behind a lock with the default layout and no diagnostics.
+    pub fn new(data: T) -> Self {
+        Self::from_raw(RawBakeryLock::new(), data)
+    }
+}
+
+impl<T: Default, const N: usize> Default for BakeryMutex<T, N> {
+    fn default() -> Self {
+        Self::new(T::default())
+    }
+}
+
+impl<T, const N: usize, O: Observer, S: SlotLayout<N>> BakeryMutex<T, N, O, S> {
+    /// Puts `data` behind `raw`, which must not be held by anyone.
+    pub fn from_raw(raw: RawBakeryLock<N, O, S>, data: T) -> Self {
+        Self {
+            raw,
+            registry: Arc::new(SlotRegistry::new()),
+            data: UnsafeCell::new(data),
+        }
+    }
+
+    /// Waits for the lock and gives access to the value until the returned guard is dropped,
+    /// using the slot assigned to the calling thread the first time it locks the mutex. Locking it
+    /// again from the same thread before then deadlocks.
+    ///
+    /// A thread locking it for the first time while all `N` slots are taken waits for another
+    /// thread to give its slot back by exiting or dropping its handle.
+    pub fn lock(&self) -> BakeryMutexGuard<'_, T, N, O, S> {
+        let slot = registry::assigned(&self.registry);
+        self.raw.lock(slot);
+        BakeryMutexGuard::new(self, slot)
+    }
+
+    /// Like [`lock`](Self::lock), but the guard holds on to the `Arc` instead of borrowing the
+    /// mutex, so that it can be kept in a struct or captured by a `'static` closure.
+    pub fn lock_arc(self: &Arc<Self>) -> ArcBakeryMutexGuard<T, N, O, S> {
+        let slot = registry::assigned(&self.registry);
+        self.raw.lock(slot);
+        ArcBakeryMutexGuard::new(Arc::clone(self), slot)
+    }
+
+    /// Like [`try_lock`](Self::try_lock), but returns an owned guard as
+    /// [`lock_arc`](Self::lock_arc) does.
+    pub fn try_lock_arc(self: &Arc<Self>) -> Option<ArcBakeryMutexGuard<T, N, O, S>> {
+        let slot = registry::try_assigned(&self.registry)?;
+        self.raw
+            .try_lock(slot)
+            .then(|| ArcBakeryMutexGuard::new(Arc::clone(self), slot))
+    }
+
+    /// Gives access to the value like [`lock`](Self::lock) if that doesn't require waiting for
+    /// another thread (or for a slot to be given back), or returns `None`.
+    pub fn try_lock(&self) -> Option<BakeryMutexGuard<'_, T, N, O, S>> {
+        let slot = registry::try_assigned(&self.registry)?;
+        self.raw
+            .try_lock(slot)
+            .then(|| BakeryMutexGuard::new(self, slot))
+    }
+
+    /// Like [`lock`](Self::lock), but gives up and returns `None` after waiting for `timeout`.
+    pub fn try_lock_for(&self, timeout: Duration) -> Option<BakeryMutexGuard<'_, T, N, O, S>> {
+        match Instant::now().checked_add(timeout) {
+            Some(deadline) => self.try_lock_until(deadline),
+            None => Some(self.lock()),
+        }
+    }
+
+    /// Like [`lock`](Self::lock), but gives up and returns `None` once `deadline` has passed,
+    /// whether the thread was still waiting for a slot or for the lock itself.
+    pub fn try_lock_until(&self, deadline: Instant) -> Option<BakeryMutexGuard<'_, T, N, O, S>> {
+        let slot = registry::assigned_until(&self.registry, deadline)?;
+        self.raw
+            .try_lock_until(slot, deadline)
+            .then(|| BakeryMutexGuard::new(self, slot))
+    }
+
+    /// Claims a free slot for the calling thread until the returned handle is dropped, or returns
+    /// `None` if all `N` are taken.
+    pub fn register(&self) -> Option<MutexSlotHandle<'_, T, N, O, S>> {
+        let slot = self.registry.claim()?;
+        Some(MutexSlotHandle { mutex: self, slot })
+    }
+
+    /// The observer the lock reports every step of the algorithm to.
+    pub fn observer(&self) -> &O {
+        self.raw.observer()
+    }
+
+    /// Samples every slot's flag and ticket, as [`RawBakeryLock::snapshot`] does.
+    pub fn snapshot(&self) -> LockSnapshot<N> {
+        self.raw.snapshot()
+    }
+
+    /// How many threads are ahead of the one locking from `slot`, estimated as
+    /// [`RawBakeryLock::queue_position`] does, or `None` if `slot` isn't in the bakery.
+    pub fn queue_position(&self, slot: usize) -> Option<usize> {
+        self.raw.queue_position(slot)
+    }
+
+    // The underlying lock. Not public: its `unlock` is safe, and would let anyone release the
+    // lock out from under a guard and hand out a second one.
+    pub(crate) fn raw(&self) -> &RawBakeryLock<N, O, S> {
+        &self.raw
+    }
+
+    /// A raw pointer to the value, for handing it to C code. It may only be dereferenced while
+    /// holding the lock, and is otherwise the same pointer as [`BakeryMutexGuard::data_ptr`].
+    pub fn data_ptr(&self) -> *mut T {
+        self.data.get()
+    }
+
+    /// The value, without locking: the exclusive borrow already rules out any other access.
+    pub fn get_mut(&mut self) -> &mut T {
+        self.data.get_mut()
+    }
+
+    pub fn into_inner(self) -> T {
+        self.data.into_inner()
+    }
+}
+
+/// One of the slots of a [`BakeryMutex`], which is returned to the mutex when the handle is
+/// dropped.
+pub struct MutexSlotHandle<'a, T, const N: usize, O: Observer, S: SlotLayout<N>> {
+    mutex: &'a BakeryMutex<T, N, O, S>,
+    slot: usize,
+}
+
+impl<T, const N: usize, O: Observer, S: SlotLayout<N>> MutexSlotHandle<'_, T, N, O, S> {
+    /// Waits for the lock and gives access to the value until the returned guard is dropped.
+    pub fn lock(&mut self) -> BakeryMutexGuard<'_, T, N, O, S> {
+        self.mutex.raw.lock(self.slot);
+        BakeryMutexGuard::new(self.mutex, self.slot)
+    }
+
+    /// Gives access to the value like [`lock`](Self::lock) if that doesn't require waiting for
+    /// another thread, or returns `None`.
+    pub fn try_lock(&mut self) -> Option<BakeryMutexGuard<'_, T, N, O, S>> {
+        self.mutex
+            .raw
+            .try_lock(self.slot)
+            .then(|| BakeryMutexGuard::new(self.mutex, self.slot))
+    }
+
+    /// Like [`lock`](Self::lock), but gives up and returns `None` after waiting for `timeout`.
+    pub fn try_lock_for(&mut self, timeout: Duration) -> Option<BakeryMutexGuard<'_, T, N, O, S>> {
+        self.mutex
+            .raw
+            .try_lock_for(self.slot, timeout)
+            .then(|| BakeryMutexGuard::new(self.mutex, self.slot))
+    }
+
+    /// Like [`lock`](Self::lock), but gives up and returns `None` once `deadline` has passed.
+    pub fn try_lock_until(
+        &mut self,
+        deadline: Instant,
+    ) -> Option<BakeryMutexGuard<'_, T, N, O, S>> {
+        self.mutex
+            .raw
+            .try_lock_until(self.slot, deadline)
+            .then(|| BakeryMutexGuard::new(self.mutex, self.slot))
+    }
+
+    /// The index of the slot in `0..N`.
+    pub fn slot(&self) -> usize {
+        self.slot
+    }
+}
+
+impl<T, const N: usize, O: Observer, S: SlotLayout<N>> Drop for MutexSlotHandle<'_, T, N, O, S> {
+    fn drop(&mut self) {
+        self.mutex.registry.release(self.slot);
+    }
+}
+
+/// Access to the value in a [`BakeryMutex`], which is given up when the guard is dropped.
+///
+/// Like `std::sync::MutexGuard`, the guard can only be shared between threads if the value can,
+/// since every thread it's shared with can read the value through it:
+///
+/// ```compile_fail
+/// use std::{cell::Cell, thread};
+///
+/// let mutex = bakery::BakeryMutex::<Cell<u64>, 4>::new(Cell::new(0));
+/// let guard = mutex.lock();
+/// thread::scope(|scope| {
+///     scope.spawn(|| guard.set(1));
+///     guard.set(2);
+/// });
+/// ```
+#[must_use = "dropping the guard immediately unlocks the mutex"]
+pub struct BakeryMutexGuard<'a, T, const N: usize, O: Observer, S: SlotLayout<N>> {
+    pub(crate) mutex: &'a BakeryMutex<T, N, O, S>,
+    pub(crate) slot: usize,
+    // Opts out of the automatic `Send` and `Sync`. The guard has to be dropped on the thread that
+    // locked, which is the one its slot belongs to, and `Sync` is implemented below.
+    _not_send: PhantomData<*const ()>,
+}
+
+// SAFETY: sharing the guard only gives out `&T`, which is fine to share if `T` is `Sync`.
+unsafe impl<T: Sync, const N: usize, O: Observer, S: SlotLayout<N>> Sync
+    for BakeryMutexGuard<'_, T, N, O, S>
+{
+}
+
+impl<'a, T, const N: usize, O: Observer, S: SlotLayout<N>> BakeryMutexGuard<'a, T, N, O, S> {
+    // Must only be called from the thread using `slot`, once it has locked `mutex`.
+    pub(crate) fn new(mutex: &'a BakeryMutex<T, N, O, S>, slot: usize) -> Self {
+        Self {
+            mutex,
+            slot,
+            _not_send: PhantomData,
+        }
+    }
+
+    /// The slot the mutex was locked from.
+    pub fn slot(&self) -> usize {
+        self.slot
+    }
+
+    /// A raw pointer to the value, for handing it to C code, which can read and write through it
+    /// until the guard is dropped. Those accesses mustn't overlap with any through the guard.
+    pub fn data_ptr(&self) -> *mut T {
+        self.mutex.data.get()
+    }
+}
+
+impl<T, const N: usize, O: Observer, S: SlotLayout<N>> Deref for BakeryMutexGuard<'_, T, N, O, S> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        // SAFETY: we hold the lock.
+        unsafe { &*self.mutex.data.get() }
+    }
+}
+
+impl<T, const N: usize, O: Observer, S: SlotLayout<N>> DerefMut
+    for BakeryMutexGuard<'_, T, N, O, S>
+{
+    fn deref_mut(&mut self) -> &mut T {
+        // SAFETY: we hold the lock, and the guard is borrowed mutably.
+        unsafe { &mut *self.mutex.data.get() }
+    }
+}
+
+impl<T, const N: usize, O: Observer, S: SlotLayout<N>> Drop for BakeryMutexGuard<'_, T, N, O, S> {
+    fn drop(&mut self) {
+        self.mutex.raw.unlock(self.slot);
+    }
+}
+
+impl<T: fmt::Debug, const N: usize, O: Observer, S: SlotLayout<N>> fmt::Debug
+    for BakeryMutexGuard<'_, T, N, O, S>
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(&**self, f)
+    }
+}
+
+/// Access to the value in a [`BakeryMutex`] behind an `Arc`, returned by
+/// [`lock_arc`](BakeryMutex::lock_arc), which keeps the mutex alive and is given up when the guard
+/// is dropped. It can be shared between threads exactly when a [`BakeryMutexGuard`] can.
+#[must_use = "dropping the guard immediately unlocks the mutex"]
+pub struct ArcBakeryMutexGuard<T, const N: usize, O: Observer, S: SlotLayout<N>> {
+    mutex: Arc<BakeryMutex<T, N, O, S>>,
+    slot: usize,
+    // As in `BakeryMutexGuard`.
+    _not_send: PhantomData<*const ()>,
+}
+
+// SAFETY: as for `BakeryMutexGuard`.
+unsafe impl<T: Sync, const N: usize, O: Observer, S: SlotLayout<N>> Sync
+    for ArcBakeryMutexGuard<T, N, O, S>
+{
+}
+
+impl<T, const N: usize, O: Observer, S: SlotLayout<N>> ArcBakeryMutexGuard<T, N, O, S> {
+    // Must only be called from the thread using `slot`, once it has locked `mutex`.
+    fn new(mutex: Arc<BakeryMutex<T, N, O, S>>, slot: usize) -> Self {
+        Self {
+            mutex,
+            slot,
+            _not_send: PhantomData,
+        }
+    }
+
+    /// The mutex the guard holds.
+    pub fn mutex(&self) -> &Arc<BakeryMutex<T, N, O, S>> {
+        &self.mutex
+    }
+
+    /// The slot the mutex was locked from.
+    pub fn slot(&self) -> usize {
+        self.slot
+    }
+
+    /// A raw pointer to the value, as for [`BakeryMutexGuard::data_ptr`].
+    pub fn data_ptr(&self) -> *mut T {
+        self.mutex.data.get()
+    }
+}
+
+impl<T, const N: usize, O: Observer, S: SlotLayout<N>> Deref for ArcBakeryMutexGuard<T, N, O, S> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        // SAFETY: we hold the lock.
+        unsafe { &*self.mutex.data.get() }
+    }
+}
+
+impl<T, const N: usize, O: Observer, S: SlotLayout<N>> DerefMut
+    for ArcBakeryMutexGuard<T, N, O, S>
+{
+    fn deref_mut(&mut self) -> &mut T {
+        // SAFETY: we hold the lock, and the guard is borrowed mutably.
+        unsafe { &mut *self.mutex.data.get() }
+    }
+}
+
+impl<T, const N: usize, O: Observer, S: SlotLayout<N>> Drop for ArcBakeryMutexGuard<T, N, O, S> {
+    fn drop(&mut self) {
+        self.mutex.raw.unlock(self.slot);
+    }
+}
+
+impl<T: fmt::Debug, const N: usize, O: Observer, S: SlotLayout<N>> fmt::Debug
+    for ArcBakeryMutexGuard<T, N, O, S>
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(&**self, f)
+    }
+}
@@ -0,0 +1,147 @@
+use core::{
+    cell::UnsafeCell,
+    fmt,
+    mem::{self, MaybeUninit},
+    ops::Deref,
+    sync::atomic::{AtomicU8, Ordering},
+};
+
+use crate::spin;
+
+const INCOMPLETE: u8 = 0;
+const RUNNING: u8 = 1;
+const COMPLETE: u8 = 2;
+
+/// Runs a piece of code exactly once, available without `std` where `std::sync::Once` isn't.
+///
+/// Whoever gets there first runs it, and everyone arriving meanwhile spins until it's done, so
+/// every return from [`call_once`](Self::call_once) happens-after the code that ran. If it panics,
+/// the next caller tries again.
+pub struct Once {
+    state: AtomicU8,
+}
+
+impl Once {
+    /// A `Once` that hasn't run anything yet.
+    pub const fn new() -> Self {
+        Self {
+            state: AtomicU8::new(INCOMPLETE),
+        }
+    }
+
+    /// Runs `f` if no call has finished running its closure yet, and otherwise waits for the one
+    /// that's running to finish, or returns straight away.
+    pub fn call_once(&self, f: impl FnOnce()) {
+        loop {
+            match self.state.compare_exchange_weak(
+                INCOMPLETE,
+                RUNNING,
+                Ordering::Acquire,
+                Ordering::Acquire,
+            ) {
+                Ok(_) => break,
+                Err(COMPLETE) => return,
+                Err(_) => spin::relax(),
+            }
+        }
+
+        // Lets the next caller in if `f` panics, as though we'd never started.
+        struct Reset<'a>(&'a AtomicU8);
+        impl Drop for Reset<'_> {
+            fn drop(&mut self) {
+                self.0.store(INCOMPLETE, Ordering::Release);
+            }
+        }
+
+        let reset = Reset(&self.state);
+        f();
+        mem::forget(reset);
+        // Synchronizes-with the acquire loads of everyone who sees it, publishing whatever `f` did.
+        self.state.store(COMPLETE, Ordering::Release);
+    }
+
+    /// Whether a call has finished running its closure.
+    pub fn is_completed(&self) -> bool {
+        self.state.load(Ordering::Acquire) == COMPLETE
+    }
+}
+
+impl Default for Once {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl fmt::Debug for Once {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Once")
+            .field("completed", &self.is_completed())
+            .finish()
+    }
+}
+
+/// A value computed by `F` the first time it's needed, through a [`Once`].
+pub struct Lazy<T, F = fn() -> T> {
+    once: Once,
+    init: UnsafeCell<Option<F>>,
+    value: UnsafeCell<MaybeUninit<T>>,
+}
+
+// SAFETY: the value is shared once initialized, and the initializer is moved to (and run on)
+// whichever thread gets there first.
+unsafe impl<T: Send + Sync, F: Send> Sync for Lazy<T, F> {}
+
+impl<T, F> Lazy<T, F> {
+    /// A value that `init` will compute on first use.
+    pub const fn new(init: F) -> Self {
+        Self {
+            once: Once::new(),
+            init: UnsafeCell::new(Some(init)),
+            value: UnsafeCell::new(MaybeUninit::uninit()),
+        }
+    }
+}
+
+impl<T, F: FnOnce() -> T> Lazy<T, F> {
+    /// Computes the value if nobody has yet, and returns it.
+    pub fn force(this: &Self) -> &T {
+        this.once.call_once(|| {
+            // SAFETY: only the thread running the `Once` gets here, and nobody reads either cell
+            // until it has completed.
+            let (init, value) = unsafe { (&mut *this.init.get(), &mut *this.value.get()) };
+            value.write(init.take().expect("`Lazy` initializer panicked before")());
+        });
+        // SAFETY: `call_once` only returns once the value has been written, and it never is again.
+        unsafe { (*this.value.get()).assume_init_ref() }
+    }
+}
+
+impl<T, F: FnOnce() -> T> Deref for Lazy<T, F> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        Self::force(self)
+    }
+}
+
+impl<T: fmt::Debug, F> fmt::Debug for Lazy<T, F> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut lazy = f.debug_tuple("Lazy");
+        if self.once.is_completed() {
+            // SAFETY: as in `force`.
+            lazy.field(unsafe { (*self.value.get()).assume_init_ref() });
+        } else {
+            lazy.field(&format_args!("<uninit>"));
+        }
+        lazy.finish()
+    }
+}
+
+impl<T, F> Drop for Lazy<T, F> {
+    fn drop(&mut self) {
+        if self.once.is_completed() {
+            // SAFETY: the value was written, and nobody else can be looking at it any more.
+            unsafe { self.value.get_mut().assume_init_drop() };
+        }
+    }
+}
@@ -0,0 +1,132 @@
+use core::fmt;
+
+// `std::env::consts::ARCH`, for the architectures `lowering` knows about.
+const ARCH: &str = if cfg!(target_arch = "x86_64") {
+    "x86_64"
+} else if cfg!(target_arch = "x86") {
+    "x86"
+} else if cfg!(target_arch = "aarch64") {
+    "aarch64"
+} else {
+    "other"
+};
+
+/// How one of the ordering sites in a lock is implemented.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum SiteOrdering {
+    SeqCstFence,
+    /// A `SeqCst` fence weakened to a compiler-only fence.
+    CompilerFence,
+    AcquireFence,
+    ReleaseStore,
+    /// Nothing at all, because the accesses on either side are one and the same load.
+    SingleLoad,
+}
+
+impl SiteOrdering {
+    /// A human-readable name, e.g. `SeqCst fence`.
+    pub fn name(self) -> &'static str {
+        match self {
+            SiteOrdering::SeqCstFence => "SeqCst fence",
+            SiteOrdering::CompilerFence => "compiler fence",
+            SiteOrdering::AcquireFence => "Acquire fence",
+            SiteOrdering::ReleaseStore => "Release store",
+            SiteOrdering::SingleLoad => "single load",
+        }
+    }
+
+    /// What the site typically compiles to on the current target.
+    pub fn lowering(self) -> &'static str {
+        match (self, ARCH) {
+            (SiteOrdering::CompilerFence | SiteOrdering::SingleLoad, _) => "nothing",
+            (SiteOrdering::SeqCstFence, "x86_64" | "x86") => "locked no-op or mfence",
+            (SiteOrdering::AcquireFence | SiteOrdering::ReleaseStore, "x86_64" | "x86") => {
+                "plain access under TSO"
+            }
+            (SiteOrdering::SeqCstFence, "aarch64") => "dmb ish",
+            (SiteOrdering::AcquireFence, "aarch64") => "dmb ishld",
+            (SiteOrdering::ReleaseStore, "aarch64") => "stlr",
+            _ => "target-specific",
+        }
+    }
+}
+
+/// The points in `lock` and `unlock` that order memory accesses between threads.
+// rustfmt would move each comment below to the end of the line before it.
+#[rustfmt::skip]
+pub const SITES: [&str; 5] = [
+    // Between setting `choosing` and reading the other tickets.
+    "doorway",
+    // Between publishing the ticket and clearing `choosing`.
+    "ticket",
+    // After seeing another thread's `choosing` cleared, before reading its ticket.
+    "choosing",
+    // At the end of `lock`, pairing with earlier owners' `unlock`.
+    "entry",
+    // Retiring the ticket in `unlock`.
+    "exit",
+];
+
+/// The effective ordering of every site in [`SITES`] for a particular lock, so that harnesses can
+/// record (and tests can assert) exactly which variant was exercised.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub struct OrderingConfig {
+    /// How each site, in the order of [`SITES`], is implemented.
+    pub sites: [SiteOrdering; 5],
+}
+
+impl OrderingConfig {
+    /// The correct algorithm, with the two SC fences optionally weakened.
+    pub fn bakery(weak_fence_1: bool, weak_fence_2: bool) -> Self {
+        let fence = |weak| {
+            if weak {
+                SiteOrdering::CompilerFence
+            } else {
+                SiteOrdering::SeqCstFence
+            }
+        };
+        Self {
+            sites: [
+                fence(weak_fence_1),
+                fence(weak_fence_2),
+                SiteOrdering::AcquireFence,
+                SiteOrdering::AcquireFence,
+                SiteOrdering::ReleaseStore,
+            ],
+        }
+    }
+
+    /// The configuration [`RawBakeryLock`](crate::RawBakeryLock) was built with.
+    pub fn build() -> Self {
+        Self::bakery(
+            cfg!(feature = "fake-fence-1"),
+            cfg!(feature = "fake-fence-2"),
+        )
+    }
+
+    /// Summarizes the two SC fences as e.g. `sc/sc` or `compiler/sc`.
+    pub fn fences(&self) -> &'static str {
+        let weak = |site: SiteOrdering| site == SiteOrdering::CompilerFence;
+        match (weak(self.sites[0]), weak(self.sites[1])) {
+            (false, false) => "sc/sc",
+            (true, false) => "compiler/sc",
+            (false, true) => "sc/compiler",
+            (true, true) => "compiler/compiler",
+        }
+    }
+}
+
+// One line per site, e.g. `doorway   SeqCst fence    (dmb ish)`.
+impl fmt::Display for OrderingConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (site, ordering) in SITES.iter().zip(self.sites) {
+            writeln!(
+                f,
+                "{site:<10} {:<16} ({})",
+                ordering.name(),
+                ordering.lowering()
+            )?;
+        }
+        Ok(())
+    }
+}
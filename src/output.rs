@@ -0,0 +1,127 @@
+use crate::results;
+
+// How a mode reports its results: the usual human-readable text, or one row per measurement for
+// scripts.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Text,
+    // One JSON object per row (JSON Lines).
+    Json,
+    // A header line followed by one line per row.
+    Csv,
+}
+
+impl Format {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "text" => Some(Format::Text),
+            "json" => Some(Format::Json),
+            "csv" => Some(Format::Csv),
+            _ => None,
+        }
+    }
+
+    pub fn is_text(self) -> bool {
+        self == Format::Text
+    }
+}
+
+pub enum Value {
+    Int(u64),
+    Float(f64),
+    Str(String),
+}
+
+impl From<usize> for Value {
+    fn from(value: usize) -> Self {
+        Value::Int(value as u64)
+    }
+}
+
+impl From<u64> for Value {
+    fn from(value: u64) -> Self {
+        Value::Int(value)
+    }
+}
+
+impl From<f64> for Value {
+    fn from(value: f64) -> Self {
+        Value::Float(value)
+    }
+}
+
+impl From<&str> for Value {
+    fn from(value: &str) -> Self {
+        Value::Str(value.to_owned())
+    }
+}
+
+impl From<String> for Value {
+    fn from(value: String) -> Self {
+        Value::Str(value)
+    }
+}
+
+impl Value {
+    fn to_json(&self) -> String {
+        match self {
+            Value::Int(value) => value.to_string(),
+            Value::Float(value) if value.is_finite() => value.to_string(),
+            Value::Float(_) => "null".to_owned(),
+            Value::Str(value) => results::quote(value),
+        }
+    }
+
+    fn to_csv(&self) -> String {
+        match self {
+            Value::Int(value) => value.to_string(),
+            Value::Float(value) => value.to_string(),
+            Value::Str(value) if value.contains([',', '"', '\n']) => {
+                format!("\"{}\"", value.replace('"', "\"\""))
+            }
+            Value::Str(value) => value.clone(),
+        }
+    }
+}
+
+// Prints rows with a fixed set of columns as they come in, in the machine-readable formats. In
+// text mode it prints nothing, leaving the mode to print its own output.
+pub struct Table {
+    format: Format,
+    columns: &'static [&'static str],
+}
+
+impl Table {
+    pub fn new(format: Format, columns: &'static [&'static str]) -> Self {
+        if format == Format::Csv {
+            println!("{}", columns.join(","));
+        }
+        Self { format, columns }
+    }
+
+    pub fn row<const N: usize>(&self, values: [Value; N]) {
+        assert_eq!(
+            N,
+            self.columns.len(),
+            "row doesn't match the table's columns"
+        );
+        match self.format {
+            Format::Text => {}
+            Format::Json => {
+                let fields: Vec<_> = self
+                    .columns
+                    .iter()
+                    .zip(&values)
+                    .map(|(column, value)| {
+                        format!("{}:{}", results::quote(column), value.to_json())
+                    })
+                    .collect();
+                println!("{{{}}}", fields.join(","));
+            }
+            Format::Csv => {
+                let fields: Vec<_> = values.iter().map(Value::to_csv).collect();
+                println!("{}", fields.join(","));
+            }
+        }
+    }
+}
@@ -0,0 +1,104 @@
+use core::sync::atomic::{self, AtomicBool, AtomicUsize, Ordering};
+
+use crate::{spin, SlotLock};
+
+/// Peterson's lock for two threads, in slots 0 and 1.
+///
+/// A thread raises its flag, lets the other thread go first by writing the other's slot into
+/// `turn`, and then waits for as long as the other thread's flag is up and it's still the other's
+/// turn. Of two threads arriving together, whichever writes `turn` last waits. Like the bakery
+/// lock, it only needs loads and stores, and it's first come, first served: once a thread has
+/// written `turn`, the other can get in ahead of it at most once.
+pub struct PetersonLock {
+    flags: [AtomicBool; 2],
+    turn: AtomicUsize,
+}
+
+impl PetersonLock {
+    /// An unlocked lock.
+    pub const fn new() -> Self {
+        Self {
+            flags: [AtomicBool::new(false), AtomicBool::new(false)],
+            turn: AtomicUsize::new(0),
+        }
+    }
+
+    /// Waits until the other thread isn't in its critical section or waiting to enter it ahead of
+    /// `thread`, and enters the critical section. `thread` must be 0 or 1, and the other slot must
+    /// be used by another thread.
+    pub fn lock(&self, thread: usize) {
+        assert!(thread < 2, "Peterson's lock only has slots 0 and 1");
+        let other = 1 - thread;
+
+        self.flags[thread].store(true, Ordering::Relaxed);
+        // Release, so that a thread that finds it's its turn because we wrote `turn` also sees
+        // everything we did before, including our last critical section. That's the only case
+        // where neither the flag store in `unlock` nor the fence below is what lets it in.
+        self.turn.store(other, Ordering::Release);
+
+        // Both stores must become visible before we read the other thread's flag and `turn`.
+        // Without this fence, this is the store-buffering pattern: both threads read the other's
+        // flag as still down, and both enter. With it, of the two threads' fences, the one later
+        // in the single total order of SC fences sees both stores from before the earlier one,
+        // which means it sees the other thread's flag up, and its own `turn` store as the latest
+        // (stores before the earlier fence come first in `turn`'s modification order), so that
+        // thread waits.
+        atomic::fence(Ordering::SeqCst);
+
+        while self.flags[other].load(Ordering::Relaxed)
+            && self.turn.load(Ordering::Relaxed) == other
+        {
+            spin::relax();
+        }
+
+        // Synchronizes-with the release store in the other thread's `unlock` if we saw its flag
+        // down, or with its release store to `turn` if it let us go first.
+        atomic::fence(Ordering::Acquire);
+    }
+
+    /// Enters the critical section like [`lock`](Self::lock) if that doesn't require waiting for
+    /// the other thread, returning whether it did.
+    pub fn try_lock(&self, thread: usize) -> bool {
+        assert!(thread < 2, "Peterson's lock only has slots 0 and 1");
+        let other = 1 - thread;
+
+        self.flags[thread].store(true, Ordering::Relaxed);
+        self.turn.store(other, Ordering::Release);
+        // See `lock`.
+        atomic::fence(Ordering::SeqCst);
+
+        if self.flags[other].load(Ordering::Relaxed) && self.turn.load(Ordering::Relaxed) == other {
+            // Lowering our flag is all it takes to withdraw: a waiting thread only needs one of
+            // the two conditions to get in.
+            self.flags[thread].store(false, Ordering::Relaxed);
+            return false;
+        }
+        atomic::fence(Ordering::Acquire);
+        true
+    }
+
+    /// Leaves the critical section entered with `lock(thread)`, letting the other thread in.
+    pub fn unlock(&self, thread: usize) {
+        self.flags[thread].store(false, Ordering::Release);
+    }
+}
+
+impl Default for PetersonLock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SlotLock for PetersonLock {
+    fn slots(&self) -> usize {
+        2
+    }
+
+    fn lock(&self, slot: usize) {
+        PetersonLock::lock(self, slot);
+    }
+
+    fn unlock(&self, slot: usize) {
+        PetersonLock::unlock(self, slot);
+    }
+}
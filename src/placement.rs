@@ -0,0 +1,138 @@
+use std::{collections::BTreeMap, sync::OnceLock};
+
+use crate::topology::{self, Cpu};
+
+// How worker threads are spread over the machine. Contenders sharing a core or a cache see each
+// other's stores much sooner than ones on different sockets, which changes both the cost of the
+// doorway and how often reorderings show up.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Placement {
+    // Fill every hardware thread of a core, then every core of a socket, before moving on.
+    Compact,
+    // One thread per physical core, alternating between sockets, before doubling up on SMT
+    // siblings.
+    Scatter,
+    // Alternate between NUMA nodes.
+    NumaInterleave,
+}
+
+impl Placement {
+    pub const ALL: [Placement; 3] = [
+        Placement::Compact,
+        Placement::Scatter,
+        Placement::NumaInterleave,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Placement::Compact => "compact",
+            Placement::Scatter => "scatter",
+            Placement::NumaInterleave => "numa-interleave",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|placement| placement.name() == name)
+    }
+
+    // Orders `cpus` so that worker `i` should be pinned to the `i`th one.
+    fn order(self, mut cpus: Vec<Cpu>) -> Vec<usize> {
+        match self {
+            Placement::Compact => cpus.sort_by_key(|cpu| (cpu.package, cpu.core, cpu.id)),
+            Placement::Scatter => {
+                // Rank each CPU among its SMT siblings and each core within its socket, then take
+                // the first sibling of the first core of every socket, and so on.
+                let mut siblings = BTreeMap::new();
+                let mut cores = BTreeMap::new();
+                let mut keys = BTreeMap::new();
+                for cpu in &cpus {
+                    let sibling = siblings.entry((cpu.package, cpu.core)).or_insert(0);
+                    let cores_in_package = cores.entry(cpu.package).or_insert(BTreeMap::new());
+                    let next_core = cores_in_package.len();
+                    let core = *cores_in_package.entry(cpu.core).or_insert(next_core);
+                    keys.insert(cpu.id, (*sibling, core, cpu.package));
+                    *sibling += 1;
+                }
+                cpus.sort_by_key(|cpu| keys[&cpu.id]);
+            }
+            Placement::NumaInterleave => {
+                let mut ranks = BTreeMap::new();
+                let mut keys = BTreeMap::new();
+                for cpu in &cpus {
+                    let rank = ranks.entry(cpu.node).or_insert(0);
+                    keys.insert(cpu.id, (*rank, cpu.node));
+                    *rank += 1;
+                }
+                cpus.sort_by_key(|cpu| keys[&cpu.id]);
+            }
+        }
+
+        cpus.into_iter().map(|cpu| cpu.id).collect()
+    }
+}
+
+// The selected placement and the CPUs it pins workers to, in order.
+static ORDER: OnceLock<(Placement, Vec<usize>)> = OnceLock::new();
+
+// Makes every worker spawned from now on pin itself according to `placement`. Returns `false` if
+// the topology or thread pinning isn't available on this platform.
+pub fn set(placement: Placement) -> bool {
+    let Some(cpus) = topology::cpus().filter(|_| affinity::SUPPORTED) else {
+        return false;
+    };
+    assert!(
+        ORDER.set((placement, placement.order(cpus))).is_ok(),
+        "placement already set"
+    );
+    true
+}
+
+pub fn current() -> Option<Placement> {
+    ORDER.get().map(|&(placement, _)| placement)
+}
+
+// Pins the calling thread, which runs worker `id`, to its CPU under the selected placement, if
+// any. Workers beyond the number of CPUs wrap around.
+pub fn pin(id: usize) {
+    if let Some((_, order)) = ORDER.get() {
+        let cpu = order[id % order.len()];
+        if !affinity::pin(cpu) {
+            eprintln!("warning: failed to pin worker {id} to CPU {cpu}");
+        }
+    }
+}
+
+#[cfg(target_os = "linux")]
+mod affinity {
+    use std::ffi::c_int;
+
+    pub const SUPPORTED: bool = true;
+
+    // Large enough for the kernel's default `NR_CPUS` on every distribution we run on.
+    const MASK_WORDS: usize = 16;
+
+    extern "C" {
+        fn sched_setaffinity(pid: c_int, size: usize, mask: *const u64) -> c_int;
+    }
+
+    pub fn pin(cpu: usize) -> bool {
+        if cpu >= MASK_WORDS * 64 {
+            return false;
+        }
+        let mut mask = [0u64; MASK_WORDS];
+        mask[cpu / 64] |= 1 << (cpu % 64);
+        // SAFETY: `mask` is a valid CPU set of the given size, and pid 0 is the calling thread.
+        unsafe { sched_setaffinity(0, size_of_val(&mask), mask.as_ptr()) == 0 }
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+mod affinity {
+    pub const SUPPORTED: bool = false;
+
+    pub fn pin(_cpu: usize) -> bool {
+        false
+    }
+}
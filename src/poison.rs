@@ -0,0 +1,200 @@
+use std::{
+    fmt,
+    ops::{Deref, DerefMut},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        LockResult, PoisonError, TryLockError, TryLockResult,
+    },
+    thread,
+};
+
+use crate::{
+    layout::SlotLayout, BakeryMutex, BakeryMutexGuard, LockSnapshot, NoObserver, Observer, Packed,
+    RawBakeryLock,
+};
+
+/// A [`BakeryMutex`] that is poisoned when a thread panics while holding it, like
+/// `std::sync::Mutex`.
+///
+/// A plain `BakeryMutex` simply unlocks when a guard is dropped during unwinding, leaving whatever
+/// the panicking thread was halfway through for the next thread to find. This one remembers that
+/// it happened, and from then on [`lock`](Self::lock) and [`try_lock`](Self::try_lock) return
+/// the guard wrapped in a [`PoisonError`], until [`clear_poison`](Self::clear_poison) is called.
+/// The data is still reachable through the error, for callers who can repair it.
+pub struct PoisonBakeryMutex<T, const N: usize, O = NoObserver, S = Packed<N>> {
+    mutex: BakeryMutex<T, N, O, S>,
+    poisoned: AtomicBool,
+}
+
+impl<T, const N: usize> PoisonBakeryMutex<T, N> {
+    /// Puts `data` behind a lock with the default layout and no diagnostics.
+    pub fn new(data: T) -> Self {
+        Self::from_raw(RawBakeryLock::new(), data)
+    }
+}
+
+impl<T: Default, const N: usize> Default for PoisonBakeryMutex<T, N> {
+    fn default() -> Self {
+        Self::new(T::default())
+    }
+}
+
+impl<T, const N: usize, O: Observer, S: SlotLayout<N>> PoisonBakeryMutex<T, N, O, S> {
+    /// Puts `data` behind `raw`, which must not be held by anyone.
+    pub fn from_raw(raw: RawBakeryLock<N, O, S>, data: T) -> Self {
+        Self {
+            mutex: BakeryMutex::from_raw(raw, data),
+            poisoned: AtomicBool::new(false),
+        }
+    }
+
+    /// Waits for the lock like [`BakeryMutex::lock`], and returns the guard as an error if the
+    /// mutex is poisoned.
+    pub fn lock(&self) -> LockResult<PoisonBakeryMutexGuard<'_, T, N, O, S>> {
+        self.guard(self.mutex.lock())
+    }
+
+    /// Gives access to the value like [`lock`](Self::lock) if that doesn't require waiting for
+    /// another thread (or for a slot to be given back).
+    pub fn try_lock(&self) -> TryLockResult<PoisonBakeryMutexGuard<'_, T, N, O, S>> {
+        let guard = self.mutex.try_lock().ok_or(TryLockError::WouldBlock)?;
+        Ok(self.guard(guard)?)
+    }
+
+    /// Whether a thread has panicked while holding the lock since it was created or last
+    /// cleared.
+    pub fn is_poisoned(&self) -> bool {
+        self.poisoned.load(Ordering::Relaxed)
+    }
+
+    /// Marks the mutex as no longer poisoned, once whatever the panicking thread left behind has
+    /// been dealt with.
+    pub fn clear_poison(&self) {
+        self.poisoned.store(false, Ordering::Relaxed);
+    }
+
+    /// The observer the lock reports every step of the algorithm to.
+    pub fn observer(&self) -> &O {
+        self.mutex.observer()
+    }
+
+    /// Samples every slot's flag and ticket, as [`BakeryMutex::snapshot`] does.
+    pub fn snapshot(&self) -> LockSnapshot<N> {
+        self.mutex.snapshot()
+    }
+
+    /// How many threads are ahead of the one locking from `slot`, as
+    /// [`BakeryMutex::queue_position`] estimates it.
+    pub fn queue_position(&self, slot: usize) -> Option<usize> {
+        self.mutex.queue_position(slot)
+    }
+
+    /// The value, without locking, as an error if the mutex is poisoned.
+    pub fn get_mut(&mut self) -> LockResult<&mut T> {
+        let poisoned = self.is_poisoned();
+        let data = self.mutex.get_mut();
+        if poisoned {
+            Err(PoisonError::new(data))
+        } else {
+            Ok(data)
+        }
+    }
+
+    /// The value, as an error if the mutex is poisoned.
+    pub fn into_inner(self) -> LockResult<T> {
+        let poisoned = self.is_poisoned();
+        let data = self.mutex.into_inner();
+        if poisoned {
+            Err(PoisonError::new(data))
+        } else {
+            Ok(data)
+        }
+    }
+
+    fn guard<'a>(
+        &'a self,
+        guard: BakeryMutexGuard<'a, T, N, O, S>,
+    ) -> LockResult<PoisonBakeryMutexGuard<'a, T, N, O, S>> {
+        let guard = PoisonBakeryMutexGuard {
+            guard,
+            poisoned: &self.poisoned,
+            panicking: thread::panicking(),
+        };
+        // Relaxed is enough: the lock itself orders the store in the panicking thread's guard
+        // before this load.
+        if self.is_poisoned() {
+            Err(PoisonError::new(guard))
+        } else {
+            Ok(guard)
+        }
+    }
+}
+
+/// Access to the value in a [`PoisonBakeryMutex`], which is given up when the guard is dropped,
+/// poisoning the mutex if that's because of a panic.
+///
+/// As with [`BakeryMutexGuard`], the guard can only be shared between threads if the value can:
+///
+/// ```compile_fail
+/// use std::{cell::Cell, thread};
+///
+/// let mutex = bakery::PoisonBakeryMutex::<Cell<u64>, 4>::new(Cell::new(0));
+/// let guard = mutex.lock().unwrap();
+/// thread::scope(|scope| {
+///     scope.spawn(|| guard.set(1));
+///     guard.set(2);
+/// });
+/// ```
+#[must_use = "dropping the guard immediately unlocks the mutex"]
+pub struct PoisonBakeryMutexGuard<'a, T, const N: usize, O: Observer, S: SlotLayout<N>> {
+    // Makes the guard `Sync` only for `T: Sync`, and never `Send`, like the guard it wraps.
+    guard: BakeryMutexGuard<'a, T, N, O, S>,
+    poisoned: &'a AtomicBool,
+    // Whether the thread was already unwinding when it locked, in which case dropping the guard
+    // while still unwinding from that same panic doesn't mean anything went wrong under the lock.
+    panicking: bool,
+}
+
+impl<T, const N: usize, O: Observer, S: SlotLayout<N>> PoisonBakeryMutexGuard<'_, T, N, O, S> {
+    /// The slot the mutex was locked from.
+    pub fn slot(&self) -> usize {
+        self.guard.slot()
+    }
+}
+
+impl<T, const N: usize, O: Observer, S: SlotLayout<N>> Deref
+    for PoisonBakeryMutexGuard<'_, T, N, O, S>
+{
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.guard
+    }
+}
+
+impl<T, const N: usize, O: Observer, S: SlotLayout<N>> DerefMut
+    for PoisonBakeryMutexGuard<'_, T, N, O, S>
+{
+    fn deref_mut(&mut self) -> &mut T {
+        &mut self.guard
+    }
+}
+
+impl<T, const N: usize, O: Observer, S: SlotLayout<N>> Drop
+    for PoisonBakeryMutexGuard<'_, T, N, O, S>
+{
+    fn drop(&mut self) {
+        // Stored before `guard` is dropped right after this, which unlocks.
+        if !self.panicking && thread::panicking() {
+            self.poisoned.store(true, Ordering::Relaxed);
+        }
+    }
+}
+
+impl<T: fmt::Debug, const N: usize, O: Observer, S: SlotLayout<N>> fmt::Debug
+    for PoisonBakeryMutexGuard<'_, T, N, O, S>
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(&**self, f)
+    }
+}
@@ -0,0 +1,158 @@
+// Kani proof harnesses for the bakery algorithm, run with `cargo kani`.
+//
+// Kani explores a single thread of execution, so rather than running `lock` itself the harnesses
+// step a model of it: every thread is a small state machine taking one shared-memory access of
+// `lock` or `unlock` per step, on a real `Packed` layout, and Kani picks which thread moves next
+// at every step. That covers every interleaving up to `STEPS` steps, but only under sequential
+// consistency: the fences that make the algorithm correct on weak memory are outside of what
+// Kani can check, and are the job of the `fake-fence-*` experiments instead.
+
+use core::sync::atomic::Ordering;
+
+use crate::layout::{Packed, SlotLayout};
+
+const N: usize = 2;
+
+// How many steps each harness explores. Enough for both threads to get through the critical
+// section and come back for another ticket.
+const STEPS: usize = 24;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Pc {
+    Idle,
+    // `choosing` is set, and the tickets of slots before `next` have been read.
+    Doorway { next: usize, max: u64 },
+    // The ticket has been taken but `choosing` is still set.
+    Ticket,
+    // Waiting for `other` to finish choosing a ticket.
+    WaitChoosing { other: usize },
+    // Waiting until `other`'s ticket doesn't take priority over ours.
+    WaitTicket { other: usize },
+    Critical,
+}
+
+struct Model {
+    slots: Packed<N>,
+    pcs: [Pc; N],
+}
+
+impl Model {
+    fn new() -> Self {
+        Self {
+            slots: Packed::new(),
+            pcs: [Pc::Idle; N],
+        }
+    }
+
+    // A model in which thread 1 has just taken the largest ticket there is, so that the next
+    // ticket taken wraps around.
+    fn near_wraparound() -> Self {
+        let mut model = Self::new();
+        model
+            .slots
+            .set_ticket(1, Packed::<N>::MAX_TICKET, Ordering::SeqCst);
+        model.pcs[1] = model.wait_from(1, 0);
+        model
+    }
+
+    // The state `thread` waits in once it has inspected slots before `other`.
+    fn wait_from(&self, thread: usize, other: usize) -> Pc {
+        match (other..N).find(|&other| other != thread) {
+            Some(other) => Pc::WaitChoosing { other },
+            None => Pc::Critical,
+        }
+    }
+
+    // Whether `thread` can take a step without waiting on another thread.
+    fn can_step(&self, thread: usize) -> bool {
+        let ticket = self.slots.ticket(thread, Ordering::SeqCst);
+        match self.pcs[thread] {
+            Pc::WaitChoosing { other } => !self.slots.is_choosing(other, Ordering::SeqCst),
+            Pc::WaitTicket { other } => {
+                let other_ticket = self.slots.ticket(other, Ordering::SeqCst);
+                other_ticket == 0
+                    || Packed::<N>::precedes(ticket, other_ticket)
+                    || (ticket == other_ticket && thread < other)
+            }
+            _ => true,
+        }
+    }
+
+    // Takes one step of `lock` or `unlock` in `thread`, returning whether it entered the critical
+    // section.
+    fn step(&mut self, thread: usize) -> bool {
+        let slots = &self.slots;
+        self.pcs[thread] = match self.pcs[thread] {
+            Pc::Idle => {
+                slots.set_choosing(thread, true, Ordering::SeqCst);
+                Pc::Doorway { next: 0, max: 0 }
+            }
+            Pc::Doorway { next, max } if next < N => {
+                let ticket = slots.ticket(next, Ordering::SeqCst);
+                let later = ticket != 0 && (max == 0 || Packed::<N>::precedes(max, ticket));
+                Pc::Doorway {
+                    next: next + 1,
+                    max: if later { ticket } else { max },
+                }
+            }
+            Pc::Doorway { max, .. } => {
+                slots.set_ticket(thread, Packed::<N>::next_ticket(max), Ordering::SeqCst);
+                Pc::Ticket
+            }
+            Pc::Ticket => {
+                slots.set_choosing(thread, false, Ordering::SeqCst);
+                self.wait_from(thread, 0)
+            }
+            Pc::WaitChoosing { other } => Pc::WaitTicket { other },
+            Pc::WaitTicket { other } => self.wait_from(thread, other + 1),
+            Pc::Critical => {
+                slots.set_ticket(thread, 0, Ordering::SeqCst);
+                Pc::Idle
+            }
+        };
+        self.pcs[thread] == Pc::Critical
+    }
+}
+
+// No two threads are ever in the critical section at the same time.
+#[kani::proof]
+#[kani::unwind(25)]
+fn mutual_exclusion() {
+    let mut model = Model::new();
+    for _ in 0..STEPS {
+        let thread: usize = kani::any();
+        kani::assume(thread < N && model.can_step(thread));
+        if model.step(thread) {
+            let mut others = (0..N).filter(|&other| other != thread);
+            assert!(others.all(|other| model.pcs[other] != Pc::Critical));
+        }
+    }
+}
+
+// The same, starting just before the tickets wrap around.
+#[kani::proof]
+#[kani::unwind(25)]
+fn mutual_exclusion_across_wraparound() {
+    let mut model = Model::near_wraparound();
+    for _ in 0..STEPS {
+        let thread: usize = kani::any();
+        kani::assume(thread < N && model.can_step(thread));
+        if model.step(thread) {
+            let mut others = (0..N).filter(|&other| other != thread);
+            assert!(others.all(|other| model.pcs[other] != Pc::Critical));
+        }
+    }
+}
+
+// Some thread can always make progress: the threads never end up all waiting on each other.
+#[kani::proof]
+#[kani::unwind(25)]
+fn deadlock_freedom() {
+    let mut model = Model::new();
+    for _ in 0..STEPS {
+        assert!((0..N).any(|thread| model.can_step(thread)));
+        let thread: usize = kani::any();
+        kani::assume(thread < N && model.can_step(thread));
+        model.step(thread);
+    }
+}
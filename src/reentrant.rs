@@ -0,0 +1,130 @@
+use std::{
+    fmt,
+    marker::PhantomData,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+};
+
+use crate::{
+    registry::{self, SlotRegistry},
+    RawBakeryLock,
+};
+
+/// A [`BakeryLock`](crate::BakeryLock) that the thread holding it can lock again, for code that
+/// can't avoid re-entering a critical section it's already in, such as callbacks.
+///
+/// The lock remembers the slot it's held in and how many guards that thread has, and only the
+/// drop of the outermost guard leaves the critical section. Only the first acquisition goes
+/// through the bakery, so nested ones never wait.
+///
+/// Slots are assigned to threads the same way as for `BakeryLock`, the first time each thread
+/// locks it.
+pub struct ReentrantBakeryLock<const N: usize> {
+    raw: RawBakeryLock<N>,
+    registry: Arc<SlotRegistry<N>>,
+    // The slot holding the lock plus one, or 0 when nobody is. Only the holder sets it to its own
+    // slot or clears it, so a thread seeing its own slot there knows it holds the lock, and any
+    // other value means it doesn't, however stale.
+    owner: AtomicUsize,
+    // How many guards the holder has. Only touched by the holder.
+    depth: AtomicUsize,
+}
+
+impl<const N: usize> ReentrantBakeryLock<N> {
+    /// An unlocked lock with every slot free.
+    pub fn new() -> Self {
+        Self {
+            raw: RawBakeryLock::new(),
+            registry: Arc::new(SlotRegistry::new()),
+            owner: AtomicUsize::new(0),
+            depth: AtomicUsize::new(0),
+        }
+    }
+
+    /// Enters the critical section until the returned guard is dropped, straight away if the
+    /// calling thread already holds the lock, and otherwise once it gets through the bakery in the
+    /// thread's slot.
+    pub fn lock(&self) -> ReentrantBakeryGuard<'_, N> {
+        let slot = registry::assigned(&self.registry);
+        if !self.holds(slot) {
+            self.raw.lock(slot);
+            self.owner.store(slot + 1, Ordering::Relaxed);
+        }
+        self.enter(slot)
+    }
+
+    /// Enters the critical section like [`lock`](Self::lock) if that doesn't require waiting for
+    /// another thread (or for a slot to be given back), or returns `None`.
+    pub fn try_lock(&self) -> Option<ReentrantBakeryGuard<'_, N>> {
+        let slot = registry::try_assigned(&self.registry)?;
+        if !self.holds(slot) {
+            if !self.raw.try_lock(slot) {
+                return None;
+            }
+            self.owner.store(slot + 1, Ordering::Relaxed);
+        }
+        Some(self.enter(slot))
+    }
+
+    fn holds(&self, slot: usize) -> bool {
+        self.owner.load(Ordering::Relaxed) == slot + 1
+    }
+
+    fn enter(&self, slot: usize) -> ReentrantBakeryGuard<'_, N> {
+        self.depth.fetch_add(1, Ordering::Relaxed);
+        ReentrantBakeryGuard {
+            lock: self,
+            slot,
+            _not_send: PhantomData,
+        }
+    }
+}
+
+impl<const N: usize> Default for ReentrantBakeryLock<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Proof that a thread is in the critical section of a [`ReentrantBakeryLock`], which it leaves
+/// when the last of its guards is dropped.
+#[must_use = "dropping the guard immediately leaves the critical section, unless it's nested"]
+pub struct ReentrantBakeryGuard<'a, const N: usize> {
+    lock: &'a ReentrantBakeryLock<N>,
+    slot: usize,
+    // The depth only counts the guards of the thread holding the lock, so they have to stay on it.
+    _not_send: PhantomData<*const ()>,
+}
+
+impl<const N: usize> ReentrantBakeryGuard<'_, N> {
+    /// The slot the lock was acquired in.
+    pub fn slot(&self) -> usize {
+        self.slot
+    }
+
+    /// How many guards the thread holds, including this one.
+    pub fn depth(&self) -> usize {
+        self.lock.depth.load(Ordering::Relaxed)
+    }
+}
+
+impl<const N: usize> Drop for ReentrantBakeryGuard<'_, N> {
+    fn drop(&mut self) {
+        if self.lock.depth.fetch_sub(1, Ordering::Relaxed) == 1 {
+            // Cleared before unlocking, so the next holder's store comes after it.
+            self.lock.owner.store(0, Ordering::Relaxed);
+            self.lock.raw.unlock(self.slot);
+        }
+    }
+}
+
+impl<const N: usize> fmt::Debug for ReentrantBakeryGuard<'_, N> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ReentrantBakeryGuard")
+            .field("slot", &self.slot)
+            .field("depth", &self.depth())
+            .finish()
+    }
+}
@@ -0,0 +1,117 @@
+use std::{
+    cell::RefCell,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    thread,
+    time::Instant,
+};
+
+// Hands out the slots of a lock to threads that ask for one, so that they never have to agree on
+// indices among themselves.
+pub struct SlotRegistry<const N: usize> {
+    claimed: [AtomicBool; N],
+}
+
+impl<const N: usize> SlotRegistry<N> {
+    pub fn new() -> Self {
+        Self {
+            claimed: std::array::from_fn(|_| AtomicBool::new(false)),
+        }
+    }
+
+    // Claims the lowest free slot, or returns `None` if all `N` are taken.
+    pub fn claim(&self) -> Option<usize> {
+        // Acquire so that the previous holder's last use of the slot happens before ours: nobody
+        // else ever writes a slot's state, so we need to see its final values.
+        (0..N).find(|&slot| {
+            self.claimed[slot]
+                .compare_exchange(false, true, Ordering::Acquire, Ordering::Relaxed)
+                .is_ok()
+        })
+    }
+
+    pub fn release(&self, slot: usize) {
+        self.claimed[slot].store(false, Ordering::Release);
+    }
+}
+
+// Lets a thread's assignments to registries of any size live in one list.
+trait Release {
+    fn release(&self, slot: usize);
+}
+
+impl<const N: usize> Release for SlotRegistry<N> {
+    fn release(&self, slot: usize) {
+        SlotRegistry::release(self, slot);
+    }
+}
+
+// The slots the current thread has been assigned automatically, released when it exits. Each
+// entry keeps its registry alive, so that a lock dropped before the thread exits is harmless and
+// no other registry can take its address in the meantime.
+#[derive(Default)]
+struct Assignments(RefCell<Vec<(Arc<dyn Release>, usize)>>);
+
+impl Drop for Assignments {
+    fn drop(&mut self) {
+        for (registry, slot) in self.0.get_mut().drain(..) {
+            registry.release(slot);
+        }
+    }
+}
+
+thread_local! {
+    static ASSIGNED: Assignments = Assignments::default();
+}
+
+// The slot of `registry` assigned to the current thread, claiming one on first use. If all `N`
+// are taken, waits for another thread to exit (or drop its handle) and free one up: threads that
+// have just finished may not have run their thread-local destructors yet.
+pub fn assigned<const N: usize>(registry: &Arc<SlotRegistry<N>>) -> usize {
+    assign(registry, || false).unwrap()
+}
+
+// Like `assigned`, but returns `None` instead of waiting.
+pub fn try_assigned<const N: usize>(registry: &Arc<SlotRegistry<N>>) -> Option<usize> {
+    assign(registry, || true)
+}
+
+// Like `assigned`, but returns `None` if no slot is free by `deadline`.
+pub fn assigned_until<const N: usize>(
+    registry: &Arc<SlotRegistry<N>>,
+    deadline: Instant,
+) -> Option<usize> {
+    assign(registry, || Instant::now() >= deadline)
+}
+
+fn assign<const N: usize>(
+    registry: &Arc<SlotRegistry<N>>,
+    give_up: impl Fn() -> bool,
+) -> Option<usize> {
+    let key = Arc::as_ptr(registry) as *const ();
+    ASSIGNED.with(|assigned| {
+        let mut assigned = assigned.0.borrow_mut();
+        // Forget registries whose lock is gone, so that threads using many short-lived locks
+        // don't accumulate them.
+        assigned.retain(|(other, _)| Arc::strong_count(other) > 1);
+
+        let existing = assigned
+            .iter()
+            .find(|(other, _)| Arc::as_ptr(other) as *const () == key);
+        if let Some(&(_, slot)) = existing {
+            return Some(slot);
+        }
+
+        let slot = loop {
+            match registry.claim() {
+                Some(slot) => break slot,
+                None if give_up() => return None,
+                None => thread::yield_now(),
+            }
+        };
+        assigned.push((registry.clone(), slot));
+        Some(slot)
+    })
+}
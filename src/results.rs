@@ -0,0 +1,342 @@
+use std::{
+    collections::BTreeMap,
+    env, fs,
+    fs::OpenOptions,
+    io::{self, Write},
+    process,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use bakery::{ordering::OrderingConfig, spin};
+
+use crate::{placement, topology::Topology};
+
+// The outcome of a single counter run, stored as one JSON object per line.
+pub struct Record {
+    timestamp: u64,
+    host: String,
+    os: String,
+    arch: String,
+    sockets: usize,
+    cores: usize,
+    cpus: usize,
+    cpu_model: String,
+    target_features: String,
+    fences: String,
+    spin: String,
+    placement: String,
+    algorithm: String,
+    threads: usize,
+    iterations: usize,
+    count: usize,
+    elapsed_ms: f64,
+}
+
+// Describes which of the two SC fences in `lock` are real.
+pub fn fence_config() -> String {
+    OrderingConfig::build().fences().to_owned()
+}
+
+// The marketing name of the first CPU, if the OS reports one.
+pub fn cpu_model() -> String {
+    let cpuinfo = fs::read_to_string("/proc/cpuinfo").unwrap_or_default();
+    cpuinfo
+        .lines()
+        .filter_map(|line| line.split_once(':'))
+        .find(|(key, _)| matches!(key.trim(), "model name" | "Model" | "cpu model"))
+        .map(|(_, value)| value.trim().to_owned())
+        .unwrap_or_else(|| "unknown".to_owned())
+}
+
+// The target features this binary was compiled with that affect how atomics and fences are
+// lowered, or how fast the surrounding code runs.
+pub fn target_features() -> String {
+    let features = [
+        ("sse2", cfg!(target_feature = "sse2")),
+        ("avx", cfg!(target_feature = "avx")),
+        ("avx2", cfg!(target_feature = "avx2")),
+        ("avx512f", cfg!(target_feature = "avx512f")),
+        ("cmpxchg16b", cfg!(target_feature = "cmpxchg16b")),
+        ("neon", cfg!(target_feature = "neon")),
+        ("lse", cfg!(target_feature = "lse")),
+        ("rcpc", cfg!(target_feature = "rcpc")),
+    ];
+    features
+        .into_iter()
+        .filter_map(|(name, enabled)| enabled.then_some(name))
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+pub fn hostname() -> String {
+    fs::read_to_string("/etc/hostname")
+        .ok()
+        .map(|name| name.trim().to_owned())
+        .filter(|name| !name.is_empty())
+        .or_else(|| env::var("HOSTNAME").ok())
+        .or_else(|| env::var("COMPUTERNAME").ok())
+        .unwrap_or_else(|| "unknown".to_owned())
+}
+
+impl Record {
+    pub fn new(
+        topology: Topology,
+        algorithm: &str,
+        threads: usize,
+        iterations: usize,
+        count: usize,
+        elapsed: Duration,
+    ) -> Self {
+        Self {
+            timestamp: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap()
+                .as_secs(),
+            host: hostname(),
+            os: env::consts::OS.to_owned(),
+            arch: env::consts::ARCH.to_owned(),
+            sockets: topology.sockets,
+            cores: topology.physical_cores,
+            cpus: topology.logical_cpus,
+            cpu_model: cpu_model(),
+            target_features: target_features(),
+            fences: fence_config(),
+            spin: spin::hint().name().to_owned(),
+            placement: placement::current()
+                .map_or("none", |placement| placement.name())
+                .to_owned(),
+            algorithm: algorithm.to_owned(),
+            threads,
+            iterations,
+            count,
+            elapsed_ms: elapsed.as_secs_f64() * 1000.0,
+        }
+    }
+
+    fn lost_updates(&self) -> usize {
+        (self.threads * self.iterations).saturating_sub(self.count)
+    }
+
+    fn to_json(&self) -> String {
+        format!(
+            "{{\"timestamp\":{},\"host\":{},\"os\":{},\"arch\":{},\"sockets\":{},\"cores\":{},\
+             \"cpus\":{},\"cpu_model\":{},\"target_features\":{},\"fences\":{},\"spin\":{},\
+             \"placement\":{},\"algorithm\":{},\"threads\":{},\"iterations\":{},\"count\":{},\"elapsed_ms\":{:.3}}}",
+            self.timestamp,
+            quote(&self.host),
+            quote(&self.os),
+            quote(&self.arch),
+            self.sockets,
+            self.cores,
+            self.cpus,
+            quote(&self.cpu_model),
+            quote(&self.target_features),
+            quote(&self.fences),
+            quote(&self.spin),
+            quote(&self.placement),
+            quote(&self.algorithm),
+            self.threads,
+            self.iterations,
+            self.count,
+            self.elapsed_ms,
+        )
+    }
+
+    fn from_json(line: &str) -> Option<Self> {
+        let fields = parse_object(line)?;
+        let field = |key: &str| fields.get(key).map(String::as_str);
+
+        Some(Self {
+            timestamp: field("timestamp")?.parse().ok()?,
+            host: field("host")?.to_owned(),
+            os: field("os")?.to_owned(),
+            arch: field("arch")?.to_owned(),
+            // Records written before topology detection only know the CPU count.
+            sockets: field("sockets").map_or(Some(0), |value| value.parse().ok())?,
+            cores: field("cores").map_or(Some(0), |value| value.parse().ok())?,
+            cpus: field("cpus")?.parse().ok()?,
+            // Neither do they know anything about the environment beyond that.
+            cpu_model: field("cpu_model").unwrap_or("unknown").to_owned(),
+            target_features: field("target_features").unwrap_or("").to_owned(),
+            fences: field("fences")?.to_owned(),
+            spin: field("spin").unwrap_or("std").to_owned(),
+            placement: field("placement").unwrap_or("none").to_owned(),
+            // Nor did they run anything other than the bakery lock.
+            algorithm: field("algorithm").unwrap_or("bakery").to_owned(),
+            threads: field("threads")?.parse().ok()?,
+            iterations: field("iterations")?.parse().ok()?,
+            count: field("count")?.parse().ok()?,
+            elapsed_ms: field("elapsed_ms")?.parse().ok()?,
+        })
+    }
+}
+
+pub fn quote(value: &str) -> String {
+    let mut quoted = String::with_capacity(value.len() + 2);
+    quoted.push('"');
+    for c in value.chars() {
+        match c {
+            '"' | '\\' => {
+                quoted.push('\\');
+                quoted.push(c);
+            }
+            c if c.is_control() => quoted.push_str(&format!("\\u{:04x}", c as u32)),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+// Parses the flat objects written by `Record::to_json`: string and number values only, no
+// nesting. Strings are returned unescaped and numbers verbatim.
+fn parse_object(line: &str) -> Option<BTreeMap<String, String>> {
+    let mut chars = line.trim().chars().peekable();
+    let mut fields = BTreeMap::new();
+
+    fn parse_string(chars: &mut impl Iterator<Item = char>) -> Option<String> {
+        let mut value = String::new();
+        loop {
+            match chars.next()? {
+                '"' => return Some(value),
+                '\\' => match chars.next()? {
+                    'u' => {
+                        let code: String = chars.by_ref().take(4).collect();
+                        value.push(char::from_u32(u32::from_str_radix(&code, 16).ok()?)?);
+                    }
+                    c => value.push(c),
+                },
+                c => value.push(c),
+            }
+        }
+    }
+
+    if chars.next()? != '{' {
+        return None;
+    }
+
+    loop {
+        match chars.next()? {
+            '}' => return Some(fields),
+            ',' | ' ' => continue,
+            '"' => {}
+            _ => return None,
+        }
+
+        let key = parse_string(&mut chars)?;
+        if chars.next()? != ':' {
+            return None;
+        }
+
+        let value = if chars.peek() == Some(&'"') {
+            chars.next();
+            parse_string(&mut chars)?
+        } else {
+            let mut value = String::new();
+            while let Some(&c) = chars.peek() {
+                if c == ',' || c == '}' {
+                    break;
+                }
+                value.push(c);
+                chars.next();
+            }
+            value
+        };
+
+        fields.insert(key, value);
+    }
+}
+
+pub fn append(path: &str, record: &Record) -> io::Result<()> {
+    let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+    writeln!(file, "{}", record.to_json())
+}
+
+#[derive(Default)]
+struct Summary {
+    runs: usize,
+    failed_runs: usize,
+    lost_updates: usize,
+    total_elapsed_ms: f64,
+}
+
+// Summarizes every run recorded in the database, grouped by host and configuration.
+pub fn report(args: &[String]) {
+    let [path] = args else {
+        eprintln!("usage: bakery report <results.jsonl>");
+        process::exit(2);
+    };
+
+    let contents = fs::read_to_string(path).unwrap_or_else(|err| {
+        eprintln!("failed to read {path}: {err}");
+        process::exit(1);
+    });
+
+    let mut summaries: BTreeMap<_, Summary> = BTreeMap::new();
+    for (lineno, line) in contents.lines().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let Some(record) = Record::from_json(line) else {
+            eprintln!("{path}:{}: skipping malformed record", lineno + 1);
+            continue;
+        };
+
+        let topology = if record.cores == 0 {
+            format!("{} cpus", record.cpus)
+        } else {
+            Topology {
+                sockets: record.sockets,
+                physical_cores: record.cores,
+                logical_cpus: record.cpus,
+            }
+            .to_string()
+        };
+        // Only mention the algorithm, spin hint and placement when they were changed from the
+        // defaults.
+        let mut config = record.fences.clone();
+        if record.algorithm != "bakery" {
+            config += &format!(", {}", record.algorithm);
+        }
+        if record.spin != "std" {
+            config += &format!(", {}", record.spin);
+        }
+        if record.placement != "none" {
+            config += &format!(", {}", record.placement);
+        }
+
+        let key = (
+            format!(
+                "{} ({}/{}, {topology})",
+                record.host, record.os, record.arch
+            ),
+            config,
+            record.threads,
+        );
+        let summary = summaries.entry(key).or_default();
+        summary.runs += 1;
+        if record.lost_updates() > 0 {
+            summary.failed_runs += 1;
+        }
+        summary.lost_updates += record.lost_updates();
+        summary.total_elapsed_ms += record.elapsed_ms;
+    }
+
+    println!(
+        "{:<40} {:<28} {:>7} {:>6} {:>8} {:>12} {:>12}",
+        "host", "config", "threads", "runs", "failed", "lost", "mean ms"
+    );
+    for ((host, config, threads), summary) in &summaries {
+        println!(
+            "{:<40} {:<28} {:>7} {:>6} {:>8} {:>12} {:>12.1}",
+            host,
+            config,
+            threads,
+            summary.runs,
+            summary.failed_runs,
+            summary.lost_updates,
+            summary.total_elapsed_ms / summary.runs as f64,
+        );
+    }
+}
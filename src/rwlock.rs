@@ -0,0 +1,408 @@
+use std::{
+    cell::UnsafeCell,
+    fmt,
+    mem::ManuallyDrop,
+    ops::{Deref, DerefMut},
+    sync::{
+        atomic::{self, AtomicBool, AtomicU32, AtomicU8, Ordering},
+        Arc,
+    },
+};
+
+use crate::{
+    algorithm::{Bakery, Blocked, Next},
+    backoff::{Backoff, NoBackoff},
+    registry::{self, SlotRegistry},
+    spin,
+};
+
+// What each slot's ticket was taken for.
+const READ: u8 = 0;
+const UPGRADEABLE: u8 = 1;
+const WRITE: u8 = 2;
+
+/// A value protected by a reader-writer bakery lock for up to `N` threads.
+///
+/// Readers and writers take tickets from the same bakery and are let in in ticket order, except
+/// that a reader only waits for the writers ahead of it: a run of consecutive readers shares the
+/// lock, while a writer waits for everyone ahead of it and keeps out everyone behind it. Neither
+/// side can starve the other, since anyone arriving later takes a later ticket.
+///
+/// An upgradeable reader shares the lock with plain readers but not with writers or other
+/// upgradeable readers, and can become a writer without giving up its ticket. A writer can likewise
+/// become a reader, letting in the readers behind it.
+///
+/// Slots are assigned to threads the same way as for [`BakeryMutex`](crate::BakeryMutex), the
+/// first time each thread locks it.
+pub struct BakeryRwLock<T, const N: usize> {
+    choosing: [AtomicBool; N],
+    tickets: [AtomicU32; N],
+    // What each slot's current ticket is for. Set in the doorway, and only changed afterwards by
+    // upgrading or downgrading a guard.
+    kinds: [AtomicU8; N],
+    // Whether each slot is a plain reader in the critical section, or about to be. This is what an
+    // upgrading reader waits on for the readers behind it that already got in.
+    reading: [AtomicBool; N],
+    registry: Arc<SlotRegistry<N>>,
+    data: UnsafeCell<T>,
+}
+
+// SAFETY: the lock hands out shared access to `data` to readers and exclusive access to one writer
+// at a time.
+unsafe impl<T: Send + Sync, const N: usize> Sync for BakeryRwLock<T, N> {}
+
+impl<T, const N: usize> BakeryRwLock<T, N> {
+    /// Puts `data` behind an unlocked lock.
+    pub fn new(data: T) -> Self {
+        assert!(
+            (N as u64) < Self::MAX_TICKET / 4,
+            "too many slots to tell tickets apart across a wraparound"
+        );
+
+        Self {
+            choosing: std::array::from_fn(|_| AtomicBool::new(false)),
+            tickets: std::array::from_fn(|_| AtomicU32::new(0)),
+            kinds: std::array::from_fn(|_| AtomicU8::new(READ)),
+            reading: std::array::from_fn(|_| AtomicBool::new(false)),
+            registry: Arc::new(SlotRegistry::new()),
+            data: UnsafeCell::new(data),
+        }
+    }
+
+    /// Waits for the writers ahead of the calling thread and gives shared access to the value until
+    /// the returned guard is dropped. Locking it again from the same thread before then panics.
+    pub fn read(&self) -> BakeryReadGuard<'_, T, N> {
+        let slot = registry::assigned(&self.registry);
+        self.acquire(slot, READ);
+        BakeryReadGuard { lock: self, slot }
+    }
+
+    /// Waits for the writers and upgradeable readers ahead of the calling thread and gives shared
+    /// access to the value until the returned guard is dropped, or until it is upgraded to
+    /// exclusive access. Locking it again from the same thread before then panics.
+    pub fn upgradeable_read(&self) -> BakeryUpgradeableGuard<'_, T, N> {
+        let slot = registry::assigned(&self.registry);
+        self.acquire(slot, UPGRADEABLE);
+        BakeryUpgradeableGuard { lock: self, slot }
+    }
+
+    /// Waits for everyone ahead of the calling thread and gives exclusive access to the value
+    /// until the returned guard is dropped. Locking it again from the same thread before then
+    /// panics.
+    pub fn write(&self) -> BakeryWriteGuard<'_, T, N> {
+        let slot = registry::assigned(&self.registry);
+        self.acquire(slot, WRITE);
+        BakeryWriteGuard { lock: self, slot }
+    }
+
+    /// Gives shared access like [`read`](Self::read) if that doesn't require waiting for a writer
+    /// (or for a slot to be given back), or returns `None`.
+    pub fn try_read(&self) -> Option<BakeryReadGuard<'_, T, N>> {
+        let slot = registry::try_assigned(&self.registry)?;
+        self.try_acquire(slot, READ)
+            .then(|| BakeryReadGuard { lock: self, slot })
+    }
+
+    /// Gives shared access like [`upgradeable_read`](Self::upgradeable_read) if that doesn't
+    /// require waiting (or for a slot to be given back), or returns `None`.
+    pub fn try_upgradeable_read(&self) -> Option<BakeryUpgradeableGuard<'_, T, N>> {
+        let slot = registry::try_assigned(&self.registry)?;
+        self.try_acquire(slot, UPGRADEABLE)
+            .then(|| BakeryUpgradeableGuard { lock: self, slot })
+    }
+
+    /// Gives exclusive access like [`write`](Self::write) if that doesn't require waiting for
+    /// anyone (or for a slot to be given back), or returns `None`.
+    pub fn try_write(&self) -> Option<BakeryWriteGuard<'_, T, N>> {
+        let slot = registry::try_assigned(&self.registry)?;
+        self.try_acquire(slot, WRITE)
+            .then(|| BakeryWriteGuard { lock: self, slot })
+    }
+
+    /// The value, without locking: the exclusive borrow already rules out any other access.
+    pub fn get_mut(&mut self) -> &mut T {
+        self.data.get_mut()
+    }
+
+    pub fn into_inner(self) -> T {
+        self.data.into_inner()
+    }
+
+    // Records what `slot`'s next ticket is for. This happens before the doorway, so the kind is
+    // published along with the ticket, and nobody looks at it while there's no ticket to go with
+    // it.
+    fn set_kind(&self, slot: usize, kind: u8) {
+        assert_eq!(
+            self.ticket(slot, Ordering::Relaxed),
+            0,
+            "the calling thread already holds the lock"
+        );
+
+        self.kinds[slot].store(kind, Ordering::Relaxed);
+    }
+
+    // Whether `other` holds a ticket ahead of `slot`'s.
+    fn is_ahead(&self, other: usize, slot: usize, ticket: u64) -> bool {
+        let other_ticket = self.ticket(other, Ordering::Relaxed);
+        !Self::goes_before((ticket, slot), (other_ticket, other))
+    }
+
+    // Whether `other`, holding a ticket ahead of a ticket of `kind`, has to leave before it can
+    // enter. Only called after seeing `other`'s `choosing` clear and an acquire fence, so that its
+    // kind is current.
+    fn conflicts(&self, other: usize, kind: u8) -> bool {
+        let other_kind = self.kinds[other].load(Ordering::Relaxed);
+        kind == WRITE || other_kind == WRITE || (kind == UPGRADEABLE && other_kind == UPGRADEABLE)
+    }
+
+    fn acquire(&self, slot: usize, kind: u8) {
+        self.set_kind(slot, kind);
+        let ticket = self.doorway(slot);
+
+        // The final acquire fence in `wait_turn` synchronizes-with the release stores in `release`
+        // by the writers that went before us, by the readers that did too if we're a writer, and
+        // with a downgrade that let us in.
+        loop {
+            self.wait_turn(slot, ticket, |other, blocked, backoff: &mut NoBackoff| {
+                if matches!(blocked, Blocked::Ticket(_)) && !self.conflicts(other, kind) {
+                    return Next::Pass;
+                }
+                backoff.snooze();
+                Next::Wait
+            });
+
+            if kind != READ || self.enter_reading(slot, ticket) {
+                break;
+            }
+        }
+    }
+
+    fn try_acquire(&self, slot: usize, kind: u8) -> bool {
+        self.set_kind(slot, kind);
+        let Some(ticket) = self.try_doorway(slot) else {
+            return false;
+        };
+
+        let entered = self.try_turn(slot, ticket, |other, blocked| match blocked {
+            Blocked::Ticket(_) if !self.conflicts(other, kind) => Next::Pass,
+            _ => Next::GiveUp,
+        });
+        if !entered || (kind == READ && !self.enter_reading(slot, ticket)) {
+            self.release(slot);
+            return false;
+        }
+        true
+    }
+
+    // Marks the plain reader in `slot` as reading, once it has waited for everyone ahead of it,
+    // and checks that no upgradeable reader ahead of it has started upgrading in the meantime.
+    // Returns false, having taken the mark back, if one has.
+    //
+    // Together with the SC fence in `upgrade`, this is the same store buffering pattern as in the
+    // doorway: either we see the upgrader's kind change to `WRITE` and back off, or the upgrader
+    // sees our mark and waits for us to leave.
+    fn enter_reading(&self, slot: usize, ticket: u64) -> bool {
+        self.reading[slot].store(true, Ordering::Relaxed);
+        atomic::fence(Ordering::SeqCst);
+
+        let upgrading = (0..N).any(|other| {
+            other != slot
+                && self.kinds[other].load(Ordering::Relaxed) == WRITE
+                && self.is_ahead(other, slot, ticket)
+        });
+        if upgrading {
+            self.reading[slot].store(false, Ordering::Relaxed);
+        }
+        !upgrading
+    }
+
+    // Turns the upgradeable reader in `slot` into a writer, keeping its ticket. Everyone ahead of
+    // it is a plain reader, since it waited for everyone else, and so are the threads behind it
+    // that it let in.
+    fn upgrade(&self, slot: usize) {
+        let ticket = self.ticket(slot, Ordering::Relaxed);
+        self.kinds[slot].store(WRITE, Ordering::Relaxed);
+        // See `enter_reading`.
+        atomic::fence(Ordering::SeqCst);
+
+        for other in (0..N).filter(|&other| other != slot) {
+            while self.is_ahead(other, slot, ticket) || self.reading[other].load(Ordering::Relaxed)
+            {
+                spin::relax();
+            }
+        }
+
+        // Synchronizes-with the release stores in `release` by the readers we waited for.
+        atomic::fence(Ordering::Acquire);
+    }
+
+    // Lets the readers behind the writer or upgradeable reader in `slot` in, keeping its ticket.
+    fn downgrade(&self, slot: usize) {
+        // Synchronizes-with the acquire fence at the end of `acquire` in the readers we let in.
+        self.kinds[slot].store(READ, Ordering::Release);
+    }
+
+    fn release(&self, slot: usize) {
+        self.reading[slot].store(false, Ordering::Release);
+        self.tickets[slot].store(0, Ordering::Release);
+    }
+}
+
+impl<T, const N: usize> Bakery for BakeryRwLock<T, N> {
+    const MAX_TICKET: u64 = u32::MAX as u64;
+
+    fn is_choosing(&self, slot: usize, order: Ordering) -> bool {
+        self.choosing[slot].load(order)
+    }
+
+    fn set_choosing(&self, slot: usize, choosing: bool, order: Ordering) {
+        self.choosing[slot].store(choosing, order);
+    }
+
+    fn ticket(&self, slot: usize, order: Ordering) -> u64 {
+        self.tickets[slot].load(order).into()
+    }
+
+    fn set_ticket(&self, slot: usize, ticket: u64, order: Ordering) {
+        self.tickets[slot].store(ticket as u32, order);
+    }
+
+    fn active_slots(&self) -> impl Iterator<Item = usize> + '_ {
+        0..N
+    }
+}
+
+impl<T: Default, const N: usize> Default for BakeryRwLock<T, N> {
+    fn default() -> Self {
+        Self::new(T::default())
+    }
+}
+
+/// Shared access to the value in a [`BakeryRwLock`], which is given up when the guard is dropped.
+#[must_use = "dropping the guard immediately unlocks the lock"]
+pub struct BakeryReadGuard<'a, T, const N: usize> {
+    lock: &'a BakeryRwLock<T, N>,
+    slot: usize,
+}
+
+impl<T, const N: usize> Deref for BakeryReadGuard<'_, T, N> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        // SAFETY: we hold the lock for reading, so there's no writer.
+        unsafe { &*self.lock.data.get() }
+    }
+}
+
+impl<T, const N: usize> Drop for BakeryReadGuard<'_, T, N> {
+    fn drop(&mut self) {
+        self.lock.release(self.slot);
+    }
+}
+
+impl<T: fmt::Debug, const N: usize> fmt::Debug for BakeryReadGuard<'_, T, N> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(&**self, f)
+    }
+}
+
+/// Shared access to the value in a [`BakeryRwLock`] that can be upgraded to exclusive access
+/// without letting anyone else in first. Given up when the guard is dropped.
+#[must_use = "dropping the guard immediately unlocks the lock"]
+pub struct BakeryUpgradeableGuard<'a, T, const N: usize> {
+    lock: &'a BakeryRwLock<T, N>,
+    slot: usize,
+}
+
+impl<'a, T, const N: usize> BakeryUpgradeableGuard<'a, T, N> {
+    /// Waits for the readers currently sharing the lock to leave and gives exclusive access. Since
+    /// the guard keeps its ticket, nobody else can get in first.
+    pub fn upgrade(self) -> BakeryWriteGuard<'a, T, N> {
+        let guard = ManuallyDrop::new(self);
+        guard.lock.upgrade(guard.slot);
+        BakeryWriteGuard {
+            lock: guard.lock,
+            slot: guard.slot,
+        }
+    }
+
+    /// Gives up the right to upgrade, letting in the upgradeable readers behind this one.
+    pub fn downgrade(self) -> BakeryReadGuard<'a, T, N> {
+        let guard = ManuallyDrop::new(self);
+        guard.lock.downgrade(guard.slot);
+        BakeryReadGuard {
+            lock: guard.lock,
+            slot: guard.slot,
+        }
+    }
+}
+
+impl<T, const N: usize> Deref for BakeryUpgradeableGuard<'_, T, N> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        // SAFETY: we hold the lock for reading, so there's no writer.
+        unsafe { &*self.lock.data.get() }
+    }
+}
+
+impl<T, const N: usize> Drop for BakeryUpgradeableGuard<'_, T, N> {
+    fn drop(&mut self) {
+        self.lock.release(self.slot);
+    }
+}
+
+impl<T: fmt::Debug, const N: usize> fmt::Debug for BakeryUpgradeableGuard<'_, T, N> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(&**self, f)
+    }
+}
+
+/// Exclusive access to the value in a [`BakeryRwLock`], which is given up when the guard is
+/// dropped.
+#[must_use = "dropping the guard immediately unlocks the lock"]
+pub struct BakeryWriteGuard<'a, T, const N: usize> {
+    lock: &'a BakeryRwLock<T, N>,
+    slot: usize,
+}
+
+impl<T, const N: usize> Deref for BakeryWriteGuard<'_, T, N> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        // SAFETY: we hold the lock for writing.
+        unsafe { &*self.lock.data.get() }
+    }
+}
+
+impl<'a, T, const N: usize> BakeryWriteGuard<'a, T, N> {
+    /// Gives up exclusive access but keeps shared access, letting in the readers behind this
+    /// writer without letting in any writer first.
+    pub fn downgrade(self) -> BakeryReadGuard<'a, T, N> {
+        let guard = ManuallyDrop::new(self);
+        guard.lock.downgrade(guard.slot);
+        BakeryReadGuard {
+            lock: guard.lock,
+            slot: guard.slot,
+        }
+    }
+}
+
+impl<T, const N: usize> DerefMut for BakeryWriteGuard<'_, T, N> {
+    fn deref_mut(&mut self) -> &mut T {
+        // SAFETY: we hold the lock for writing, and the guard is borrowed mutably.
+        unsafe { &mut *self.lock.data.get() }
+    }
+}
+
+impl<T, const N: usize> Drop for BakeryWriteGuard<'_, T, N> {
+    fn drop(&mut self) {
+        self.lock.release(self.slot);
+    }
+}
+
+impl<T: fmt::Debug, const N: usize> fmt::Debug for BakeryWriteGuard<'_, T, N> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(&**self, f)
+    }
+}
@@ -0,0 +1,147 @@
+use std::{
+    process,
+    sync::atomic::{AtomicU64, Ordering},
+    thread,
+    time::{Duration, Instant},
+};
+
+use bakery::{
+    layout::{Packed, Padded, SlotLayout, Tracked},
+    BakeryMutex, Event, Observer, RawBakeryLock,
+};
+
+use crate::{
+    clock::{Clock, ClockSource},
+    workers,
+};
+
+// Measures how long each thread spends in the doorway, which is where the `O(N)` ticket scan
+// happens.
+struct DoorwayTimer<const N: usize> {
+    clock: Clock,
+    // When each thread last entered the doorway.
+    entered: [AtomicU64; N],
+    total: [AtomicU64; N],
+}
+
+impl<const N: usize> Observer for DoorwayTimer<N> {
+    fn on_event(&self, thread: usize, event: Event) {
+        // Only `thread` ever touches its own entries.
+        match event {
+            Event::Doorway => self.entered[thread].store(self.clock.now(), Ordering::Relaxed),
+            Event::Ticket(_) => {
+                let entered = self.entered[thread].swap(0, Ordering::Relaxed);
+                let elapsed = self.clock.now().saturating_sub(entered);
+                self.total[thread].fetch_add(elapsed, Ordering::Relaxed);
+            }
+            _ => {}
+        }
+    }
+}
+
+struct Measurement {
+    per_acquisition: Duration,
+    doorway: Duration,
+}
+
+// Has `N` threads count to `N * iterations` on a lock with `N` slots laid out as `S`, panicking
+// on any lost update.
+fn measure<const N: usize, S: SlotLayout<N> + Sync>(
+    clock: Clock,
+    iterations: usize,
+) -> Measurement {
+    let mut counter = BakeryMutex::from_raw(
+        RawBakeryLock::from_parts(
+            DoorwayTimer::<N> {
+                clock,
+                entered: std::array::from_fn(|_| AtomicU64::new(0)),
+                total: std::array::from_fn(|_| AtomicU64::new(0)),
+            },
+            S::new(),
+        ),
+        0,
+    );
+
+    let start = Instant::now();
+    thread::scope(|scope| {
+        for thread_id in 0..N {
+            let counter = &counter;
+            workers::spawn(scope, "scale", thread_id, move |_| {
+                let mut slot = counter.register().expect("more workers than slots");
+                for _ in 0..iterations {
+                    *slot.lock() += 1;
+                }
+            });
+        }
+    });
+    let elapsed = start.elapsed();
+
+    let acquisitions = N * iterations;
+    assert_eq!(
+        *counter.get_mut(),
+        acquisitions,
+        "lost updates with {N} threads"
+    );
+
+    let doorway_total: u64 = counter
+        .observer()
+        .total
+        .iter()
+        .map(|total| total.load(Ordering::Relaxed))
+        .sum();
+
+    Measurement {
+        per_acquisition: elapsed / acquisitions as u32,
+        doorway: Duration::from_nanos(doorway_total / acquisitions as u64),
+    }
+}
+
+fn report<const N: usize>(clock: Clock, iterations: usize) {
+    let results = [
+        ("packed", measure::<N, Packed<N>>(clock, iterations)),
+        ("padded", measure::<N, Padded<N>>(clock, iterations)),
+        (
+            "tracked padded",
+            measure::<N, Tracked<N, Padded<N>>>(clock, iterations),
+        ),
+    ];
+
+    for (layout, measurement) in results {
+        println!(
+            "{N:>7} {layout:<16} {:>12.2?} {:>12.2?}",
+            measurement.per_acquisition, measurement.doorway
+        );
+    }
+}
+
+fn usage() -> ! {
+    eprintln!("usage: bakery scale [--iterations <n>]");
+    process::exit(2);
+}
+
+// Runs the counter with up to 256 threads (one per slot) on each layout, checking the final count
+// and reporting how the cost of the doorway grows with the number of slots.
+pub fn run(args: &[String]) {
+    let iterations = match args {
+        [] => 1000,
+        [flag, value] if flag == "--iterations" => value.parse().unwrap_or_else(|_| usage()),
+        _ => usage(),
+    };
+
+    let source = if ClockSource::Counter.is_available() {
+        ClockSource::Counter
+    } else {
+        ClockSource::Instant
+    };
+    let clock = Clock::new(source);
+
+    println!("{iterations} iterations per thread, clock: {clock}");
+    println!(
+        "{:>7} {:<16} {:>12} {:>12}",
+        "threads", "layout", "per acq", "doorway"
+    );
+    report::<4>(clock, iterations);
+    report::<16>(clock, iterations);
+    report::<64>(clock, iterations);
+    report::<256>(clock, iterations);
+}
@@ -0,0 +1,245 @@
+use std::{
+    fs, process,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Mutex,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+use bakery::{Event, Observer, RawBakeryLock};
+
+use crate::{teach, workers};
+
+const MAX_THREADS: usize = 8;
+
+// The points in the algorithm at which a scenario can inject delays, named after the events the
+// lock reports there.
+const LABELS: [&str; 9] = [
+    "doorway",
+    "wrapped",
+    "ticket",
+    "wait-choosing",
+    "wait-ticket",
+    "passed",
+    "gave-up",
+    "acquired",
+    "released",
+];
+
+fn label(event: Event) -> &'static str {
+    match event {
+        Event::Doorway => "doorway",
+        Event::TicketWrapped => "wrapped",
+        Event::Ticket(_) => "ticket",
+        Event::WaitChoosing { .. } => "wait-choosing",
+        Event::WaitTicket { .. } => "wait-ticket",
+        Event::Passed { .. } => "passed",
+        Event::GaveUp => "gave-up",
+        Event::Acquired => "acquired",
+        Event::Released => "released",
+    }
+}
+
+#[derive(Clone, Copy)]
+enum Step {
+    Lock,
+    Unlock,
+    Sleep(Duration),
+}
+
+struct Delay {
+    thread: usize,
+    label: &'static str,
+    duration: Duration,
+}
+
+struct Scenario {
+    steps: Vec<Vec<Step>>,
+    delays: Vec<Delay>,
+}
+
+// Parses a scenario, one directive per line (`#` starts a comment):
+//
+//   <thread> lock
+//   <thread> unlock
+//   <thread> sleep <ms>
+//   <thread> at <label> delay <ms>
+//
+// The steps of each thread run in order, while `at` directives pause the thread every time it
+// reaches the labeled point.
+fn parse(contents: &str) -> Result<Scenario, String> {
+    let mut steps: Vec<Vec<Step>> = Vec::new();
+    let mut delays = Vec::new();
+
+    for (number, line) in contents.lines().enumerate() {
+        let line = line.split('#').next().unwrap().trim();
+        if line.is_empty() {
+            continue;
+        }
+
+        let error = |message: &str| format!("line {}: {message}", number + 1);
+        let millis = |value: &str| {
+            value
+                .parse()
+                .map(Duration::from_millis)
+                .map_err(|_| error("malformed duration"))
+        };
+
+        let words: Vec<_> = line.split_whitespace().collect();
+        let thread: usize = words[0].parse().map_err(|_| error("malformed thread"))?;
+        if thread >= MAX_THREADS {
+            return Err(error(&format!(
+                "at most {MAX_THREADS} threads are supported"
+            )));
+        }
+        if steps.len() <= thread {
+            steps.resize(thread + 1, Vec::new());
+        }
+
+        match words[1..] {
+            ["lock"] => steps[thread].push(Step::Lock),
+            ["unlock"] => steps[thread].push(Step::Unlock),
+            ["sleep", ms] => steps[thread].push(Step::Sleep(millis(ms)?)),
+            ["at", name, "delay", ms] => delays.push(Delay {
+                thread,
+                label: LABELS
+                    .into_iter()
+                    .find(|&label| label == name)
+                    .ok_or_else(|| error(&format!("unknown label `{name}`")))?,
+                duration: millis(ms)?,
+            }),
+            _ => return Err(error(&format!("unknown directive `{line}`"))),
+        }
+    }
+
+    for (thread, steps) in steps.iter().enumerate() {
+        let mut holding = false;
+        for step in steps {
+            match step {
+                Step::Lock if holding => {
+                    return Err(format!(
+                        "thread {thread} locks while already holding the lock"
+                    ))
+                }
+                Step::Unlock if !holding => {
+                    return Err(format!("thread {thread} unlocks without holding the lock"))
+                }
+                Step::Lock | Step::Unlock => holding = !holding,
+                Step::Sleep(_) => {}
+            }
+        }
+        if holding {
+            return Err(format!("thread {thread} never releases the lock"));
+        }
+    }
+
+    Ok(Scenario { steps, delays })
+}
+
+// Prints every event with the time it happened at and applies the scenario's delays.
+struct Director<'a> {
+    start: Instant,
+    delays: &'a [Delay],
+    // The last event reported by each thread, so that spinning neither floods the output nor
+    // re-applies the same delay on every iteration.
+    last: Mutex<[Option<Event>; MAX_THREADS]>,
+    acquisitions: Mutex<Vec<usize>>,
+}
+
+impl Observer for Director<'_> {
+    fn on_event(&self, thread: usize, event: Event) {
+        let repeated = {
+            let mut last = self.last.lock().unwrap();
+            last[thread].replace(event) == Some(event)
+        };
+        if repeated {
+            return;
+        }
+
+        if event == Event::Acquired {
+            self.acquisitions.lock().unwrap().push(thread);
+        }
+        println!(
+            "{:>10.1?}  {}",
+            self.start.elapsed(),
+            teach::describe(thread, event)
+        );
+
+        for delay in self.delays {
+            if delay.thread == thread && delay.label == label(event) {
+                thread::sleep(delay.duration);
+            }
+        }
+    }
+}
+
+fn usage() -> ! {
+    eprintln!("usage: bakery scenario <file>");
+    process::exit(2);
+}
+
+// Plays a scripted interleaving on the real lock, checking that no two threads are ever inside
+// their critical sections together.
+pub fn run(args: &[String]) {
+    let [path] = args else { usage() };
+
+    let scenario = fs::read_to_string(path)
+        .map_err(|err| err.to_string())
+        .and_then(|contents| parse(&contents))
+        .unwrap_or_else(|err| {
+            eprintln!("bad scenario {path}: {err}");
+            process::exit(1);
+        });
+
+    let lock = RawBakeryLock::<MAX_THREADS, _>::with_observer(Director {
+        start: Instant::now(),
+        delays: &scenario.delays,
+        last: Mutex::new([None; MAX_THREADS]),
+        acquisitions: Mutex::new(Vec::new()),
+    });
+    let inside = AtomicUsize::new(0);
+    let violations = AtomicUsize::new(0);
+
+    thread::scope(|scope| {
+        for (thread_id, steps) in scenario.steps.iter().enumerate() {
+            let lock = &lock;
+            let inside = &inside;
+            let violations = &violations;
+            workers::spawn(scope, "scenario", thread_id, move |worker| {
+                for &step in steps {
+                    match step {
+                        Step::Lock => {
+                            lock.lock(worker.id);
+                            if inside.fetch_add(1, Ordering::Relaxed) != 0 {
+                                violations.fetch_add(1, Ordering::Relaxed);
+                            }
+                        }
+                        Step::Unlock => {
+                            inside.fetch_sub(1, Ordering::Relaxed);
+                            lock.unlock(worker.id);
+                        }
+                        Step::Sleep(duration) => thread::sleep(duration),
+                    }
+                }
+            });
+        }
+    });
+
+    let order = lock.observer().acquisitions.lock().unwrap();
+    println!(
+        "acquisition order: {}",
+        order
+            .iter()
+            .map(|thread| thread.to_string())
+            .collect::<Vec<_>>()
+            .join(" ")
+    );
+
+    let violations = violations.into_inner();
+    if violations > 0 {
+        println!("mutual exclusion violated {violations} times");
+        process::exit(1);
+    }
+}
@@ -0,0 +1,72 @@
+use std::{
+    sync::atomic::{AtomicUsize, Ordering},
+    thread,
+};
+
+use crate::BakeryLock;
+
+/// A counting semaphore for up to `N` threads, which hands out permits in the order threads asked
+/// for them.
+///
+/// Acquiring goes through a [`BakeryLock`], and the thread at the head of the bakery waits for
+/// enough permits while still holding it, so a thread asking for many permits can't be starved by
+/// a stream of threads asking for few: nobody behind it gets any until it has been served.
+/// Releasing doesn't take the lock, and can be done by any thread, not just one that acquired.
+/// The thread at the head spins (yielding to the scheduler) rather than sleeps, like a
+/// [`BakeryCondvar`](crate::BakeryCondvar) waiter.
+///
+/// Slots are assigned to threads the same way as for [`BakeryLock`], the first time each thread
+/// acquires.
+pub struct BakerySemaphore<const N: usize> {
+    // Only ever decreased by whoever holds `queue`, and increased by anyone.
+    permits: AtomicUsize,
+    queue: BakeryLock<N>,
+}
+
+impl<const N: usize> BakerySemaphore<N> {
+    /// A semaphore with `permits` permits available.
+    pub fn new(permits: usize) -> Self {
+        Self {
+            permits: AtomicUsize::new(permits),
+            queue: BakeryLock::new(),
+        }
+    }
+
+    /// The number of permits currently available.
+    pub fn available_permits(&self) -> usize {
+        self.permits.load(Ordering::Relaxed)
+    }
+
+    /// Waits for every thread that asked before to be served, and then for `n` permits to be
+    /// available, and takes them. Asking for more permits than will ever be released waits forever,
+    /// and holds up everyone behind.
+    pub fn acquire(&self, n: usize) {
+        let _head = self.queue.lock();
+        while !self.take(n) {
+            thread::yield_now();
+        }
+    }
+
+    /// Takes `n` permits like [`acquire`](Self::acquire) if that doesn't require waiting, returning
+    /// whether it did.
+    pub fn try_acquire(&self, n: usize) -> bool {
+        self.queue.try_lock().is_some_and(|_head| self.take(n))
+    }
+
+    /// Makes `n` more permits available, to the thread at the head of the queue first.
+    pub fn release(&self, n: usize) {
+        // Synchronizes-with the acquire load in `take`, so whatever we did with the permits
+        // happens-before whoever gets them next.
+        self.permits.fetch_add(n, Ordering::Release);
+    }
+
+    // Only called with `queue` held, so the permits can only grow between the load and the update.
+    fn take(&self, n: usize) -> bool {
+        let available = self.permits.load(Ordering::Acquire);
+        if available < n {
+            return false;
+        }
+        self.permits.fetch_sub(n, Ordering::Relaxed);
+        true
+    }
+}
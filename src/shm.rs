@@ -0,0 +1,236 @@
+use core::sync::atomic::{AtomicU32, Ordering};
+
+use crate::{
+    algorithm::{Bakery, Next},
+    backoff::{Backoff, NoBackoff},
+    SlotLock,
+};
+
+// The owner of a slot that `recover` is in the middle of freeing.
+const RECOVERING: u32 = u32::MAX;
+
+/// Lamport's bakery lock for up to `N` processes sharing memory, laid out so that it can live in
+/// an `mmap`'d region.
+///
+/// The lock is nothing but three arrays of 32-bit words, with no pointers and nothing specific to
+/// the process that created it, and all zeroes is an unlocked lock with every slot free. A freshly
+/// created shared memory object (`shm_open` and `ftruncate`, say) therefore needs no
+/// initialization at all: every process maps it and gets the lock with
+/// [`from_ptr`](Self::from_ptr). The lock-free atomics it uses work between processes just as they
+/// do between threads.
+///
+/// A process claims a slot by writing its id into the slot's owner word with
+/// [`claim`](Self::claim), and gives it back with [`release`](Self::release). If it dies while in
+/// the doorway or holding a ticket, everyone behind it waits forever, so a process that has been
+/// waiting suspiciously long (or a supervisor) calls [`recover`](Self::recover), which frees the
+/// slots of owners that are no longer alive. That lets the others in, but whatever the dead
+/// process was halfway through in its critical section is left as it was.
+#[repr(C)]
+pub struct ShmBakeryLock<const N: usize> {
+    // The id of the process each slot belongs to, 0 when it's free.
+    owners: [AtomicU32; N],
+    // 0 or 1, as 32-bit words so that the layout doesn't depend on how a platform packs `bool`s.
+    choosing: [AtomicU32; N],
+    tickets: [AtomicU32; N],
+}
+
+impl<const N: usize> ShmBakeryLock<N> {
+    /// An unlocked lock with every slot free, for a region that is initialized by copying one in
+    /// rather than by zeroing it.
+    pub const fn new() -> Self {
+        assert!(
+            (N as u64) < Self::MAX_TICKET / 4,
+            "too many slots to tell tickets apart across a wraparound"
+        );
+
+        Self {
+            owners: [const { AtomicU32::new(0) }; N],
+            choosing: [const { AtomicU32::new(0) }; N],
+            tickets: [const { AtomicU32::new(0) }; N],
+        }
+    }
+
+    /// The lock at `ptr`, in memory shared with other processes.
+    ///
+    /// # Safety
+    ///
+    /// `ptr` must be aligned for `u32` and point to `size_of::<ShmBakeryLock<N>>()` bytes that
+    /// stay mapped for `'a` and are only ever accessed as this lock, and that are either all zero
+    /// or hold a lock that other processes have been using.
+    pub unsafe fn from_ptr<'a>(ptr: *mut u8) -> &'a Self {
+        assert!(
+            ptr.cast::<AtomicU32>().is_aligned(),
+            "shared lock is misaligned"
+        );
+        // SAFETY: the caller guarantees that the memory is valid for `'a`, and all zeroes is a
+        // valid lock. The lock only ever accesses it through atomics.
+        unsafe { &*ptr.cast::<Self>() }
+    }
+
+    /// Claims a free slot for the process `owner`, or returns `None` if all `N` are taken.
+    /// `owner` must be neither 0 nor `u32::MAX`, and should be something
+    /// [`recover`](Self::recover) can tell is dead, like a process id.
+    pub fn claim(&self, owner: u32) -> Option<usize> {
+        assert!(
+            owner != 0 && owner != RECOVERING,
+            "owner {owner} is reserved"
+        );
+        (0..N).find(|&slot| {
+            self.owners[slot]
+                .compare_exchange(0, owner, Ordering::Acquire, Ordering::Relaxed)
+                .is_ok()
+        })
+    }
+
+    /// Gives back a slot claimed with [`claim`](Self::claim), which must not be holding the lock.
+    pub fn release(&self, slot: usize) {
+        self.owners[slot].store(0, Ordering::Release);
+    }
+
+    /// The process that claimed `slot`, if any.
+    pub fn owner(&self, slot: usize) -> Option<u32> {
+        match self.owners[slot].load(Ordering::Relaxed) {
+            0 => None,
+            owner => Some(owner),
+        }
+    }
+
+    /// Waits until every process ahead of `slot` in the bakery has left and enters the critical
+    /// section. `slot` must have been claimed by the calling process.
+    pub fn lock(&self, slot: usize) {
+        let ticket = self.doorway(slot);
+        self.wait_turn(slot, ticket, |_, _, backoff: &mut NoBackoff| {
+            backoff.snooze();
+            Next::Wait
+        });
+    }
+
+    /// Enters the critical section like [`lock`](Self::lock) if that doesn't require waiting for
+    /// another process, returning whether it did.
+    pub fn try_lock(&self, slot: usize) -> bool {
+        let Some(ticket) = self.try_doorway(slot) else {
+            return false;
+        };
+        let entered = self.try_turn(slot, ticket, |_, _| Next::GiveUp);
+        if !entered {
+            self.unlock(slot);
+        }
+        entered
+    }
+
+    /// Leaves the critical section entered from `slot`, letting the next process in.
+    pub fn unlock(&self, slot: usize) {
+        self.tickets[slot].store(0, Ordering::Release);
+    }
+
+    /// Frees every claimed slot whose owner `is_alive` says is dead, clearing whatever it left in
+    /// the doorway or in the queue, and returns how many there were.
+    ///
+    /// To everyone waiting, a dead process's ticket disappearing looks like it unlocked, so if it
+    /// died in its critical section, the next process in finds the data however it was left.
+    pub fn recover(&self, mut is_alive: impl FnMut(u32) -> bool) -> usize {
+        let mut recovered = 0;
+        for slot in 0..N {
+            let owner = self.owners[slot].load(Ordering::Relaxed);
+            if owner == 0 || owner == RECOVERING || is_alive(owner) {
+                continue;
+            }
+            // Marking the slot first means only one of several processes recovering at once
+            // clears it, and that nobody can claim it again until it has been.
+            if self.owners[slot]
+                .compare_exchange(owner, RECOVERING, Ordering::Acquire, Ordering::Relaxed)
+                .is_err()
+            {
+                continue;
+            }
+            // Cleared with release stores like `unlock`, before the slot is freed for whoever
+            // claims it next.
+            self.choosing[slot].store(0, Ordering::Release);
+            self.tickets[slot].store(0, Ordering::Release);
+            self.owners[slot].store(0, Ordering::Release);
+            recovered += 1;
+        }
+        recovered
+    }
+}
+
+impl<const N: usize> Bakery for ShmBakeryLock<N> {
+    const MAX_TICKET: u64 = u32::MAX as u64;
+
+    fn is_choosing(&self, slot: usize, order: Ordering) -> bool {
+        self.choosing[slot].load(order) != 0
+    }
+
+    fn set_choosing(&self, slot: usize, choosing: bool, order: Ordering) {
+        self.choosing[slot].store(choosing.into(), order);
+    }
+
+    fn ticket(&self, slot: usize, order: Ordering) -> u64 {
+        self.tickets[slot].load(order).into()
+    }
+
+    fn set_ticket(&self, slot: usize, ticket: u64, order: Ordering) {
+        self.tickets[slot].store(ticket as u32, order);
+    }
+
+    fn active_slots(&self) -> impl Iterator<Item = usize> + '_ {
+        0..N
+    }
+}
+
+#[cfg(all(feature = "std", unix))]
+impl<const N: usize> ShmBakeryLock<N> {
+    /// Claims a free slot for the calling process, by its process id.
+    pub fn claim_for_process(&self) -> Option<usize> {
+        self.claim(std::process::id())
+    }
+
+    /// Frees the slots of processes that have exited, as [`recover`](Self::recover) with a check
+    /// that each owner's process id still exists. Process ids get reused, so a slot whose owner
+    /// died a long time ago may look alive again.
+    pub fn recover_dead(&self) -> usize {
+        self.recover(process_alive)
+    }
+}
+
+impl<const N: usize> Default for ShmBakeryLock<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<const N: usize> SlotLock for ShmBakeryLock<N> {
+    fn slots(&self) -> usize {
+        N
+    }
+
+    fn lock(&self, slot: usize) {
+        ShmBakeryLock::lock(self, slot);
+    }
+
+    fn unlock(&self, slot: usize) {
+        ShmBakeryLock::unlock(self, slot);
+    }
+}
+
+#[cfg(all(feature = "std", unix))]
+fn process_alive(pid: u32) -> bool {
+    use std::{ffi::c_int, io};
+
+    // `ESRCH` on every Unix we know of.
+    const ESRCH: i32 = 3;
+
+    extern "C" {
+        fn kill(pid: c_int, signal: c_int) -> c_int;
+    }
+
+    let Ok(pid) = c_int::try_from(pid) else {
+        return false;
+    };
+    // SAFETY: signal 0 only checks that the process exists.
+    if unsafe { kill(pid, 0) } == 0 {
+        return true;
+    }
+    // Anything but "no such process", like not being allowed to signal it, means it's there.
+    io::Error::last_os_error().raw_os_error() != Some(ESRCH)
+}
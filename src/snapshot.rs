@@ -0,0 +1,111 @@
+use core::{fmt, sync::atomic::Ordering};
+
+use crate::layout::SlotLayout;
+
+// How many times `sample` reads every slot looking for two passes that agree.
+const ATTEMPTS: usize = 4;
+
+/// Every slot's `choosing` flag and ticket, as sampled by
+/// [`RawBakeryLock::snapshot`](crate::RawBakeryLock::snapshot), for printing when something goes
+/// wrong.
+///
+/// The slots can't all be read at the same instant, so the snapshot reads them all repeatedly until
+/// two passes in a row agree. When they never do, for a lock too busy to stand still, it keeps the
+/// last pass and says so in [`consistent`](Self::consistent); that pass may show a combination of
+/// tickets that never existed at once.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct LockSnapshot<const N: usize> {
+    /// Whether each slot was in the doorway.
+    pub choosing: [bool; N],
+    /// Each slot's ticket, 0 for none.
+    pub tickets: [u64; N],
+    /// The slot that had finished choosing and held the oldest ticket, and so was either in its
+    /// critical section or about to enter it.
+    pub front: Option<usize>,
+    /// The slot the critical section had been handed to with
+    /// [`unlock_to`](crate::RawBakeryLock::unlock_to) and that hadn't picked it up yet.
+    pub handoff: Option<usize>,
+    /// Whether two passes over the slots agreed.
+    pub consistent: bool,
+}
+
+impl<const N: usize> LockSnapshot<N> {
+    pub(crate) fn sample<S: SlotLayout<N>>(slots: &S, handoff: Option<usize>) -> Self {
+        let pass = || -> ([bool; N], [u64; N]) {
+            (
+                core::array::from_fn(|slot| slots.is_choosing(slot, Ordering::Relaxed)),
+                core::array::from_fn(|slot| slots.ticket(slot, Ordering::Relaxed)),
+            )
+        };
+
+        let mut last = pass();
+        let mut consistent = false;
+        for _ in 1..ATTEMPTS {
+            let next = pass();
+            consistent = next == last;
+            last = next;
+            if consistent {
+                break;
+            }
+        }
+
+        let (choosing, tickets) = last;
+        let front = (0..N)
+            .filter(|&slot| !choosing[slot] && tickets[slot] != 0)
+            .reduce(|front, slot| {
+                if S::precedes(tickets[slot], tickets[front]) {
+                    slot
+                } else {
+                    front
+                }
+            });
+        Self {
+            choosing,
+            tickets,
+            front,
+            handoff,
+            consistent,
+        }
+    }
+
+    /// Whether no slot was in the doorway or held a ticket.
+    pub fn is_idle(&self) -> bool {
+        !self.choosing.contains(&true) && !self.tickets.iter().any(|&ticket| ticket != 0)
+    }
+}
+
+/// Lists the slots that were in use, as in "slot 3 choosing, slot 5 holds ticket 17 (front)".
+impl<const N: usize> fmt::Display for LockSnapshot<N> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.is_idle() {
+            f.write_str("all slots free")?;
+        }
+
+        let mut separator = "";
+        for slot in 0..N {
+            let ticket = self.tickets[slot];
+            if self.choosing[slot] {
+                write!(f, "{separator}slot {slot} choosing")?;
+                if ticket != 0 {
+                    write!(f, " (ticket {ticket})")?;
+                }
+            } else if ticket != 0 {
+                write!(f, "{separator}slot {slot} holds ticket {ticket}")?;
+                if self.front == Some(slot) {
+                    f.write_str(" (front)")?;
+                }
+            } else {
+                continue;
+            }
+            separator = ", ";
+        }
+
+        if let Some(handoff) = self.handoff {
+            write!(f, ", handed off to slot {handoff}")?;
+        }
+        if !self.consistent {
+            f.write_str(" (inconsistent)")?;
+        }
+        Ok(())
+    }
+}
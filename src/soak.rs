@@ -0,0 +1,216 @@
+use std::{
+    cell::UnsafeCell,
+    fs, io, process, thread,
+    time::{Duration, Instant},
+};
+
+use bakery::{Observer, RawBakeryLock};
+
+use crate::{bundle, results, workers, UnsafeSyncCell};
+
+const NUM_THREADS: usize = 10;
+
+// Statistics accumulated over the whole soak, across restarts.
+#[derive(Default)]
+struct Totals {
+    rounds: u64,
+    violating_rounds: u64,
+    lost_updates: u64,
+    acquisitions: u64,
+    elapsed: Duration,
+}
+
+impl Totals {
+    fn serialize(&self) -> String {
+        format!(
+            "fences={}\nrounds={}\nviolating_rounds={}\nlost_updates={}\nacquisitions={}\nelapsed_ms={}\n",
+            results::fence_config(),
+            self.rounds,
+            self.violating_rounds,
+            self.lost_updates,
+            self.acquisitions,
+            self.elapsed.as_millis(),
+        )
+    }
+
+    fn deserialize(contents: &str) -> Result<Self, String> {
+        let mut totals = Self::default();
+
+        for line in contents.lines().filter(|line| !line.trim().is_empty()) {
+            let (key, value) = line
+                .split_once('=')
+                .ok_or_else(|| format!("malformed line `{line}`"))?;
+            let number = || {
+                value
+                    .parse::<u64>()
+                    .map_err(|_| format!("malformed value for `{key}`"))
+            };
+
+            match key {
+                "fences" if value != results::fence_config() => {
+                    return Err(format!(
+                        "checkpoint was taken with fences {value}, but this build uses {}",
+                        results::fence_config()
+                    ));
+                }
+                "fences" => {}
+                "rounds" => totals.rounds = number()?,
+                "violating_rounds" => totals.violating_rounds = number()?,
+                "lost_updates" => totals.lost_updates = number()?,
+                "acquisitions" => totals.acquisitions = number()?,
+                "elapsed_ms" => totals.elapsed = Duration::from_millis(number()?),
+                _ => return Err(format!("unknown key `{key}`")),
+            }
+        }
+
+        Ok(totals)
+    }
+
+    fn print(&self) {
+        println!(
+            "{} rounds ({} acquisitions) over {:?}: {} rounds lost a total of {} updates",
+            self.rounds, self.acquisitions, self.elapsed, self.violating_rounds, self.lost_updates
+        );
+    }
+}
+
+// Writes the checkpoint to a temporary file first so that an interruption never leaves a
+// truncated checkpoint behind.
+fn save(path: &str, totals: &Totals) -> io::Result<()> {
+    let tmp = format!("{path}.tmp");
+    fs::write(&tmp, totals.serialize())?;
+    fs::rename(tmp, path)
+}
+
+fn round<O: Observer + Sync>(lock: &RawBakeryLock<NUM_THREADS, O>, iterations: usize) -> usize {
+    let mut num = UnsafeSyncCell(UnsafeCell::new(0));
+
+    thread::scope(|scope| {
+        for thread_id in 0..NUM_THREADS {
+            let num = &num;
+            workers::spawn(scope, "soak", thread_id, move |worker| {
+                for _ in 0..iterations {
+                    lock.lock(worker.id);
+                    unsafe {
+                        *num.0.get() += 1;
+                    }
+                    lock.unlock(worker.id);
+                }
+            });
+        }
+    });
+
+    *num.0.get_mut()
+}
+
+fn usage() -> ! {
+    eprintln!(
+        "usage: bakery soak --checkpoint <path> [--interval <minutes>] [--duration <minutes>] \
+         [--iterations <n>] [--bundle <dir>]"
+    );
+    process::exit(2);
+}
+
+// Repeats the counter demo until `--duration` elapses (or forever), checkpointing the accumulated
+// statistics every `--interval` minutes. Restarting with the same checkpoint resumes from the last
+// checkpoint taken.
+pub fn run(args: &[String]) {
+    let mut checkpoint = None;
+    let mut interval = Duration::from_secs(5 * 60);
+    let mut duration = None;
+    let mut iterations = 100000;
+    let mut bundle = None;
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        let mut value = || args.next().unwrap_or_else(|| usage());
+        let minutes = |value: &String| {
+            let minutes: f64 = value.parse().unwrap_or_else(|_| usage());
+            Duration::try_from_secs_f64(minutes * 60.0).unwrap_or_else(|_| usage())
+        };
+
+        match arg.as_str() {
+            "--checkpoint" => checkpoint = Some(value().clone()),
+            "--interval" => interval = minutes(value()),
+            "--duration" => duration = Some(minutes(value())),
+            "--iterations" => iterations = value().parse().unwrap_or_else(|_| usage()),
+            "--bundle" => bundle = Some(value().clone()),
+            _ => usage(),
+        }
+    }
+    let Some(checkpoint) = checkpoint else {
+        usage();
+    };
+
+    let mut totals = match fs::read_to_string(&checkpoint) {
+        Ok(contents) => {
+            let totals = Totals::deserialize(&contents).unwrap_or_else(|err| {
+                eprintln!("{checkpoint}: {err}");
+                process::exit(1);
+            });
+            print!("resuming from {checkpoint}: ");
+            totals.print();
+            totals
+        }
+        Err(err) if err.kind() == io::ErrorKind::NotFound => Totals::default(),
+        Err(err) => {
+            eprintln!("failed to read {checkpoint}: {err}");
+            process::exit(1);
+        }
+    };
+
+    let start = Instant::now();
+    let mut last_checkpoint = start;
+    let mut last_elapsed = start;
+
+    loop {
+        let expected = NUM_THREADS * iterations;
+        let count = match &bundle {
+            Some(bundle) => {
+                let lock = RawBakeryLock::<NUM_THREADS, _>::with_observer(bundle::EventRing::<
+                    NUM_THREADS,
+                >::new());
+                let count = round(&lock, iterations);
+                if count != expected {
+                    let failure = format!(
+                        "round {} counted to {count} instead of {expected}",
+                        totals.rounds + 1
+                    );
+                    match bundle::write(bundle, &failure, &lock.observer().dump()) {
+                        Ok(dir) => println!("failure bundle written to {}", dir.display()),
+                        Err(err) => eprintln!("failed to write failure bundle to {bundle}: {err}"),
+                    }
+                }
+                count
+            }
+            None => round(&RawBakeryLock::new(), iterations),
+        };
+
+        let now = Instant::now();
+        totals.rounds += 1;
+        totals.acquisitions += expected as u64;
+        totals.elapsed += now - last_elapsed;
+        last_elapsed = now;
+        if count != expected {
+            totals.violating_rounds += 1;
+            totals.lost_updates += expected.saturating_sub(count) as u64;
+            println!(
+                "round {} counted to {count} instead of {expected}",
+                totals.rounds
+            );
+        }
+
+        let finished = duration.is_some_and(|duration| now - start >= duration);
+        if finished || now - last_checkpoint >= interval {
+            if let Err(err) = save(&checkpoint, &totals) {
+                eprintln!("failed to write checkpoint {checkpoint}: {err}");
+            }
+            last_checkpoint = now;
+            totals.print();
+        }
+
+        if finished {
+            break;
+        }
+    }
+}
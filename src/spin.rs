@@ -0,0 +1,170 @@
+use core::hint;
+#[cfg(feature = "spin-hint")]
+use core::sync::atomic::{AtomicU8, Ordering};
+
+/// The instruction run on every iteration of the lock's wait loops. The standard library picks one
+/// per architecture (`pause` on x86, `isb` on aarch64, and nothing at all on WebAssembly, which has
+/// no such instruction); the others are there to measure how much that choice matters, and can be
+/// selected with `set_hint` when the `spin-hint` feature is enabled.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum SpinHint {
+    /// `std::hint::spin_loop`.
+    Std,
+    /// x86 `pause`.
+    Pause,
+    /// A short `tpause` on x86 CPUs with WAITPKG, which sleeps in a light power state until the
+    /// deadline passes.
+    Tpause,
+    /// aarch64 `isb`.
+    Isb,
+    /// aarch64 `yield`.
+    Yield,
+    /// Spin without any hint.
+    None,
+}
+
+impl SpinHint {
+    /// Every hint, whether or not it's available on this CPU.
+    pub const ALL: [SpinHint; 6] = [
+        SpinHint::Std,
+        SpinHint::Pause,
+        SpinHint::Tpause,
+        SpinHint::Isb,
+        SpinHint::Yield,
+        SpinHint::None,
+    ];
+
+    /// The hint's name, as accepted by [`from_name`](Self::from_name).
+    pub fn name(self) -> &'static str {
+        match self {
+            SpinHint::Std => "std",
+            SpinHint::Pause => "pause",
+            SpinHint::Tpause => "tpause",
+            SpinHint::Isb => "isb",
+            SpinHint::Yield => "yield",
+            SpinHint::None => "none",
+        }
+    }
+
+    /// The hint called `name`, if there is one.
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|hint| hint.name() == name)
+    }
+
+    /// Whether the hint can be used on this CPU.
+    pub fn is_available(self) -> bool {
+        match self {
+            SpinHint::Std | SpinHint::None => true,
+            SpinHint::Pause => cfg!(target_arch = "x86_64"),
+            SpinHint::Tpause => has_waitpkg(),
+            SpinHint::Isb | SpinHint::Yield => cfg!(target_arch = "aarch64"),
+        }
+    }
+}
+
+#[cfg(feature = "spin-hint")]
+static HINT: AtomicU8 = AtomicU8::new(SpinHint::Std as u8);
+
+/// Selects the hint used by every lock from now on. Panics if `hint` isn't available on this CPU.
+#[cfg(feature = "spin-hint")]
+pub fn set_hint(hint: SpinHint) {
+    assert!(
+        hint.is_available(),
+        "spin hint `{}` is not available on this CPU",
+        hint.name()
+    );
+    HINT.store(hint as u8, Ordering::Relaxed);
+}
+
+/// The hint currently used by every lock, which is always [`SpinHint::Std`] without the
+/// `spin-hint` feature.
+pub fn hint() -> SpinHint {
+    #[cfg(feature = "spin-hint")]
+    return SpinHint::ALL[HINT.load(Ordering::Relaxed) as usize];
+    #[cfg(not(feature = "spin-hint"))]
+    SpinHint::Std
+}
+
+/// Called by the wait loops in place of `hint::spin_loop`, which it is unless the `spin-hint`
+/// feature is enabled. Then every call starts by loading the selected hint.
+#[inline]
+pub fn relax() {
+    #[cfg(feature = "spin-hint")]
+    match hint() {
+        SpinHint::Std => hint::spin_loop(),
+        SpinHint::None => {}
+        hint => arch::relax(hint),
+    }
+    #[cfg(not(feature = "spin-hint"))]
+    hint::spin_loop();
+}
+
+// Whether the CPU has `tpause`.
+fn has_waitpkg() -> bool {
+    // CPUID.(EAX=7, ECX=0):ECX[bit 5]
+    #[cfg(target_arch = "x86_64")]
+    return core::arch::x86_64::__cpuid_count(7, 0).ecx & (1 << 5) != 0;
+    #[cfg(not(target_arch = "x86_64"))]
+    false
+}
+
+#[cfg(all(feature = "spin-hint", target_arch = "x86_64"))]
+mod arch {
+    use core::arch::{asm, x86_64};
+
+    use super::SpinHint;
+
+    // How long a single `tpause` may sleep for, in TSC ticks.
+    const TPAUSE_TICKS: u64 = 1000;
+
+    pub fn relax(hint: SpinHint) {
+        match hint {
+            // SAFETY: `pause` is available on every x86_64 CPU.
+            SpinHint::Pause => unsafe { asm!("pause", options(nomem, nostack)) },
+            SpinHint::Tpause => {
+                // SAFETY: `rdtsc` is available on every x86_64 CPU.
+                let deadline = unsafe { x86_64::_rdtsc() } + TPAUSE_TICKS;
+                // SAFETY: `set_hint` only selects `tpause` on CPUs with WAITPKG. Bit 0 of the
+                // control register selects the lighter C0.1 state, which wakes up faster.
+                unsafe {
+                    asm!(
+                        "tpause {control:e}",
+                        control = in(reg) 1u32,
+                        in("eax") deadline as u32,
+                        in("edx") (deadline >> 32) as u32,
+                        options(nomem, nostack),
+                    );
+                }
+            }
+            _ => unreachable!(),
+        }
+    }
+}
+
+#[cfg(all(feature = "spin-hint", target_arch = "aarch64"))]
+mod arch {
+    use core::arch::asm;
+
+    use super::SpinHint;
+
+    pub fn relax(hint: SpinHint) {
+        match hint {
+            // SAFETY: both are available on every aarch64 CPU.
+            SpinHint::Isb => unsafe { asm!("isb", options(nomem, nostack)) },
+            SpinHint::Yield => unsafe { asm!("yield", options(nomem, nostack)) },
+            _ => unreachable!(),
+        }
+    }
+}
+
+#[cfg(all(
+    feature = "spin-hint",
+    not(any(target_arch = "x86_64", target_arch = "aarch64"))
+))]
+mod arch {
+    use super::SpinHint;
+
+    pub fn relax(_hint: SpinHint) {
+        unreachable!()
+    }
+}
@@ -0,0 +1,170 @@
+#[cfg(feature = "test-and-set")]
+use std::sync::atomic::AtomicBool;
+use std::{
+    process,
+    sync::atomic::{AtomicUsize, Ordering},
+    thread,
+};
+
+#[cfg(feature = "filter")]
+use bakery::filter::FilterLock;
+#[cfg(feature = "test-and-set")]
+use bakery::spin;
+#[cfg(feature = "ticket")]
+use bakery::ticket::TicketLock;
+#[cfg(feature = "ttas")]
+use bakery::ttas::TtasLock;
+use bakery::{Event, Observer, RawBakeryLock};
+
+use crate::workers;
+
+const NUM_THREADS: usize = 4;
+
+// The thread whose progress is tracked.
+const VICTIM: usize = 0;
+
+// Counts how many critical sections other threads get through while the victim waits.
+#[derive(Default)]
+struct BypassMeter {
+    // Critical sections entered so far by all threads. Only updated inside the critical section.
+    entries: AtomicUsize,
+    // The value of `entries` when the victim last took a ticket.
+    at_ticket: AtomicUsize,
+    max_after_ticket: AtomicUsize,
+}
+
+impl Observer for BypassMeter {
+    fn on_event(&self, thread: usize, event: Event) {
+        match event {
+            Event::Ticket(_) if thread == VICTIM => {
+                let entries = self.entries.load(Ordering::Relaxed);
+                self.at_ticket.store(entries, Ordering::Relaxed);
+            }
+            Event::Acquired => {
+                let entries = self.entries.load(Ordering::Relaxed);
+                if thread == VICTIM {
+                    let bypass = entries - self.at_ticket.load(Ordering::Relaxed);
+                    self.max_after_ticket.fetch_max(bypass, Ordering::Relaxed);
+                }
+                self.entries.store(entries + 1, Ordering::Relaxed);
+            }
+            _ => {}
+        }
+    }
+}
+
+// A test-and-set spinlock, which makes no promises at all about who gets the lock next.
+#[cfg(feature = "test-and-set")]
+#[derive(Default)]
+pub struct SpinLock(AtomicBool);
+
+#[cfg(feature = "test-and-set")]
+impl SpinLock {
+    pub fn lock(&self) {
+        while self.0.swap(true, Ordering::Acquire) {
+            spin::relax();
+        }
+    }
+
+    pub fn unlock(&self) {
+        self.0.store(false, Ordering::Release);
+    }
+}
+
+// Has every thread take the lock `iterations` times, returning the largest number of critical
+// sections other threads entered between the victim calling `lock` and getting the lock.
+fn hammer(iterations: usize, lock: impl Fn(usize) + Sync, unlock: impl Fn(usize) + Sync) -> usize {
+    let entries = AtomicUsize::new(0);
+    let max_bypass = AtomicUsize::new(0);
+
+    thread::scope(|scope| {
+        for thread_id in 0..NUM_THREADS {
+            let (lock, unlock) = (&lock, &unlock);
+            let (entries, max_bypass) = (&entries, &max_bypass);
+            workers::spawn(scope, "starvation", thread_id, move |worker| {
+                for _ in 0..iterations {
+                    // Only updated inside the critical section, so this is exact when read there
+                    // and at worst slightly stale here.
+                    let before = entries.load(Ordering::Relaxed);
+                    lock(worker.id);
+                    let now = entries.load(Ordering::Relaxed);
+                    entries.store(now + 1, Ordering::Relaxed);
+                    if worker.id == VICTIM {
+                        max_bypass.fetch_max(now - before, Ordering::Relaxed);
+                    }
+                    unlock(worker.id);
+                }
+            });
+        }
+    });
+
+    max_bypass.into_inner()
+}
+
+fn usage() -> ! {
+    eprintln!("usage: bakery starvation [--iterations <n>]");
+    process::exit(2);
+}
+
+// Tracks how often one thread is overtaken while the others hammer the lock. Once a thread has
+// taken its ticket, the bakery lets every other thread in at most once before it, which is checked
+// here; the filter lock and the test-and-set and TTAS spinlocks, which promise no such bound, and a
+// ticket spinlock, which only ever lets in threads that took their ticket first, are measured
+// alongside for comparison.
+pub fn run(args: &[String]) {
+    let iterations = match args {
+        [] => 20000,
+        [flag, value] if flag == "--iterations" => value.parse().unwrap_or_else(|_| usage()),
+        _ => usage(),
+    };
+
+    println!("{NUM_THREADS} threads, {iterations} iterations each, thread {VICTIM} is the victim");
+
+    let bakery = RawBakeryLock::<NUM_THREADS, _>::with_observer(BypassMeter::default());
+    let bakery_bypass = hammer(
+        iterations,
+        |thread| bakery.lock(thread),
+        |thread| bakery.unlock(thread),
+    );
+    let after_ticket = bakery.observer().max_after_ticket.load(Ordering::Relaxed);
+    let bound = NUM_THREADS - 1;
+
+    println!(
+        "bakery: overtaken at most {bakery_bypass} times after calling `lock`, \
+         {after_ticket} after taking a ticket (bound {bound})"
+    );
+    #[cfg(feature = "filter")]
+    {
+        let filter = FilterLock::<NUM_THREADS>::new();
+        let filter_bypass = hammer(
+            iterations,
+            |thread| filter.lock(thread),
+            |thread| filter.unlock(thread),
+        );
+        println!("filter: overtaken at most {filter_bypass} times after calling `lock`");
+    }
+    #[cfg(feature = "ticket")]
+    {
+        let ticket = TicketLock::new();
+        let ticket_bypass = hammer(iterations, |_| ticket.lock(), |_| ticket.unlock());
+        println!("ticket: overtaken at most {ticket_bypass} times after calling `lock`");
+    }
+    #[cfg(feature = "test-and-set")]
+    {
+        let spin_lock = SpinLock::default();
+        let spin_bypass = hammer(iterations, |_| spin_lock.lock(), |_| spin_lock.unlock());
+        println!("test-and-set: overtaken at most {spin_bypass} times after calling `lock`");
+    }
+
+    #[cfg(feature = "ttas")]
+    {
+        let ttas = TtasLock::new();
+        let ttas_bypass = hammer(iterations, |_| ttas.lock(), |_| ttas.unlock());
+        println!("ttas: overtaken at most {ttas_bypass} times after calling `lock`");
+    }
+
+    if after_ticket > bound {
+        println!("the victim was overtaken more often than the bakery allows");
+        process::exit(1);
+    }
+}
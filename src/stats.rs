@@ -0,0 +1,90 @@
+// Outcome of comparing two sets of measurements with the Mann-Whitney U test.
+pub struct Comparison {
+    // Two-sided p-value for the hypothesis that neither sample tends to be larger than the other.
+    pub p_value: f64,
+    // Rank-biserial correlation, from -1 (every `a` is smaller than every `b`) to 1 (every `a` is
+    // larger).
+    pub effect_size: f64,
+}
+
+// Compares `a` and `b` without assuming anything about how they're distributed, which matters for
+// timings: they tend to have a long tail of runs interrupted by the scheduler. Uses the normal
+// approximation (with a tie correction), which is reasonable from about 8 samples each.
+pub fn mann_whitney(a: &[f64], b: &[f64]) -> Comparison {
+    let (n1, n2) = (a.len() as f64, b.len() as f64);
+    let n = n1 + n2;
+
+    let mut combined: Vec<(f64, bool)> = a
+        .iter()
+        .map(|&value| (value, true))
+        .chain(b.iter().map(|&value| (value, false)))
+        .collect();
+    combined.sort_by(|x, y| x.0.total_cmp(&y.0));
+
+    // Give tied values the average of the ranks they span.
+    let mut rank_sum_a = 0.0;
+    let mut tie_correction = 0.0;
+    let mut start = 0;
+    while start < combined.len() {
+        let end = start
+            + combined[start..]
+                .iter()
+                .take_while(|(value, _)| *value == combined[start].0)
+                .count();
+        let rank = (start + end + 1) as f64 / 2.0;
+        let ties = (end - start) as f64;
+
+        rank_sum_a += rank
+            * combined[start..end]
+                .iter()
+                .filter(|(_, in_a)| *in_a)
+                .count() as f64;
+        tie_correction += ties * ties * ties - ties;
+        start = end;
+    }
+
+    let u = rank_sum_a - n1 * (n1 + 1.0) / 2.0;
+    let mean = n1 * n2 / 2.0;
+    let variance = n1 * n2 / 12.0 * ((n + 1.0) - tie_correction / (n * (n - 1.0)));
+
+    let p_value = if variance > 0.0 {
+        // With continuity correction.
+        let z = ((u - mean).abs() - 0.5).max(0.0) / variance.sqrt();
+        erfc(z / std::f64::consts::SQRT_2)
+    } else {
+        1.0
+    };
+
+    Comparison {
+        p_value,
+        effect_size: 2.0 * u / (n1 * n2) - 1.0,
+    }
+}
+
+pub fn median(values: &[f64]) -> f64 {
+    let mut sorted = values.to_vec();
+    sorted.sort_by(f64::total_cmp);
+    let mid = sorted.len() / 2;
+    if sorted.len().is_multiple_of(2) {
+        (sorted[mid - 1] + sorted[mid]) / 2.0
+    } else {
+        sorted[mid]
+    }
+}
+
+// The value below which a fraction `p` of `values` lie, by the nearest-rank method.
+pub fn percentile(values: &[f64], p: f64) -> f64 {
+    let mut sorted = values.to_vec();
+    sorted.sort_by(f64::total_cmp);
+    let rank = (p * sorted.len() as f64).ceil() as usize;
+    sorted[rank.clamp(1, sorted.len()) - 1]
+}
+
+// The complementary error function for `x >= 0`, to within 1.5e-7 (Abramowitz and Stegun 7.1.26).
+fn erfc(x: f64) -> f64 {
+    let t = 1.0 / (1.0 + 0.3275911 * x);
+    let poly = t
+        * (0.254829592
+            + t * (-0.284496736 + t * (1.421413741 + t * (-1.453152027 + t * 1.061405429))));
+    poly * (-x * x).exp()
+}
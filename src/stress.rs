@@ -0,0 +1,389 @@
+use std::{
+    cell::UnsafeCell,
+    process, thread,
+    time::{Duration, Instant},
+};
+
+#[cfg(feature = "burns-lynch")]
+use bakery::burns_lynch::BurnsLynchLock;
+#[cfg(feature = "filter")]
+use bakery::filter::FilterLock;
+#[cfg(feature = "lamport-fast")]
+use bakery::lamport_fast::LamportFastLock;
+#[cfg(feature = "szymanski")]
+use bakery::szymanski::SzymanskiLock;
+#[cfg(feature = "ticket")]
+use bakery::ticket::TicketLock;
+#[cfg(feature = "ttas")]
+use bakery::ttas::TtasLock;
+#[cfg(feature = "black-white")]
+use bakery::BWBakeryLock;
+#[cfg(feature = "futex")]
+use bakery::FutexBakeryLock;
+#[cfg(feature = "hierarchical")]
+use bakery::HierarchicalBakeryLock;
+#[cfg(feature = "shm")]
+use bakery::ShmBakeryLock;
+use bakery::{
+    layout::{Compact, Fused, Packed, Padded, SlotLayout, Tracked},
+    Barrier, DynBakeryLock, NoObserver, RawBakeryLock,
+};
+
+use crate::{
+    output::{Format, Table},
+    stats, topology,
+    workers::{self, Profile},
+    UnsafeSyncCell,
+};
+
+const NUM_SLOTS: usize = 10;
+
+// Has `threads` workers count to `iterations` each through `lock` and `unlock`, following
+// `profile`, and returns how many updates were lost and how long it took.
+//
+// This deliberately uses the raw slot protocol rather than a guard: most of the algorithms have no
+// guard type, and every one of them has to run the same plain increment between its own `lock`
+// and `unlock`, so that one that fails to exclude shows up as lost updates.
+fn count(
+    threads: usize,
+    iterations: usize,
+    profile: Profile,
+    lock: impl Fn(usize) + Sync,
+    unlock: impl Fn(usize) + Sync,
+) -> (usize, Duration) {
+    let mut num = UnsafeSyncCell(UnsafeCell::new(0));
+    // Held until every worker is running, so that neither the first ones to be spawned get a head
+    // start nor thread creation ends up in the time.
+    let start_line = Barrier::new(threads + 1);
+
+    let start = thread::scope(|scope| {
+        for thread_id in 0..threads {
+            let (lock, unlock, num, start_line) = (&lock, &unlock, &num, &start_line);
+            workers::spawn(scope, "stress", thread_id, move |mut worker| {
+                start_line.wait();
+                for _ in 0..iterations {
+                    lock(worker.id);
+                    unsafe {
+                        *num.0.get() += 1;
+                    }
+                    profile.critical_section(&mut worker.rng);
+                    unlock(worker.id);
+                    profile.outside(&mut worker.rng);
+                }
+            });
+        }
+        start_line.wait();
+        Instant::now()
+    });
+
+    let elapsed = start.elapsed();
+
+    (threads * iterations - *num.0.get_mut(), elapsed)
+}
+
+// Runs `count` on a fresh bakery lock with layout `S`.
+fn lost_updates<S: SlotLayout<NUM_SLOTS> + Sync>(
+    threads: usize,
+    iterations: usize,
+    profile: Profile,
+) -> (usize, Duration) {
+    let lock = RawBakeryLock::from_parts(NoObserver, S::new());
+    let (lost, elapsed) = count(
+        threads,
+        iterations,
+        profile,
+        |thread| lock.lock(thread),
+        |thread| lock.unlock(thread),
+    );
+
+    // Everyone has left by now, so anything still in the slots is as much a bug as a lost update.
+    let snapshot = lock.snapshot();
+    if lost != 0 || !snapshot.is_idle() {
+        eprintln!("lock state after the round: {snapshot}");
+    }
+    (lost, elapsed)
+}
+
+// Runs `count` on a fresh black-white bakery lock.
+#[cfg(feature = "black-white")]
+fn black_white(threads: usize, iterations: usize, profile: Profile) -> (usize, Duration) {
+    let lock = BWBakeryLock::<NUM_SLOTS>::new();
+    count(
+        threads,
+        iterations,
+        profile,
+        |thread| lock.lock(thread),
+        |thread| lock.unlock(thread),
+    )
+}
+
+// Runs `count` on a fresh one-bit lock.
+#[cfg(feature = "burns-lynch")]
+fn burns_lynch(threads: usize, iterations: usize, profile: Profile) -> (usize, Duration) {
+    let lock = BurnsLynchLock::<NUM_SLOTS>::new();
+    count(
+        threads,
+        iterations,
+        profile,
+        |thread| lock.lock(thread),
+        |thread| lock.unlock(thread),
+    )
+}
+
+// Runs `count` on a fresh runtime-sized bakery lock with exactly as many slots as threads.
+fn dynamic(threads: usize, iterations: usize, profile: Profile) -> (usize, Duration) {
+    let lock = DynBakeryLock::new(threads);
+    count(
+        threads,
+        iterations,
+        profile,
+        |thread| lock.lock(thread),
+        |thread| lock.unlock(thread),
+    )
+}
+
+// Runs `count` on a fresh filter lock.
+#[cfg(feature = "filter")]
+fn filter(threads: usize, iterations: usize, profile: Profile) -> (usize, Duration) {
+    let lock = FilterLock::<NUM_SLOTS>::new();
+    count(
+        threads,
+        iterations,
+        profile,
+        |thread| lock.lock(thread),
+        |thread| lock.unlock(thread),
+    )
+}
+
+// Runs `count` on a fresh futex-backed bakery lock.
+#[cfg(feature = "futex")]
+fn futex(threads: usize, iterations: usize, profile: Profile) -> (usize, Duration) {
+    let lock = FutexBakeryLock::<NUM_SLOTS>::new();
+    count(
+        threads,
+        iterations,
+        profile,
+        |thread| lock.lock(thread),
+        |thread| lock.unlock(thread),
+    )
+}
+
+// Runs `count` on a fresh hierarchical bakery lock, with two slots to each of its groups.
+#[cfg(feature = "hierarchical")]
+fn hierarchical(threads: usize, iterations: usize, profile: Profile) -> (usize, Duration) {
+    let lock = HierarchicalBakeryLock::<{ NUM_SLOTS / 2 }, 2>::new();
+    count(
+        threads,
+        iterations,
+        profile,
+        |thread| lock.lock(thread),
+        |thread| lock.unlock(thread),
+    )
+}
+
+// Runs `count` on a fresh Lamport fast lock.
+#[cfg(feature = "lamport-fast")]
+fn lamport_fast(threads: usize, iterations: usize, profile: Profile) -> (usize, Duration) {
+    let lock = LamportFastLock::<NUM_SLOTS>::new();
+    count(
+        threads,
+        iterations,
+        profile,
+        |thread| lock.lock(thread),
+        |thread| lock.unlock(thread),
+    )
+}
+
+// Runs `count` on a shared-memory bakery lock placed in zeroed memory, the way a freshly mapped
+// region would hold it, with a slot claimed for every thread.
+#[cfg(feature = "shm")]
+fn shm(threads: usize, iterations: usize, profile: Profile) -> (usize, Duration) {
+    let mut region = vec![0u32; std::mem::size_of::<ShmBakeryLock<NUM_SLOTS>>() / 4];
+    // SAFETY: the region is zeroed, aligned for `u32`, and outlives the lock, which is the only
+    // thing that uses it.
+    let lock = unsafe { ShmBakeryLock::<NUM_SLOTS>::from_ptr(region.as_mut_ptr().cast()) };
+    for thread in 0..threads {
+        assert_eq!(lock.claim(process::id()), Some(thread));
+    }
+    count(
+        threads,
+        iterations,
+        profile,
+        |thread| lock.lock(thread),
+        |thread| lock.unlock(thread),
+    )
+}
+
+// Runs `count` on a fresh Szymanski lock.
+#[cfg(feature = "szymanski")]
+fn szymanski(threads: usize, iterations: usize, profile: Profile) -> (usize, Duration) {
+    let lock = SzymanskiLock::<NUM_SLOTS>::new();
+    count(
+        threads,
+        iterations,
+        profile,
+        |thread| lock.lock(thread),
+        |thread| lock.unlock(thread),
+    )
+}
+
+// Runs `count` on a fresh ticket spinlock, which ignores the slots.
+#[cfg(feature = "ticket")]
+fn ticket(threads: usize, iterations: usize, profile: Profile) -> (usize, Duration) {
+    let lock = TicketLock::new();
+    count(
+        threads,
+        iterations,
+        profile,
+        |_| lock.lock(),
+        |_| lock.unlock(),
+    )
+}
+
+// Runs `count` on a fresh TTAS spinlock, which ignores the slots.
+#[cfg(feature = "ttas")]
+fn ttas(threads: usize, iterations: usize, profile: Profile) -> (usize, Duration) {
+    let lock = TtasLock::new();
+    count(
+        threads,
+        iterations,
+        profile,
+        |_| lock.lock(),
+        |_| lock.unlock(),
+    )
+}
+
+// One round of the counter on one lock.
+type Round = fn(usize, usize, Profile) -> (usize, Duration);
+
+// The layouts every round runs on, along with the other bakery variants that are built.
+const LAYOUTS: &[(&str, Round)] = &[
+    ("packed", lost_updates::<Packed<NUM_SLOTS>>),
+    ("padded", lost_updates::<Padded<NUM_SLOTS>>),
+    ("compact", lost_updates::<Compact<NUM_SLOTS>>),
+    ("fused", lost_updates::<Fused<NUM_SLOTS>>),
+    (
+        "tracked",
+        lost_updates::<Tracked<NUM_SLOTS, Packed<NUM_SLOTS>>>,
+    ),
+    ("dynamic", dynamic),
+    #[cfg(feature = "black-white")]
+    ("black-white", black_white),
+    #[cfg(feature = "burns-lynch")]
+    ("burns-lynch", burns_lynch),
+    #[cfg(feature = "filter")]
+    ("filter", filter),
+    #[cfg(feature = "futex")]
+    ("futex", futex),
+    #[cfg(feature = "hierarchical")]
+    ("hierarchical", hierarchical),
+    #[cfg(feature = "lamport-fast")]
+    ("lamport-fast", lamport_fast),
+    #[cfg(feature = "shm")]
+    ("shm", shm),
+    #[cfg(feature = "szymanski")]
+    ("szymanski", szymanski),
+    #[cfg(feature = "ticket")]
+    ("ticket", ticket),
+    #[cfg(feature = "ttas")]
+    ("ttas", ttas),
+];
+
+fn usage() -> ! {
+    eprintln!(
+        "usage: bakery stress [--rounds <n>] [--threads <n>] [--iterations <n>] [--cs-work <n>] \
+         [--outside <n>] [--jitter <percent>] [--output <text|json|csv>]"
+    );
+    process::exit(2);
+}
+
+// Repeats the counter on every layout for a number of rounds and fails if any of them lost an
+// update, which is the quickest way to check a build of the lock (or a fence configuration) on a
+// new machine.
+pub fn run(args: &[String]) {
+    let topology = topology::detect();
+    let mut rounds = 10;
+    let mut threads = topology.physical_cores.clamp(2, NUM_SLOTS);
+    let mut iterations = 20000;
+    let mut profile = Profile::default();
+    let mut format = Format::Text;
+    let mut args = args;
+    while let [flag, value, rest @ ..] = args {
+        if flag == "--output" {
+            format = Format::from_name(value).unwrap_or_else(|| usage());
+            args = rest;
+            continue;
+        }
+        let value = value.parse().unwrap_or_else(|_| usage());
+        match flag.as_str() {
+            "--rounds" => rounds = value as usize,
+            "--threads" if (1..=NUM_SLOTS as u64).contains(&value) => threads = value as usize,
+            "--iterations" => iterations = value as usize,
+            "--cs-work" => profile.critical_section = value,
+            "--outside" => profile.outside = value,
+            "--jitter" if value <= 100 => profile.jitter = value,
+            _ => usage(),
+        }
+        args = rest;
+    }
+    if !args.is_empty() {
+        usage();
+    }
+
+    if format.is_text() {
+        println!(
+            "{rounds} rounds of {threads} threads, {iterations} iterations each ({topology}), \
+             {} spins inside the lock and {} outside (±{}%)",
+            profile.critical_section, profile.outside, profile.jitter
+        );
+    }
+    let table = Table::new(
+        format,
+        &[
+            "layout",
+            "rounds",
+            "threads",
+            "iterations",
+            "cs_work",
+            "outside",
+            "jitter",
+            "failed_rounds",
+            "lost_updates",
+            "median_round_ms",
+            "ns_per_acquisition",
+        ],
+    );
+
+    let mut failed = false;
+    for &(name, lost_updates) in LAYOUTS {
+        let (lost, elapsed): (Vec<_>, Vec<_>) = (0..rounds)
+            .map(|_| lost_updates(threads, iterations, profile))
+            .unzip();
+        let bad_rounds = lost.iter().filter(|&&lost| lost > 0).count();
+        let lost = lost.iter().sum::<usize>();
+        let elapsed: Vec<_> = elapsed.iter().map(Duration::as_secs_f64).collect();
+        let median = stats::median(&elapsed);
+
+        if format.is_text() {
+            println!("{name:<12} {bad_rounds} of {rounds} rounds lost updates, {lost} in total");
+        }
+        table.row([
+            name.into(),
+            rounds.into(),
+            threads.into(),
+            iterations.into(),
+            profile.critical_section.into(),
+            profile.outside.into(),
+            profile.jitter.into(),
+            bad_rounds.into(),
+            lost.into(),
+            (median * 1e3).into(),
+            (median * 1e9 / (threads * iterations) as f64).into(),
+        ]);
+        failed |= bad_rounds > 0;
+    }
+
+    if failed {
+        process::exit(1);
+    }
+}
@@ -0,0 +1,121 @@
+use core::sync::atomic::{self, AtomicU8, Ordering};
+
+use crate::{spin, SlotLock};
+
+// The states of a slot's flag, in the order a thread goes through them.
+//
+// Not trying to enter.
+const IDLE: u8 = 0;
+// Waiting outside for the door to the waiting room to open.
+const OUTSIDE: u8 = 1;
+// In the waiting room, waiting for someone to close the door behind the threads that were outside.
+const WAITING: u8 = 2;
+// In the doorway.
+const DOORWAY: u8 = 3;
+// Behind the closed door, waiting for the threads with lower slots to leave, or in the critical
+// section.
+const INSIDE: u8 = 4;
+
+/// Szymanski's lock for up to `N` threads.
+///
+/// Threads enter in batches through a waiting room with a door. A thread waits outside until the
+/// door is open, steps into the doorway, and waits in the room if anyone else is still outside, so
+/// that everyone who arrived together gets in before the door closes. The first of them to find
+/// nobody outside closes it, and then they take the critical section in slot order, while
+/// everyone arriving later waits for the whole batch to leave. Nobody waits for a thread that
+/// arrived after it completed the doorway (linear wait), and every acquisition takes a fixed number
+/// of writes, all of them to the thread's own flag. Like the bakery lock, it only needs loads and
+/// stores, but its flags are bounded, with five states each.
+pub struct SzymanskiLock<const N: usize> {
+    flags: [AtomicU8; N],
+}
+
+impl<const N: usize> SzymanskiLock<N> {
+    /// An unlocked lock.
+    pub const fn new() -> Self {
+        Self {
+            flags: [const { AtomicU8::new(IDLE) }; N],
+        }
+    }
+
+    /// Waits for a batch to let `thread` in and for its turn in that batch, and enters the
+    /// critical section.
+    pub fn lock(&self, thread: usize) {
+        assert!(thread < N, "slot out of range");
+
+        self.set(thread, OUTSIDE);
+        self.wait_for_all(0..N, |flag| flag < DOORWAY);
+
+        // Our own flag is never the one these look for.
+        self.set(thread, DOORWAY);
+        if (0..N).any(|other| self.flag(other) == OUTSIDE) {
+            self.set(thread, WAITING);
+            while !(0..N).any(|other| self.flag(other) == INSIDE) {
+                spin::relax();
+            }
+        }
+
+        self.set(thread, INSIDE);
+        self.wait_for_all(0..thread, |flag| flag < WAITING);
+
+        // Synchronizes-with the release store that the thread that held the lock before us made
+        // leaving it, or a later one of its: from the same batch, it had a lower slot and we just
+        // saw it leave, and otherwise we saw it leave before the door opened for us.
+        atomic::fence(Ordering::Acquire);
+    }
+
+    /// Leaves the critical section entered with `lock(thread)`, once the rest of the batch has seen
+    /// the door closed.
+    pub fn unlock(&self, thread: usize) {
+        // Threads of our batch with higher slots that are still in the doorway or the waiting room
+        // haven't seen the door closed yet. If we left first, they could find nobody inside and
+        // wait for someone to close it forever, while their flags keep everyone else out.
+        self.wait_for_all(thread + 1..N, |flag| !(WAITING..=DOORWAY).contains(&flag));
+        self.flags[thread].store(IDLE, Ordering::Release);
+    }
+
+    fn set(&self, thread: usize, state: u8) {
+        // Release, like the stores in `FilterLock::arrive`: a thread that moves on because of one of
+        // our later states must see the critical section we last left.
+        self.flags[thread].store(state, Ordering::Release);
+        // Every state is followed by looking at the other threads' flags, which needs the new state
+        // visible first, or two threads can each miss the other's: both see the door open and step
+        // through without waiting for each other, or both close the door and both enter. This is
+        // the store-buffering pattern again, and the same fence that rules it out for
+        // `PetersonLock` does here.
+        atomic::fence(Ordering::SeqCst);
+    }
+
+    fn flag(&self, slot: usize) -> u8 {
+        self.flags[slot].load(Ordering::Relaxed)
+    }
+
+    // Waits until every slot in `slots` has a flag that satisfies `ok`, one slot after the other.
+    fn wait_for_all(&self, slots: core::ops::Range<usize>, ok: impl Fn(u8) -> bool) {
+        for slot in slots {
+            while !ok(self.flag(slot)) {
+                spin::relax();
+            }
+        }
+    }
+}
+
+impl<const N: usize> Default for SzymanskiLock<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<const N: usize> SlotLock for SzymanskiLock<N> {
+    fn slots(&self) -> usize {
+        N
+    }
+
+    fn lock(&self, slot: usize) {
+        SzymanskiLock::lock(self, slot);
+    }
+
+    fn unlock(&self, slot: usize) {
+        SzymanskiLock::unlock(self, slot);
+    }
+}
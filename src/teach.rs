@@ -0,0 +1,91 @@
+use std::{sync::Mutex, thread, time::Duration};
+
+use bakery::{BakeryMutex, Event, Observer, RawBakeryLock};
+
+use crate::workers;
+
+const NUM_THREADS: usize = 3;
+const ITERATIONS: usize = 2;
+
+// Injected after every event so that the progression can be followed by eye.
+const STEP_DELAY: Duration = Duration::from_millis(300);
+
+// Narrates every event of the lock and slows the thread generating it down.
+struct Teacher<const N: usize> {
+    // The last event reported by each thread, used to avoid repeating the same observation while a
+    // thread spins.
+    last: Mutex<[Option<Event>; N]>,
+}
+
+impl<const N: usize> Observer for Teacher<N> {
+    fn on_event(&self, thread: usize, event: Event) {
+        let repeated = {
+            let mut last = self.last.lock().unwrap();
+            last[thread].replace(event) == Some(event)
+        };
+
+        if !repeated {
+            println!("{}", describe(thread, event));
+        }
+
+        thread::sleep(STEP_DELAY);
+    }
+}
+
+pub fn describe(thread: usize, event: Event) -> String {
+    match event {
+        Event::Doorway => format!("thread {thread} enters the doorway (choosing[{thread}]=true)"),
+        Event::TicketWrapped => {
+            format!("thread {thread} found the largest ticket taken, wraps around to ticket 1")
+        }
+        Event::Ticket(ticket) => {
+            format!("thread {thread} takes ticket {ticket} and leaves the doorway")
+        }
+        Event::WaitChoosing { other } => {
+            format!("thread {thread} saw choosing[{other}]=true, waits for it to pick a ticket")
+        }
+        Event::WaitTicket { other, ticket } => {
+            format!("thread {thread} saw ticket[{other}]={ticket}, keeps waiting")
+        }
+        Event::Passed { other, ticket: 0 } => {
+            format!("thread {thread} saw ticket[{other}]=0, moves on")
+        }
+        Event::Passed { other, ticket } => {
+            format!("thread {thread} saw ticket[{other}]={ticket}, goes first and moves on")
+        }
+        Event::GaveUp => format!("thread {thread} won't wait, withdraws from the bakery"),
+        Event::Acquired => format!("thread {thread} enters its critical section"),
+        Event::Released => format!("thread {thread} leaves its critical section"),
+    }
+}
+
+// Runs a tiny, heavily slowed-down version of the counter demo with every step of the algorithm
+// narrated.
+pub fn run() {
+    let counter = BakeryMutex::from_raw(
+        RawBakeryLock::<NUM_THREADS, _>::with_observer(Teacher {
+            last: Mutex::new([None; NUM_THREADS]),
+        }),
+        0,
+    );
+
+    println!("teaching run with seed {}", workers::seed());
+
+    thread::scope(|scope| {
+        for thread_id in 0..NUM_THREADS {
+            let counter = &counter;
+            workers::spawn(scope, "teach", thread_id, move |mut worker| {
+                let mut slot = counter.register().expect("more workers than slots");
+                for _ in 0..ITERATIONS {
+                    let mut num = slot.lock();
+                    *num += 1;
+                    // Hold the lock for a random number of steps so that different seeds lead to
+                    // different interleavings.
+                    thread::sleep(STEP_DELAY * (1 + worker.rng.below(3) as u32));
+                }
+            });
+        }
+    });
+
+    println!("counted to {}", counter.into_inner());
+}
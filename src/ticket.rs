@@ -0,0 +1,83 @@
+use core::sync::atomic::{AtomicU32, Ordering};
+
+use crate::{spin, SlotLock};
+
+/// A ticket spinlock, the bakery lock as hardware with an atomic increment would have it.
+///
+/// A thread takes the next ticket with a single `fetch_add` and waits for the number being served
+/// to reach it, and leaving serves the next one. That's first come, first served like the bakery,
+/// with two words of state however many threads there are, no slots, and an uncontended
+/// acquisition in one read-modify-write. It's the lock the bakery algorithm sets out to do without:
+/// it needs atomic read-modify-write operations, which are what cores like Cortex-M0 lack, and
+/// every waiter spins on the same cache line, which the holder writes on every unlock.
+pub struct TicketLock {
+    next: AtomicU32,
+    serving: AtomicU32,
+}
+
+impl TicketLock {
+    /// An unlocked lock.
+    pub const fn new() -> Self {
+        Self {
+            next: AtomicU32::new(0),
+            serving: AtomicU32::new(0),
+        }
+    }
+
+    /// Takes a ticket, waits for it to be served, and enters the critical section.
+    pub fn lock(&self) {
+        // Tickets wrap around, which only matters with 2^32 threads waiting at once.
+        let ticket = self.next.fetch_add(1, Ordering::Relaxed);
+        // Acquire, to synchronize-with the release store in the `unlock` that served our ticket.
+        while self.serving.load(Ordering::Acquire) != ticket {
+            spin::relax();
+        }
+    }
+
+    /// Enters the critical section if nobody holds or is waiting for the lock, returning whether it
+    /// did.
+    pub fn try_lock(&self) -> bool {
+        // Acquire, to synchronize-with the release store in the last `unlock`, if the exchange
+        // below shows that nobody has taken a ticket since.
+        let serving = self.serving.load(Ordering::Acquire);
+        // Takes the next ticket only if it's the one being served.
+        self.next
+            .compare_exchange(
+                serving,
+                serving.wrapping_add(1),
+                Ordering::Relaxed,
+                Ordering::Relaxed,
+            )
+            .is_ok()
+    }
+
+    /// Leaves the critical section, serving the next ticket.
+    pub fn unlock(&self) {
+        // Only the holder writes `serving`, so this doesn't need to be a read-modify-write.
+        let serving = self.serving.load(Ordering::Relaxed);
+        self.serving
+            .store(serving.wrapping_add(1), Ordering::Release);
+    }
+}
+
+impl Default for TicketLock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// Threads don't need slots, so every slot is the same, and there are as many as anyone could ask
+// for.
+impl SlotLock for TicketLock {
+    fn slots(&self) -> usize {
+        usize::MAX
+    }
+
+    fn lock(&self, _slot: usize) {
+        TicketLock::lock(self);
+    }
+
+    fn unlock(&self, _slot: usize) {
+        TicketLock::unlock(self);
+    }
+}
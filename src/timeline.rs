@@ -0,0 +1,169 @@
+use std::{fmt::Write as _, fs, mem, sync::Mutex, thread, time::Duration};
+
+use bakery::{BakeryMutex, Event, Observer, RawBakeryLock};
+
+use crate::{
+    clock::{Clock, ClockSource},
+    workers,
+};
+
+const NUM_THREADS: usize = 10;
+const ITERATIONS: usize = 20;
+
+const WIDTH: usize = 72;
+
+// A single acquisition, from entering the doorway to unlocking, in nanoseconds since the start of
+// the run.
+struct Span {
+    start: u64,
+    ticket: u64,
+    acquired: u64,
+    released: u64,
+}
+
+#[derive(Default)]
+struct ThreadLog {
+    start: Option<u64>,
+    ticket: Option<u64>,
+    acquired: Option<u64>,
+    spans: Vec<Span>,
+}
+
+// Records when each thread starts waiting, acquires and releases the lock.
+struct Recorder<const N: usize> {
+    clock: Clock,
+    logs: [Mutex<ThreadLog>; N],
+}
+
+impl<const N: usize> Observer for Recorder<N> {
+    fn on_event(&self, thread: usize, event: Event) {
+        let now = self.clock.now();
+        let mut log = self.logs[thread].lock().unwrap();
+
+        match event {
+            Event::Doorway => log.start = Some(now),
+            Event::Ticket(_) => log.ticket = Some(now),
+            // Only acquisitions are drawn.
+            Event::GaveUp => {
+                log.start = None;
+                log.ticket = None;
+            }
+            Event::Acquired => log.acquired = Some(now),
+            Event::Released => {
+                let start = log.start.take().unwrap();
+                let acquired = log.acquired.take().unwrap();
+                // A thread handed the lock before it even got a ticket never left the doorway.
+                let ticket = log.ticket.take().unwrap_or(acquired);
+                log.spans.push(Span {
+                    start,
+                    ticket,
+                    acquired,
+                    released: now,
+                });
+            }
+            _ => {}
+        }
+    }
+}
+
+// Index of the column covering `offset` into a run of length `total`.
+fn column(offset: u64, total: u64) -> usize {
+    let column = offset as f64 / total.max(1) as f64 * WIDTH as f64;
+    (column as usize).min(WIDTH - 1)
+}
+
+fn render(logs: &[ThreadLog], epoch: u64, total: u64) {
+    println!(
+        "{:>6} |{}| {:.2?}",
+        "thread",
+        "-".repeat(WIDTH),
+        Duration::from_nanos(total)
+    );
+
+    for (thread, log) in logs.iter().enumerate() {
+        let mut row = [' '; WIDTH];
+
+        // Waiting first, so that holding wins any column shared between the two.
+        for span in &log.spans {
+            let from = column(span.start.saturating_sub(epoch), total);
+            let to = column(span.acquired.saturating_sub(epoch), total);
+            row[from..=to].fill('░');
+        }
+        for span in &log.spans {
+            let from = column(span.acquired.saturating_sub(epoch), total);
+            let to = column(span.released.saturating_sub(epoch), total);
+            row[from..=to].fill('█');
+        }
+
+        println!("{thread:>6} |{}|", row.iter().collect::<String>());
+    }
+
+    println!("{:>6}  ░ waiting  █ holding", "");
+}
+
+// Writes the time each thread spent in the doorway, waiting and in its critical section as folded
+// stacks (one `frame;frame;... <weight>` line per stack, weighted in nanoseconds), which
+// `inferno-flamegraph` and `flamegraph.pl` turn into a flame graph.
+fn write_folded(path: &str, logs: &[ThreadLog]) {
+    let mut folded = String::new();
+    for (thread, log) in logs.iter().enumerate() {
+        let total = |phase: fn(&Span) -> u64| log.spans.iter().map(phase).sum::<u64>();
+        let phases = [
+            ("lock;doorway", total(|span| span.ticket - span.start)),
+            ("lock;waiting", total(|span| span.acquired - span.ticket)),
+            (
+                "critical section",
+                total(|span| span.released - span.acquired),
+            ),
+        ];
+        for (phase, nanos) in phases {
+            let _ = writeln!(folded, "timeline-{thread};{phase} {nanos}");
+        }
+    }
+
+    if let Err(err) = fs::write(path, folded) {
+        eprintln!("failed to write {path}: {err}");
+    }
+}
+
+// Runs a short version of the counter demo and draws when each thread was waiting for or holding
+// the lock, using timestamps from `source`, optionally also writing folded stacks to `flame`.
+pub fn run(source: ClockSource, flame: Option<&str>) {
+    let clock = Clock::new(source);
+    let counter = BakeryMutex::from_raw(
+        RawBakeryLock::<NUM_THREADS, _>::with_observer(Recorder::<NUM_THREADS> {
+            clock,
+            logs: std::array::from_fn(|_| Mutex::default()),
+        }),
+        0,
+    );
+
+    let epoch = clock.now();
+    thread::scope(|scope| {
+        for thread_id in 0..NUM_THREADS {
+            let counter = &counter;
+            workers::spawn(scope, "timeline", thread_id, move |_| {
+                let mut slot = counter.register().expect("more workers than slots");
+                for _ in 0..ITERATIONS {
+                    *slot.lock() += 1;
+                }
+            });
+        }
+    });
+    let total = clock.now() - epoch;
+
+    // Nobody is recording any more, so the logs can be taken out from behind their mutexes.
+    let logs: Vec<_> = counter
+        .observer()
+        .logs
+        .iter()
+        .map(|log| mem::take(&mut *log.lock().unwrap()))
+        .collect();
+    println!("clock: {clock}");
+    render(&logs, epoch, total);
+    if let Some(flame) = flame {
+        write_folded(flame, &logs);
+    }
+
+    println!("{}", counter.into_inner());
+}
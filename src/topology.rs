@@ -0,0 +1,117 @@
+use std::{collections::BTreeSet, fmt, fs, thread};
+
+// The shape of the machine we're running on. Where and how often reorderings become visible
+// depends heavily on this, so it's recorded alongside results.
+#[derive(Clone, Copy)]
+pub struct Topology {
+    pub sockets: usize,
+    pub physical_cores: usize,
+    pub logical_cpus: usize,
+}
+
+// Formats as e.g. `1s8c16t` (sockets, physical cores, hardware threads).
+impl fmt::Display for Topology {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}s{}c{}t",
+            self.sockets, self.physical_cores, self.logical_cpus
+        )
+    }
+}
+
+// Reads the topology from sysfs where possible, falling back to treating every available CPU as
+// an independent core on a single socket.
+pub fn detect() -> Topology {
+    detect_sysfs().unwrap_or_else(|| {
+        let cpus = thread::available_parallelism().map_or(1, |cpus| cpus.get());
+        Topology {
+            sockets: 1,
+            physical_cores: cpus,
+            logical_cpus: cpus,
+        }
+    })
+}
+
+fn detect_sysfs() -> Option<Topology> {
+    let cpus = cpus()?;
+
+    Some(Topology {
+        sockets: cpus
+            .iter()
+            .map(|cpu| cpu.package)
+            .collect::<BTreeSet<_>>()
+            .len(),
+        physical_cores: cpus
+            .iter()
+            .map(|cpu| (cpu.package, cpu.core))
+            .collect::<BTreeSet<_>>()
+            .len(),
+        logical_cpus: cpus.len(),
+    })
+}
+
+// Where a single online CPU sits in the machine.
+#[derive(Clone, Copy)]
+pub struct Cpu {
+    pub id: usize,
+    pub package: i64,
+    pub core: i64,
+    // The NUMA node, or 0 if the kernel doesn't report one.
+    pub node: usize,
+}
+
+// Lists the online CPUs from sysfs, ordered by id, or `None` if sysfs isn't available.
+pub fn cpus() -> Option<Vec<Cpu>> {
+    let mut cpus = Vec::new();
+
+    for entry in fs::read_dir("/sys/devices/system/cpu").ok()? {
+        let entry = entry.ok()?;
+        let name = entry.file_name();
+        let Some(Ok(id)) = name.to_str()?.strip_prefix("cpu").map(str::parse) else {
+            continue;
+        };
+
+        // Offline CPUs have no topology information.
+        let topology = entry.path().join("topology");
+        let read = |file| -> Option<i64> {
+            fs::read_to_string(topology.join(file))
+                .ok()?
+                .trim()
+                .parse()
+                .ok()
+        };
+        let (Some(package), Some(core)) = (read("physical_package_id"), read("core_id")) else {
+            continue;
+        };
+
+        // The node shows up as a `node<n>` link next to `topology`.
+        let node = fs::read_dir(entry.path())
+            .ok()?
+            .filter_map(|entry| {
+                entry
+                    .ok()?
+                    .file_name()
+                    .to_str()?
+                    .strip_prefix("node")?
+                    .parse()
+                    .ok()
+            })
+            .next()
+            .unwrap_or(0);
+
+        cpus.push(Cpu {
+            id,
+            package,
+            core,
+            node,
+        });
+    }
+
+    if cpus.is_empty() {
+        return None;
+    }
+
+    cpus.sort_by_key(|cpu| cpu.id);
+    Some(cpus)
+}
@@ -0,0 +1,108 @@
+use std::{
+    fmt::Write as _,
+    fs, io,
+    sync::atomic::{AtomicU64, AtomicUsize, Ordering},
+};
+
+use bakery::{Event, Observer};
+
+use crate::{
+    bundle,
+    clock::{Clock, ClockSource},
+    teach,
+};
+
+// How many of its most recent events each thread keeps.
+const PER_THREAD: usize = 1024;
+
+// One thread's events, written only by that thread, so recording takes no more than a couple of
+// relaxed stores.
+struct ThreadTrace {
+    // Events packed by `bundle::pack`, and when they happened in nanoseconds since the start of
+    // the trace.
+    events: Box<[AtomicU64]>,
+    times: Box<[AtomicU64]>,
+    len: AtomicUsize,
+    // The last event recorded, so that a thread spinning on the same slot is only recorded once.
+    last: AtomicU64,
+}
+
+impl ThreadTrace {
+    fn new() -> Self {
+        Self {
+            events: (0..PER_THREAD).map(|_| AtomicU64::new(0)).collect(),
+            times: (0..PER_THREAD).map(|_| AtomicU64::new(0)).collect(),
+            len: AtomicUsize::new(0),
+            last: AtomicU64::new(0),
+        }
+    }
+}
+
+// Records timestamped events per thread, for working out afterwards why a thread stalled or got
+// overtaken. Unlike `bundle::EventRing`, every thread keeps its own recent history, so one busy
+// thread can't push everyone else's events out.
+pub struct TraceRecorder<const N: usize> {
+    clock: Clock,
+    threads: [ThreadTrace; N],
+}
+
+impl<const N: usize> TraceRecorder<N> {
+    pub fn new() -> Self {
+        // The cycle counter is by far the cheapest to read, where there is one.
+        let source = if ClockSource::Counter.is_available() {
+            ClockSource::Counter
+        } else {
+            ClockSource::Instant
+        };
+        Self {
+            clock: Clock::new(source),
+            threads: std::array::from_fn(|_| ThreadTrace::new()),
+        }
+    }
+
+    // Describes every thread's recorded events, merged into a single timeline. Only meaningful
+    // once the threads have stopped.
+    pub fn dump(&self) -> String {
+        let mut events: Vec<(u64, usize, Event)> = Vec::new();
+        for trace in &self.threads {
+            let len = trace.len.load(Ordering::Relaxed);
+            for index in len.saturating_sub(PER_THREAD)..len {
+                let (thread, event) =
+                    bundle::unpack(trace.events[index % PER_THREAD].load(Ordering::Relaxed));
+                let time = trace.times[index % PER_THREAD].load(Ordering::Relaxed);
+                events.push((time, thread, event));
+            }
+        }
+        events.sort_by_key(|&(time, thread, _)| (time, thread));
+
+        let mut dump = format!("clock: {}\n", self.clock);
+        for (time, thread, event) in events {
+            let _ = writeln!(
+                dump,
+                "{:>14.3}us  {}",
+                time as f64 / 1e3,
+                teach::describe(thread, event)
+            );
+        }
+        dump
+    }
+
+    pub fn write(&self, path: &str) -> io::Result<()> {
+        fs::write(path, self.dump())
+    }
+}
+
+impl<const N: usize> Observer for TraceRecorder<N> {
+    fn on_event(&self, thread: usize, event: Event) {
+        let trace = &self.threads[thread];
+        let word = bundle::pack(thread, event);
+        if trace.last.swap(word, Ordering::Relaxed) == word {
+            return;
+        }
+
+        let index = trace.len.load(Ordering::Relaxed);
+        trace.events[index % PER_THREAD].store(word, Ordering::Relaxed);
+        trace.times[index % PER_THREAD].store(self.clock.now(), Ordering::Relaxed);
+        trace.len.store(index + 1, Ordering::Relaxed);
+    }
+}
@@ -0,0 +1,90 @@
+use core::{
+    marker::PhantomData,
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+use crate::{
+    backoff::{Backoff, Exponential},
+    spin, SlotLock,
+};
+
+/// A test-and-test-and-set spinlock, whose waiters back off according to `B` after losing a race
+/// for it.
+///
+/// A waiter reads the lock until it's free, which keeps the cache line shared between all of them
+/// while it's held, and only then tries to take it with a `swap`. When it's released, every waiter
+/// sees it at once and they all try; one wins, and the rest back off before they go back to
+/// reading, so that the winner's cache line isn't fought over straight away. It's the fastest
+/// thing to deploy when atomic read-modify-writes are cheap, and it makes no promise about who gets
+/// the lock next: a thread can lose every race, however long it has been waiting.
+pub struct TtasLock<B = Exponential> {
+    locked: AtomicBool,
+    backoff: PhantomData<fn() -> B>,
+}
+
+impl TtasLock {
+    /// An unlocked lock with exponential backoff.
+    pub const fn new() -> Self {
+        Self {
+            locked: AtomicBool::new(false),
+            backoff: PhantomData,
+        }
+    }
+}
+
+impl Default for TtasLock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<B: Backoff> TtasLock<B> {
+    /// The same lock with waiters backing off according to `B2`, as with
+    /// [`RawBakeryLock::with_backoff`](crate::RawBakeryLock::with_backoff).
+    pub fn with_backoff<B2: Backoff>(self) -> TtasLock<B2> {
+        TtasLock {
+            locked: self.locked,
+            backoff: PhantomData,
+        }
+    }
+
+    /// Waits until the lock is free, takes it, and enters the critical section.
+    pub fn lock(&self) {
+        let mut backoff = B::new();
+        loop {
+            while self.locked.load(Ordering::Relaxed) {
+                spin::relax();
+            }
+            // Acquire, to synchronize-with the release store in the `unlock` that freed it.
+            if !self.locked.swap(true, Ordering::Acquire) {
+                return;
+            }
+            backoff.snooze();
+        }
+    }
+
+    /// Enters the critical section if the lock is free, returning whether it did.
+    pub fn try_lock(&self) -> bool {
+        !self.locked.load(Ordering::Relaxed) && !self.locked.swap(true, Ordering::Acquire)
+    }
+
+    /// Leaves the critical section.
+    pub fn unlock(&self) {
+        self.locked.store(false, Ordering::Release);
+    }
+}
+
+// Like `TicketLock`, this doesn't need slots.
+impl<B: Backoff> SlotLock for TtasLock<B> {
+    fn slots(&self) -> usize {
+        usize::MAX
+    }
+
+    fn lock(&self, _slot: usize) {
+        TtasLock::lock(self);
+    }
+
+    fn unlock(&self, _slot: usize) {
+        TtasLock::unlock(self);
+    }
+}
@@ -0,0 +1,122 @@
+use std::{
+    fmt::Write as _,
+    io::{self, Write as _},
+    sync::atomic::{AtomicUsize, Ordering},
+    thread,
+    time::Duration,
+};
+
+use bakery::BakeryMutex;
+
+use crate::estimate::{self, HoldTimer};
+
+const REFRESH_INTERVAL: Duration = Duration::from_millis(100);
+
+#[derive(Clone, Copy)]
+enum SlotState {
+    Idle,
+    Doorway,
+    Waiting,
+    Holding,
+}
+
+impl SlotState {
+    fn name(self) -> &'static str {
+        match self {
+            SlotState::Idle => "idle",
+            SlotState::Doorway => "doorway",
+            SlotState::Waiting => "waiting",
+            SlotState::Holding => "holding",
+        }
+    }
+
+    // ANSI SGR color used to render the state.
+    fn color(self) -> u8 {
+        match self {
+            SlotState::Idle => 90,
+            SlotState::Doorway => 33,
+            SlotState::Waiting => 36,
+            SlotState::Holding => 32,
+        }
+    }
+}
+
+// Redraws the state of every slot in `lock` until all `threads` worker threads have bumped
+// `finished`.
+pub fn run<T, const N: usize>(
+    lock: &BakeryMutex<T, N, HoldTimer<N>>,
+    finished: &AtomicUsize,
+    threads: usize,
+) {
+    let mut frame = String::new();
+    let mut stdout = io::stdout();
+
+    loop {
+        // Sample this before the slots so the final frame reflects the quiescent lock.
+        let done = finished.load(Ordering::Relaxed);
+
+        render(lock, done, threads, &mut frame);
+        let _ = stdout.write_all(frame.as_bytes());
+        let _ = stdout.flush();
+
+        if done == threads {
+            break;
+        }
+
+        thread::sleep(REFRESH_INTERVAL);
+    }
+}
+
+fn render<T, const N: usize>(
+    lock: &BakeryMutex<T, N, HoldTimer<N>>,
+    done: usize,
+    threads: usize,
+    frame: &mut String,
+) {
+    // The snapshot may be slightly inconsistent while the lock is busy; that's good enough to
+    // follow the lock by eye.
+    let snapshot = lock.snapshot();
+    let (choosing, ticket) = (snapshot.choosing, snapshot.tickets);
+
+    // Out of all slots that have finished choosing, the one with the minimal `(ticket, slot)` is
+    // allowed into its critical section.
+    let holder = (0..N)
+        .filter(|&slot| !choosing[slot] && ticket[slot] != 0)
+        .min_by_key(|&slot| (ticket[slot], slot));
+
+    frame.clear();
+    frame.push_str("\x1b[2J\x1b[H");
+    let _ = writeln!(
+        frame,
+        "bakery: {done}/{threads} threads finished, mean hold {:.2?}\n",
+        lock.observer().mean_hold()
+    );
+    let _ = writeln!(frame, "slot  choosing      ticket  state    est. wait");
+
+    for slot in 0..N {
+        let state = if choosing[slot] {
+            SlotState::Doorway
+        } else if ticket[slot] == 0 {
+            SlotState::Idle
+        } else if Some(slot) == holder {
+            SlotState::Holding
+        } else {
+            SlotState::Waiting
+        };
+
+        let estimate = match state {
+            SlotState::Waiting => estimate::estimated_wait(lock, slot)
+                .map_or(String::new(), |wait| format!("{wait:.2?}")),
+            _ => String::new(),
+        };
+
+        let _ = writeln!(
+            frame,
+            "{slot:>4}  {:>8}  {:>10}  \x1b[{}m{:<7}\x1b[0m  {estimate:>9}",
+            if choosing[slot] { "yes" } else { "no" },
+            ticket[slot],
+            state.color(),
+            state.name(),
+        );
+    }
+}
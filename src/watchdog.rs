@@ -0,0 +1,260 @@
+use std::{
+    fmt::Write as _,
+    sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering},
+    thread,
+    time::{Duration, Instant},
+};
+
+use bakery::{Event, LockSnapshot, Observer};
+
+// Catches lost updates to the shared counter as they happen instead of at the end of the run.
+//
+// Every thread keeps its own tally of increments next to the shared counter. Both are only
+// updated inside the critical section, so as long as the lock provides mutual exclusion their sum
+// matches the counter whenever the lock is held.
+pub struct CounterWatchdog {
+    // How many of its own acquisitions a thread makes between checks.
+    interval: usize,
+    tallies: Box<[AtomicUsize]>,
+    // Lost updates reported so far, so that each new loss is only reported once.
+    reported_loss: AtomicUsize,
+}
+
+impl CounterWatchdog {
+    // A watchdog for `threads` threads, `0..threads`.
+    pub fn new(interval: usize, threads: usize) -> Self {
+        assert!(interval > 0, "watchdog interval must be positive");
+
+        Self {
+            interval,
+            tallies: (0..threads).map(|_| AtomicUsize::new(0)).collect(),
+            reported_loss: AtomicUsize::new(0),
+        }
+    }
+
+    // Must be called with the lock held, right after `thread` has incremented the counter to
+    // `count`.
+    pub fn record(&self, thread: usize, count: usize) {
+        // Only `thread` ever writes its own tally.
+        let tally = self.tallies[thread].load(Ordering::Relaxed) + 1;
+        self.tallies[thread].store(tally, Ordering::Relaxed);
+
+        if !tally.is_multiple_of(self.interval) {
+            return;
+        }
+
+        // The lock orders these loads after every earlier critical section, just like the
+        // accesses to the counter itself.
+        let total: usize = self
+            .tallies
+            .iter()
+            .map(|tally| tally.load(Ordering::Relaxed))
+            .sum();
+        let loss = total.saturating_sub(count);
+
+        if loss > self.reported_loss.load(Ordering::Relaxed) {
+            self.reported_loss.store(loss, Ordering::Relaxed);
+            eprintln!(
+                "watchdog: thread {thread} found the counter at {count} after {total} increments \
+                 ({loss} lost so far)"
+            );
+        }
+    }
+}
+
+// How many overlaps `OverlapChecker` describes before it only counts them.
+const REPORTED_OVERLAPS: usize = 10;
+
+// Catches two threads in the critical section at once, at the moment it happens, independently of
+// whatever the critical section itself does.
+//
+// Entering bumps an occupancy count with a SeqCst RMW, so of any two threads that are inside at the
+// same time, the later one to enter always finds the count above zero. The RMWs come after the
+// wait loop, and so can't stand in for the fences the doorway is missing in the `fake-fence-*`
+// builds.
+pub struct OverlapChecker<const N: usize> {
+    occupancy: AtomicUsize,
+    inside: [AtomicBool; N],
+    // The latest ticket each thread took.
+    tickets: [AtomicU64; N],
+    overlaps: AtomicUsize,
+}
+
+impl<const N: usize> OverlapChecker<N> {
+    pub fn new() -> Self {
+        Self {
+            occupancy: AtomicUsize::new(0),
+            inside: std::array::from_fn(|_| AtomicBool::new(false)),
+            tickets: std::array::from_fn(|_| AtomicU64::new(0)),
+            overlaps: AtomicUsize::new(0),
+        }
+    }
+
+    // How many times a thread entered the critical section while someone else was inside.
+    pub fn overlaps(&self) -> usize {
+        self.overlaps.load(Ordering::Relaxed)
+    }
+}
+
+impl<const N: usize> Observer for OverlapChecker<N> {
+    fn on_event(&self, thread: usize, event: Event) {
+        match event {
+            Event::Ticket(ticket) => self.tickets[thread].store(ticket, Ordering::Relaxed),
+            Event::Acquired => {
+                self.inside[thread].store(true, Ordering::SeqCst);
+                if self.occupancy.fetch_add(1, Ordering::SeqCst) == 0 {
+                    return;
+                }
+
+                let overlaps = self.overlaps.fetch_add(1, Ordering::Relaxed) + 1;
+                if overlaps > REPORTED_OVERLAPS {
+                    return;
+                }
+                // Whoever else is inside may well be on its way out by now, so this is a best
+                // effort.
+                let others: Vec<_> = (0..N)
+                    .filter(|&other| other != thread && self.inside[other].load(Ordering::SeqCst))
+                    .map(|other| {
+                        let ticket = self.tickets[other].load(Ordering::Relaxed);
+                        format!("thread {other} (ticket {ticket})")
+                    })
+                    .collect();
+                eprintln!(
+                    "overlap: thread {thread} (ticket {}) entered the critical section while {} \
+                     {} inside{}",
+                    self.tickets[thread].load(Ordering::Relaxed),
+                    if others.is_empty() {
+                        "another thread".to_owned()
+                    } else {
+                        others.join(", ")
+                    },
+                    if others.len() > 1 { "were" } else { "was" },
+                    if overlaps == REPORTED_OVERLAPS {
+                        ", not reporting any more"
+                    } else {
+                        ""
+                    }
+                );
+            }
+            Event::Released => {
+                self.inside[thread].store(false, Ordering::SeqCst);
+                self.occupancy.fetch_sub(1, Ordering::SeqCst);
+            }
+            _ => {}
+        }
+    }
+}
+
+// Catches a thread that keeps waiting in `lock` while other threads keep getting through. Once a
+// thread holds a ticket, every other thread can get into the critical section at most once before
+// it, so a thread that has been waiting for longer than the threshold and been overtaken more
+// often than that points at a livelock or a fairness bug. A thread that waits because nobody is
+// making progress at all, or just because it was descheduled, isn't reported.
+//
+// The observer side only notes when every thread started waiting; `watch` polls that from a
+// thread of its own, so that a waiter is noticed even while it's stuck in a loop.
+pub struct StallWatchdog<const N: usize> {
+    threshold: Duration,
+    epoch: Instant,
+    // When each thread entered the doorway of its current acquisition, in nanoseconds since
+    // `epoch` plus one, or 0 if it isn't waiting.
+    waiting_since: [AtomicU64; N],
+    // The value of `acquisitions` when each thread took its ticket, or started trying to until it
+    // gets one.
+    acquisitions_before: [AtomicU64; N],
+    // Critical sections entered so far. Only updated inside the critical section.
+    acquisitions: AtomicU64,
+    reported: [AtomicBool; N],
+}
+
+impl<const N: usize> StallWatchdog<N> {
+    pub fn new(threshold: Duration) -> Self {
+        Self {
+            threshold,
+            epoch: Instant::now(),
+            waiting_since: std::array::from_fn(|_| AtomicU64::new(0)),
+            acquisitions_before: std::array::from_fn(|_| AtomicU64::new(0)),
+            acquisitions: AtomicU64::new(0),
+            reported: std::array::from_fn(|_| AtomicBool::new(false)),
+        }
+    }
+
+    fn now(&self) -> u64 {
+        self.epoch.elapsed().as_nanos() as u64 + 1
+    }
+
+    // Checks on the waiting threads every quarter of the threshold until `done` returns true,
+    // describing every slot of the lock `snapshot` samples when one is found stalled.
+    pub fn watch(&self, snapshot: impl Fn() -> LockSnapshot<N>, done: impl Fn() -> bool) {
+        let interval = (self.threshold / 4).max(Duration::from_millis(1));
+        while !done() {
+            thread::sleep(interval);
+            for thread in 0..N {
+                let since = self.waiting_since[thread].load(Ordering::Relaxed);
+                if since == 0 {
+                    continue;
+                }
+                let waiting = Duration::from_nanos(self.now().saturating_sub(since));
+                let overtaken = self
+                    .acquisitions
+                    .load(Ordering::Relaxed)
+                    .saturating_sub(self.acquisitions_before[thread].load(Ordering::Relaxed));
+                if waiting <= self.threshold
+                    || overtaken < N as u64
+                    || self.reported[thread].swap(true, Ordering::Relaxed)
+                {
+                    continue;
+                }
+
+                let snapshot = snapshot();
+                let mut tickets = String::new();
+                for slot in 0..N {
+                    let (ticket, choosing) = (snapshot.tickets[slot], snapshot.choosing[slot]);
+                    let _ = write!(
+                        tickets,
+                        " {slot}:{ticket}{}",
+                        if choosing { " (choosing)" } else { "" }
+                    );
+                }
+                eprintln!(
+                    "starvation watchdog: slot {thread} (ticket {}) has been waiting for \
+                     {waiting:.2?} and was overtaken {overtaken} times since it took its ticket; \
+                     tickets:{tickets}",
+                    snapshot.tickets[thread]
+                );
+            }
+        }
+    }
+}
+
+impl<const N: usize> Observer for StallWatchdog<N> {
+    fn on_event(&self, thread: usize, event: Event) {
+        match event {
+            Event::Doorway => {
+                let acquisitions = self.acquisitions.load(Ordering::Relaxed);
+                self.acquisitions_before[thread].store(acquisitions, Ordering::Relaxed);
+                self.waiting_since[thread].store(self.now(), Ordering::Relaxed);
+            }
+            Event::Ticket(_) => {
+                let acquisitions = self.acquisitions.load(Ordering::Relaxed);
+                self.acquisitions_before[thread].store(acquisitions, Ordering::Relaxed);
+            }
+            Event::Acquired => {
+                let since = self.waiting_since[thread].swap(0, Ordering::Relaxed);
+                if since != 0 && self.reported[thread].swap(false, Ordering::Relaxed) {
+                    eprintln!(
+                        "starvation watchdog: slot {thread} got the lock after {:.2?}",
+                        Duration::from_nanos(self.now() - since)
+                    );
+                }
+                let acquisitions = self.acquisitions.load(Ordering::Relaxed);
+                self.acquisitions.store(acquisitions + 1, Ordering::Relaxed);
+            }
+            Event::GaveUp => {
+                self.waiting_since[thread].store(0, Ordering::Relaxed);
+                self.reported[thread].store(false, Ordering::Relaxed);
+            }
+            _ => {}
+        }
+    }
+}
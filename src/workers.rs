@@ -0,0 +1,110 @@
+use std::{
+    hint,
+    sync::OnceLock,
+    thread::{self, Scope, ScopedJoinHandle},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use crate::placement;
+
+static SEED: OnceLock<u64> = OnceLock::new();
+
+// Sets the seed every worker's RNG is derived from. Must be called before the first call to
+// `seed`, if at all.
+pub fn set_seed(seed: u64) {
+    SEED.set(seed).expect("seed already initialized");
+}
+
+// The global seed, picked from the clock unless set explicitly with `set_seed`.
+pub fn seed() -> u64 {
+    *SEED.get_or_init(|| {
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs()
+    })
+}
+
+// SplitMix64: tiny, fast, and good enough to drive workloads reproducibly.
+pub struct Rng(u64);
+
+impl Rng {
+    pub fn new(seed: u64) -> Self {
+        Self(seed)
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e3779b97f4a7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+        z ^ (z >> 31)
+    }
+
+    // A number in `0..bound`. The modulo bias is irrelevant for the small bounds used here.
+    pub fn below(&mut self, bound: u64) -> u64 {
+        self.next_u64() % bound
+    }
+}
+
+// How long a counting worker spends inside and outside the critical section on every iteration,
+// in iterations of `hint::spin_loop`. Both are varied by up to `jitter` percent either way.
+#[derive(Clone, Copy, Default)]
+pub struct Profile {
+    pub critical_section: u64,
+    pub outside: u64,
+    pub jitter: u64,
+}
+
+impl Profile {
+    pub fn critical_section(&self, rng: &mut Rng) {
+        spin(self.vary(self.critical_section, rng));
+    }
+
+    pub fn outside(&self, rng: &mut Rng) {
+        spin(self.vary(self.outside, rng));
+    }
+
+    fn vary(&self, work: u64, rng: &mut Rng) -> u64 {
+        let spread = work * self.jitter.min(100) / 100;
+        if spread == 0 {
+            return work;
+        }
+        work - spread + rng.below(2 * spread + 1)
+    }
+}
+
+fn spin(iterations: u64) {
+    for _ in 0..iterations {
+        hint::spin_loop();
+    }
+}
+
+// What a worker thread knows about itself.
+pub struct Worker {
+    pub id: usize,
+    pub rng: Rng,
+}
+
+// Spawns worker `id` of `workload` on `scope`, named `<workload>-<id>` so that it can be told apart
+// in debuggers and profilers and given its own RNG derived from the global seed.
+pub fn spawn<'scope, 'env, T, F>(
+    scope: &'scope Scope<'scope, 'env>,
+    workload: &str,
+    id: usize,
+    f: F,
+) -> ScopedJoinHandle<'scope, T>
+where
+    F: FnOnce(Worker) -> T + Send + 'scope,
+    T: Send + 'scope,
+{
+    let rng = Rng::new(Rng::new(seed()).next_u64() ^ id as u64);
+
+    thread::Builder::new()
+        .name(format!("{workload}-{id}"))
+        .spawn_scoped(scope, move || {
+            placement::pin(id);
+            f(Worker { id, rng })
+        })
+        .expect("failed to spawn worker thread")
+}
@@ -0,0 +1,1304 @@
+//! End-to-end runs of the lock that are small enough for Miri to get through. Under `cfg(miri)`
+//! every test shrinks to a handful of acquisitions per thread, so the interesting coverage comes
+//! from running the suite under many schedules rather than from any single run:
+//!
+//! ```bash
+//! MIRIFLAGS="-Zmiri-many-seeds=0..32 -Zmiri-preemption-rate=0.1" cargo +nightly miri test --test miri
+//! ```
+//!
+//! Outside Miri they double as a quick smoke test of the library.
+
+#![cfg(feature = "std")]
+
+use std::{
+    future::{self, Future},
+    pin::pin,
+    sync::{
+        atomic::{AtomicU16, AtomicU64, AtomicU8, AtomicUsize, Ordering},
+        Arc,
+    },
+    task::{Context, Poll, Wake, Waker},
+    thread,
+    time::Duration,
+};
+
+use bakery::{
+    backoff::{Backoff, Exponential, RandomizedExponential, SpinThenYield},
+    layout::{Compact, Fused, Packed, Padded, SlotLayout, TicketCell, Tracked},
+    BakeryMutex, NoObserver, RawBakeryLock, SlotLock,
+};
+
+const THREADS: usize = if cfg!(miri) { 3 } else { 4 };
+const ITERATIONS: usize = if cfg!(miri) { 4 } else { 200 };
+
+// Has `THREADS` threads increment a counter `ITERATIONS` times each through registered slots of a
+// lock with layout `S`.
+fn count_with_layout<S: SlotLayout<THREADS> + Sync>() {
+    let counter = BakeryMutex::from_raw(RawBakeryLock::from_parts(NoObserver, S::new()), 0);
+    thread::scope(|scope| {
+        for _ in 0..THREADS {
+            scope.spawn(|| {
+                let mut slot = counter.register().expect("more threads than slots");
+                for _ in 0..ITERATIONS {
+                    *slot.lock() += 1;
+                }
+            });
+        }
+    });
+    assert_eq!(counter.into_inner(), THREADS * ITERATIONS);
+}
+
+#[test]
+fn packed() {
+    count_with_layout::<Packed<THREADS>>();
+}
+
+#[test]
+fn packed_u16() {
+    count_with_layout::<Packed<THREADS, AtomicU16>>();
+}
+
+#[test]
+fn packed_u64() {
+    count_with_layout::<Packed<THREADS, AtomicU64>>();
+}
+
+#[test]
+fn padded() {
+    count_with_layout::<Padded<THREADS>>();
+}
+
+#[test]
+fn compact() {
+    count_with_layout::<Compact<THREADS>>();
+}
+
+#[test]
+fn fused() {
+    count_with_layout::<Fused<THREADS>>();
+}
+
+#[test]
+fn tracked() {
+    count_with_layout::<Tracked<THREADS, Packed<THREADS>>>();
+}
+
+#[cfg(feature = "black-white")]
+#[test]
+fn black_white() {
+    count_with_slot_lock(&bakery::BWBakeryLock::<THREADS>::new());
+}
+
+#[cfg(feature = "hierarchical")]
+#[test]
+fn hierarchical() {
+    let lock = bakery::HierarchicalBakeryLock::<2, 2>::new();
+    let counter = AtomicUsize::new(0);
+    thread::scope(|scope| {
+        for thread in 0..THREADS {
+            let (lock, counter) = (&lock, &counter);
+            scope.spawn(move || {
+                for _ in 0..ITERATIONS {
+                    lock.lock(thread);
+                    let count = counter.load(Ordering::Relaxed);
+                    counter.store(count + 1, Ordering::Relaxed);
+                    lock.unlock(thread);
+                }
+            });
+        }
+    });
+    assert_eq!(counter.into_inner(), THREADS * ITERATIONS);
+}
+
+// The critical section sleeps for long enough that waiters run out of spins and park.
+#[cfg(feature = "futex")]
+#[test]
+fn futex() {
+    let lock = bakery::FutexBakeryLock::<THREADS>::new();
+    let counter = AtomicUsize::new(0);
+    thread::scope(|scope| {
+        for thread in 0..THREADS {
+            let (lock, counter) = (&lock, &counter);
+            scope.spawn(move || {
+                for _ in 0..ITERATIONS {
+                    lock.lock(thread);
+                    let count = counter.load(Ordering::Relaxed);
+                    thread::sleep(Duration::from_micros(20));
+                    counter.store(count + 1, Ordering::Relaxed);
+                    lock.unlock(thread);
+                }
+            });
+        }
+    });
+    assert_eq!(counter.into_inner(), THREADS * ITERATIONS);
+}
+
+// Threads stand in for processes, each claiming its slot under its own id in a lock that starts out
+// as zeroed memory, as a freshly mapped region would.
+#[cfg(feature = "shm")]
+#[test]
+fn shm() {
+    let mut region = [0u32; 3 * THREADS];
+    // SAFETY: the region is zeroed, aligned for `u32`, as large as the lock, and only used by it.
+    let lock = unsafe { bakery::ShmBakeryLock::<THREADS>::from_ptr(region.as_mut_ptr().cast()) };
+    let counter = AtomicUsize::new(0);
+    thread::scope(|scope| {
+        for owner in 1..=THREADS as u32 {
+            let counter = &counter;
+            scope.spawn(move || {
+                let slot = lock.claim(owner).unwrap();
+                for _ in 0..ITERATIONS {
+                    lock.lock(slot);
+                    let count = counter.load(Ordering::Relaxed);
+                    counter.store(count + 1, Ordering::Relaxed);
+                    lock.unlock(slot);
+                }
+                lock.release(slot);
+            });
+        }
+    });
+    assert_eq!(counter.into_inner(), THREADS * ITERATIONS);
+    assert!((0..THREADS).all(|slot| lock.owner(slot).is_none()));
+}
+
+// A process that dies holding the lock keeps everyone else out until its slot is recovered.
+#[cfg(feature = "shm")]
+#[test]
+fn shm_recover() {
+    const DEAD: u32 = 7;
+    let lock = bakery::ShmBakeryLock::<THREADS>::new();
+    let dead = lock.claim(DEAD).unwrap();
+    let alive = lock.claim(1).unwrap();
+    lock.lock(dead);
+    assert!(!lock.try_lock(alive));
+
+    assert_eq!(lock.recover(|owner| owner != DEAD), 1);
+    assert_eq!(lock.owner(dead), None);
+    assert_eq!(lock.recover(|owner| owner != DEAD), 0);
+    assert!(lock.try_lock(alive));
+    lock.unlock(alive);
+
+    // The same, with a real process that has exited, which Miri can't spawn.
+    #[cfg(unix)]
+    if !cfg!(miri) {
+        let mut child = std::process::Command::new("true").spawn().unwrap();
+        let exited = lock.claim(child.id()).unwrap();
+        child.wait().unwrap();
+        let ours = lock.claim_for_process().unwrap();
+        lock.lock(exited);
+        assert_eq!(lock.recover_dead(), 1);
+        assert_eq!(lock.owner(ours), Some(std::process::id()));
+        assert!(lock.try_lock(ours));
+        lock.unlock(ours);
+    }
+}
+
+// The C API, called from Rust: the heap lock from threads, and the shared-memory one through a
+// region as C would hand it over.
+#[cfg(feature = "cdylib")]
+#[test]
+fn ffi() {
+    use bakery::ffi::*;
+
+    let lock = bakery_lock_new(THREADS);
+    // SAFETY: the lock is only freed once every thread is done with it.
+    let shared = unsafe { &*lock };
+    let counter = AtomicUsize::new(0);
+    thread::scope(|scope| {
+        for slot in 0..THREADS {
+            let counter = &counter;
+            scope.spawn(move || {
+                for _ in 0..ITERATIONS {
+                    // SAFETY: as above.
+                    unsafe { bakery_lock_acquire(shared, slot) };
+                    let count = counter.load(Ordering::Relaxed);
+                    counter.store(count + 1, Ordering::Relaxed);
+                    // SAFETY: as above.
+                    unsafe { bakery_lock_release(shared, slot) };
+                }
+            });
+        }
+    });
+    // SAFETY: nobody uses the lock any more.
+    unsafe { bakery_lock_free(lock) };
+    assert_eq!(counter.into_inner(), THREADS * ITERATIONS);
+
+    let mut region = [0u32; 3 * SHM_SLOTS];
+    assert_eq!(bakery_shm_lock_size(), size_of_val(&region));
+    // SAFETY: the region is zeroed, as large as the lock, and only used by it.
+    let shm = unsafe { bakery_shm_lock_at(region.as_mut_ptr().cast()) };
+    // SAFETY: `shm` points into `region`, which outlives every use of it.
+    unsafe {
+        let slot = bakery_shm_lock_claim(shm, 1) as usize;
+        assert!(bakery_shm_lock_try_acquire(shm, slot));
+        assert_eq!(bakery_shm_lock_claim(shm, 2), 1);
+        assert!(!bakery_shm_lock_try_acquire(shm, 1));
+        bakery_shm_lock_release(shm, slot);
+        bakery_shm_lock_release_slot(shm, slot);
+        bakery_shm_lock_acquire(shm, 1);
+        bakery_shm_lock_release(shm, 1);
+    }
+    assert!(
+        unsafe { bakery_shm_lock_at(region.as_mut_ptr().cast::<u8>().wrapping_add(1).cast()) }
+            .is_null()
+    );
+}
+
+// Every function `src/ffi.rs` exports has a prototype in `include/bakery.h` with the matching C
+// types, and the header declares nothing else, since nothing checks the hand-written header
+// against the library when it's built. This reads both as text rather than compiling any C, so it
+// only understands the handful of types the API uses.
+#[test]
+#[cfg_attr(miri, ignore = "reads files, which Miri's isolation forbids")]
+fn ffi_header() {
+    use std::collections::BTreeMap;
+
+    // The C spelling of a Rust type from the API, with no space before a `*`.
+    fn c_type(rust: &str) -> String {
+        if let Some(pointee) = rust.strip_prefix("*mut ") {
+            return format!("{}*", c_type(pointee));
+        }
+        if let Some(pointee) = rust.strip_prefix("*const ") {
+            return format!("const {}*", c_type(pointee));
+        }
+        match rust {
+            "bool" => "bool",
+            "u32" => "uint32_t",
+            "usize" => "size_t",
+            "isize" => "intptr_t",
+            "c_void" => "void",
+            "DynBakeryLock" => "bakery_lock",
+            "ShmLock" => "bakery_shm_lock",
+            _ => panic!("no C type for `{rust}`"),
+        }
+        .to_owned()
+    }
+
+    type Prototypes = BTreeMap<String, (Vec<String>, String)>;
+
+    let root = env!("CARGO_MANIFEST_DIR");
+    let rust = std::fs::read_to_string(format!("{root}/src/ffi.rs")).unwrap();
+    let header = std::fs::read_to_string(format!("{root}/include/bakery.h")).unwrap();
+
+    let mut exported = Prototypes::new();
+    for item in rust.split("#[no_mangle]").skip(1) {
+        let signature = item[..item.find('{').unwrap()].split_whitespace();
+        let signature = signature.collect::<Vec<_>>().join(" ");
+        let (_, signature) = signature.split_once("fn ").unwrap();
+        let (name, rest) = signature.split_once('(').unwrap();
+        let (params, ret) = rest.split_once(')').unwrap();
+        let params = params
+            .split(',')
+            .map(str::trim)
+            .filter(|param| !param.is_empty())
+            .map(|param| c_type(param.split_once(": ").unwrap().1))
+            .collect();
+        let ret = match ret.trim().strip_prefix("-> ") {
+            Some(ret) => c_type(ret),
+            None => "void".to_owned(),
+        };
+        exported.insert(name.to_owned(), (params, ret));
+    }
+
+    let mut code = String::new();
+    let mut rest = header.as_str();
+    while let Some(start) = rest.find("/*") {
+        code.push_str(&rest[..start]);
+        rest = &rest[start + rest[start..].find("*/").unwrap() + 2..];
+    }
+    code.push_str(rest);
+
+    // Spells a C type the way `c_type` does.
+    let normalize = |c: &str| c.trim().replace(" *", "*").replace("* ", "*");
+    let identifier = |c: char| c.is_alphanumeric() || c == '_';
+    let mut declared = Prototypes::new();
+    for line in code.lines().map(str::trim) {
+        if line.starts_with('#') || !line.contains('(') {
+            continue;
+        }
+        let line = line.strip_suffix(");").unwrap();
+        let (before, params) = line.split_once('(').unwrap();
+        let name_start = before.trim_end_matches(identifier).len();
+        let params = match params.trim() {
+            "void" => Vec::new(),
+            params => params
+                .split(',')
+                .map(|param| normalize(param.trim_end_matches(identifier)))
+                .collect(),
+        };
+        declared.insert(
+            before[name_start..].to_owned(),
+            (params, normalize(&before[..name_start])),
+        );
+    }
+
+    assert_eq!(exported, declared);
+}
+
+// Stands in for masking interrupts by counting how deeply the calling thread has masked them.
+#[cfg(feature = "irq")]
+struct CountMask;
+
+#[cfg(feature = "irq")]
+thread_local! {
+    static MASKED: std::cell::Cell<usize> = const { std::cell::Cell::new(0) };
+}
+
+#[cfg(feature = "irq")]
+impl bakery::irq::InterruptMask for CountMask {
+    type State = usize;
+
+    fn disable() -> usize {
+        MASKED.with(|masked| masked.replace(masked.get() + 1))
+    }
+
+    fn restore(state: usize) {
+        MASKED.with(|masked| {
+            assert_eq!(masked.get(), state + 1, "restored out of order");
+            masked.set(state);
+        });
+    }
+}
+
+// Every critical section runs with interrupts masked, and a slot used again while it's already in
+// the bakery, as by a handler sharing the slot of the code it interrupted, is turned away.
+#[cfg(feature = "irq")]
+#[test]
+fn irq() {
+    let lock = bakery::irq::IrqBakeryLock::<THREADS, CountMask>::new();
+    let counter = AtomicUsize::new(0);
+    thread::scope(|scope| {
+        for slot in 0..THREADS {
+            let (lock, counter) = (&lock, &counter);
+            scope.spawn(move || {
+                for _ in 0..ITERATIONS {
+                    lock.with(slot, || {
+                        assert_eq!(MASKED.with(|masked| masked.get()), 1);
+                        let count = counter.load(Ordering::Relaxed);
+                        counter.store(count + 1, Ordering::Relaxed);
+                    });
+                }
+                assert_eq!(MASKED.with(|masked| masked.get()), 0);
+            });
+        }
+    });
+    assert_eq!(counter.into_inner(), THREADS * ITERATIONS);
+
+    let guard = lock.lock(1);
+    assert!(lock.try_lock(1).is_none());
+    let reentered = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| drop(lock.lock(1))));
+    assert!(reentered.is_err());
+    assert_eq!(MASKED.with(|masked| masked.get()), 1);
+    drop(guard);
+    assert_eq!(MASKED.with(|masked| masked.get()), 0);
+    assert_eq!(lock.try_lock(1).map(|guard| guard.slot()), Some(1));
+}
+
+// Has as many threads as `lock` has slots, up to `THREADS`, increment a counter `ITERATIONS` times
+// each in its critical section.
+fn count_with_slot_lock(lock: &(impl SlotLock + Sync)) {
+    let threads = lock.slots().min(THREADS);
+    let counter = AtomicUsize::new(0);
+    thread::scope(|scope| {
+        for thread in 0..threads {
+            let counter = &counter;
+            scope.spawn(move || {
+                for _ in 0..ITERATIONS {
+                    lock.lock(thread);
+                    // Deliberately not an RMW, so that a broken lock loses updates.
+                    let count = counter.load(Ordering::Relaxed);
+                    counter.store(count + 1, Ordering::Relaxed);
+                    lock.unlock(thread);
+                }
+            });
+        }
+    });
+    assert_eq!(counter.into_inner(), threads * ITERATIONS);
+}
+
+// As above, on a bakery lock whose waiters back off with `B`.
+fn count_with_backoff<B: Backoff>() {
+    count_with_slot_lock(&RawBakeryLock::<THREADS>::new().with_backoff::<B>());
+}
+
+#[test]
+fn exponential_backoff() {
+    count_with_backoff::<Exponential>();
+    count_with_backoff::<Exponential<2, 4>>();
+}
+
+#[test]
+fn randomized_backoff() {
+    count_with_backoff::<RandomizedExponential>();
+    count_with_backoff::<RandomizedExponential<2, 4>>();
+}
+
+#[test]
+fn spin_then_yield() {
+    count_with_backoff::<SpinThenYield>();
+    count_with_backoff::<SpinThenYield<0>>();
+}
+
+#[cfg(feature = "burns-lynch")]
+#[test]
+fn burns_lynch() {
+    let lock = bakery::burns_lynch::BurnsLynchLock::<THREADS>::new();
+    count_with_slot_lock(&lock);
+
+    // Neither a lower nor a higher slot gets in past a holder.
+    lock.lock(1);
+    assert!(!lock.try_lock(0) && !lock.try_lock(2));
+    lock.unlock(1);
+    assert!(lock.try_lock(2));
+    lock.unlock(2);
+}
+
+#[cfg(feature = "dekker")]
+#[test]
+fn dekker() {
+    let lock = bakery::dekker::DekkerLock::new();
+    count_with_slot_lock(&lock);
+
+    lock.lock(1);
+    assert!(!lock.try_lock(0));
+    lock.unlock(1);
+    assert!(lock.try_lock(0));
+    lock.unlock(0);
+}
+
+// With its fences weakened, both threads can see the other's flag down and enter together. That
+// takes two cores, or Miri's weak memory emulation, and some luck, so it only runs when asked:
+//
+//     cargo test --release -F fake-fence-dekker --test miri -- --ignored dekker_without_fences
+#[cfg(feature = "fake-fence-dekker")]
+#[test]
+#[ignore = "only fails the lock on some schedules"]
+fn dekker_without_fences() {
+    let lock = bakery::dekker::DekkerLock::new();
+    let inside = AtomicUsize::new(0);
+    let overlaps = AtomicUsize::new(0);
+    thread::scope(|scope| {
+        for thread in 0..2 {
+            let (lock, inside, overlaps) = (&lock, &inside, &overlaps);
+            scope.spawn(move || {
+                for _ in 0..if cfg!(miri) { 1000 } else { 1_000_000 } {
+                    lock.lock(thread);
+                    if inside.fetch_add(1, Ordering::Relaxed) != 0 {
+                        overlaps.fetch_add(1, Ordering::Relaxed);
+                    }
+                    inside.fetch_sub(1, Ordering::Relaxed);
+                    lock.unlock(thread);
+                }
+            });
+        }
+    });
+    assert_ne!(
+        overlaps.into_inner(),
+        0,
+        "the two threads were never in the critical section at once"
+    );
+}
+
+#[cfg(feature = "filter")]
+#[test]
+fn filter() {
+    let lock = bakery::filter::FilterLock::<THREADS>::new();
+    count_with_slot_lock(&lock);
+
+    lock.lock(1);
+    assert!((0..THREADS).all(|thread| thread == 1 || !lock.try_lock(thread)));
+    lock.unlock(1);
+    assert!(lock.try_lock(0));
+    lock.unlock(0);
+}
+
+#[cfg(feature = "lamport-fast")]
+#[test]
+fn lamport_fast() {
+    count_with_slot_lock(&bakery::lamport_fast::LamportFastLock::<THREADS>::new());
+}
+
+#[cfg(feature = "peterson")]
+#[test]
+fn peterson() {
+    let lock = bakery::peterson::PetersonLock::new();
+    count_with_slot_lock(&lock);
+
+    lock.lock(0);
+    assert!(!lock.try_lock(1));
+    lock.unlock(0);
+    assert!(lock.try_lock(1));
+    assert!(!lock.try_lock(0));
+    lock.unlock(1);
+}
+
+#[cfg(feature = "szymanski")]
+#[test]
+fn szymanski() {
+    count_with_slot_lock(&bakery::szymanski::SzymanskiLock::<THREADS>::new());
+}
+
+#[cfg(feature = "ticket")]
+#[test]
+fn ticket() {
+    let lock = bakery::ticket::TicketLock::new();
+    count_with_slot_lock(&lock);
+
+    lock.lock();
+    assert!(!lock.try_lock());
+    lock.unlock();
+    assert!(lock.try_lock());
+    lock.unlock();
+}
+
+#[cfg(feature = "ttas")]
+#[test]
+fn ttas() {
+    let lock = bakery::ttas::TtasLock::new();
+    count_with_slot_lock(&lock);
+    count_with_slot_lock(&bakery::ttas::TtasLock::new().with_backoff::<RandomizedExponential>());
+
+    lock.lock();
+    assert!(!lock.try_lock());
+    lock.unlock();
+    assert!(lock.try_lock());
+    lock.unlock();
+}
+
+#[test]
+fn dynamic() {
+    let lock = bakery::DynBakeryLock::new(THREADS);
+    let counter = AtomicUsize::new(0);
+    thread::scope(|scope| {
+        for thread in 0..THREADS {
+            let (lock, counter) = (&lock, &counter);
+            scope.spawn(move || {
+                for _ in 0..ITERATIONS {
+                    lock.lock(thread);
+                    let count = counter.load(Ordering::Relaxed);
+                    counter.store(count + 1, Ordering::Relaxed);
+                    lock.unlock(thread);
+                }
+            });
+        }
+    });
+    assert_eq!(counter.into_inner(), THREADS * ITERATIONS);
+}
+
+// Every thread writes its phase number before the barrier and checks everyone else's after it,
+// which only works out if nobody gets through a phase before the last thread arrives. Each
+// generation of the barrier must also have exactly one leader.
+#[test]
+fn barrier() {
+    let barrier = bakery::Barrier::new(THREADS);
+    let phases: Vec<_> = (0..THREADS).map(|_| AtomicUsize::new(0)).collect();
+    let leaders: Vec<_> = (0..2 * ITERATIONS).map(|_| AtomicUsize::new(0)).collect();
+    thread::scope(|scope| {
+        for thread in 0..THREADS {
+            let (barrier, phases, leaders) = (&barrier, &phases, &leaders);
+            scope.spawn(move || {
+                for phase in 1..=ITERATIONS {
+                    phases[thread].store(phase, Ordering::Relaxed);
+                    let led_writes = barrier.wait().is_leader();
+                    for other in phases {
+                        assert_eq!(other.load(Ordering::Relaxed), phase);
+                    }
+                    // Nobody may start the next phase's write until everyone has checked.
+                    let led_checks = barrier.wait().is_leader();
+                    let generation = 2 * (phase - 1);
+                    leaders[generation].fetch_add(usize::from(led_writes), Ordering::Relaxed);
+                    leaders[generation + 1].fetch_add(usize::from(led_checks), Ordering::Relaxed);
+                }
+            });
+        }
+    });
+    for leaders in leaders {
+        assert_eq!(leaders.into_inner(), 1);
+    }
+}
+
+// A thread waiting alone times out, and takes its arrival back in doing so, or the second attempt
+// would complete the phase by itself. Then every thread keeps timing out and retrying, which
+// races the withdrawals against the last arrival; each generation must still let everyone go
+// together, with exactly one leader.
+#[test]
+fn barrier_timeout() {
+    let barrier = bakery::Barrier::new(2);
+    assert!(barrier.wait_timeout(Duration::from_millis(10)).is_none());
+    assert!(barrier.wait_timeout(Duration::from_millis(10)).is_none());
+
+    let barrier = bakery::Barrier::new(THREADS);
+    let phases: Vec<_> = (0..THREADS).map(|_| AtomicUsize::new(0)).collect();
+    let leaders: Vec<_> = (0..ITERATIONS).map(|_| AtomicUsize::new(0)).collect();
+    thread::scope(|scope| {
+        for thread in 0..THREADS {
+            let (barrier, phases, leaders) = (&barrier, &phases, &leaders);
+            scope.spawn(move || {
+                for phase in 1..=ITERATIONS {
+                    phases[thread].store(phase, Ordering::Relaxed);
+                    let result = loop {
+                        if let Some(result) = barrier.wait_timeout(Duration::from_micros(50)) {
+                            break result;
+                        }
+                    };
+                    for other in phases {
+                        assert!(other.load(Ordering::Relaxed) >= phase);
+                    }
+                    leaders[phase - 1]
+                        .fetch_add(usize::from(result.is_leader()), Ordering::Relaxed);
+                }
+            });
+        }
+    });
+    for leaders in leaders {
+        assert_eq!(leaders.into_inner(), 1);
+    }
+}
+
+// Every thread races to initialize, and whichever one didn't must still see the initialization
+// done. The first attempt panics, which should let the next caller try again.
+#[test]
+fn once() {
+    let once = bakery::Once::new();
+    let runs = AtomicUsize::new(0);
+    let panicked = std::panic::catch_unwind(|| once.call_once(|| panic!("first attempt")));
+    assert!(panicked.is_err() && !once.is_completed());
+
+    thread::scope(|scope| {
+        for _ in 0..THREADS {
+            scope.spawn(|| {
+                once.call_once(|| {
+                    runs.store(runs.load(Ordering::Relaxed) + 1, Ordering::Relaxed);
+                });
+                assert_eq!(runs.load(Ordering::Relaxed), 1);
+            });
+        }
+    });
+    assert!(once.is_completed());
+}
+
+#[test]
+fn lazy() {
+    static CALLS: AtomicUsize = AtomicUsize::new(0);
+    static VALUE: bakery::Lazy<Vec<usize>> = bakery::Lazy::new(|| {
+        CALLS.fetch_add(1, Ordering::Relaxed);
+        (0..THREADS).collect()
+    });
+
+    thread::scope(|scope| {
+        for thread in 0..THREADS {
+            scope.spawn(move || assert_eq!(VALUE[thread], thread));
+        }
+    });
+    assert_eq!(CALLS.load(Ordering::Relaxed), 1);
+}
+
+// Every thread reads the counter in the outer critical section and writes it back from a nested
+// one after the inner guard is dropped, which only works out if the inner drop didn't let anyone
+// else in.
+#[test]
+fn reentrant() {
+    let lock = bakery::ReentrantBakeryLock::<THREADS>::new();
+    let counter = AtomicUsize::new(0);
+    thread::scope(|scope| {
+        for _ in 0..THREADS {
+            scope.spawn(|| {
+                for _ in 0..ITERATIONS {
+                    let outer = lock.lock();
+                    let count = {
+                        let inner = lock.try_lock().expect("the holder couldn't lock again");
+                        assert_eq!((inner.slot(), inner.depth()), (outer.slot(), 2));
+                        counter.load(Ordering::Relaxed)
+                    };
+                    thread::yield_now();
+                    let _inner = lock.lock();
+                    counter.store(count + 1, Ordering::Relaxed);
+                }
+            });
+        }
+    });
+    assert_eq!(counter.into_inner(), THREADS * ITERATIONS);
+}
+
+// Every thread mostly reads and sometimes writes, checking that the two halves of the value never
+// disagree, which they would if a reader got in alongside a writer.
+#[test]
+fn rwlock() {
+    let lock = bakery::BakeryRwLock::<(usize, usize), THREADS>::new((0, 0));
+    thread::scope(|scope| {
+        for _ in 0..THREADS {
+            scope.spawn(|| {
+                for i in 0..ITERATIONS {
+                    if i % 4 == 0 {
+                        let mut value = lock.write();
+                        value.0 += 1;
+                        thread::sleep(Duration::from_micros(10));
+                        value.1 += 1;
+                    } else {
+                        let value = lock.read();
+                        let first = value.0;
+                        thread::yield_now();
+                        assert_eq!(first, value.1, "read a half-written value");
+                    }
+                }
+            });
+        }
+    });
+    let writes = ITERATIONS.div_ceil(4);
+    assert_eq!(lock.into_inner(), (THREADS * writes, THREADS * writes));
+}
+
+// Mixes plain readers with upgrades and downgrades. An upgraded reader must find the value just as
+// it read it, since no writer may get in between, and a downgraded writer must still see what it
+// wrote.
+#[test]
+fn rwlock_upgrade() {
+    let lock = bakery::BakeryRwLock::<(usize, usize), THREADS>::new((0, 0));
+    thread::scope(|scope| {
+        for _ in 0..THREADS {
+            scope.spawn(|| {
+                for i in 0..ITERATIONS {
+                    match i % 3 {
+                        0 => {
+                            let value = lock.upgradeable_read();
+                            let seen = *value;
+                            thread::yield_now();
+                            let mut value = value.upgrade();
+                            assert_eq!(*value, seen, "a writer got in before the upgrade");
+                            value.0 += 1;
+                            thread::yield_now();
+                            value.1 += 1;
+                        }
+                        1 => {
+                            let mut value = lock.write();
+                            value.0 += 1;
+                            value.1 += 1;
+                            let written = *value;
+                            let value = value.downgrade();
+                            thread::yield_now();
+                            assert_eq!(*value, written, "a writer got in after the downgrade");
+                        }
+                        _ => {
+                            let value = lock.read();
+                            let first = value.0;
+                            thread::yield_now();
+                            assert_eq!(first, value.1, "read a half-written value");
+                        }
+                    }
+                }
+            });
+        }
+    });
+    let writes = ITERATIONS.div_ceil(3) + (ITERATIONS + 1) / 3;
+    assert_eq!(lock.into_inner(), (THREADS * writes, THREADS * writes));
+}
+
+// Producers hand items to a single consumer through a queue, waking it with `notify_one` after
+// every item and with `notify_all` once they're all done.
+#[test]
+fn condvar() {
+    let queue = BakeryMutex::<(Vec<usize>, usize), THREADS>::new((Vec::new(), 0));
+    let condvar = bakery::BakeryCondvar::new();
+    let producers = THREADS - 1;
+    thread::scope(|scope| {
+        for _ in 0..producers {
+            scope.spawn(|| {
+                for item in 0..ITERATIONS {
+                    queue.lock().0.push(item);
+                    condvar.notify_one();
+                }
+                queue.lock().1 += 1;
+                condvar.notify_all();
+            });
+        }
+
+        let mut received = 0;
+        let mut queue = queue.lock();
+        loop {
+            queue =
+                condvar.wait_while(queue, |(items, done)| items.is_empty() && *done < producers);
+            if queue.0.is_empty() {
+                break;
+            }
+            received += queue.0.drain(..).count();
+        }
+        assert_eq!(received, producers * ITERATIONS);
+    });
+}
+
+// Threads take one or two permits out of three at a time, checking that no more than three are
+// ever in use.
+#[test]
+fn semaphore() {
+    let semaphore = bakery::BakerySemaphore::<THREADS>::new(3);
+    let in_use = AtomicUsize::new(0);
+    thread::scope(|scope| {
+        for thread in 0..THREADS {
+            let (semaphore, in_use) = (&semaphore, &in_use);
+            scope.spawn(move || {
+                let n = 1 + thread % 2;
+                for _ in 0..ITERATIONS {
+                    semaphore.acquire(n);
+                    assert!(in_use.fetch_add(n, Ordering::Relaxed) + n <= 3);
+                    thread::yield_now();
+                    in_use.fetch_sub(n, Ordering::Relaxed);
+                    semaphore.release(n);
+                }
+            });
+        }
+    });
+    assert_eq!(semaphore.available_permits(), 3);
+    assert!(semaphore.try_acquire(3));
+    assert!(!semaphore.try_acquire(1));
+    semaphore.release(3);
+    assert_eq!(semaphore.available_permits(), 3);
+}
+
+// With every permit taken, a thread asking for three queues up first and one asking for one
+// behind it. Releasing a single permit must not let the second one through ahead of the first,
+// however long it's left there.
+#[test]
+fn semaphore_fifo() {
+    let semaphore = bakery::BakerySemaphore::<THREADS>::new(3);
+    semaphore.acquire(3);
+    let granted = std::sync::Mutex::new(Vec::new());
+    thread::scope(|scope| {
+        let (semaphore, granted) = (&semaphore, &granted);
+        let waiter = move |n| {
+            move || {
+                semaphore.acquire(n);
+                granted.lock().unwrap().push(n);
+                semaphore.release(n);
+            }
+        };
+        scope.spawn(waiter(3));
+        // The first waiter is at the head of the queue once it holds the lock that even taking
+        // no permits needs. Give up after a while rather than hang if it never holds on to it.
+        for _ in 0..1000 {
+            if !semaphore.try_acquire(0) {
+                break;
+            }
+            thread::sleep(Duration::from_millis(1));
+        }
+        scope.spawn(waiter(1));
+
+        // Who got in first is only checked once everyone is done, since panicking here would leave
+        // the first waiter stuck without the rest of the permits.
+        semaphore.release(1);
+        thread::sleep(Duration::from_millis(10));
+        semaphore.release(2);
+    });
+    assert_eq!(granted.into_inner().unwrap(), [3, 1]);
+    assert_eq!(semaphore.available_permits(), 3);
+}
+
+// A thread panics halfway through updating both halves of the value, which the next thread to
+// lock should be told about, and can then repair.
+#[test]
+fn poison() {
+    let mutex = bakery::PoisonBakeryMutex::<(usize, usize), THREADS>::new((0, 0));
+    thread::scope(|scope| {
+        let panicked = scope.spawn(|| {
+            let mut guard = mutex.lock().unwrap();
+            guard.0 += 1;
+            panic!("in the critical section");
+        });
+        assert!(panicked.join().is_err());
+    });
+    assert!(mutex.is_poisoned());
+    assert!(
+        mutex.snapshot().is_idle(),
+        "the panicking thread didn't unlock"
+    );
+
+    let mut guard = mutex.lock().unwrap_err().into_inner();
+    assert_eq!(*guard, (1, 0));
+    guard.1 = guard.0;
+    drop(guard);
+    assert!(mutex.try_lock().is_err());
+    mutex.clear_poison();
+
+    thread::scope(|scope| {
+        for _ in 0..THREADS {
+            scope.spawn(|| {
+                for _ in 0..ITERATIONS {
+                    let mut guard = mutex.lock().unwrap();
+                    guard.0 += 1;
+                    guard.1 += 1;
+                }
+            });
+        }
+    });
+    let total = 1 + THREADS * ITERATIONS;
+    assert_eq!(mutex.into_inner().unwrap(), (total, total));
+}
+
+// Writes through the raw pointer, as C code would, are what the next thread to lock sees.
+#[test]
+fn data_ptr() {
+    let counter = BakeryMutex::<usize, THREADS>::new(0);
+    thread::scope(|scope| {
+        for _ in 0..THREADS {
+            scope.spawn(|| {
+                for _ in 0..ITERATIONS {
+                    let guard = counter.lock();
+                    // SAFETY: the guard is alive and unused while we write.
+                    unsafe { *guard.data_ptr() += 1 };
+                }
+            });
+        }
+    });
+    assert_eq!(counter.data_ptr(), counter.lock().data_ptr());
+    assert_eq!(counter.into_inner(), THREADS * ITERATIONS);
+}
+
+// The mutex's diagnostics show the slot holding the guard at the front, and nobody once it's gone.
+#[test]
+fn mutex_diagnostics() {
+    let counter = BakeryMutex::<usize, THREADS>::new(0);
+    let guard = counter.lock();
+    let snapshot = counter.snapshot();
+    assert_eq!(snapshot.front, Some(guard.slot()));
+    assert_eq!(counter.queue_position(guard.slot()), Some(0));
+    let slot = guard.slot();
+    drop(guard);
+    assert!(counter.snapshot().is_idle());
+    assert_eq!(counter.queue_position(slot), None);
+}
+
+// Owned guards outlive any borrow of the mutex, here by being moved into a `'static` closure on
+// threads that own their share of it.
+#[test]
+fn lock_arc() {
+    let counter = Arc::new(BakeryMutex::<usize, THREADS>::new(0));
+    let threads: Vec<_> = (0..THREADS)
+        .map(|_| {
+            let counter = Arc::clone(&counter);
+            thread::spawn(move || {
+                for _ in 0..ITERATIONS {
+                    let mut guard = counter.lock_arc();
+                    let increment: Box<dyn FnOnce()> = Box::new(move || *guard += 1);
+                    increment();
+                }
+            })
+        })
+        .collect();
+    for thread in threads {
+        thread.join().unwrap();
+    }
+    drop(counter.try_lock_arc().expect("nobody holds the lock"));
+    let counter = Arc::into_inner(counter).unwrap();
+    assert_eq!(counter.into_inner(), THREADS * ITERATIONS);
+}
+
+// One more thread than there are slots, so that someone always has to wait for a slot to be
+// given back.
+#[test]
+fn assigned_slots() {
+    let counter = BakeryMutex::<usize, THREADS>::new(0);
+    thread::scope(|scope| {
+        for _ in 0..=THREADS {
+            scope.spawn(|| {
+                for _ in 0..ITERATIONS {
+                    *counter.lock() += 1;
+                }
+            });
+        }
+    });
+    assert_eq!(counter.into_inner(), (THREADS + 1) * ITERATIONS);
+}
+
+// Threads that give up must neither enter the critical section nor leave anything behind that
+// keeps the others out.
+#[test]
+fn try_lock() {
+    let counter = BakeryMutex::<usize, THREADS>::new(0);
+    let entered = AtomicUsize::new(0);
+    thread::scope(|scope| {
+        for thread in 0..THREADS {
+            let (counter, entered) = (&counter, &entered);
+            scope.spawn(move || {
+                for i in 0..ITERATIONS {
+                    let guard = match (thread + i) % 3 {
+                        0 => Some(counter.lock()),
+                        1 => counter.try_lock(),
+                        _ => counter.try_lock_for(Duration::from_micros(10)),
+                    };
+                    if let Some(mut guard) = guard {
+                        *guard += 1;
+                        entered.fetch_add(1, Ordering::Relaxed);
+                    }
+                }
+            });
+        }
+    });
+    assert_eq!(counter.into_inner(), entered.into_inner());
+}
+
+// Every owner hands the lock on to the next slot in a circle, which has to win over ticket order
+// every time, including when the chain comes back around to slot 0, whose ticket is still keeping
+// everyone else out. Slot 0 enters first from the main thread, and last once everyone is done.
+#[test]
+fn unlock_to() {
+    let lock = RawBakeryLock::<THREADS>::new();
+    let turn = AtomicUsize::new(1);
+    lock.lock(0);
+    lock.unlock_to(0, 1);
+
+    thread::scope(|scope| {
+        for thread in 0..THREADS {
+            let (lock, turn) = (&lock, &turn);
+            let iterations = if thread == 0 {
+                ITERATIONS - 1
+            } else {
+                ITERATIONS
+            };
+            scope.spawn(move || {
+                for _ in 0..iterations {
+                    lock.lock(thread);
+                    let count = turn.load(Ordering::Relaxed);
+                    assert_eq!(count % THREADS, thread, "entered out of handoff order");
+                    turn.store(count + 1, Ordering::Relaxed);
+                    lock.unlock_to(thread, (thread + 1) % THREADS);
+                }
+            });
+        }
+    });
+
+    // The last handoff is still waiting for slot 0, and nobody else can get in until it's taken.
+    assert!(!lock.try_lock(1));
+    assert!(lock.try_lock(0));
+    assert_eq!(turn.into_inner(), THREADS * ITERATIONS);
+    lock.unlock(0);
+    assert!(lock.snapshot().is_idle());
+    assert!(lock.try_lock(1));
+    lock.unlock(1);
+}
+
+// A thread that dies holding the lock, one that dies in the doorway and one that dies before
+// picking up a handoff each keep everyone else out until their slot is cleaned up after them.
+#[test]
+fn abandoned_slots() {
+    let lock = RawBakeryLock::<THREADS>::new();
+
+    thread::scope(|scope| {
+        scope.spawn(|| lock.lock(1)).join().unwrap();
+        assert!(!lock.try_lock(0));
+        let waiter = scope.spawn(|| {
+            lock.lock(2);
+            lock.unlock(2);
+        });
+        // SAFETY: the thread that used slot 1 has exited.
+        unsafe { lock.force_unlock(1) };
+        waiter.join().unwrap();
+    });
+
+    // Stands in for a thread that died picking its ticket.
+    lock.slots().set_choosing(2, true, Ordering::Relaxed);
+    lock.slots().set_ticket(2, 1, Ordering::Relaxed);
+    assert!(!lock.try_lock(0));
+    // SAFETY: nobody uses slot 2.
+    unsafe { lock.reclaim_slot(2) };
+    assert!(lock.try_lock(0));
+
+    lock.unlock_to(0, 2);
+    assert!(!lock.try_lock(1));
+    // SAFETY: as above. The lock was handed to slot 2, so reclaiming it unlocks.
+    unsafe { lock.reclaim_slot(2) };
+    assert!(lock.snapshot().is_idle());
+    assert!(lock.try_lock(1));
+    lock.unlock(1);
+}
+
+// Five-bit tickets, which wrap around every 31 acquisitions while the bakery stays busy.
+struct TinyTicket(AtomicU8);
+
+impl TicketCell for TinyTicket {
+    const MAX: u64 = 31;
+
+    fn empty() -> Self {
+        TinyTicket(AtomicU8::new(0))
+    }
+
+    fn load(&self, order: Ordering) -> u64 {
+        self.0.load(order).into()
+    }
+
+    fn store(&self, ticket: u64, order: Ordering) {
+        assert!(ticket <= Self::MAX);
+        self.0.store(ticket as u8, order);
+    }
+}
+
+// Slot 0 is made to hold the lock with the second-to-last ticket, and everyone else queues up
+// behind it one at a time, so that the later tickets wrap around to 1. They must still be let in
+// in the order they arrived, one at a time. A contended run, whose tickets wrap whenever the bakery
+// stays busy for long enough, mustn't lose any updates either.
+#[test]
+fn wraparound() {
+    let lock = RawBakeryLock::from_parts(NoObserver, Packed::<THREADS, TinyTicket>::new());
+    let turn = AtomicUsize::new(1);
+    lock.slots()
+        .set_ticket(0, TinyTicket::MAX - 1, Ordering::Relaxed);
+
+    thread::scope(|scope| {
+        for thread in 1..THREADS {
+            let (lock, turn) = (&lock, &turn);
+            scope.spawn(move || {
+                lock.lock(thread);
+                let count = turn.load(Ordering::Relaxed);
+                assert_eq!(count, thread, "entered out of ticket order");
+                turn.store(count + 1, Ordering::Relaxed);
+                lock.unlock(thread);
+            });
+            let slots = lock.slots();
+            while slots.ticket(thread, Ordering::Relaxed) == 0
+                || slots.is_choosing(thread, Ordering::Relaxed)
+            {
+                thread::yield_now();
+            }
+        }
+        let last = lock.slots().ticket(THREADS - 1, Ordering::Relaxed);
+        assert_eq!(last, THREADS as u64 - 2, "the tickets didn't wrap around");
+        lock.unlock(0);
+    });
+    assert_eq!(turn.into_inner(), THREADS);
+    assert!(lock.snapshot().is_idle());
+
+    count_with_layout::<Packed<THREADS, TinyTicket>>();
+}
+
+// Timed lockers stuck behind slot 0 keep giving up and coming back, each time taking the ticket
+// after the others', so that left to themselves their tickets would leapfrog all the way around
+// past slot 0's and look older than it. None may get in while slot 0 holds the lock.
+#[test]
+fn leapfrog() {
+    let lock = RawBakeryLock::from_parts(NoObserver, Packed::<THREADS, TinyTicket>::new())
+        .with_backoff::<SpinThenYield>();
+    lock.lock(0);
+    thread::scope(|scope| {
+        for slot in 1..THREADS {
+            let lock = &lock;
+            scope.spawn(move || {
+                for _ in 0..4 * TinyTicket::MAX {
+                    let entered =
+                        lock.try_lock_for(slot, Duration::from_millis(1)) || lock.try_lock(slot);
+                    assert!(!entered, "slot {slot} got in alongside slot 0");
+                    thread::yield_now();
+                }
+            });
+        }
+    });
+    lock.unlock(0);
+    assert!(lock.snapshot().is_idle());
+}
+
+// A snapshot of a quiet lock shows exactly what its one holder published, and nothing once it has
+// left.
+#[test]
+fn snapshot() {
+    let lock = RawBakeryLock::<THREADS>::new();
+    lock.lock(1);
+    assert!(!lock.try_lock(2));
+    let snapshot = lock.snapshot();
+    assert!(snapshot.consistent && !snapshot.is_idle());
+    assert_eq!((snapshot.front, snapshot.tickets[1]), (Some(1), 1));
+    assert_eq!(snapshot.to_string(), "slot 1 holds ticket 1 (front)");
+    assert!(format!("{lock:?}").contains("slot 1 holds ticket 1"));
+
+    lock.unlock(1);
+    assert!(lock.snapshot().is_idle());
+    assert_eq!(lock.snapshot().to_string(), "all slots free");
+}
+
+// Runs `future` to completion on the calling thread, parking it whenever the future is pending.
+fn block_on<F: Future>(future: F) -> F::Output {
+    struct Unpark(thread::Thread);
+
+    impl Wake for Unpark {
+        fn wake(self: Arc<Self>) {
+            self.0.unpark();
+        }
+    }
+
+    let waker = Waker::from(Arc::new(Unpark(thread::current())));
+    let mut context = Context::from_waker(&waker);
+    let mut future = pin!(future);
+    loop {
+        match future.as_mut().poll(&mut context) {
+            Poll::Ready(output) => return output,
+            Poll::Pending => thread::park(),
+        }
+    }
+}
+
+// One more thread than there are slots each drive their own lock futures, so that both the wait
+// for the tasks ahead and the wait for a slot have to be woken. Before every acquisition each also
+// gives up on a lock future after a single poll, which mustn't hold anyone up.
+#[test]
+fn async_mutex() {
+    let mutex = bakery::AsyncBakeryMutex::<usize, THREADS>::new(0);
+    thread::scope(|scope| {
+        for _ in 0..=THREADS {
+            scope.spawn(|| {
+                block_on(async {
+                    for _ in 0..ITERATIONS {
+                        let mut abandoned = Box::pin(mutex.lock());
+                        future::poll_fn(|context| {
+                            let _ = abandoned.as_mut().poll(context);
+                            Poll::Ready(())
+                        })
+                        .await;
+                        drop(abandoned);
+
+                        let mut guard = mutex.lock().await;
+                        thread::yield_now();
+                        *guard += 1;
+                    }
+                })
+            });
+        }
+    });
+    assert_eq!(mutex.into_inner(), (THREADS + 1) * ITERATIONS);
+}
+
+// Counts how many times it has been woken.
+#[derive(Default)]
+struct CountWakes(AtomicUsize);
+
+impl Wake for CountWakes {
+    fn wake(self: Arc<Self>) {
+        self.0.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+// A lock future that is woken once the lock is free and then dropped instead of polled, as when a
+// timeout wins a `select!`, still wakes the future waiting behind it, which then gets in.
+#[test]
+fn async_mutex_cancel() {
+    let mutex = bakery::AsyncBakeryMutex::<(), THREADS>::new(());
+    let (cancelled_wakes, behind_wakes) =
+        (Arc::<CountWakes>::default(), Arc::<CountWakes>::default());
+    let cancelled_waker = Waker::from(cancelled_wakes.clone());
+    let behind_waker = Waker::from(behind_wakes.clone());
+
+    let guard = mutex.try_lock().unwrap();
+    let mut cancelled = Box::pin(mutex.lock());
+    assert!(cancelled
+        .as_mut()
+        .poll(&mut Context::from_waker(&cancelled_waker))
+        .is_pending());
+    drop(guard);
+    assert_eq!(cancelled_wakes.0.load(Ordering::Relaxed), 1);
+
+    let mut behind = pin!(mutex.lock());
+    let mut context = Context::from_waker(&behind_waker);
+    assert!(behind.as_mut().poll(&mut context).is_pending());
+    drop(cancelled);
+    assert_ne!(behind_wakes.0.load(Ordering::Relaxed), 0);
+    let guard = match behind.as_mut().poll(&mut context) {
+        Poll::Ready(guard) => guard,
+        Poll::Pending => panic!("the cancelled future is still in the way"),
+    };
+    assert_eq!(guard.slot(), 0);
+}
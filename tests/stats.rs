@@ -0,0 +1,80 @@
+// The statistics behind `bench compare` live in the binary, so this pulls in its module directly
+// rather than going through the library.
+
+#[path = "../src/stats.rs"]
+mod stats;
+
+use stats::{mann_whitney, median, percentile, Comparison};
+
+// Recovers `a`'s U statistic from the rank-biserial correlation, `2 * U / (n1 * n2) - 1`.
+fn u(comparison: &Comparison, a: &[f64], b: &[f64]) -> f64 {
+    (comparison.effect_size + 1.0) * (a.len() * b.len()) as f64 / 2.0
+}
+
+fn assert_close(actual: f64, expected: f64) {
+    assert!(
+        (actual - expected).abs() < 1e-6,
+        "expected {expected}, got {actual}"
+    );
+}
+
+// Small samples with no ties, where U is just the number of pairs with the `a` larger: none when
+// every `a` is smaller, all of them when every `a` is larger, and a textbook case in between.
+#[test]
+fn mann_whitney_u() {
+    let (low, high) = ([1.0, 2.0, 3.0], [4.0, 5.0, 6.0]);
+    let comparison = mann_whitney(&low, &high);
+    assert_close(u(&comparison, &low, &high), 0.0);
+    assert_close(comparison.effect_size, -1.0);
+    let comparison = mann_whitney(&high, &low);
+    assert_close(u(&comparison, &high, &low), 9.0);
+    assert_close(comparison.effect_size, 1.0);
+
+    let a = [19.0, 22.0, 16.0, 29.0, 24.0];
+    let b = [20.0, 11.0, 17.0, 12.0];
+    assert_close(u(&mann_whitney(&a, &b), &a, &b), 17.0);
+    assert_close(u(&mann_whitney(&b, &a), &b, &a), 3.0);
+}
+
+// Tied values share the average of the ranks they span, which counts each tied pair as half, and
+// shrink the variance. With every value tied there's nothing to tell the samples apart by.
+#[test]
+fn mann_whitney_ties() {
+    // The three 2s span ranks 2 to 4, so each gets 3: U is 1 + 3 + 3 + 5 - 10 = 2.
+    let (a, b) = ([1.0, 2.0, 2.0, 3.0], [2.0, 4.0]);
+    let comparison = mann_whitney(&a, &b);
+    assert_close(u(&comparison, &a, &b), 2.0);
+    // A variance of 8 / 12 * (7 - 24 / 30) rather than 8 / 12 * 7 without the correction.
+    assert_close(comparison.p_value, 0.460_633_5);
+
+    let comparison = mann_whitney(&[5.0; 3], &[5.0; 2]);
+    assert_close(comparison.effect_size, 0.0);
+    assert_close(comparison.p_value, 1.0);
+}
+
+// The normal approximation, which `bench compare` relies on from about 8 samples each: completely
+// separated samples are significant, and identical ones aren't at all. Swapping the samples only
+// flips the sign of the effect.
+#[test]
+fn mann_whitney_p_value() {
+    let a: Vec<_> = (0..8).map(f64::from).collect();
+    let b: Vec<_> = (8..16).map(f64::from).collect();
+    let comparison = mann_whitney(&a, &b);
+    assert_close(comparison.p_value, 0.000_939_1);
+    let swapped = mann_whitney(&b, &a);
+    assert_close(swapped.p_value, comparison.p_value);
+    assert_close(swapped.effect_size, -comparison.effect_size);
+
+    assert_close(mann_whitney(&a, &a).p_value, 1.0);
+}
+
+#[test]
+fn median_and_percentile() {
+    assert_eq!(median(&[3.0, 1.0, 2.0]), 2.0);
+    assert_eq!(median(&[4.0, 1.0, 3.0, 2.0]), 2.5);
+
+    let values: Vec<_> = (1..=10).rev().map(f64::from).collect();
+    assert_eq!(percentile(&values, 0.9), 9.0);
+    assert_eq!(percentile(&values, 0.0), 1.0);
+    assert_eq!(percentile(&values, 1.0), 10.0);
+}